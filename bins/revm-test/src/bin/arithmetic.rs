@@ -0,0 +1,58 @@
+use std::time::{Duration, Instant};
+
+use primitive_types::H160;
+use revm::{db::BenchmarkDB, opcode::*, Bytecode, TransactTo};
+
+/// Number of loop iterations, every one executes arithmetic opcodes on the pushed operands.
+const ITERATIONS: u32 = 200_000;
+
+/// Loop that executes MUL, DIV, MOD, ADDMOD, MULMOD, ADD, LT and GT on `a` and `b`.
+fn code(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let push = |value: &[u8]| [&[PUSH1 - 1 + value.len() as u8][..], value].concat();
+    let op2 = |op| [push(b), push(a), vec![op, POP]].concat();
+    let op3 = |op| [push(b), push(b), push(a), vec![op, POP]].concat();
+    let [_, x, y, z] = ITERATIONS.to_be_bytes();
+    let body = [
+        op2(MUL),
+        op2(DIV),
+        op2(MOD),
+        op3(ADDMOD),
+        op3(MULMOD),
+        op2(ADD),
+        op2(LT),
+        op2(GT),
+    ]
+    .concat();
+    [
+        vec![PUSH3, x, y, z, JUMPDEST], // counter
+        body,
+        vec![PUSH1, 1, SWAP1, SUB, DUP1, PUSH1, 4, JUMPI, STOP], // loop until counter is zero
+    ]
+    .concat()
+}
+
+fn main() {
+    let bench = |name: &str, code: Vec<u8>| {
+        let mut evm = revm::new();
+        evm.env.tx.caller = H160::from_low_u64_be(0x1000);
+        evm.env.tx.transact_to = TransactTo::Call(Default::default());
+        evm.env.tx.gas_limit = u64::MAX / 2;
+        evm.env.block.gas_limit = u64::MAX.into();
+        evm.database(BenchmarkDB::new_bytecode(
+            Bytecode::new_raw(code.into()).to_analysed::<revm::LondonSpec>(),
+        ));
+        // just to spead up processor.
+        let (result, _) = evm.transact();
+        assert!(result.is_success(), "{:?}", result.exit_reason);
+        let mut elapsed = Duration::ZERO;
+        for _ in 0..10 {
+            let timer = Instant::now();
+            evm.transact();
+            elapsed += timer.elapsed();
+        }
+        println!("{} elapsed time: {:?}", name, elapsed / 10);
+    };
+
+    bench("word operands", code(&[0x12, 0x34, 0x56], &[0x07]));
+    bench("wide operands", code(&[0xff; 20], &[0x07; 12]));
+}
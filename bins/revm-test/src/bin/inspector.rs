@@ -0,0 +1,46 @@
+use std::time::{Duration, Instant};
+
+use primitive_types::H160;
+use revm::{db::BenchmarkDB, opcode::*, Bytecode, GasInspector, NoOpInspector, TransactTo};
+
+/// Arithmetic loop that executes about `ITERATIONS * 11` opcodes.
+const ITERATIONS: u32 = 1_000_000;
+
+fn main() {
+    let [_, a, b, c] = ITERATIONS.to_be_bytes();
+    let code = vec![
+        PUSH3, a, b, c, // counter
+        JUMPDEST, DUP1, DUP1, MUL, POP, // some work
+        PUSH1, 1, SWAP1, SUB, DUP1, PUSH1, 4, JUMPI, // loop until counter is zero
+        STOP,
+    ];
+
+    let mut evm = revm::new();
+    evm.env.tx.caller = H160::from_low_u64_be(0x1000);
+    evm.env.tx.transact_to = TransactTo::Call(Default::default());
+    evm.database(BenchmarkDB::new_bytecode(
+        Bytecode::new_raw(code.into()).to_analysed::<revm::LondonSpec>(),
+    ));
+
+    let bench = |name: &str, run: &mut dyn FnMut()| {
+        // just to spead up processor.
+        run();
+        let mut elapsed = Duration::ZERO;
+        for _ in 0..10 {
+            let timer = Instant::now();
+            run();
+            elapsed += timer.elapsed();
+        }
+        println!("{} elapsed time: {:?}", name, elapsed / 10);
+    };
+
+    bench("No inspector", &mut || {
+        let (_, _) = evm.clone().transact();
+    });
+    bench("NoOpInspector", &mut || {
+        let (_, _) = evm.clone().inspect(NoOpInspector());
+    });
+    bench("GasInspector", &mut || {
+        let (_, _) = evm.clone().inspect(GasInspector::default());
+    });
+}
@@ -0,0 +1,63 @@
+use std::{mem, time::Instant};
+
+use primitive_types::H160;
+use revm::{
+    db::BenchmarkDB, opcode::*, Bytecode, EVMData, Inspector, Interpreter, JournalEntry, OpcodeSet,
+    Return, TransactTo,
+};
+
+/// Stores loop counter to two slots `ITERATIONS` times and reverts at the end.
+const ITERATIONS: u32 = 100_000;
+
+/// Records size of journal just before the revert.
+#[derive(Default)]
+struct JournalSize {
+    entries: usize,
+    bytes: usize,
+}
+
+impl<DB: revm::Database> Inspector<DB> for JournalSize {
+    fn step(&mut self, _interp: &mut Interpreter, data: &mut EVMData<'_, DB>, _: bool) -> Return {
+        let journal = &data.journaled_state.journal;
+        self.entries = journal.iter().map(Vec::len).sum();
+        self.bytes = journal
+            .iter()
+            .map(|entries| entries.capacity() * mem::size_of::<JournalEntry>())
+            .sum();
+        Return::Continue
+    }
+
+    fn step_opcodes(&self) -> OpcodeSet {
+        OpcodeSet::NONE.with(REVERT)
+    }
+}
+
+fn main() {
+    let [_, a, b, c] = ITERATIONS.to_be_bytes();
+    let code = vec![
+        PUSH3, a, b, c, // counter
+        JUMPDEST, DUP1, PUSH1, 0, SSTORE, DUP1, PUSH1, 1, SSTORE, // store it twice
+        PUSH1, 1, SWAP1, SUB, DUP1, PUSH1, 4, JUMPI, // loop until counter is zero
+        PUSH1, 0, DUP1, REVERT,
+    ];
+
+    let mut evm = revm::new();
+    evm.env.tx.caller = H160::from_low_u64_be(0x1000);
+    evm.env.tx.transact_to = TransactTo::Call(Default::default());
+    evm.database(BenchmarkDB::new_bytecode(Bytecode::new_raw(code.into())));
+
+    let mut size = JournalSize::default();
+    let (result, _) = evm.inspect(&mut size);
+    assert_eq!(result.exit_reason, Return::Revert);
+    println!(
+        "Journal before revert: {} entries, {} KiB",
+        size.entries,
+        size.bytes / 1024
+    );
+
+    let timer = Instant::now();
+    for _ in 0..10 {
+        let (_, _) = evm.transact();
+    }
+    println!("Elapsed time: {:?}", timer.elapsed());
+}
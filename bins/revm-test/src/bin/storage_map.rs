@@ -0,0 +1,114 @@
+//! Compares [revm::StorageMap] with hash map on synthetic accounts that access given number of
+//! slots, by time and allocations per account.
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use hashbrown::HashMap;
+use primitive_types::U256;
+use revm::{StorageMap, StorageSlot};
+
+/// Number of accounts whose storage is built and read in every measurement.
+const ACCOUNTS: usize = 100_000;
+
+/// Counts allocations so that maps can be compared by allocations per account.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Storage access of one account as journaled state does it: every slot is loaded, read a few
+/// times as SLOAD and SSTORE do, and original values are reset at the end of transaction.
+macro_rules! account {
+    ($map:expr, $slots:expr) => {{
+        let mut map = $map;
+        for key in 0..$slots {
+            map.entry(U256::from(key * 7))
+                .or_insert_slot(StorageSlot::new(key.into()));
+        }
+        for _ in 0..4 {
+            for key in 0..$slots {
+                black_box(map.get(&U256::from(key * 7)));
+            }
+        }
+        for slot in map.values_mut() {
+            slot.reset_original();
+        }
+        map
+    }};
+}
+
+/// `or_insert` of both entry types.
+trait OrInsertSlot {
+    fn or_insert_slot(self, slot: StorageSlot);
+}
+
+impl OrInsertSlot
+    for hashbrown::hash_map::Entry<'_, U256, StorageSlot, hashbrown::hash_map::DefaultHashBuilder>
+{
+    fn or_insert_slot(self, slot: StorageSlot) {
+        self.or_insert(slot);
+    }
+}
+
+impl OrInsertSlot for revm::storage_map::Entry<'_> {
+    fn or_insert_slot(self, slot: StorageSlot) {
+        if let revm::storage_map::Entry::Vacant(entry) = self {
+            entry.insert(slot);
+        }
+    }
+}
+
+fn measure(f: impl Fn()) -> (Duration, f64) {
+    // just to spead up processor.
+    f();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let timer = Instant::now();
+    f();
+    let elapsed = timer.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    (elapsed, allocations as f64 / ACCOUNTS as f64)
+}
+
+fn main() {
+    println!("slots | hash map time, allocs/account | storage map time, allocs/account");
+    for slots in [0u64, 1, 2, 4, 8, 32] {
+        let (hash_time, hash_allocs) = measure(|| {
+            let accounts: Vec<_> = (0..ACCOUNTS)
+                .map(|_| account!(HashMap::<U256, StorageSlot>::new(), slots))
+                .collect();
+            black_box(accounts);
+        });
+        let (small_time, small_allocs) = measure(|| {
+            let accounts: Vec<_> = (0..ACCOUNTS)
+                .map(|_| account!(StorageMap::new(), slots))
+                .collect();
+            black_box(accounts);
+        });
+        println!(
+            "{:>5} | {:>10?}, {:.2} | {:>10?}, {:.2}",
+            slots, hash_time, hash_allocs, small_time, small_allocs
+        );
+    }
+}
@@ -38,6 +38,7 @@ impl From<CliEnv> for Env {
 
         local_fill!(env.tx.caller, from.tx.caller);
         local_fill!(env.tx.gas_limit, from.tx.tx_gas_limit);
+        local_fill!(env.tx.gas_price, from.tx.gas_price, U256::from);
         local_fill!(env.tx.value, from.tx.value, U256::from);
         local_fill!(env.tx.data, from.tx.data);
         env.tx.gas_priority_fee = from.tx.gas_priority_fee.map(U256::from);
@@ -100,7 +101,7 @@ pub struct CliEnvTx {
     //TODO pub access_list: Vec<(H160, Vec<U256>)>,
 }
 
-fn parse_hex(src: &str) -> Result<Bytes, hex::FromHexError> {
+pub fn parse_hex(src: &str) -> Result<Bytes, hex::FromHexError> {
     Ok(Bytes::from(hex::decode(src)?))
 }
 
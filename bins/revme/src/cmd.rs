@@ -1,4 +1,4 @@
-use crate::{runner, statetest};
+use crate::{repro, runner, statetest};
 use structopt::{clap::AppSettings, StructOpt};
 
 #[derive(StructOpt, Debug)]
@@ -7,14 +7,18 @@ use structopt::{clap::AppSettings, StructOpt};
 pub enum MainCmd {
     Statetest(statetest::Cmd),
     Run(runner::Cmd),
+    Repro(repro::Cmd),
 }
 
 use thiserror::Error as ThisError;
 
 #[derive(Debug, ThisError)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     #[error("Statetest: {0}")]
     Statetest(statetest::Error),
+    #[error("Repro: {0}")]
+    Repro(repro::Error),
     #[error("Generic system error")]
     SystemError,
 }
@@ -23,7 +27,11 @@ impl MainCmd {
     pub fn run(&self) -> Result<(), Error> {
         match self {
             Self::Statetest(cmd) => cmd.run().map_err(Error::Statetest),
-            _ => Ok(()),
+            Self::Run(cmd) => {
+                cmd.run();
+                Ok(())
+            }
+            Self::Repro(cmd) => cmd.run().map_err(Error::Repro),
         }
     }
 }
@@ -1,5 +1,6 @@
 mod cmd;
 mod exec;
+mod repro;
 mod runner;
 mod statetest;
 use cmd::Error;
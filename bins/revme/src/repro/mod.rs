@@ -0,0 +1,43 @@
+use revm::{ReproBundle, ReproMismatch, EVM};
+use std::{fs, io, path::PathBuf};
+use structopt::StructOpt;
+use thiserror::Error as ThisError;
+
+/// Replay transaction of repro bundle and check it against expected result of the bundle.
+#[derive(StructOpt, Debug)]
+pub struct Cmd {
+    /// JSON file with the bundle.
+    #[structopt(required = true)]
+    path: PathBuf,
+}
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("Can't read bundle: {0}")]
+    Io(#[from] io::Error),
+    #[error("Can't parse bundle: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Result differs from expected: {}", display_mismatches(.0))]
+    Mismatch(Vec<ReproMismatch>),
+}
+
+fn display_mismatches(mismatches: &[ReproMismatch]) -> String {
+    mismatches
+        .iter()
+        .map(|mismatch| mismatch.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Cmd {
+    pub fn run(&self) -> Result<(), Error> {
+        let bundle: ReproBundle = serde_json::from_str(&fs::read_to_string(&self.path)?)?;
+        let (result, _) = EVM::from_repro_bundle(&bundle).transact_ref();
+        println!("{:?}", result);
+        let mismatches = bundle.check(&result);
+        if !mismatches.is_empty() {
+            return Err(Error::Mismatch(mismatches));
+        }
+        Ok(())
+    }
+}
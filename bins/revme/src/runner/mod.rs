@@ -1,4 +1,47 @@
+use crate::cli_env::{parse_hex, CliEnv};
+use bytes::Bytes;
+use revm::{AccountInfo, Bytecode, Env, InMemoryDB, SpecId, TransactTo};
 use structopt::StructOpt;
 
+/// Execute single transaction on empty state.
 #[derive(StructOpt, Debug)]
-pub struct Cmd {}
+pub struct Cmd {
+    /// Code of the contract at `env.tx.to`. If transaction is create it is used as init code.
+    #[structopt(long, parse(try_from_str = parse_hex))]
+    code: Option<Bytes>,
+    /// Name of the spec as used by state tests, for example `Berlin`.
+    #[structopt(long, default_value = "Latest")]
+    spec: String,
+    /// Execute transaction under every spec and print table of results.
+    #[structopt(long)]
+    all_specs: bool,
+    #[structopt(flatten)]
+    env: CliEnv,
+}
+
+impl Cmd {
+    pub fn run(&self) {
+        let mut env = Env::from(self.env.clone());
+        env.cfg.spec_id = SpecId::from(self.spec.as_str());
+        let mut db = InMemoryDB::default();
+        if let Some(code) = &self.code {
+            match env.tx.transact_to {
+                TransactTo::Call(address) => db.insert_account_info(
+                    address,
+                    AccountInfo::new(0.into(), 0, Bytecode::new_raw(code.clone())),
+                ),
+                TransactTo::Create(_) => env.tx.data = code.clone(),
+            }
+        }
+        let mut evm = revm::new();
+        evm.database(db);
+        evm.env = env;
+
+        if self.all_specs {
+            print!("{}", evm.transact_all_specs());
+        } else {
+            let (result, _) = evm.transact_ref();
+            println!("{:?}", result);
+        }
+    }
+}
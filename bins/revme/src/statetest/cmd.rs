@@ -1,21 +1,57 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
-use super::runner::{find_all_json_tests, run, TestError};
+use super::{
+    manifest::Manifest,
+    runner::{find_all_json_tests, run, GoldenTraces, TestError, TraceConfig},
+};
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
 pub struct Cmd {
     #[structopt(required = true)]
     path: Vec<PathBuf>,
+    /// Trace only memory regions changed since last step when printing trace of failed test.
+    #[structopt(long)]
+    sparse_memory: bool,
+    /// Cap number of memory bytes printed per step in sparse memory trace. Full memory is not
+    /// printed without `sparse-memory`, so it requires it.
+    #[structopt(long, requires = "sparse-memory")]
+    memory_limit: Option<usize>,
+    /// Compare EIP-3155 trace of every test with trace stored in this directory.
+    #[structopt(long)]
+    golden_traces: Option<PathBuf>,
+    /// Write traces to `golden-traces` directory instead of comparing them.
+    #[structopt(long, requires = "golden-traces")]
+    write_golden: bool,
+    /// TOML or JSON manifest of skipped and expected to fail tests. Default is
+    /// `statetest-manifest.toml` of revme.
+    #[structopt(long)]
+    manifest: Option<PathBuf>,
 }
 
 impl Cmd {
     pub fn run(&self) -> Result<(), TestError> {
+        let manifest = match &self.manifest {
+            Some(path) => Manifest::load(path)?,
+            None => Manifest::builtin(),
+        };
+        let manifest = Arc::new(manifest);
         for path in &self.path {
             println!("Start running tests on: {:?}", path);
             let test_files = find_all_json_tests(path);
-            run(test_files)?
+            run(test_files, self.trace_config(), manifest.clone())?
         }
         Ok(())
     }
+
+    fn trace_config(&self) -> TraceConfig {
+        TraceConfig {
+            sparse_memory: self.sparse_memory,
+            memory_limit: self.memory_limit,
+            golden: self.golden_traces.clone().map(|dir| GoldenTraces {
+                dir,
+                write: self.write_golden,
+            }),
+        }
+    }
 }
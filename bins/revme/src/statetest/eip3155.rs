@@ -0,0 +1,179 @@
+use bytes::Bytes;
+use primitive_types::H160;
+use revm::{
+    opcode, CallInputs, CreateInputs, Database, EVMData, Gas, GasInspector, Inspector, Interpreter,
+    Return,
+};
+use serde_json::json;
+
+/// Inspector that records execution as EIP-3155 trace, one JSON object per executed opcode.
+///
+/// Line of opcode is finished in `step_end` so that it contains gas cost of the opcode.
+#[derive(Clone, Debug, Default)]
+pub struct Eip3155Tracer {
+    gas_inspector: GasInspector,
+    lines: Vec<String>,
+    pending: Option<Step>,
+}
+
+#[derive(Clone, Debug)]
+struct Step {
+    pc: usize,
+    op: u8,
+    gas: u64,
+    mem_size: usize,
+    stack: Vec<String>,
+    depth: u64,
+    refund: i64,
+}
+
+impl Eip3155Tracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finish trace with summary line and return all lines.
+    pub fn finish(mut self, output: &Bytes, gas_used: u64, exit_reason: Return) -> Vec<String> {
+        let mut summary = json!({
+            "output": hex::encode(output),
+            "gasUsed": format!("{:#x}", gas_used),
+        });
+        if !matches!(
+            exit_reason,
+            Return::Continue | Return::Stop | Return::Return
+        ) {
+            summary["error"] = format!("{:?}", exit_reason).into();
+        }
+        self.lines.push(summary.to_string());
+        self.lines
+    }
+}
+
+impl<DB: Database> Inspector<DB> for Eip3155Tracer {
+    fn initialize_interp(
+        &mut self,
+        interp: &mut Interpreter,
+        data: &mut EVMData<'_, DB>,
+        is_static: bool,
+    ) -> Return {
+        self.gas_inspector
+            .initialize_interp(interp, data, is_static);
+        Return::Continue
+    }
+
+    fn step(
+        &mut self,
+        interp: &mut Interpreter,
+        data: &mut EVMData<'_, DB>,
+        is_static: bool,
+    ) -> Return {
+        self.pending = Some(Step {
+            pc: interp.program_counter(),
+            op: interp.current_opcode(),
+            // gas is charged for whole block of opcodes so it is taken from gas inspector.
+            gas: self.gas_inspector.gas_remaining(),
+            mem_size: interp.memory.data().len(),
+            stack: interp
+                .stack
+                .data()
+                .iter()
+                .map(|value| format!("{:#x}", value))
+                .collect(),
+            depth: data.journaled_state.depth(),
+            refund: interp.gas.refunded(),
+        });
+        self.gas_inspector.step(interp, data, is_static);
+        Return::Continue
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        data: &mut EVMData<'_, DB>,
+        is_static: bool,
+        eval: Return,
+    ) -> Return {
+        self.gas_inspector.step_end(interp, data, is_static, eval);
+        if let Some(step) = self.pending.take() {
+            let gas_cost = step.gas.saturating_sub(self.gas_inspector.gas_remaining());
+            let line = json!({
+                "pc": step.pc,
+                "op": step.op,
+                "gas": format!("{:#x}", step.gas),
+                "gasCost": format!("{:#x}", gas_cost),
+                "memSize": step.mem_size,
+                "stack": step.stack,
+                "depth": step.depth,
+                "refund": step.refund,
+                "opName": opcode::OPCODE_JUMPMAP[step.op as usize].unwrap_or("UNKNOWN"),
+            });
+            self.lines.push(line.to_string());
+        }
+        Return::Continue
+    }
+
+    fn call_end(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: Return,
+        out: Bytes,
+        is_static: bool,
+    ) -> (Return, Gas, Bytes) {
+        self.gas_inspector
+            .call_end(data, inputs, remaining_gas, ret, out.clone(), is_static);
+        (ret, remaining_gas, out)
+    }
+
+    fn create_end(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &CreateInputs,
+        ret: Return,
+        address: Option<H160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (Return, Option<H160>, Gas, Bytes) {
+        self.gas_inspector
+            .create_end(data, inputs, ret, address, remaining_gas, out.clone());
+        (ret, address, remaining_gas, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitive_types::U256;
+    use revm::{AccountInfo, Bytecode, InMemoryDB, TransactTo};
+
+    #[test]
+    fn test_eip3155_trace() {
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(
+                U256::zero(),
+                0,
+                Bytecode::new_raw(Bytes::from_static(&[opcode::PUSH1, 1, opcode::STOP])),
+            ),
+        );
+        let mut evm = revm::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        let mut tracer = Eip3155Tracer::new();
+        let (result, _) = evm.inspect(&mut tracer);
+        let lines = tracer.finish(&Bytes::new(), result.gas_used, result.exit_reason);
+
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"depth":1,"gas":"0x13498","gasCost":"0x3","memSize":0,"op":96,"opName":"PUSH1","pc":0,"refund":0,"stack":[]}"#,
+                r#"{"depth":1,"gas":"0x13495","gasCost":"0x0","memSize":0,"op":0,"opName":"STOP","pc":2,"refund":0,"stack":["0x1"]}"#,
+                r#"{"gasUsed":"0x520b","output":""}"#,
+            ]
+        );
+    }
+}
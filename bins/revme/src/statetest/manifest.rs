@@ -0,0 +1,168 @@
+use std::path::Path;
+
+use revm::SpecId;
+use serde_derive::Deserialize;
+
+use super::{models::SpecName, runner::TestError};
+
+/// Manifest used when none is given on command line.
+const DEFAULT_MANIFEST: &str = include_str!("../../statetest-manifest.toml");
+
+/// Tests that are skipped or expected to fail, with reasons. Read from TOML or JSON file, see
+/// `statetest-manifest.toml` for format.
+#[derive(Debug, Default, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub skip: Vec<ManifestEntry>,
+    #[serde(default)]
+    pub expect_fail: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ManifestEntry {
+    /// Pattern of file name or name of test, `*` matches any characters.
+    pub test: String,
+    /// Forks the entry applies to, all forks if empty.
+    #[serde(default)]
+    pub forks: Vec<SpecName>,
+    /// Indexes of tests of a fork that are expected to fail, all if empty. Only used by
+    /// `expect_fail` entries.
+    #[serde(default)]
+    pub indexes: Vec<usize>,
+    pub reason: String,
+}
+
+/// Test that failed as expected by manifest.
+#[derive(Debug)]
+pub struct KnownFailure {
+    pub file: String,
+    pub name: String,
+    pub spec_id: SpecId,
+    pub id: usize,
+    pub reason: String,
+    pub error: String,
+}
+
+impl Manifest {
+    /// Read manifest from file, it is parsed as JSON if file has `json` extension and as TOML
+    /// otherwise.
+    pub fn load(path: &Path) -> Result<Self, TestError> {
+        let manifest_error = |error: String| TestError::Manifest {
+            path: path.to_path_buf(),
+            error,
+        };
+        let content = std::fs::read_to_string(path).map_err(|e| manifest_error(e.to_string()))?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&content).map_err(|e| manifest_error(e.to_string()))
+        } else {
+            toml::from_str(&content).map_err(|e| manifest_error(e.to_string()))
+        }
+    }
+
+    /// Manifest of `statetest-manifest.toml` that is compiled into the binary.
+    pub fn builtin() -> Self {
+        toml::from_str(DEFAULT_MANIFEST).expect("builtin manifest is valid")
+    }
+
+    /// Entry that skips whole file without reading it.
+    pub fn skip_file(&self, file: &str) -> Option<&ManifestEntry> {
+        self.skip
+            .iter()
+            .find(|entry| entry.forks.is_empty() && glob_match(&entry.test, file))
+    }
+
+    /// Entry that skips test `name` of `file` for fork `spec`.
+    pub fn skip(&self, file: &str, name: &str, spec: &SpecName) -> Option<&ManifestEntry> {
+        self.skip
+            .iter()
+            .find(|entry| entry.matches(file, name, spec))
+    }
+
+    /// Entry that expects test `id` of test `name` of `file` to fail for fork `spec`.
+    pub fn expect_fail(
+        &self,
+        file: &str,
+        name: &str,
+        spec: &SpecName,
+        id: usize,
+    ) -> Option<&ManifestEntry> {
+        self.expect_fail.iter().find(|entry| {
+            entry.matches(file, name, spec)
+                && (entry.indexes.is_empty() || entry.indexes.contains(&id))
+        })
+    }
+}
+
+impl ManifestEntry {
+    fn matches(&self, file: &str, name: &str, spec: &SpecName) -> bool {
+        (self.forks.is_empty() || self.forks.contains(spec))
+            && (glob_match(&self.test, file) || glob_match(&self.test, name))
+    }
+}
+
+/// Match `text` against `pattern` where `*` matches any, possibly empty, sequence of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<_> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+    if !text.starts_with(first) || text.len() < first.len() + last.len() {
+        return false;
+    }
+    // middle parts are matched greedily from the left, between first and last part.
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    text.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("loopMul.json", "loopMul.json"));
+        assert!(!glob_match("loopMul.json", "loopMul.json.bak"));
+        assert!(glob_match("loop*", "loopExp.json"));
+        assert!(glob_match("*.json", "loopExp.json"));
+        assert!(glob_match("*Call*sha256*", "static_Call50000_sha256.json"));
+        assert!(glob_match("a*b*a", "aba"));
+        assert!(!glob_match("a*b*a", "ab"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn test_manifest() {
+        let manifest: Manifest = serde_json::from_str(
+            r#"{
+                "skip": [{ "test": "slow*", "reason": "slow" }],
+                "expect_fail": [
+                    { "test": "*Transient*", "forks": ["Cancun"], "reason": "EIP-1153" },
+                    { "test": "sstore", "indexes": [1, 3], "reason": "gas" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert!(manifest.skip_file("slowLoop.json").is_some());
+        assert!(manifest.skip_file("fast.json").is_none());
+
+        let expect = |name, spec, id| manifest.expect_fail("file.json", name, &spec, id).is_some();
+        assert!(expect("tstoreTransientStorage", SpecName::Cancun, 0));
+        assert!(expect("tstoreTransientStorage", SpecName::Cancun, 5));
+        assert!(!expect("tstoreTransientStorage", SpecName::London, 0));
+        assert!(!expect("sstore", SpecName::Cancun, 0));
+        assert!(expect("sstore", SpecName::Cancun, 1));
+        assert!(expect("sstore", SpecName::London, 3));
+        assert!(!expect("sstore", SpecName::London, 2));
+
+        let builtin = Manifest::builtin();
+        assert!(builtin.skip_file("ValueOverflow.json").is_some());
+        assert!(builtin.skip.iter().all(|entry| !entry.reason.is_empty()));
+    }
+}
@@ -1,6 +1,9 @@
 mod cmd;
+mod eip3155;
+mod manifest;
 pub mod merkle_trie;
 pub mod models;
+mod post_state;
 mod runner;
 mod trace;
 
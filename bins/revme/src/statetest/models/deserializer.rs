@@ -86,3 +86,14 @@ where
     Option::<WrappedValue>::deserialize(deserializer)
         .map(|opt_wrapped: Option<WrappedValue>| opt_wrapped.map(|wrapped: WrappedValue| wrapped.0))
 }
+
+pub fn deserialize_opt_str_as_u64<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    #[derive(Debug, Deserialize)]
+    struct WrappedValue(#[serde(deserialize_with = "deserialize_str_as_u64")] u64);
+
+    Option::<WrappedValue>::deserialize(deserializer)
+        .map(|opt_wrapped: Option<WrappedValue>| opt_wrapped.map(|wrapped: WrappedValue| wrapped.0))
+}
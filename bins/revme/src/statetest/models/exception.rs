@@ -0,0 +1,125 @@
+use revm::Return;
+
+/// Category of `expectException` of invalid transaction. Fillers use both legacy names
+/// (`TR_NoFunds`) and names of execution spec tests (`TransactionException.INSUFFICIENT_ACCOUNT_FUNDS`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExceptionCategory {
+    /// Balance does not cover gas limit or value.
+    InsufficientFunds,
+    /// Gas limit is lower than intrinsic gas.
+    IntrinsicGas,
+    /// EIP-1559: Max fee is lower than basefee.
+    FeeCapLessThanBasefee,
+    /// EIP-1559: Priority fee is greater than max fee.
+    PriorityGreaterThanFeeCap,
+    /// Gas limit is more than gas limit of block.
+    GasLimitReached,
+    /// EIP-2681: Sender nonce is at max value.
+    NonceMax,
+    /// EIP-3860: Init code is too large.
+    InitcodeSizeExceeded,
+    /// EIP-3607: Sender has deployed code.
+    SenderNotEoa,
+    /// Exception that is not mapped to revm error. Any rejection of transaction matches it.
+    Other(String),
+}
+
+impl ExceptionCategory {
+    /// Parse `expectException` that can contain multiple alternatives separated by `|`.
+    pub fn parse_all(exception: &str) -> Vec<Self> {
+        exception
+            .split('|')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(Self::from_name)
+            .collect()
+    }
+
+    pub fn from_name(name: &str) -> Self {
+        let short = name.trim_start_matches("TransactionException.");
+        match short {
+            "TR_NoFunds" | "TR_NoFundsValue" | "TR_NoFundsOrGas" | "INSUFFICIENT_ACCOUNT_FUNDS" => {
+                Self::InsufficientFunds
+            }
+            "TR_IntrinsicGas" | "INTRINSIC_GAS_TOO_LOW" => Self::IntrinsicGas,
+            "TR_FeeCapLessThanBlocks" | "INSUFFICIENT_MAX_FEE_PER_GAS" => {
+                Self::FeeCapLessThanBasefee
+            }
+            "TR_TipGtFeeCap" | "PRIORITY_GREATER_THAN_MAX_FEE_PER_GAS" => {
+                Self::PriorityGreaterThanFeeCap
+            }
+            "TR_GasLimitReached" | "GAS_ALLOWANCE_EXCEEDED" => Self::GasLimitReached,
+            "TR_NonceHasMaxValue" | "NONCE_IS_MAX" => Self::NonceMax,
+            "TR_InitCodeLimitExceeded" | "INITCODE_SIZE_EXCEEDED" => Self::InitcodeSizeExceeded,
+            "SenderNotEOA" | "SENDER_NOT_EOA" => Self::SenderNotEoa,
+            _ => Self::Other(name.to_string()),
+        }
+    }
+
+    /// Check if revm rejected transaction for reason of this category.
+    pub fn matches(&self, exit_reason: Return, gas_used: u64) -> bool {
+        match self {
+            Self::InsufficientFunds => matches!(
+                exit_reason,
+                Return::LackOfFundForGasLimit | Return::OutOfFund | Return::OverflowPayment
+            ),
+            // intrinsic gas is checked before execution so no gas is used.
+            Self::IntrinsicGas => exit_reason == Return::OutOfGas && gas_used == 0,
+            Self::FeeCapLessThanBasefee => exit_reason == Return::GasPriceLessThenBasefee,
+            Self::PriorityGreaterThanFeeCap => {
+                exit_reason == Return::GasMaxFeeGreaterThanPriorityFee
+            }
+            Self::GasLimitReached => exit_reason == Return::CallerGasLimitMoreThenBlock,
+            Self::NonceMax => exit_reason == Return::NonceOverflow,
+            Self::InitcodeSizeExceeded => exit_reason == Return::CreateInitcodeSizeLimit,
+            Self::SenderNotEoa => exit_reason == Return::RejectCallerWithCode,
+            Self::Other(_) => is_rejected(exit_reason, gas_used),
+        }
+    }
+}
+
+/// Transaction was rejected before execution and state was not changed.
+fn is_rejected(exit_reason: Return, gas_used: u64) -> bool {
+    gas_used == 0
+        && matches!(
+            exit_reason,
+            Return::OutOfGas
+                | Return::OutOfFund
+                | Return::GasMaxFeeGreaterThanPriorityFee
+                | Return::GasPriceLessThenBasefee
+                | Return::CallerGasLimitMoreThenBlock
+                | Return::RejectCallerWithCode
+                | Return::LackOfFundForGasLimit
+                | Return::OverflowPayment
+                | Return::NonceOverflow
+                | Return::InvalidChainId
+                | Return::CreateInitcodeSizeLimit
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_exception() {
+        assert_eq!(
+            ExceptionCategory::parse_all("TR_NoFunds|TransactionException.INTRINSIC_GAS_TOO_LOW"),
+            vec![
+                ExceptionCategory::InsufficientFunds,
+                ExceptionCategory::IntrinsicGas
+            ]
+        );
+        let unknown = ExceptionCategory::from_name("TR_TypeNotSupported");
+        assert_eq!(
+            unknown,
+            ExceptionCategory::Other("TR_TypeNotSupported".to_string())
+        );
+        assert!(unknown.matches(Return::InvalidChainId, 0));
+        assert!(!unknown.matches(Return::Stop, 21000));
+
+        // out of gas during execution is not intrinsic gas exception.
+        assert!(ExceptionCategory::IntrinsicGas.matches(Return::OutOfGas, 0));
+        assert!(!ExceptionCategory::IntrinsicGas.matches(Return::OutOfGas, 50000));
+    }
+}
@@ -2,12 +2,14 @@ use bytes::Bytes;
 use primitive_types::{H160, H256, U256};
 use std::collections::{BTreeMap, HashMap};
 mod deserializer;
+mod exception;
 mod spec;
 
 use deserializer::*;
 
 use serde_derive::*;
 
+pub use self::exception::ExceptionCategory;
 pub use self::spec::SpecName;
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
@@ -25,14 +27,22 @@ pub struct TestUnit {
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 pub struct Test {
     /// Post state hash
-    pub hash: H256,
+    #[serde(default)]
+    pub hash: Option<H256>,
     /// Indexes
     pub indexes: TxPartIndices,
     // logs
-    pub logs: H256,
+    #[serde(default)]
+    pub logs: Option<H256>,
+    /// Post state in expanded form. Only listed accounts and their listed fields are checked.
+    #[serde(default, rename = "postState", alias = "state")]
+    pub post_state: Option<HashMap<H160, PostAccount>>,
     #[serde(default)]
     #[serde(deserialize_with = "deserialize_opt_str_as_bytes")]
     pub txbytes: Option<Bytes>,
+    /// Exception of invalid transaction. If set transaction needs to be rejected.
+    #[serde(default, rename = "expectException")]
+    pub expect_exception: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq, Deserialize)]
@@ -54,6 +64,21 @@ pub struct AccountInfo {
     pub storage: HashMap<U256, U256>,
 }
 
+/// Expected account of the post state, fields that are not set are not checked.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostAccount {
+    #[serde(default)]
+    pub balance: Option<U256>,
+    #[serde(default, deserialize_with = "deserialize_opt_str_as_bytes")]
+    pub code: Option<Bytes>,
+    #[serde(default, deserialize_with = "deserialize_opt_str_as_u64")]
+    pub nonce: Option<u64>,
+    /// Listed slots, other slots of the account are not checked.
+    #[serde(default)]
+    pub storage: HashMap<U256, U256>,
+}
+
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Env {
@@ -21,6 +21,7 @@ pub enum SpecName {
     BerlinToLondonAt5, // done
     London,            // done
     Merge,             //done
+    Cancun,
 }
 
 impl SpecName {
@@ -38,6 +39,7 @@ impl SpecName {
             Self::Berlin => SpecId::BERLIN,
             Self::London | Self::BerlinToLondonAt5 => SpecId::LONDON,
             Self::Merge => SpecId::MERGE,
+            Self::Cancun => SpecId::CANCUN,
             Self::ByzantiumToConstantinopleAt5 | Self::Constantinople => {
                 panic!("Overriden with PETERSBURG")
             } //_ => panic!("Conversion failed"),
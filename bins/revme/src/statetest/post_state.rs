@@ -0,0 +1,186 @@
+use std::{collections::HashMap, fmt};
+
+use bytes::Bytes;
+use primitive_types::{H160, U256};
+use revm::{db::DatabaseRef, InMemoryDB};
+
+use super::models::PostAccount;
+
+/// Difference between expected post state and the state after execution.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PostStateDiff {
+    MissingAccount {
+        address: H160,
+    },
+    Balance {
+        address: H160,
+        expect: U256,
+        got: U256,
+    },
+    Nonce {
+        address: H160,
+        expect: u64,
+        got: u64,
+    },
+    Code {
+        address: H160,
+        expect: Bytes,
+        got: Bytes,
+    },
+    Storage {
+        address: H160,
+        key: U256,
+        expect: U256,
+        got: U256,
+    },
+}
+
+impl fmt::Display for PostStateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingAccount { address } => write!(f, "{:?}: account does not exist", address),
+            Self::Balance {
+                address,
+                expect,
+                got,
+            } => write!(f, "{:?}: balance expected {} got {}", address, expect, got),
+            Self::Nonce {
+                address,
+                expect,
+                got,
+            } => write!(f, "{:?}: nonce expected {} got {}", address, expect, got),
+            Self::Code {
+                address,
+                expect,
+                got,
+            } => write!(
+                f,
+                "{:?}: code expected 0x{} got 0x{}",
+                address,
+                hex::encode(expect),
+                hex::encode(got)
+            ),
+            Self::Storage {
+                address,
+                key,
+                expect,
+                got,
+            } => write!(
+                f,
+                "{:?}: storage[{:#x}] expected {:#x} got {:#x}",
+                address, key, expect, got
+            ),
+        }
+    }
+}
+
+/// Compare listed accounts and their fields with `db`. Diffs are sorted by address.
+pub fn post_state_diffs(
+    expected: &HashMap<H160, PostAccount>,
+    db: &InMemoryDB,
+) -> Vec<PostStateDiff> {
+    let mut addresses: Vec<_> = expected.keys().collect();
+    addresses.sort();
+
+    let mut diffs = Vec::new();
+    for &address in addresses {
+        let expect = &expected[&address];
+        let info = match DatabaseRef::basic(db, address).unwrap() {
+            Some(info) => info,
+            None => {
+                diffs.push(PostStateDiff::MissingAccount { address });
+                continue;
+            }
+        };
+        if let Some(balance) = expect.balance.filter(|balance| *balance != info.balance) {
+            diffs.push(PostStateDiff::Balance {
+                address,
+                expect: balance,
+                got: info.balance,
+            });
+        }
+        if let Some(nonce) = expect.nonce.filter(|nonce| *nonce != info.nonce) {
+            diffs.push(PostStateDiff::Nonce {
+                address,
+                expect: nonce,
+                got: info.nonce,
+            });
+        }
+        if let Some(code) = &expect.code {
+            let bytecode = match info.code {
+                Some(bytecode) => bytecode,
+                None => DatabaseRef::code_by_hash(db, info.code_hash).unwrap(),
+            };
+            let got = bytecode.bytes().slice(..bytecode.len());
+            if *code != got {
+                diffs.push(PostStateDiff::Code {
+                    address,
+                    expect: code.clone(),
+                    got,
+                });
+            }
+        }
+        let mut keys: Vec<_> = expect.storage.keys().collect();
+        keys.sort();
+        for &key in keys {
+            let got = DatabaseRef::storage(db, address, key).unwrap();
+            if expect.storage[&key] != got {
+                diffs.push(PostStateDiff::Storage {
+                    address,
+                    key,
+                    expect: expect.storage[&key],
+                    got,
+                });
+            }
+        }
+    }
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use revm::{AccountInfo, Bytecode};
+
+    #[test]
+    fn test_post_state_diffs() {
+        let json = r#"{
+            "0x0000000000000000000000000000000000000100": {
+                "balance": "0x0a",
+                "code": "0x6000",
+                "nonce": "0x01",
+                "storage": { "0x01": "0x02", "0x02": "0x00", "0x03": "0x05" }
+            },
+            "0x0000000000000000000000000000000000000200": { "balance": "0x01" },
+            "0x0000000000000000000000000000000000000300": { "nonce": "0x00" }
+        }"#;
+        let expected: HashMap<H160, PostAccount> = serde_json::from_str(json).unwrap();
+
+        let first = H160::from_low_u64_be(0x100);
+        let second = H160::from_low_u64_be(0x200);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            first,
+            AccountInfo::new(10.into(), 2, Bytecode::new_raw(vec![0x60, 0x01].into())),
+        );
+        db.insert_account_storage(first, 1.into(), 2.into())
+            .unwrap();
+        db.insert_account_storage(first, 3.into(), 4.into())
+            .unwrap();
+        db.insert_account_info(second, AccountInfo::from_balance(1.into()));
+
+        let diffs: Vec<_> = post_state_diffs(&expected, &db)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            diffs,
+            vec![
+                "0x0000000000000000000000000000000000000100: nonce expected 1 got 2",
+                "0x0000000000000000000000000000000000000100: code expected 0x6000 got 0x6001",
+                "0x0000000000000000000000000000000000000100: storage[0x3] expected 0x5 got 0x4",
+                "0x0000000000000000000000000000000000000300: account does not exist",
+            ]
+        );
+    }
+}
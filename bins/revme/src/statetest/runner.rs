@@ -1,6 +1,5 @@
 use std::{
     collections::HashMap,
-    ffi::OsStr,
     path::{Path, PathBuf},
     str::FromStr,
     sync::{atomic::AtomicBool, Arc, Mutex},
@@ -11,13 +10,18 @@ use sha3::{Digest, Keccak256};
 
 use indicatif::ProgressBar;
 use primitive_types::{H160, H256, U256};
-use revm::{db::AccountState, Bytecode, CreateScheme, Env, ExecutionResult, SpecId, TransactTo};
+use revm::{
+    db::AccountState, Bytecode, CreateScheme, Env, ExecutionResult, Return, SpecId, TransactTo,
+};
 use std::sync::atomic::Ordering;
 use walkdir::{DirEntry, WalkDir};
 
 use super::{
+    eip3155::Eip3155Tracer,
+    manifest::{KnownFailure, Manifest},
     merkle_trie::{log_rlp_hash, state_merkle_trie_root},
-    models::{SpecName, TestSuit},
+    models::{ExceptionCategory, SpecName, TestSuit},
+    post_state::{post_state_diffs, PostStateDiff},
     trace::CustomPrintTracer,
 };
 use thiserror::Error;
@@ -31,12 +35,140 @@ pub enum TestError {
         got: H256,
         expect: H256,
     },
+    #[error(" Test:{spec_id:?}:{id}, Post state missmatched:{}", display_diffs(.diffs))]
+    PostStateMissmatch {
+        spec_id: SpecId,
+        id: usize,
+        diffs: Vec<PostStateDiff>,
+    },
     #[error("Serde json error")]
     SerdeDeserialize(#[from] serde_json::Error),
     #[error("Internal system error")]
     SystemError,
+    #[error(" Test:{spec_id:?}:{id}, Expected exception: {expect} got:{got:?}")]
+    UnexpectedException {
+        spec_id: SpecId,
+        id: usize,
+        got: Return,
+        expect: String,
+    },
+    #[error(
+        " Test:{spec_id:?}:{id}, Trace missmatched at line {line}, Expected: {expect} got:{got}"
+    )]
+    TraceMissmatch {
+        spec_id: SpecId,
+        id: usize,
+        line: usize,
+        got: String,
+        expect: String,
+    },
+    #[error("Golden trace {path:?} error: {error}")]
+    GoldenTraceIo {
+        path: PathBuf,
+        error: std::io::Error,
+    },
     #[error("Unknown private key: {private_key:?}")]
     UnknownPrivateKey { private_key: H256 },
+    #[error("Manifest {path:?} error: {error}")]
+    Manifest { path: PathBuf, error: String },
+    #[error(
+        " Test {path:?} {name}:{spec_id:?}:{id}, Expected to fail but passed, remove it from manifest: {reason}"
+    )]
+    UnexpectedPass {
+        path: PathBuf,
+        name: String,
+        spec_id: SpecId,
+        id: usize,
+        reason: String,
+    },
+}
+
+fn display_diffs(diffs: &[PostStateDiff]) -> String {
+    diffs.iter().map(|diff| format!("\n  {}", diff)).collect()
+}
+
+/// Options of the tracer used to print execution of failed tests.
+#[derive(Clone, Debug, Default)]
+pub struct TraceConfig {
+    pub sparse_memory: bool,
+    pub memory_limit: Option<usize>,
+    /// Directory with EIP-3155 traces that every executed test is compared against.
+    pub golden: Option<GoldenTraces>,
+}
+
+impl TraceConfig {
+    fn tracer(&self) -> CustomPrintTracer {
+        let tracer = CustomPrintTracer::new();
+        if self.sparse_memory {
+            tracer.with_sparse_memory(self.memory_limit)
+        } else {
+            tracer
+        }
+    }
+}
+
+/// EIP-3155 traces of tests stored in directory, one file for every test and fork.
+#[derive(Clone, Debug)]
+pub struct GoldenTraces {
+    pub dir: PathBuf,
+    /// Write traces to directory instead of comparing with it.
+    pub write: bool,
+}
+
+impl GoldenTraces {
+    /// Path of trace: `<dir>/<test file stem>/<unit name>/<spec>_<index>.jsonl`.
+    fn trace_path(&self, path: &Path, unit: &str, spec_name: &SpecName, id: usize) -> PathBuf {
+        let sanitize = |name: &str| name.replace(['/', '\\'], "_");
+        let file = path.file_stem().unwrap_or_default().to_string_lossy();
+        self.dir
+            .join(sanitize(&file))
+            .join(sanitize(unit))
+            .join(format!("{:?}_{}.jsonl", spec_name, id))
+    }
+
+    /// Write trace or compare it with the stored one.
+    fn check(
+        &self,
+        path: &Path,
+        unit: &str,
+        spec_name: &SpecName,
+        id: usize,
+        trace: Vec<String>,
+    ) -> Result<(), TestError> {
+        let trace_path = self.trace_path(path, unit, spec_name, id);
+        let io_error = |error| TestError::GoldenTraceIo {
+            path: trace_path.clone(),
+            error,
+        };
+        if self.write {
+            if let Some(parent) = trace_path.parent() {
+                std::fs::create_dir_all(parent).map_err(io_error)?;
+            }
+            let mut content = trace.join("\n");
+            content.push('\n');
+            return std::fs::write(&trace_path, content).map_err(io_error);
+        }
+
+        let expected = std::fs::read_to_string(&trace_path).map_err(io_error)?;
+        let mut expected = expected.lines();
+        let mut got = trace.iter();
+        for line in 0.. {
+            match (expected.next(), got.next()) {
+                (None, None) => break,
+                (Some(expect), Some(got)) if expect == got => (),
+                (expect, got) => {
+                    return Err(TestError::TraceMissmatch {
+                        spec_id: spec_name.to_spec_id(),
+                        id,
+                        line,
+                        got: got.cloned().unwrap_or_default(),
+                        expect: expect.unwrap_or_default().to_string(),
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 pub fn find_all_json_tests(path: &Path) -> Vec<PathBuf> {
@@ -48,40 +180,19 @@ pub fn find_all_json_tests(path: &Path) -> Vec<PathBuf> {
         .collect::<Vec<PathBuf>>()
 }
 
-pub fn execute_test_suit(path: &Path, elapsed: &Arc<Mutex<Duration>>) -> Result<(), TestError> {
-    // funky test with `bigint 0x00` value in json :) not possible to happen on mainnet and require custom json parser.
-    // https://github.com/ethereum/tests/issues/971
-    if path.file_name() == Some(OsStr::new("ValueOverflow.json")) {
-        return Ok(());
-    }
-    // txbyte is of type 02 and we dont parse tx bytes for this test to fail.
-    if path.file_name() == Some(OsStr::new("typeTwoBerlin.json")) {
-        return Ok(());
-    }
-    // Test checks if nonce overflows. We are handling this correctly but we are not parsing exception in testsuite
-    // There are more nonce overflow tests that are in internal call/create, and those tests are passing and are enabled.
-    if path.file_name() == Some(OsStr::new("CreateTransactionHighNonce.json")) {
-        return Ok(());
-    }
-
-    // Skip test where basefee/accesslist/diffuculty is present but it shouldn't be supported in London/Berlin/TheMerge.
-    // https://github.com/ethereum/tests/blob/5b7e1ab3ffaf026d99d20b17bb30f533a2c80c8b/GeneralStateTests/stExample/eip1559.json#L130
-    // It is expected to not execute these tests.
-    if path.file_name() == Some(OsStr::new("accessListExample.json"))
-        || path.file_name() == Some(OsStr::new("basefeeExample.json"))
-        || path.file_name() == Some(OsStr::new("eip1559.json"))
-        || path.file_name() == Some(OsStr::new("mergeTest.json"))
-    {
-        return Ok(());
-    }
-
-    // These tests are passing, but they take a lot of time to execute so we are going to skip them.
-    if path.file_name() == Some(OsStr::new("loopExp.json"))
-        || path.file_name() == Some(OsStr::new("Call50000_sha256.json"))
-        || path.file_name() == Some(OsStr::new("static_Call50000_sha256.json"))
-        || path.file_name() == Some(OsStr::new("loopMul.json"))
-        || path.file_name() == Some(OsStr::new("CALLBlake2f_MaxRounds.json"))
-    {
+pub fn execute_test_suit(
+    path: &Path,
+    elapsed: &Arc<Mutex<Duration>>,
+    trace_config: &TraceConfig,
+    manifest: &Manifest,
+    known_failures: &Mutex<Vec<KnownFailure>>,
+) -> Result<(), TestError> {
+    let file = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    if manifest.skip_file(&file).is_some() {
         return Ok(());
     }
 
@@ -174,6 +285,10 @@ pub fn execute_test_suit(path: &Path, elapsed: &Arc<Mutex<Duration>>) -> Result<
                 continue;
             }
 
+            if manifest.skip(&file, &name, &spec_name).is_some() {
+                continue;
+            }
+
             env.cfg.spec_id = spec_name.to_spec_id();
 
             for (id, test) in tests.into_iter().enumerate() {
@@ -219,60 +334,125 @@ pub fn execute_test_suit(path: &Path, elapsed: &Arc<Mutex<Duration>>) -> Result<
                 };
                 env.tx.transact_to = to;
 
-                let mut database_cloned = database.clone();
-                let mut evm = revm::new();
-                evm.database(&mut database_cloned);
-                evm.env = env.clone();
-                // do the deed
-
-                let timer = Instant::now();
-                let ExecutionResult {
-                    exit_reason,
-                    gas_used,
-                    gas_refunded,
-                    logs,
-                    ..
-                } = evm.transact_commit();
-                let timer = timer.elapsed();
-
-                *elapsed.lock().unwrap() += timer;
-
-                let is_legacy = !SpecId::enabled(evm.env.cfg.spec_id, SpecId::SPURIOUS_DRAGON);
-                let db = evm.db().unwrap();
-                let state_root = state_merkle_trie_root(
-                    db.accounts
-                        .iter()
-                        .filter(|(_address, acc)| {
-                            (is_legacy && !matches!(acc.account_state, AccountState::NotExisting))
-                                || (!is_legacy
-                                    && (!(acc.info.is_empty())
-                                        || matches!(acc.account_state, AccountState::None)))
-                        })
-                        .map(|(k, v)| (*k, v.clone())),
-                );
-                let logs_root = log_rlp_hash(logs);
-                if test.hash != state_root || test.logs != logs_root {
-                    println!(
-                        "ROOTS mismath:\nstate_root:{:?}:{:?}\nlogs_root:{:?}:{:?}",
-                        test.hash, state_root, test.logs, logs_root
-                    );
+                // failures are returned from closure so that expected ones can be recorded.
+                let execute = || -> Result<(), TestError> {
                     let mut database_cloned = database.clone();
+                    let mut evm = revm::new();
                     evm.database(&mut database_cloned);
-                    evm.inspect_commit(CustomPrintTracer::new());
+                    evm.env = env.clone();
+                    // do the deed
+
+                    let timer = Instant::now();
+                    let mut golden_tracer =
+                        trace_config.golden.as_ref().map(|_| Eip3155Tracer::new());
+                    let result = match golden_tracer.as_mut() {
+                        Some(tracer) => evm.inspect_commit(tracer),
+                        None => evm.transact_commit(),
+                    };
+                    let timer = timer.elapsed();
+                    let golden_trace = golden_tracer.map(|tracer| {
+                        let output = result.output().cloned().unwrap_or_default();
+                        tracer.finish(&output, result.gas_used, result.exit_reason)
+                    });
+                    let ExecutionResult {
+                        exit_reason,
+                        gas_used,
+                        gas_refunded,
+                        logs,
+                        ..
+                    } = result;
+
+                    *elapsed.lock().unwrap() += timer;
+
+                    if let Some(expect) = test.expect_exception {
+                        if !ExceptionCategory::parse_all(&expect)
+                            .iter()
+                            .any(|category| category.matches(exit_reason, gas_used))
+                        {
+                            return Err(TestError::UnexpectedException {
+                                spec_id: env.cfg.spec_id,
+                                id,
+                                got: exit_reason,
+                                expect,
+                            });
+                        }
+                    }
+
+                    if let Some(post_state) = &test.post_state {
+                        let diffs = post_state_diffs(post_state, evm.db().unwrap());
+                        if !diffs.is_empty() {
+                            return Err(TestError::PostStateMissmatch {
+                                spec_id: env.cfg.spec_id,
+                                id,
+                                diffs,
+                            });
+                        }
+                    }
+
+                    // touched empty accounts are already removed since Spurious Dragon.
                     let db = evm.db().unwrap();
-                    println!("{:?} UNIT_TEST:{}\n", path, name);
-                    println!(
-                        "fail reson: {:?} {:?} UNIT_TEST:{}\n gas:{:?} ({:?} refunded)",
-                        exit_reason, path, name, gas_used, gas_refunded,
+                    let state_root = state_merkle_trie_root(
+                        db.accounts
+                            .iter()
+                            .filter(|(_address, acc)| {
+                                !matches!(acc.account_state, AccountState::NotExisting)
+                            })
+                            .map(|(k, v)| (*k, v.clone())),
                     );
-                    println!("\nApplied state:{:?}\n", db);
-                    println!("\nStateroot: {:?}\n", state_root);
-                    return Err(TestError::RootMissmatch {
-                        spec_id: env.cfg.spec_id,
+                    let logs_root = log_rlp_hash(logs);
+                    // roots are checked only if they are part of the test.
+                    if test.hash.is_some_and(|hash| hash != state_root)
+                        || test.logs.is_some_and(|logs| logs != logs_root)
+                    {
+                        println!(
+                            "ROOTS mismath:\nstate_root:{:?}:{:?}\nlogs_root:{:?}:{:?}",
+                            test.hash, state_root, test.logs, logs_root
+                        );
+                        let mut database_cloned = database.clone();
+                        evm.database(&mut database_cloned);
+                        evm.inspect_commit(trace_config.tracer());
+                        let db = evm.db().unwrap();
+                        println!("{:?} UNIT_TEST:{}\n", path, name);
+                        println!(
+                            "fail reson: {:?} {:?} UNIT_TEST:{}\n gas:{:?} ({:?} refunded)",
+                            exit_reason, path, name, gas_used, gas_refunded,
+                        );
+                        println!("\nApplied state:{:?}\n", db);
+                        println!("\nStateroot: {:?}\n", state_root);
+                        return Err(TestError::RootMissmatch {
+                            spec_id: env.cfg.spec_id,
+                            id,
+                            got: state_root,
+                            expect: test.hash.unwrap_or(state_root),
+                        });
+                    }
+                    if let (Some(golden), Some(trace)) = (&trace_config.golden, golden_trace) {
+                        golden.check(path, &name, &spec_name, id, trace)?;
+                    }
+                    Ok(())
+                };
+                let outcome = execute();
+                let spec_id = env.cfg.spec_id;
+                match (outcome, manifest.expect_fail(&file, &name, &spec_name, id)) {
+                    (Ok(()), None) => {}
+                    (Err(err), None) => return Err(err),
+                    (Ok(()), Some(entry)) => {
+                        return Err(TestError::UnexpectedPass {
+                            path: path.to_path_buf(),
+                            name: name.clone(),
+                            spec_id,
+                            id,
+                            reason: entry.reason.clone(),
+                        })
+                    }
+                    (Err(err), Some(entry)) => known_failures.lock().unwrap().push(KnownFailure {
+                        file: file.clone(),
+                        name: name.clone(),
+                        spec_id,
                         id,
-                        got: state_root,
-                        expect: test.hash,
-                    });
+                        reason: entry.reason.clone(),
+                        error: err.to_string(),
+                    }),
                 }
             }
         }
@@ -280,17 +460,25 @@ pub fn execute_test_suit(path: &Path, elapsed: &Arc<Mutex<Duration>>) -> Result<
     Ok(())
 }
 
-pub fn run(test_files: Vec<PathBuf>) -> Result<(), TestError> {
+pub fn run(
+    test_files: Vec<PathBuf>,
+    trace_config: TraceConfig,
+    manifest: Arc<Manifest>,
+) -> Result<(), TestError> {
     let endjob = Arc::new(AtomicBool::new(false));
     let console_bar = Arc::new(ProgressBar::new(test_files.len() as u64));
     let mut joins: Vec<std::thread::JoinHandle<Result<(), TestError>>> = Vec::new();
     let queue = Arc::new(Mutex::new((0, test_files)));
     let elapsed = Arc::new(Mutex::new(std::time::Duration::ZERO));
+    let known_failures = Arc::new(Mutex::new(Vec::new()));
     for _ in 0..10 {
         let queue = queue.clone();
         let endjob = endjob.clone();
         let console_bar = console_bar.clone();
         let elapsed = elapsed.clone();
+        let trace_config = trace_config.clone();
+        let manifest = manifest.clone();
+        let known_failures = known_failures.clone();
 
         joins.push(
             std::thread::Builder::new()
@@ -309,7 +497,13 @@ pub fn run(test_files: Vec<PathBuf>) -> Result<(), TestError> {
                         return Ok(());
                     }
                     //println!("Test:{:?}\n",test_path);
-                    if let Err(err) = execute_test_suit(&test_path, &elapsed) {
+                    if let Err(err) = execute_test_suit(
+                        &test_path,
+                        &elapsed,
+                        &trace_config,
+                        &manifest,
+                        &known_failures,
+                    ) {
                         endjob.store(true, Ordering::SeqCst);
                         println!("Test[{}] named:\n{:?} failed: {}\n", index, test_path, err);
                         return Err(err);
@@ -325,6 +519,21 @@ pub fn run(test_files: Vec<PathBuf>) -> Result<(), TestError> {
         handler.join().map_err(|_| TestError::SystemError)??;
     }
     console_bar.finish();
+    let known_failures = known_failures.lock().unwrap();
+    if !known_failures.is_empty() {
+        println!("Known failures: {}", known_failures.len());
+        for failure in known_failures.iter() {
+            println!(
+                "  {}:{} {:?}:{} ({}): {}",
+                failure.file,
+                failure.name,
+                failure.spec_id,
+                failure.id,
+                failure.reason,
+                failure.error.trim()
+            );
+        }
+    }
     println!("Finished execution. Time:{:?}", elapsed.lock().unwrap());
     Ok(())
 }
@@ -3,20 +3,82 @@ use primitive_types::H160;
 pub use revm::Inspector;
 use revm::{
     opcode::{self},
-    CallInputs, CreateInputs, Database, EVMData, Gas, GasInspector, Return,
+    CallInputs, CreateInputs, Database, EVMData, Gas, GasInspector, Return, SelfDestructResult,
 };
 
 #[derive(Clone)]
 pub struct CustomPrintTracer {
     gas_inspector: GasInspector,
+    /// Print only memory regions written since last step instead of full memory.
+    sparse_memory: bool,
+    /// Maximal number of memory bytes captured per step.
+    memory_limit: Option<usize>,
+    /// Memory seen at last step, one entry for every call depth.
+    last_memory: Vec<Vec<u8>>,
 }
 
 impl CustomPrintTracer {
     pub fn new() -> Self {
         Self {
             gas_inspector: GasInspector::default(),
+            sparse_memory: false,
+            memory_limit: None,
+            last_memory: Vec::new(),
         }
     }
+
+    /// Enable sparse memory tracing. Every step prints memory regions changed since the previous
+    /// step of the same frame, capped to `memory_limit` bytes if set.
+    pub fn with_sparse_memory(mut self, memory_limit: Option<usize>) -> Self {
+        self.sparse_memory = true;
+        self.memory_limit = memory_limit;
+        self
+    }
+
+    /// Return memory regions as (offset, data) that differ from memory seen on last step at this depth,
+    /// and remember current memory for next step.
+    fn memory_diff(&mut self, depth: usize, memory: &[u8]) -> Vec<(usize, Bytes)> {
+        if self.last_memory.len() <= depth {
+            self.last_memory.resize(depth + 1, Vec::new());
+        }
+        let last = &mut self.last_memory[depth];
+        let mut limit = self.memory_limit.unwrap_or(usize::MAX);
+        let mut diffs = Vec::new();
+        let mut region: Option<(usize, usize)> = None;
+        for (i, word) in memory.chunks(32).enumerate() {
+            let offset = i * 32;
+            let old = last.get(offset..).unwrap_or_default();
+            let changed = word
+                .iter()
+                .enumerate()
+                .any(|(j, byte)| *byte != old.get(j).copied().unwrap_or_default());
+            match (changed, region) {
+                (true, Some((start, _))) => region = Some((start, offset + word.len())),
+                (true, None) => region = Some((offset, offset + word.len())),
+                (false, Some((start, end))) => {
+                    push_capped(&mut diffs, &mut limit, start, &memory[start..end]);
+                    region = None;
+                }
+                (false, None) => (),
+            }
+        }
+        if let Some((start, end)) = region {
+            push_capped(&mut diffs, &mut limit, start, &memory[start..end]);
+        }
+        last.clear();
+        last.extend_from_slice(memory);
+        diffs
+    }
+}
+
+/// Push memory region while the byte limit allows it. The last region is truncated to fit.
+fn push_capped(diffs: &mut Vec<(usize, Bytes)>, limit: &mut usize, offset: usize, data: &[u8]) {
+    if *limit == 0 {
+        return;
+    }
+    let len = data.len().min(*limit);
+    *limit -= len;
+    diffs.push((offset, Bytes::copy_from_slice(&data[..len])));
 }
 
 impl<DB: Database> Inspector<DB> for CustomPrintTracer {
@@ -26,6 +88,13 @@ impl<DB: Database> Inspector<DB> for CustomPrintTracer {
         data: &mut EVMData<'_, DB>,
         is_static: bool,
     ) -> Return {
+        if self.sparse_memory {
+            // new frame starts with empty memory.
+            let depth = data.journaled_state.depth() as usize;
+            if let Some(last) = self.last_memory.get_mut(depth) {
+                last.clear();
+            }
+        }
         self.gas_inspector
             .initialize_interp(interp, data, is_static);
         Return::Continue
@@ -58,6 +127,14 @@ impl<DB: Database> Inspector<DB> for CustomPrintTracer {
             interp.memory.data().len(),
         );
 
+        if self.sparse_memory {
+            let diffs =
+                self.memory_diff(data.journaled_state.depth() as usize, interp.memory.data());
+            for (offset, data) in diffs {
+                println!("    Memory[{:#x}]: {}", offset, hex::encode(data));
+            }
+        }
+
         self.gas_inspector.step(interp, data, is_static);
 
         Return::Continue
@@ -135,8 +212,35 @@ impl<DB: Database> Inspector<DB> for CustomPrintTracer {
         (Return::Continue, None, Gas::new(0), Bytes::new())
     }
 
-    fn selfdestruct(&mut self) {
-        //, address: H160, target: H160) {
-        println!("SELFDESTRUCT on "); //{:?} target: {:?}", address, target);
+    fn selfdestruct(&mut self, address: H160, target: H160, result: &SelfDestructResult) {
+        println!(
+            "SELFDESTRUCT on {:?} target: {:?} burned: {:?}",
+            address, target, result.burned_balance
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparse_memory_diff() {
+        let mut tracer = CustomPrintTracer::new().with_sparse_memory(Some(40));
+        let mut memory = vec![0u8; 128];
+        assert!(tracer.memory_diff(1, &memory).is_empty());
+
+        memory[33] = 1;
+        memory[100] = 2;
+        assert_eq!(
+            tracer.memory_diff(1, &memory),
+            vec![
+                (32, Bytes::copy_from_slice(&memory[32..64])),
+                (96, Bytes::copy_from_slice(&memory[96..104])),
+            ]
+        );
+        // nested frame has its own memory.
+        assert!(tracer.memory_diff(2, &[0u8; 32]).is_empty());
+        assert!(tracer.memory_diff(1, &memory).is_empty());
     }
 }
@@ -0,0 +1,468 @@
+//! Static analysis of bytecode.
+//!
+//! [predict_access_list] walks the code with constant propagation, starting from known
+//! transaction inputs, to find storage slots and accounts that execution is likely to touch.
+//! Prediction can be used to warm [crate::db::CacheDB] with [crate::db::CacheDB::prewarm]
+//! in one batch before simulation instead of fetching every slot from remote state on demand.
+//!
+//! [calldata_address_hints] is cheaper and finds addresses that are passed as arguments, to be
+//! loaded with [crate::db::CacheDB::prefetch].
+use crate::{
+    alloc::{vec, vec::Vec},
+    opcode::*,
+    Bytecode, BytecodeLocked, LatestSpec, TxEnv,
+};
+use hashbrown::HashMap as Map;
+use primitive_types::{H160, U256};
+use sha3::{Digest, Keccak256};
+
+/// Maximal number of instructions evaluated over all paths.
+const MAX_STEPS: usize = 100_000;
+/// Maximal number of times that analysis enters same jump destination, counted over all paths
+/// together. Bounds loops and number of forked paths while still allowing internal functions
+/// to be analysed for a few call sites.
+const MAX_JUMPDEST_VISITS: u8 = 8;
+const STACK_LIMIT: usize = 1024;
+
+/// Predict access list of transaction that executes `code` at `address`.
+///
+/// Values that depend only on code, calldata, caller and value of transaction are propagated,
+/// including memory words hashed by SHA3, so that slots of mappings keyed by calldata or caller
+/// (`keccak(key . slot)`) are found. Both branches of conditional jumps are followed if
+/// condition is unknown. Prediction is best effort: slots computed from state or from
+/// return data of calls are missed and slots of paths not taken at runtime are included.
+///
+/// First entry is always `address` with sorted slots read or written by SLOAD and SSTORE.
+/// It is followed by constant addresses used by calls and account opcodes.
+pub fn predict_access_list(address: H160, code: &Bytecode, tx: &TxEnv) -> Vec<(H160, Vec<U256>)> {
+    let code = code.clone().lock::<LatestSpec>();
+    let mut analyzer = Analyzer {
+        code: &code,
+        address,
+        tx,
+        steps: 0,
+        visits: Map::new(),
+        slots: Vec::new(),
+        accounts: Vec::new(),
+    };
+    let mut paths = vec![Path::default()];
+    while let Some(path) = paths.pop() {
+        analyzer.run(path, &mut paths);
+    }
+
+    let Analyzer {
+        mut slots,
+        accounts,
+        ..
+    } = analyzer;
+    slots.sort_unstable();
+    slots.dedup();
+    let mut list = vec![(address, slots)];
+    for account in accounts {
+        if !list.iter().any(|(known, _)| *known == account) {
+            list.push((account, Vec::new()));
+        }
+    }
+    list
+}
+
+/// Words of ABI encoded `data` that look like addresses, in order of appearance and without
+/// duplicates. Four byte selector is skipped.
+///
+/// Word is taken as address if its upper 12 bytes are zero and one of the first four bytes of
+/// address is not, so that amounts and offsets smaller than 2^128 are not mistaken for addresses.
+pub fn calldata_address_hints(data: &[u8]) -> Vec<H160> {
+    let mut hints = Vec::new();
+    for word in data.get(4..).unwrap_or_default().chunks_exact(32) {
+        let (padding, address) = word.split_at(12);
+        if padding.iter().any(|byte| *byte != 0) || address[..4].iter().all(|byte| *byte == 0) {
+            continue;
+        }
+        let address = H160::from_slice(address);
+        if !hints.contains(&address) {
+            hints.push(address);
+        }
+    }
+    hints
+}
+
+/// State of one execution path. `None` is value that is not known statically.
+#[derive(Clone, Default)]
+struct Path {
+    pc: usize,
+    stack: Vec<Option<U256>>,
+    /// Known 32 byte words by their memory offset.
+    memory: Map<usize, U256>,
+}
+
+struct Analyzer<'a> {
+    code: &'a BytecodeLocked,
+    address: H160,
+    tx: &'a TxEnv,
+    steps: usize,
+    /// Entries into jump destinations, shared by all paths.
+    visits: Map<usize, u8>,
+    slots: Vec<U256>,
+    accounts: Vec<H160>,
+}
+
+impl<'a> Analyzer<'a> {
+    /// Evaluate path until it halts or forks. Forked paths are pushed to `paths`.
+    fn run(&mut self, mut path: Path, paths: &mut Vec<Path>) {
+        let code = self.code.original_bytecode_slice();
+        while path.pc < code.len() {
+            if self.steps == MAX_STEPS {
+                return;
+            }
+            self.steps += 1;
+
+            let op = code[path.pc];
+            let (inputs, outputs) = match stack_io(op) {
+                Some(io) => io,
+                // undefined opcode halts execution.
+                None => return,
+            };
+            if path.stack.len() < inputs || path.stack.len() - inputs + outputs > STACK_LIMIT {
+                return;
+            }
+            let at = path.stack.len() - inputs;
+            let args: Vec<Option<U256>> = path.stack.drain(at..).rev().collect();
+            let mut next = path.pc + 1;
+
+            let result = match op {
+                PUSH1..=PUSH32 => {
+                    let len = (op - PUSH1 + 1) as usize;
+                    // push data past the end of code is padded with zeroes.
+                    let mut data = [0u8; 32];
+                    let end = code.len().min(next + len);
+                    data[32 - len..32 - len + end - next].copy_from_slice(&code[next..end]);
+                    next += len;
+                    Some(U256::from_big_endian(&data))
+                }
+                DUP1..=DUP16 => {
+                    let n = (op - DUP1 + 1) as usize;
+                    // arguments are reversed so that `args[n - 1]` is the duplicated item.
+                    let value = args[n - 1];
+                    path.stack.extend(args.into_iter().rev());
+                    path.stack.push(value);
+                    path.pc = next;
+                    continue;
+                }
+                SWAP1..=SWAP16 => {
+                    let mut args = args;
+                    let last = args.len() - 1;
+                    args.swap(0, last);
+                    path.stack.extend(args.into_iter().rev());
+                    path.pc = next;
+                    continue;
+                }
+                ADD => binary(&args, |a, b| a.overflowing_add(b).0),
+                SUB => binary(&args, |a, b| a.overflowing_sub(b).0),
+                MUL => binary(&args, |a, b| a.overflowing_mul(b).0),
+                DIV => binary(&args, |a, b| a.checked_div(b).unwrap_or_default()),
+                AND => binary(&args, |a, b| a & b),
+                OR => binary(&args, |a, b| a | b),
+                XOR => binary(&args, |a, b| a ^ b),
+                EQ => binary(&args, |a, b| U256::from((a == b) as u8)),
+                LT => binary(&args, |a, b| U256::from((a < b) as u8)),
+                GT => binary(&args, |a, b| U256::from((a > b) as u8)),
+                SHL => binary(&args, |shift, value| match as_usize(shift) {
+                    Some(shift) if shift < 256 => value << shift,
+                    _ => U256::zero(),
+                }),
+                SHR => binary(&args, |shift, value| match as_usize(shift) {
+                    Some(shift) if shift < 256 => value >> shift,
+                    _ => U256::zero(),
+                }),
+                ISZERO => args[0].map(|a| U256::from(a.is_zero() as u8)),
+                NOT => args[0].map(|a| !a),
+                ADDRESS => Some(h160_to_u256(self.address)),
+                CALLER => Some(h160_to_u256(self.tx.caller)),
+                CALLVALUE => Some(self.tx.value),
+                CALLDATASIZE => Some(U256::from(self.tx.data.len())),
+                CALLDATALOAD => args[0].map(|offset| self.calldata_word(offset)),
+                SHA3 => self.sha3(&path, args[0], args[1]),
+                MSTORE => {
+                    match args[0].and_then(as_usize) {
+                        Some(offset) => {
+                            forget_memory(&mut path.memory, offset, 32);
+                            if let Some(value) = args[1] {
+                                path.memory.insert(offset, value);
+                            }
+                        }
+                        None => path.memory.clear(),
+                    }
+                    None
+                }
+                MSTORE8 => {
+                    match args[0].and_then(as_usize) {
+                        Some(offset) => forget_memory(&mut path.memory, offset, 1),
+                        None => path.memory.clear(),
+                    }
+                    None
+                }
+                // other opcodes that write to memory.
+                CALLDATACOPY | CODECOPY | EXTCODECOPY | RETURNDATACOPY | CALL | CALLCODE
+                | DELEGATECALL | STATICCALL => {
+                    path.memory.clear();
+                    match op {
+                        CALL | CALLCODE | DELEGATECALL | STATICCALL => self.account(args[1]),
+                        EXTCODECOPY => self.account(args[0]),
+                        _ => (),
+                    }
+                    None
+                }
+                BALANCE | EXTCODESIZE | EXTCODEHASH => {
+                    self.account(args[0]);
+                    None
+                }
+                SLOAD | SSTORE => {
+                    if let Some(slot) = args[0] {
+                        self.slots.push(slot);
+                    }
+                    None
+                }
+                JUMP | JUMPI => {
+                    let dest = args[0]
+                        .and_then(as_usize)
+                        .filter(|dest| self.code.jumptable().is_valid(*dest));
+                    match (op, args.get(1).copied().flatten()) {
+                        (JUMPI, Some(cond)) if cond.is_zero() => path.pc = next,
+                        (JUMPI, None) => {
+                            // condition is not known, follow both branches.
+                            if let Some(dest) = dest {
+                                self.fork(dest, &path, paths);
+                            }
+                            path.pc = next;
+                        }
+                        _ => match dest {
+                            Some(dest) if self.visit(dest) => path.pc = dest,
+                            _ => return,
+                        },
+                    }
+                    continue;
+                }
+                STOP | RETURN | REVERT | SELFDESTRUCT | INVALID => return,
+                _ => None,
+            };
+            if outputs == 1 {
+                path.stack.push(result);
+            }
+            path.pc = next;
+        }
+    }
+
+    /// Continue with copy of `path` at jump destination.
+    fn fork(&mut self, dest: usize, path: &Path, paths: &mut Vec<Path>) {
+        if self.visit(dest) {
+            let mut fork = path.clone();
+            fork.pc = dest;
+            paths.push(fork);
+        }
+    }
+
+    /// Count entry into jump destination. Returns false if limit is reached.
+    fn visit(&mut self, dest: usize) -> bool {
+        let visits = self.visits.entry(dest).or_default();
+        if *visits == MAX_JUMPDEST_VISITS {
+            return false;
+        }
+        *visits += 1;
+        true
+    }
+
+    fn account(&mut self, address: Option<U256>) {
+        if let Some(address) = address {
+            let mut bytes = [0u8; 32];
+            address.to_big_endian(&mut bytes);
+            self.accounts.push(H160::from_slice(&bytes[12..]));
+        }
+    }
+
+    fn calldata_word(&self, offset: U256) -> U256 {
+        let mut word = [0u8; 32];
+        if let Some(offset) = as_usize(offset) {
+            let data = &self.tx.data;
+            if offset < data.len() {
+                let end = data.len().min(offset + 32);
+                word[..end - offset].copy_from_slice(&data[offset..end]);
+            }
+        }
+        U256::from_big_endian(&word)
+    }
+
+    /// Hash memory if all words in range are known.
+    fn sha3(&self, path: &Path, offset: Option<U256>, len: Option<U256>) -> Option<U256> {
+        let offset = offset.and_then(as_usize)?;
+        let len = len.and_then(as_usize)?;
+        if len % 32 != 0 {
+            return None;
+        }
+        let mut hasher = Keccak256::new();
+        for word_offset in (offset..offset + len).step_by(32) {
+            let mut word = [0u8; 32];
+            path.memory.get(&word_offset)?.to_big_endian(&mut word);
+            hasher.update(word);
+        }
+        Some(U256::from_big_endian(hasher.finalize().as_slice()))
+    }
+}
+
+/// Number of stack inputs and outputs of opcode, or `None` if opcode is undefined.
+fn stack_io(op: u8) -> Option<(usize, usize)> {
+    Some(match op {
+        STOP | JUMPDEST | INVALID => (0, 0),
+        ADD | MUL | SUB | DIV | SDIV | MOD | SMOD | EXP | SIGNEXTEND | LT | GT | SLT | SGT | EQ
+        | AND | OR | XOR | BYTE | SHL | SHR | SAR | SHA3 => (2, 1),
+        ADDMOD | MULMOD => (3, 1),
+        ISZERO | NOT | CALLDATALOAD | BALANCE | EXTCODESIZE | EXTCODEHASH | BLOCKHASH | MLOAD
+        | SLOAD => (1, 1),
+        ADDRESS | ORIGIN | CALLER | CALLVALUE | CALLDATASIZE | CODESIZE | GASPRICE
+        | RETURNDATASIZE | COINBASE | TIMESTAMP | NUMBER | DIFFICULTY | GASLIMIT | CHAINID
+        | SELFBALANCE | BASEFEE | PC | MSIZE | GAS => (0, 1),
+        CALLDATACOPY | CODECOPY | RETURNDATACOPY => (3, 0),
+        EXTCODECOPY => (4, 0),
+        POP | JUMP | SELFDESTRUCT => (1, 0),
+        MSTORE | MSTORE8 | SSTORE | JUMPI | RETURN | REVERT => (2, 0),
+        PUSH1..=PUSH32 => (0, 1),
+        DUP1..=DUP16 => ((op - DUP1 + 1) as usize, (op - DUP1 + 2) as usize),
+        SWAP1..=SWAP16 => ((op - SWAP1 + 2) as usize, (op - SWAP1 + 2) as usize),
+        LOG0..=LOG4 => ((op - LOG0 + 2) as usize, 0),
+        CREATE => (3, 1),
+        CREATE2 => (4, 1),
+        CALL | CALLCODE => (7, 1),
+        DELEGATECALL | STATICCALL => (6, 1),
+        _ => return None,
+    })
+}
+
+fn binary(args: &[Option<U256>], f: impl FnOnce(U256, U256) -> U256) -> Option<U256> {
+    Some(f(args[0]?, args[1]?))
+}
+
+fn as_usize(value: U256) -> Option<usize> {
+    (value <= U256::from(u32::MAX)).then(|| value.as_usize())
+}
+
+fn h160_to_u256(address: H160) -> U256 {
+    U256::from_big_endian(address.as_bytes())
+}
+
+/// Remove known words that overlap with `len` bytes written at `offset`.
+fn forget_memory(memory: &mut Map<usize, U256>, offset: usize, len: usize) {
+    memory.retain(|word, _| *word + 32 <= offset || offset + len <= *word);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{CacheDB, InMemoryDB},
+        AccountInfo, TransactTo,
+    };
+    use bytes::Bytes;
+
+    /// Slot of `mapping` at `slot` for `key`.
+    fn mapping_slot(key: U256, slot: u8) -> U256 {
+        let mut preimage = [0u8; 64];
+        key.to_big_endian(&mut preimage[..32]);
+        preimage[63] = slot;
+        U256::from_big_endian(Keccak256::digest(preimage).as_slice())
+    }
+
+    #[test]
+    fn test_predict_access_list() {
+        let code = [
+            // SLOAD balances[calldata[4..36]] where balances is mapping at slot 1
+            &[
+                PUSH1,
+                4,
+                CALLDATALOAD,
+                PUSH1,
+                0,
+                MSTORE,
+                PUSH1,
+                1,
+                PUSH1,
+                0x20,
+                MSTORE,
+            ][..],
+            &[PUSH1, 0x40, PUSH1, 0, SHA3, SLOAD],
+            // SLOAD allowed[caller] where allowed is mapping at slot 2
+            &[CALLER, PUSH1, 0, MSTORE, PUSH1, 2, PUSH1, 0x20, MSTORE],
+            &[PUSH1, 0x40, PUSH1, 0, SHA3, SLOAD],
+            // branch on loaded value, both sides are predicted.
+            &[ADD, PUSH1, 41, JUMPI, PUSH1, 3, SLOAD, POP, STOP],
+            &[JUMPDEST, PUSH2, 0xbe, 0xef, BALANCE, PUSH1, 4, SSTORE, STOP],
+        ]
+        .concat();
+        let bytecode = Bytecode::new_raw(code.into());
+        let contract = H160::from_low_u64_be(0x1000);
+        let caller = H160::from_low_u64_be(0x2000);
+        let key = U256::from(0x3000);
+        let mut data = vec![0xaa, 0xbb, 0xcc, 0xdd];
+        data.extend_from_slice(&[0u8; 32]);
+        key.to_big_endian(&mut data[4..]);
+        let tx = TxEnv {
+            caller,
+            transact_to: TransactTo::Call(contract),
+            data: Bytes::from(data),
+            gas_limit: 100_000,
+            ..Default::default()
+        };
+
+        let access_list = predict_access_list(contract, &bytecode, &tx);
+        let mut slots = vec![
+            U256::from(3),
+            U256::from(4),
+            mapping_slot(key, 1),
+            mapping_slot(h160_to_u256(caller), 2),
+        ];
+        slots.sort_unstable();
+        assert_eq!(
+            access_list,
+            vec![(contract, slots), (H160::from_low_u64_be(0xbeef), vec![])]
+        );
+
+        // after prewarm, execution does not need any new slot from underlying database.
+        let mut remote = InMemoryDB::default();
+        remote.insert_account_info(contract, AccountInfo::new(U256::zero(), 0, bytecode));
+        for slot in &access_list[0].1 {
+            remote
+                .insert_account_storage(contract, *slot, U256::one())
+                .unwrap();
+        }
+        let mut db = CacheDB::new(remote);
+        db.prewarm(&access_list).unwrap();
+        assert_eq!(db.cache_stats().storage_slots, 4);
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx = tx;
+        assert_eq!(evm.transact().0.exit_reason, crate::Return::Stop);
+        assert_eq!(evm.db().unwrap().cache_stats().storage_slots, 4);
+    }
+
+    #[test]
+    fn test_calldata_address_hints() {
+        let token = H160::repeat_byte(0x11);
+        let recipient = H160::from_low_u64_be(0xdead) | H160::repeat_byte(0x22);
+        let word = |address: H160| h160_to_u256(address);
+        // transfer(recipient, amount), approve(token, max) and recipient again.
+        let mut data = vec![0xa9, 0x05, 0x9c, 0xbb];
+        for value in [
+            word(recipient),
+            U256::from(10).pow(24.into()),
+            word(token),
+            U256::MAX,
+            word(recipient),
+        ] {
+            let mut bytes = [0u8; 32];
+            value.to_big_endian(&mut bytes);
+            data.extend_from_slice(&bytes);
+        }
+        // trailing bytes that are not whole word are ignored.
+        data.extend_from_slice(&[0x11; 20]);
+        assert_eq!(calldata_address_hints(&data), vec![recipient, token]);
+        assert!(calldata_address_hints(&[0xa9]).is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{interpreter::bytecode::Bytecode, SpecId};
+use primitive_types::H256;
+
+/// Cache of analysed bytecode (jump table and gas blocks) keyed by code hash, that can be shared
+/// between EVM instances so that popular contracts are analysed only once.
+///
+/// Gas blocks depend on the spec so entries are stored per [SpecId]. Code that is already
+/// analysed in the database and execution with [crate::CfgEnv::custom_spec] bypass the cache.
+pub trait AnalysisCache: Send + Sync {
+    /// Get analysed bytecode for code hash.
+    fn get(&self, spec_id: SpecId, code_hash: H256) -> Option<Bytecode>;
+    /// Insert bytecode that was analysed with given spec.
+    fn insert(&self, spec_id: SpecId, bytecode: Bytecode);
+}
+
+#[cfg(feature = "std")]
+pub use shared::SharedAnalysisCache;
+
+#[cfg(feature = "std")]
+mod shared {
+    use super::*;
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use hashbrown::HashMap as Map;
+    use std::sync::RwLock;
+
+    /// [AnalysisCache] that can be used concurrently from many threads.
+    ///
+    /// Cache is not bounded, if number of distinct contracts is not bounded it should be
+    /// cleared from time to time.
+    #[derive(Debug, Default)]
+    pub struct SharedAnalysisCache {
+        contracts: RwLock<Map<(SpecId, H256), Bytecode>>,
+        hits: AtomicU64,
+        misses: AtomicU64,
+    }
+
+    impl SharedAnalysisCache {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Number of cached contracts, over all specs.
+        pub fn len(&self) -> usize {
+            self.contracts.read().unwrap().len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// Number of lookups that found analysed bytecode.
+        pub fn hits(&self) -> u64 {
+            self.hits.load(Ordering::Relaxed)
+        }
+
+        /// Number of lookups after which bytecode needed to be analysed.
+        pub fn misses(&self) -> u64 {
+            self.misses.load(Ordering::Relaxed)
+        }
+
+        pub fn clear(&self) {
+            self.contracts.write().unwrap().clear();
+        }
+    }
+
+    impl AnalysisCache for SharedAnalysisCache {
+        fn get(&self, spec_id: SpecId, code_hash: H256) -> Option<Bytecode> {
+            let bytecode = self
+                .contracts
+                .read()
+                .unwrap()
+                .get(&(spec_id, code_hash))
+                .cloned();
+            let counter = if bytecode.is_some() {
+                &self.hits
+            } else {
+                &self.misses
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+            bytecode
+        }
+
+        fn insert(&self, spec_id: SpecId, bytecode: Bytecode) {
+            self.contracts
+                .write()
+                .unwrap()
+                .insert((spec_id, bytecode.hash()), bytecode);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{db::InMemoryDB, opcode::*, AccountInfo, BytecodeState, Return, TransactTo};
+    use alloc::sync::Arc;
+    use primitive_types::{H160, U256};
+
+    #[test]
+    fn test_shared_analysis_cache() {
+        let code = [PUSH1, 4, JUMP, INVALID, JUMPDEST, STOP];
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let cache = Arc::new(SharedAnalysisCache::new());
+
+        for _ in 0..3 {
+            let mut evm = crate::new();
+            evm.database(db.clone());
+            evm.analysis_cache = Some(cache.clone());
+            evm.env.tx.transact_to = TransactTo::Call(contract);
+            evm.env.tx.gas_limit = 100_000;
+            assert_eq!(evm.transact().0.exit_reason, Return::Stop);
+        }
+        assert_eq!((cache.misses(), cache.hits()), (1, 2));
+        assert_eq!(cache.len(), 1);
+
+        let code_hash = db.accounts[&contract].info.code_hash;
+        let cached = cache.get(SpecId::LATEST, code_hash).unwrap();
+        assert!(matches!(cached.state(), BytecodeState::Analysed { .. }));
+        // analysis of other spec is separate.
+        assert!(cache.get(SpecId::BERLIN, code_hash).is_none());
+    }
+}
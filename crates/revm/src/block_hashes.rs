@@ -0,0 +1,205 @@
+use crate::db::{BlockHashProvider, DatabaseRef};
+use alloc::collections::VecDeque;
+use primitive_types::{H256, U256};
+
+/// Source of hashes of recent blocks read by BLOCKHASH, used before [crate::Database::block_hash].
+///
+/// Provider is set on [crate::EVM::block_hashes] and is shared, so EVMs that execute
+/// transactions of the same block can use one provider instead of every database fetching the
+/// same hashes. Block that provider doesn't have is read from database of the EVM.
+pub trait BlockHashes: Send + Sync {
+    /// Hash of block `number` or `None` if provider doesn't have it.
+    fn block_hash(&self, number: U256) -> Option<H256>;
+}
+
+impl BlockHashes for BlockHashProvider {
+    fn block_hash(&self, number: U256) -> Option<H256> {
+        BlockHashProvider::block_hash(self, number).ok()
+    }
+}
+
+/// Adapter that provides block hashes with [DatabaseRef::block_hash] of wrapped database.
+/// Failed reads are treated as missing blocks.
+#[derive(Clone, Debug, Default)]
+pub struct DatabaseBlockHashes<DB>(pub DB);
+
+impl<DB: DatabaseRef + Send + Sync> BlockHashes for DatabaseBlockHashes<DB> {
+    fn block_hash(&self, number: U256) -> Option<H256> {
+        self.0.block_hash(number).ok()
+    }
+}
+
+/// Header pushed to [RecentBlockHashes] doesn't continue the chain.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HeaderChainError {
+    /// Number of header is not next after the last pushed one.
+    NumberGap { expected: u64, got: u64 },
+    /// Parent hash of header is not hash of the last pushed header.
+    ParentHashMismatch { expected: H256, got: H256 },
+}
+
+/// Ring buffer of hashes of the last 256 blocks, the ones BLOCKHASH can read.
+///
+/// Headers are pushed in order and every header is checked to be child of the previous one,
+/// so buffer always holds one valid chain. Push header of the parent of executed block, hash
+/// of executed block itself is not readable by BLOCKHASH.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RecentBlockHashes {
+    /// Number of the first hash in `hashes`.
+    first: u64,
+    hashes: VecDeque<H256>,
+}
+
+impl RecentBlockHashes {
+    /// Number of hashes that are kept.
+    pub const CAPACITY: usize = 256;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add hash of block `number` whose header has `parent_hash`. First pushed header is
+    /// trusted, every next one needs to continue it.
+    pub fn push(
+        &mut self,
+        number: u64,
+        hash: H256,
+        parent_hash: H256,
+    ) -> Result<(), HeaderChainError> {
+        if let Some(last) = self.last() {
+            let expected = last.0 + 1;
+            if number != expected {
+                return Err(HeaderChainError::NumberGap {
+                    expected,
+                    got: number,
+                });
+            }
+            if parent_hash != last.1 {
+                return Err(HeaderChainError::ParentHashMismatch {
+                    expected: last.1,
+                    got: parent_hash,
+                });
+            }
+        } else {
+            self.first = number;
+        }
+        if self.hashes.len() == Self::CAPACITY {
+            self.hashes.pop_front();
+            self.first += 1;
+        }
+        self.hashes.push_back(hash);
+        Ok(())
+    }
+
+    /// Number and hash of the last pushed block.
+    pub fn last(&self) -> Option<(u64, H256)> {
+        let hash = *self.hashes.back()?;
+        Some((self.first + self.hashes.len() as u64 - 1, hash))
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+}
+
+impl BlockHashes for RecentBlockHashes {
+    fn block_hash(&self, number: U256) -> Option<H256> {
+        if number.bits() > 64 {
+            return None;
+        }
+        let index = number.as_u64().checked_sub(self.first)?;
+        self.hashes.get(usize::try_from(index).ok()?).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::EmptyDB, opcode::*, AccountInfo, Bytecode, InMemoryDB, Return, TransactOut, TransactTo,
+    };
+    use alloc::sync::Arc;
+
+    #[test]
+    fn test_recent_block_hashes() {
+        let hash = |number: u64| H256::from_low_u64_be(number + 1);
+        let mut hashes = RecentBlockHashes::new();
+        for number in 10..300 {
+            hashes.push(number, hash(number), hash(number - 1)).unwrap();
+        }
+        assert_eq!(hashes.len(), RecentBlockHashes::CAPACITY);
+        assert_eq!(hashes.last(), Some((299, hash(299))));
+        assert_eq!(hashes.block_hash(U256::from(44)), Some(hash(44)));
+        assert_eq!(hashes.block_hash(U256::from(43)), None);
+        assert_eq!(hashes.block_hash(U256::from(300)), None);
+        assert_eq!(
+            hashes.push(301, hash(301), hash(300)),
+            Err(HeaderChainError::NumberGap {
+                expected: 300,
+                got: 301
+            })
+        );
+        assert_eq!(
+            hashes.push(300, hash(300), H256::zero()),
+            Err(HeaderChainError::ParentHashMismatch {
+                expected: hash(299),
+                got: H256::zero()
+            })
+        );
+
+        // EVMs share provider, database that has no hashes is asked only for older blocks.
+        let contract = primitive_types::H160::from_low_u64_be(0x1000);
+        // return BLOCKHASH of number in first word of call data.
+        let code = [
+            PUSH1,
+            0,
+            CALLDATALOAD,
+            BLOCKHASH,
+            PUSH1,
+            0,
+            MSTORE,
+            PUSH1,
+            32,
+            PUSH1,
+            0,
+            RETURN,
+        ];
+        let mut db = InMemoryDB::with_block_hash_provider(BlockHashProvider::Error);
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 1, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let provider: Arc<dyn BlockHashes> = Arc::new(hashes);
+        let run = |block_number: u64, number: u64| {
+            let mut evm = crate::new();
+            evm.database(db.clone());
+            evm.block_hashes = Some(provider.clone());
+            evm.env.block.number = U256::from(block_number);
+            evm.env.tx.transact_to = TransactTo::Call(contract);
+            evm.env.tx.gas_limit = 100_000;
+            let mut data = [0; 32];
+            U256::from(number).to_big_endian(&mut data);
+            evm.env.tx.data = data.to_vec().into();
+            let (result, _) = evm.transact();
+            match result.out {
+                TransactOut::Call(out) => (result.exit_reason, out.to_vec()),
+                _ => panic!("expected call output"),
+            }
+        };
+        let hash_out = |number| (Return::Return, hash(number).as_bytes().to_vec());
+        assert_eq!(run(300, 299), hash_out(299));
+        assert_eq!(run(300, 44), hash_out(44));
+        // older block is not in provider and database fails to read it.
+        assert_eq!(run(299, 43).0, Return::FatalExternalError);
+
+        let adapter = DatabaseBlockHashes(EmptyDB::default());
+        assert_eq!(
+            adapter.block_hash(U256::one()),
+            EmptyDB::default().block_hash(U256::one()).ok()
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{alloc::vec::Vec, db::Database, Account, AccountInfo, DatabaseCommit, SpecId, State};
+use hashbrown::hash_map::Entry;
+use primitive_types::{H160, U256};
+
+/// Uncle (ommer) header included in the block.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Uncle {
+    pub number: u64,
+    pub coinbase: H160,
+}
+
+/// Static reward of block miner. 5 ETH before Byzantium, 3 ETH before Constantinople (EIP-649),
+/// 2 ETH before the Merge (EIP-1234) and none after it.
+pub fn block_reward(spec_id: SpecId) -> U256 {
+    let eth = if spec_id.supports_proof_of_stake() {
+        0
+    } else if SpecId::enabled(spec_id, SpecId::CONSTANTINOPLE) {
+        2
+    } else if SpecId::enabled(spec_id, SpecId::BYZANTIUM) {
+        3
+    } else {
+        5
+    };
+    U256::exp10(18) * eth
+}
+
+/// Rewards of block `number` mined by `coinbase`. Miner gets block reward and 1/32 of it for
+/// every included uncle, miner of uncle gets `(uncle.number + 8 - number) / 8` of block reward.
+///
+/// Rewards of the same account are not merged.
+pub fn block_rewards(
+    spec_id: SpecId,
+    number: u64,
+    coinbase: H160,
+    uncles: &[Uncle],
+) -> Vec<(H160, U256)> {
+    let reward = block_reward(spec_id);
+    if reward.is_zero() {
+        return Vec::new();
+    }
+    let mut rewards = Vec::with_capacity(uncles.len() + 1);
+    rewards.push((coinbase, reward + reward / 32 * uncles.len()));
+    for uncle in uncles {
+        let depth = (uncle.number + 8).saturating_sub(number);
+        rewards.push((uncle.coinbase, reward * depth / 8));
+    }
+    rewards
+}
+
+/// Add [block_rewards] to balances of accounts in `db`. It is applied after all transactions of
+/// the block for historical replay of chains before the Merge.
+pub fn apply_block_rewards<DB: Database + DatabaseCommit>(
+    db: &mut DB,
+    spec_id: SpecId,
+    number: u64,
+    coinbase: H160,
+    uncles: &[Uncle],
+) -> Result<(), DB::Error> {
+    let mut state = State::new();
+    for (address, reward) in block_rewards(spec_id, number, coinbase, uncles) {
+        let account = match state.entry(address) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let info = db.basic(address)?.unwrap_or_default();
+                let mut account = Account::from(AccountInfo { code: None, ..info });
+                account.is_touched = true;
+                entry.insert(account)
+            }
+        };
+        account.info.balance = account.info.balance.saturating_add(reward);
+    }
+    db.commit(state);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::InMemoryDB;
+
+    #[test]
+    fn test_block_rewards() {
+        let eth = U256::exp10(18);
+        assert_eq!(block_reward(SpecId::HOMESTEAD), eth * 5);
+        assert_eq!(block_reward(SpecId::BYZANTIUM), eth * 3);
+        assert_eq!(block_reward(SpecId::CONSTANTINOPLE), eth * 2);
+        assert_eq!(block_reward(SpecId::LONDON), eth * 2);
+        assert_eq!(block_reward(SpecId::MERGE), U256::zero());
+
+        let coinbase = H160::from_low_u64_be(1);
+        let uncles = [
+            Uncle {
+                number: 99,
+                coinbase: H160::from_low_u64_be(2),
+            },
+            Uncle {
+                number: 94,
+                coinbase,
+            },
+        ];
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(coinbase, AccountInfo::from_balance(eth));
+        apply_block_rewards(&mut db, SpecId::BYZANTIUM, 100, coinbase, &uncles).unwrap();
+
+        let reward = eth * 3;
+        let balance = |db: &mut InMemoryDB, address| db.basic(address).unwrap().unwrap().balance;
+        assert_eq!(
+            balance(&mut db, coinbase),
+            eth + reward + reward / 32 * 2 + reward * 2 / 8
+        );
+        assert_eq!(balance(&mut db, uncles[0].coinbase), reward * 7 / 8);
+
+        // nothing is rewarded after the Merge.
+        assert!(block_rewards(SpecId::MERGE, 100, coinbase, &uncles).is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{
+    alloc::{vec, vec::Vec},
+    evm_impl::EVMData,
+    Database, Inspector, Interpreter, Return,
+};
+use hashbrown::HashSet;
+use primitive_types::H256;
+
+/// Default size of coverage map, same as used by AFL.
+pub const COVERAGE_MAP_SIZE: usize = 1 << 16;
+
+/// Stable identifier of executed opcode. It depends only on hash of the code and program counter,
+/// so it is the same for every deployment of the contract and between runs.
+pub fn coverage_id(code_hash: H256, pc: usize) -> u64 {
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&code_hash.as_bytes()[..8]);
+    u64::from_be_bytes(prefix) ^ (pc as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+/// Inspector that records executed `(code hash, pc)` pairs as feedback for coverage guided fuzzing.
+///
+/// Every executed opcode increments saturating hit counter in bitmap at [coverage_id] modulo size
+/// of the map. Exact pairs can be additionally recorded with [CoverageInspector::track_pairs].
+#[derive(Clone, Debug)]
+pub struct CoverageInspector {
+    map: Vec<u8>,
+    pairs: Option<HashSet<(H256, usize)>>,
+}
+
+impl Default for CoverageInspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CoverageInspector {
+    pub fn new() -> Self {
+        Self::with_map_size(COVERAGE_MAP_SIZE)
+    }
+
+    /// Panics if size is zero.
+    pub fn with_map_size(size: usize) -> Self {
+        assert!(size > 0, "coverage map can't be empty");
+        Self {
+            map: vec![0; size],
+            pairs: None,
+        }
+    }
+
+    /// Record exact `(code hash, pc)` pairs in addition to bitmap.
+    pub fn track_pairs(mut self) -> Self {
+        self.pairs = Some(HashSet::new());
+        self
+    }
+
+    /// Hit counters indexed by [coverage_id] modulo size of the map.
+    pub fn map(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Executed pairs if they are tracked.
+    pub fn pairs(&self) -> Option<&HashSet<(H256, usize)>> {
+        self.pairs.as_ref()
+    }
+
+    /// Number of map entries that were hit.
+    pub fn covered(&self) -> usize {
+        self.map.iter().filter(|hits| **hits != 0).count()
+    }
+
+    /// Clear coverage so that inspector can be reused for next input.
+    pub fn reset(&mut self) {
+        self.map.iter_mut().for_each(|hits| *hits = 0);
+        if let Some(pairs) = self.pairs.as_mut() {
+            pairs.clear();
+        }
+    }
+
+    /// Merge coverage into map accumulated over all inputs. Returns true if this run hit an entry
+    /// that was not hit before. Panics if maps have different size.
+    pub fn merge_into(&self, global: &mut [u8]) -> bool {
+        assert_eq!(global.len(), self.map.len(), "coverage map size mismatch");
+        let mut new_coverage = false;
+        for (global, hits) in global.iter_mut().zip(self.map.iter()) {
+            if *hits != 0 && *global == 0 {
+                new_coverage = true;
+            }
+            *global = global.saturating_add(*hits);
+        }
+        new_coverage
+    }
+}
+
+impl<DB: Database> Inspector<DB> for CoverageInspector {
+    fn step(
+        &mut self,
+        interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+        _is_static: bool,
+    ) -> Return {
+        // hash of the code is computed when bytecode is locked so this is cheap.
+        let code_hash = interp.contract.bytecode.hash();
+        let pc = interp.program_counter();
+        let index = (coverage_id(code_hash, pc) % self.map.len() as u64) as usize;
+        self.map[index] = self.map[index].saturating_add(1);
+        if let Some(pairs) = self.pairs.as_mut() {
+            pairs.insert((code_hash, pc));
+        }
+        Return::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::InMemoryDB, opcode::*, AccountInfo, Bytecode, TransactTo};
+    use bytes::Bytes;
+    use primitive_types::{H160, U256};
+
+    /// Run contract that jumps over `PUSH1 1 POP` if first byte of calldata is not zero.
+    fn run(coverage: &mut CoverageInspector, input: u8) -> H256 {
+        let code = [
+            &[PUSH1, 0, CALLDATALOAD, PUSH1, 9, JUMPI][..],
+            &[PUSH1, 1, POP, JUMPDEST, STOP],
+        ]
+        .concat();
+        let bytecode = Bytecode::new_raw(code.into());
+        let code_hash = bytecode.hash();
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(contract, AccountInfo::new(U256::zero(), 0, bytecode));
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.data = Bytes::from(vec![input]);
+        evm.env.tx.gas_limit = 100_000;
+        assert_eq!(evm.inspect(&mut *coverage).0.exit_reason, Return::Stop);
+        code_hash
+    }
+
+    #[test]
+    fn test_coverage_inspector() {
+        let mut global = vec![0; COVERAGE_MAP_SIZE];
+
+        let mut coverage = CoverageInspector::new().track_pairs();
+        let code_hash = run(&mut coverage, 0);
+        let mut pcs: Vec<_> = coverage
+            .pairs()
+            .unwrap()
+            .iter()
+            .map(|(_, pc)| *pc)
+            .collect();
+        pcs.sort_unstable();
+        assert_eq!(pcs, vec![0, 2, 3, 5, 6, 8, 9, 10]);
+        assert!(coverage
+            .pairs()
+            .unwrap()
+            .iter()
+            .all(|(hash, _)| *hash == code_hash));
+        assert_eq!(coverage.covered(), 8);
+        assert!(coverage.merge_into(&mut global));
+
+        // same input does not give new coverage.
+        coverage.reset();
+        run(&mut coverage, 0);
+        assert!(!coverage.merge_into(&mut global));
+
+        // taking the jump skips opcodes that were already covered.
+        coverage.reset();
+        run(&mut coverage, 1);
+        assert_eq!(coverage.covered(), 6);
+        assert!(!coverage.merge_into(&mut global));
+    }
+}
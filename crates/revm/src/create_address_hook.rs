@@ -0,0 +1,87 @@
+use crate::CreateScheme;
+use primitive_types::{H160, H256};
+
+/// Derivation of addresses of created contracts, used instead of [crate::create_address] and
+/// [crate::create2_address] for both create transactions and CREATE/CREATE2 opcodes.
+///
+/// Useful for chains that namespace contract addresses differently or for tests that need
+/// deployment addresses independent of nonce of the deployer.
+pub trait CreateAddressHook: Send + Sync {
+    /// Address of contract created by `caller`. `nonce` is nonce of caller before it was
+    /// increased by this create and `init_code_hash` is keccak of init code.
+    fn create_address(
+        &self,
+        caller: H160,
+        nonce: u64,
+        scheme: CreateScheme,
+        init_code_hash: H256,
+    ) -> H160;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::InMemoryDB, opcode::*, AccountInfo, Bytecode, Return, TransactTo};
+    use alloc::sync::Arc;
+    use bytes::Bytes;
+    use primitive_types::U256;
+
+    /// Puts created contracts under `0xff..` prefix, CREATE2 keeps default derivation.
+    struct Namespaced;
+
+    impl CreateAddressHook for Namespaced {
+        fn create_address(
+            &self,
+            caller: H160,
+            nonce: u64,
+            scheme: CreateScheme,
+            init_code_hash: H256,
+        ) -> H160 {
+            match scheme {
+                CreateScheme::Create => {
+                    let mut address = crate::create_address(caller, nonce);
+                    address.0[..2].copy_from_slice(&[0xff, 0xff]);
+                    address
+                }
+                CreateScheme::Create2 { salt } => {
+                    crate::create2_address(caller, init_code_hash, salt)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_create_address_hook() {
+        let caller = H160::from_low_u64_be(0x1000);
+        let factory = H160::from_low_u64_be(0x2000);
+        // CREATE with empty init code and store created address at slot 0.
+        let code = [PUSH1, 0, PUSH1, 0, PUSH1, 0, CREATE, PUSH1, 0, SSTORE, STOP];
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            factory,
+            AccountInfo::new(U256::zero(), 1, Bytecode::new_raw(code.to_vec().into())),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.create_address_hook = Some(Arc::new(Namespaced));
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::create();
+        evm.env.tx.gas_limit = 100_000;
+        let result = evm.transact().0;
+        assert_eq!(result.exit_reason, Return::Continue);
+        let mut expected = crate::create_address(caller, 0);
+        expected.0[..2].copy_from_slice(&[0xff, 0xff]);
+        assert_eq!(result.created_address(), Some(expected));
+
+        evm.env.tx.transact_to = TransactTo::Call(factory);
+        evm.env.tx.data = Bytes::new();
+        let (result, state) = evm.transact();
+        assert_eq!(result.exit_reason, Return::Stop);
+        let mut expected = crate::create_address(factory, 1);
+        expected.0[..2].copy_from_slice(&[0xff, 0xff]);
+        let stored = state[&factory].storage[&U256::zero()].present_value();
+        assert_eq!(stored, U256::from(expected.as_bytes()));
+        assert!(state.contains_key(&expected));
+    }
+}
@@ -1,13 +1,33 @@
+mod callback_db;
+mod cow_db;
 mod in_memory_db;
+#[cfg(feature = "std")]
+mod instrumented_db;
+#[cfg(feature = "std")]
+mod shared_db;
 
 #[cfg(feature = "web3db")]
 pub mod web3db;
 #[cfg(feature = "web3db")]
-pub use web3db::Web3DB;
+pub use web3db::{ForkDB, Web3DB};
 
-pub use in_memory_db::{AccountState, BenchmarkDB, CacheDB, DbAccount, EmptyDB, InMemoryDB};
+pub use callback_db::CallbackDB;
+pub use cow_db::CowDB;
+pub use in_memory_db::{
+    AccountState, BenchmarkDB, BlockHashNotFound, BlockHashProvider, BlockHashProviderDB,
+    CacheCheckpoint, CacheDB, CacheLimits, CacheStats, CommitReport, DbAccount, EmptyDB,
+    InMemoryDB,
+};
+#[cfg(feature = "std")]
+pub use instrumented_db::{
+    DbStats, InstrumentedDB, LatencyHistogram, MethodStats, LATENCY_BUCKETS,
+};
+#[cfg(feature = "std")]
+pub use shared_db::{SharedCache, SharedCacheDB};
 
 use crate::{interpreter::bytecode::Bytecode, Account};
+use alloc::vec::Vec;
+use bytes::Bytes;
 use hashbrown::HashMap as Map;
 use primitive_types::{H160, H256, U256};
 
@@ -26,14 +46,38 @@ pub trait Database {
 
     // History related
     fn block_hash(&mut self, number: U256) -> Result<H256, Self::Error>;
+
+    /// Get at most `len` bytes of code starting at `offset`, fewer if code ends before.
+    ///
+    /// EXTCODECOPY of up to [CODE_RANGE_MAX_LEN] bytes from code that is not loaded yet reads
+    /// it here, so databases that keep code on disk can override it to read only the copied
+    /// part instead of the whole code.
+    fn code_range(
+        &mut self,
+        code_hash: H256,
+        offset: usize,
+        len: usize,
+    ) -> Result<Bytes, Self::Error> {
+        Ok(self.code_by_hash(code_hash)?.range(offset, len))
+    }
+
+    /// Get size of code. EXTCODESIZE of account which code is not loaded reads it here, so
+    /// databases that store size of code next to it can override it to not read the code.
+    fn code_size(&mut self, code_hash: H256) -> Result<usize, Self::Error> {
+        Ok(self.code_by_hash(code_hash)?.len())
+    }
 }
 
+/// Largest EXTCODECOPY that reads code with [Database::code_range] when code of the account
+/// is not loaded. Larger copies load the whole code, which is then kept for next reads.
+pub const CODE_RANGE_MAX_LEN: usize = 1024;
+
 #[auto_impl(& mut, Box)]
 pub trait DatabaseCommit {
     fn commit(&mut self, changes: Map<H160, Account>);
 }
 
-#[auto_impl(&, Box)]
+#[auto_impl(&, Box, Arc)]
 pub trait DatabaseRef {
     type Error;
     /// Whether account at address exists.
@@ -47,35 +91,75 @@ pub trait DatabaseRef {
 
     // History related
     fn block_hash(&self, number: U256) -> Result<H256, Self::Error>;
-}
 
-pub struct RefDBWrapper<'a, Error> {
-    pub db: &'a dyn DatabaseRef<Error = Error>,
+    /// Get part of code, same as [Database::code_range].
+    fn code_range(&self, code_hash: H256, offset: usize, len: usize) -> Result<Bytes, Self::Error> {
+        Ok(self.code_by_hash(code_hash)?.range(offset, len))
+    }
+
+    /// Get size of code, same as [Database::code_size].
+    fn code_size(&self, code_hash: H256) -> Result<usize, Self::Error> {
+        Ok(self.code_by_hash(code_hash)?.len())
+    }
+
+    /// Get basic information of many accounts, result of every address is at its index.
+    /// Databases that fetch state over network override it to fetch accounts concurrently.
+    fn basic_batch(&self, addresses: &[H160]) -> Vec<Result<Option<AccountInfo>, Self::Error>> {
+        addresses
+            .iter()
+            .map(|address| self.basic(*address))
+            .collect()
+    }
+
+    /// Get storage values of many slots, same as [DatabaseRef::basic_batch].
+    fn storage_batch(&self, slots: &[(H160, U256)]) -> Vec<Result<U256, Self::Error>> {
+        slots
+            .iter()
+            .map(|(address, index)| self.storage(*address, *index))
+            .collect()
+    }
 }
 
-impl<'a, Error> RefDBWrapper<'a, Error> {
-    pub fn new(db: &'a dyn DatabaseRef<Error = Error>) -> Self {
-        Self { db }
+/// Wrapper that implements [Database] for a reference to [DatabaseRef]. Calls are dispatched
+/// statically and error type of wrapped database is kept.
+pub struct WrapDatabaseRef<'a, T: ?Sized>(pub &'a T);
+
+impl<'a, T: ?Sized> From<&'a T> for WrapDatabaseRef<'a, T> {
+    fn from(db: &'a T) -> Self {
+        Self(db)
     }
 }
 
-impl<'a, Error> Database for RefDBWrapper<'a, Error> {
-    type Error = Error;
+impl<'a, T: DatabaseRef + ?Sized> Database for WrapDatabaseRef<'a, T> {
+    type Error = T::Error;
     /// Get basic account information.
     fn basic(&mut self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
-        self.db.basic(address)
+        self.0.basic(address)
     }
     /// Get account code by its hash
     fn code_by_hash(&mut self, code_hash: H256) -> Result<Bytecode, Self::Error> {
-        self.db.code_by_hash(code_hash)
+        self.0.code_by_hash(code_hash)
     }
     /// Get storage value of address at index.
     fn storage(&mut self, address: H160, index: U256) -> Result<U256, Self::Error> {
-        self.db.storage(address, index)
+        self.0.storage(address, index)
     }
 
     // History related
     fn block_hash(&mut self, number: U256) -> Result<H256, Self::Error> {
-        self.db.block_hash(number)
+        self.0.block_hash(number)
+    }
+
+    fn code_range(
+        &mut self,
+        code_hash: H256,
+        offset: usize,
+        len: usize,
+    ) -> Result<Bytes, Self::Error> {
+        self.0.code_range(code_hash, offset, len)
+    }
+
+    fn code_size(&mut self, code_hash: H256) -> Result<usize, Self::Error> {
+        self.0.code_size(code_hash)
     }
 }
@@ -0,0 +1,163 @@
+use super::{BlockHashNotFound, BlockHashProvider, BlockHashProviderDB, DatabaseRef};
+use crate::{interpreter::bytecode::Bytecode, AccountInfo};
+use alloc::boxed::Box;
+use bytes::Bytes;
+use core::fmt;
+use primitive_types::{H160, H256, U256};
+
+type Callback<T> = Box<dyn Fn(H160) -> T + Send + Sync>;
+
+/// [BlockHashProviderDB] whose accounts and storage are provided lazily by closures, for quick experiments
+/// and examples that don't need their own database type.
+///
+/// Every accessor that has no closure returns the same value as [BlockHashProviderDB]. Account exists if
+/// it has balance, nonce or code. Code is returned together with account, so closures are
+/// called again every time account is loaded, wrap it with [super::CacheDB] to call them once.
+pub struct CallbackDB {
+    balance: Option<Callback<U256>>,
+    nonce: Option<Callback<u64>>,
+    code: Option<Callback<Bytes>>,
+    storage: Option<Box<dyn Fn(H160, U256) -> U256 + Send + Sync>>,
+    pub block_hash_provider: BlockHashProvider,
+}
+
+impl CallbackDB {
+    pub fn new() -> Self {
+        Self::from(BlockHashProviderDB::default())
+    }
+
+    pub fn with_balance(mut self, balance: impl Fn(H160) -> U256 + Send + Sync + 'static) -> Self {
+        self.balance = Some(Box::new(balance));
+        self
+    }
+
+    pub fn with_nonce(mut self, nonce: impl Fn(H160) -> u64 + Send + Sync + 'static) -> Self {
+        self.nonce = Some(Box::new(nonce));
+        self
+    }
+
+    /// Raw code of account, it is analysed when it is executed.
+    pub fn with_code(mut self, code: impl Fn(H160) -> Bytes + Send + Sync + 'static) -> Self {
+        self.code = Some(Box::new(code));
+        self
+    }
+
+    pub fn with_storage(
+        mut self,
+        storage: impl Fn(H160, U256) -> U256 + Send + Sync + 'static,
+    ) -> Self {
+        self.storage = Some(Box::new(storage));
+        self
+    }
+
+    pub fn with_block_hash_provider(mut self, block_hash_provider: BlockHashProvider) -> Self {
+        self.block_hash_provider = block_hash_provider;
+        self
+    }
+}
+
+impl Default for CallbackDB {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<BlockHashProviderDB> for CallbackDB {
+    fn from(db: BlockHashProviderDB) -> Self {
+        Self {
+            balance: None,
+            nonce: None,
+            code: None,
+            storage: None,
+            block_hash_provider: db.block_hash_provider,
+        }
+    }
+}
+
+impl fmt::Debug for CallbackDB {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackDB")
+            .field("balance", &self.balance.is_some())
+            .field("nonce", &self.nonce.is_some())
+            .field("code", &self.code.is_some())
+            .field("storage", &self.storage.is_some())
+            .field("block_hash_provider", &self.block_hash_provider)
+            .finish()
+    }
+}
+
+impl DatabaseRef for CallbackDB {
+    type Error = BlockHashNotFound;
+
+    fn basic(&self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+        let balance = self
+            .balance
+            .as_ref()
+            .map(|f| f(address))
+            .unwrap_or_default();
+        let nonce = self.nonce.as_ref().map(|f| f(address)).unwrap_or_default();
+        let code = self.code.as_ref().map(|f| f(address)).unwrap_or_default();
+        let info = AccountInfo::new(balance, nonce, Bytecode::new_raw(code));
+        Ok(info.exists().then_some(info))
+    }
+
+    /// Code is returned with account, so code is only found by hash for empty code.
+    fn code_by_hash(&self, _code_hash: H256) -> Result<Bytecode, Self::Error> {
+        Ok(Bytecode::new())
+    }
+
+    fn storage(&self, address: H160, index: U256) -> Result<U256, Self::Error> {
+        Ok(self
+            .storage
+            .as_ref()
+            .map(|f| f(address, index))
+            .unwrap_or_default())
+    }
+
+    fn block_hash(&self, number: U256) -> Result<H256, Self::Error> {
+        self.block_hash_provider.block_hash(number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::CacheDB, opcode::*, Return, TransactOut, TransactTo};
+    use alloc::vec;
+
+    #[test]
+    fn test_callback_db() {
+        let contract = H160::from_low_u64_be(0x1000);
+        let db = CallbackDB::new()
+            .with_balance(|address| U256::from(address.to_low_u64_be()))
+            .with_code(move |address| {
+                if address == contract {
+                    // return value of slot 7.
+                    vec![
+                        PUSH1, 7, SLOAD, PUSH1, 0, MSTORE, PUSH1, 32, PUSH1, 0, RETURN,
+                    ]
+                    .into()
+                } else {
+                    Bytes::new()
+                }
+            })
+            .with_storage(|_, index| index * 2);
+
+        assert_eq!(db.basic(H160::zero()).unwrap(), None);
+        let info = db.basic(H160::from_low_u64_be(5)).unwrap().unwrap();
+        assert_eq!((info.balance, info.nonce), (U256::from(5), 0));
+        assert_eq!(db.storage(contract, U256::one()).unwrap(), U256::from(2));
+
+        let mut evm = crate::new();
+        evm.database(CacheDB::new(db));
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        let (result, state) = evm.transact();
+        assert_eq!(result.exit_reason, Return::Return);
+        let TransactOut::Call(out) = result.out else {
+            panic!("expected call output");
+        };
+        assert_eq!(U256::from_big_endian(&out), U256::from(14));
+        assert_eq!(state[&contract].info.balance, U256::from(0x1000));
+    }
+}
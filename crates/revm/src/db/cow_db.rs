@@ -0,0 +1,325 @@
+use super::{Database, DatabaseCommit, DatabaseRef};
+use crate::{interpreter::bytecode::Bytecode, Account, AccountInfo, KECCAK_EMPTY};
+use alloc::{sync::Arc, vec::Vec};
+use bytes::Bytes;
+use core::mem;
+use hashbrown::HashMap as Map;
+use primitive_types::{H160, H256, U256};
+
+/// Copy-on-write state for branched (what-if) simulations from the same base state.
+///
+/// Committed changes are kept in a private layer above `base`. [CowDB::branch] freezes that
+/// layer and shares it with the new branch, so branching is O(1) no matter how large the state
+/// is, and hundreds of branches share the base and all common changes. Reads go through the
+/// layers from newest to oldest and then to `base`, [CowDB::flatten] can be used to shorten
+/// long chain of layers.
+///
+/// Reads of `base` are not cached, so it is usually a [super::CacheDB] or [super::SharedCache].
+#[derive(Debug)]
+pub struct CowDB<ExtDB: DatabaseRef> {
+    base: Arc<ExtDB>,
+    /// Changes of this branch that are not shared with any other branch.
+    changes: Layer,
+}
+
+#[derive(Debug, Default)]
+struct Layer {
+    accounts: Map<H160, LayerAccount>,
+    contracts: Map<H256, Bytecode>,
+    /// Frozen layer below this one.
+    parent: Option<Arc<Layer>>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct LayerAccount {
+    /// None if account is destroyed.
+    info: Option<AccountInfo>,
+    storage: Map<U256, U256>,
+    /// Storage of lower layers and base is not visible.
+    storage_cleared: bool,
+}
+
+impl Layer {
+    /// Iterate over this layer and all frozen layers below it, from newest to oldest.
+    fn iter(&self) -> impl Iterator<Item = &Layer> {
+        let mut next = Some(self);
+        core::iter::from_fn(move || {
+            let layer = next?;
+            next = layer.parent.as_deref();
+            Some(layer)
+        })
+    }
+}
+
+impl<ExtDB: DatabaseRef> CowDB<ExtDB> {
+    pub fn new(base: ExtDB) -> Self {
+        Self::from_arc(Arc::new(base))
+    }
+
+    /// Use base that is already shared.
+    pub fn from_arc(base: Arc<ExtDB>) -> Self {
+        Self {
+            base,
+            changes: Layer::default(),
+        }
+    }
+
+    pub fn base(&self) -> &Arc<ExtDB> {
+        &self.base
+    }
+
+    /// Create new branch that sees all changes committed so far. Changes committed after
+    /// branching are visible only in the branch that committed them.
+    pub fn branch(&mut self) -> Self {
+        if !self.changes.accounts.is_empty() || !self.changes.contracts.is_empty() {
+            let frozen = Arc::new(mem::take(&mut self.changes));
+            self.changes.parent = Some(frozen);
+        }
+        Self {
+            base: self.base.clone(),
+            changes: Layer {
+                parent: self.changes.parent.clone(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Number of layers above base, including the private one.
+    pub fn depth(&self) -> usize {
+        self.changes.iter().count()
+    }
+
+    /// Merge all layers into a private one. Memory of frozen layers is not shared anymore
+    /// but reads don't need to go through them.
+    pub fn flatten(&mut self) {
+        let layers: Vec<&Layer> = self.changes.iter().collect();
+        let mut merged = Layer::default();
+        for layer in layers.into_iter().rev() {
+            merged.contracts.extend(
+                layer
+                    .contracts
+                    .iter()
+                    .map(|(hash, code)| (*hash, code.clone())),
+            );
+            for (address, account) in &layer.accounts {
+                match merged.accounts.get_mut(address) {
+                    Some(merged) if !account.storage_cleared => {
+                        merged.info = account.info.clone();
+                        merged.storage.extend(account.storage.iter());
+                    }
+                    _ => {
+                        merged.accounts.insert(*address, account.clone());
+                    }
+                }
+            }
+        }
+        self.changes = merged;
+    }
+
+    fn account(&self, address: &H160) -> Option<&LayerAccount> {
+        self.changes
+            .iter()
+            .find_map(|layer| layer.accounts.get(address))
+    }
+}
+
+impl<ExtDB: DatabaseRef> DatabaseRef for CowDB<ExtDB> {
+    type Error = ExtDB::Error;
+
+    fn basic(&self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+        match self.account(&address) {
+            Some(account) => Ok(account.info.clone()),
+            None => self.base.basic(address),
+        }
+    }
+
+    fn code_by_hash(&self, code_hash: H256) -> Result<Bytecode, Self::Error> {
+        match self
+            .changes
+            .iter()
+            .find_map(|layer| layer.contracts.get(&code_hash))
+        {
+            Some(code) => Ok(code.clone()),
+            None => self.base.code_by_hash(code_hash),
+        }
+    }
+
+    fn code_range(&self, code_hash: H256, offset: usize, len: usize) -> Result<Bytes, Self::Error> {
+        match self
+            .changes
+            .iter()
+            .find_map(|layer| layer.contracts.get(&code_hash))
+        {
+            Some(code) => Ok(code.range(offset, len)),
+            None => self.base.code_range(code_hash, offset, len),
+        }
+    }
+
+    fn code_size(&self, code_hash: H256) -> Result<usize, Self::Error> {
+        match self
+            .changes
+            .iter()
+            .find_map(|layer| layer.contracts.get(&code_hash))
+        {
+            Some(code) => Ok(code.len()),
+            None => self.base.code_size(code_hash),
+        }
+    }
+
+    fn storage(&self, address: H160, index: U256) -> Result<U256, Self::Error> {
+        for layer in self.changes.iter() {
+            if let Some(account) = layer.accounts.get(&address) {
+                if let Some(value) = account.storage.get(&index) {
+                    return Ok(*value);
+                }
+                if account.storage_cleared {
+                    return Ok(U256::zero());
+                }
+            }
+        }
+        self.base.storage(address, index)
+    }
+
+    fn block_hash(&self, number: U256) -> Result<H256, Self::Error> {
+        self.base.block_hash(number)
+    }
+}
+
+impl<ExtDB: DatabaseRef> Database for CowDB<ExtDB> {
+    type Error = ExtDB::Error;
+
+    fn basic(&mut self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+        DatabaseRef::basic(self, address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: H256) -> Result<Bytecode, Self::Error> {
+        DatabaseRef::code_by_hash(self, code_hash)
+    }
+
+    fn storage(&mut self, address: H160, index: U256) -> Result<U256, Self::Error> {
+        DatabaseRef::storage(self, address, index)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<H256, Self::Error> {
+        DatabaseRef::block_hash(self, number)
+    }
+
+    fn code_range(
+        &mut self,
+        code_hash: H256,
+        offset: usize,
+        len: usize,
+    ) -> Result<Bytes, Self::Error> {
+        DatabaseRef::code_range(self, code_hash, offset, len)
+    }
+
+    fn code_size(&mut self, code_hash: H256) -> Result<usize, Self::Error> {
+        DatabaseRef::code_size(self, code_hash)
+    }
+}
+
+impl<ExtDB: DatabaseRef> DatabaseCommit for CowDB<ExtDB> {
+    fn commit(&mut self, changes: Map<H160, Account>) {
+        for (address, mut account) in changes {
+            if account.is_destroyed {
+                self.changes.accounts.insert(
+                    address,
+                    LayerAccount {
+                        info: None,
+                        storage: Map::new(),
+                        storage_cleared: true,
+                    },
+                );
+                continue;
+            }
+            if let Some(code) = account.info.code.as_ref().filter(|code| !code.is_empty()) {
+                account.info.code_hash = code.hash();
+                self.changes
+                    .contracts
+                    .entry(account.info.code_hash)
+                    .or_insert_with(|| code.clone());
+            }
+            if account.info.code_hash.is_zero() {
+                account.info.code_hash = KECCAK_EMPTY;
+            }
+
+            let layer_account = self.changes.accounts.entry(address).or_default();
+            layer_account.info = Some(account.info);
+            if account.storage_cleared {
+                layer_account.storage.clear();
+                layer_account.storage_cleared = true;
+            }
+            layer_account.storage.extend(
+                account
+                    .storage
+                    .into_iter()
+                    .map(|(key, slot)| (key, slot.present_value())),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::InMemoryDB, StorageSlot};
+
+    fn change(balance: u64, storage: &[(u64, u64)]) -> Map<H160, Account> {
+        let mut account: Account = AccountInfo::from_balance(balance.into()).into();
+        account.is_touched = true;
+        account.storage = storage
+            .iter()
+            .map(|(key, value)| {
+                (
+                    U256::from(*key),
+                    StorageSlot::new_changed(U256::zero(), U256::from(*value)),
+                )
+            })
+            .collect();
+        Map::from([(H160::from_low_u64_be(1), account)])
+    }
+
+    #[test]
+    fn test_cow_db_branches() {
+        let address = H160::from_low_u64_be(1);
+        let mut base = InMemoryDB::default();
+        base.insert_account_info(address, AccountInfo::from_balance(10.into()));
+        base.insert_account_storage(address, 1.into(), 100.into())
+            .unwrap();
+        let balance = |db: &CowDB<InMemoryDB>| db.basic(address).unwrap().unwrap().balance;
+        let slot = |db: &CowDB<InMemoryDB>, key: u64| db.storage(address, key.into()).unwrap();
+
+        let mut root = CowDB::new(base);
+        root.commit(change(20, &[(2, 200)]));
+
+        let mut left = root.branch();
+        let mut right = root.branch();
+        left.commit(change(30, &[(1, 101)]));
+        let mut destroyed = right.branch();
+        let mut account: Account = AccountInfo::default().into();
+        account.is_destroyed = true;
+        destroyed.commit(Map::from([(address, account)]));
+
+        assert_eq!(
+            (balance(&root), slot(&root, 1), slot(&root, 2)),
+            (20.into(), 100.into(), 200.into())
+        );
+        assert_eq!(
+            (balance(&left), slot(&left, 1), slot(&left, 2)),
+            (30.into(), 101.into(), 200.into())
+        );
+        assert_eq!((balance(&right), slot(&right, 1)), (20.into(), 100.into()));
+        assert_eq!(destroyed.basic(address).unwrap(), None);
+        assert_eq!(slot(&destroyed, 1), U256::zero());
+        // all branches share the same base.
+        assert_eq!(Arc::strong_count(root.base()), 4);
+
+        assert_eq!(left.depth(), 2);
+        left.flatten();
+        assert_eq!(left.depth(), 1);
+        assert_eq!(
+            (balance(&left), slot(&left, 1), slot(&left, 2)),
+            (30.into(), 101.into(), 200.into())
+        );
+    }
+}
@@ -1,9 +1,10 @@
 use super::{DatabaseCommit, DatabaseRef};
 use crate::{interpreter::bytecode::Bytecode, Database, KECCAK_EMPTY};
 use crate::{Account, AccountInfo, Log};
-use alloc::vec::Vec;
-use core::convert::Infallible;
-use hashbrown::{hash_map::Entry, HashMap as Map};
+use alloc::{collections::BTreeMap, vec::Vec};
+use bytes::Bytes;
+use core::{convert::Infallible, hash::Hash, ops::RangeBounds};
+use hashbrown::{hash_map::Entry, HashMap as Map, HashSet};
 use primitive_types::{H160, H256, U256};
 use sha3::{Digest, Keccak256};
 
@@ -11,7 +12,12 @@ pub type InMemoryDB = CacheDB<EmptyDB>;
 
 impl InMemoryDB {
     pub fn default() -> Self {
-        CacheDB::new(EmptyDB {})
+        CacheDB::new(EmptyDB::default())
+    }
+
+    /// Database where hashes of blocks that are not inserted in `block_hashes` come from `provider`.
+    pub fn with_block_hash_provider(provider: BlockHashProvider) -> CacheDB<BlockHashProviderDB> {
+        CacheDB::new(BlockHashProviderDB::new(provider))
     }
 }
 
@@ -25,6 +31,146 @@ pub struct CacheDB<ExtDB: DatabaseRef> {
     pub logs: Vec<Log>,
     pub block_hashes: Map<U256, H256>,
     pub db: ExtDB,
+    /// Bounds on entries fetched from `db`. By default cache is unbounded.
+    pub limits: CacheLimits,
+    lru: CacheLru,
+    /// Previous values of entries changed by commit. Recorded only while there is a checkpoint.
+    undo_log: Option<Vec<CommitUndo>>,
+}
+
+/// Checkpoint of [CacheDB] returned by [CacheDB::checkpoint]. Used to revert all commits done after it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CacheCheckpoint {
+    undo_i: usize,
+}
+
+#[derive(Debug, Clone)]
+enum CommitUndo {
+    /// Account that was changed by commit. None if it was not present in cache.
+    Account {
+        address: H160,
+        previous: Option<Box<DbAccount>>,
+    },
+    /// Contract that was inserted by commit.
+    Contract { hash: H256 },
+}
+
+/// Limits on number of entries that [CacheDB] keeps after fetching them from underlying database.
+///
+/// Only clean entries (loaded from database and not modified) are counted and evicted.
+/// Accounts and contracts inserted by user or written by `commit` are never evicted.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct CacheLimits {
+    /// Max number of clean accounts.
+    pub max_accounts: Option<usize>,
+    /// Max number of storage slots summed over all clean accounts.
+    pub max_storage_slots: Option<usize>,
+    /// Max number of clean contracts.
+    pub max_contracts: Option<usize>,
+}
+
+/// Counters and sizes of [CacheDB].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct CacheStats {
+    /// Number of account, storage and code lookups that were found in cache.
+    pub hits: u64,
+    /// Number of account, storage and code lookups that needed to go to underlying database.
+    pub misses: u64,
+    pub evicted_accounts: u64,
+    pub evicted_contracts: u64,
+    /// Number of accounts currently in cache.
+    pub accounts: usize,
+    /// Number of accounts that can be evicted.
+    pub clean_accounts: usize,
+    /// Number of storage slots currently in cache.
+    pub storage_slots: usize,
+    /// Number of contracts currently in cache.
+    pub contracts: usize,
+}
+
+/// Summary of changes applied by [CacheDB::commit_with_report].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CommitReport {
+    /// Accounts created by CREATE/CREATE2 in committed transaction.
+    pub created: Vec<H160>,
+    /// Accounts that are selfdestructed, or touched while empty since Spurious Dragon, and removed.
+    pub destroyed: Vec<H160>,
+    /// Hashes of bytecodes that were not known before commit.
+    pub code_inserted: Vec<H256>,
+    /// Storage slots which value was changed.
+    pub slots_written: Vec<(H160, U256)>,
+}
+
+/// Bookkeeping of clean entries ordered by their last access.
+#[derive(Debug, Clone, Default)]
+struct CacheLru {
+    accounts: LruIndex<H160>,
+    contracts: LruIndex<H256>,
+    /// Sum of storage slots of accounts found in `accounts` index.
+    clean_storage_slots: usize,
+    hits: u64,
+    misses: u64,
+    evicted_accounts: u64,
+    evicted_contracts: u64,
+}
+
+#[derive(Debug, Clone)]
+struct LruIndex<K> {
+    tick: u64,
+    by_key: Map<K, u64>,
+    by_tick: BTreeMap<u64, K>,
+}
+
+impl<K> Default for LruIndex<K> {
+    fn default() -> Self {
+        Self {
+            tick: 0,
+            by_key: Map::new(),
+            by_tick: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Copy> LruIndex<K> {
+    /// Mark key as most recently used. Key is added if not present.
+    fn touch(&mut self, key: K) {
+        self.tick += 1;
+        if let Some(old) = self.by_key.insert(key, self.tick) {
+            self.by_tick.remove(&old);
+        }
+        self.by_tick.insert(self.tick, key);
+    }
+
+    /// Touch key only if it is already tracked. Returns true if key was tracked.
+    fn refresh(&mut self, key: &K) -> bool {
+        let tracked = self.by_key.contains_key(key);
+        if tracked {
+            self.touch(*key);
+        }
+        tracked
+    }
+
+    /// Stop tracking key. Returns true if key was tracked.
+    fn remove(&mut self, key: &K) -> bool {
+        match self.by_key.remove(key) {
+            Some(tick) => {
+                self.by_tick.remove(&tick);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn pop_oldest(&mut self) -> Option<K> {
+        let (&tick, &key) = self.by_tick.iter().next()?;
+        self.by_tick.remove(&tick);
+        self.by_key.remove(&key);
+        Some(key)
+    }
+
+    fn len(&self) -> usize {
+        self.by_key.len()
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -79,9 +225,10 @@ impl From<AccountInfo> for DbAccount {
 #[derive(Debug, Clone, Default)]
 pub enum AccountState {
     /// Before Spurious Dragon hardfork there were a difference between empty and not existing.
-    /// And we are flaging it here.
+    /// And we are flaging it here. Since Spurious Dragon touched empty accounts are removed and
+    /// committed with this state.
     NotExisting,
-    /// EVM touched this account. For newer hardfork this means it can be clearead/removed from state.
+    /// EVM touched this account. Before Spurious Dragon it exists even if it is empty.
     Touched,
     /// EVM cleared storage of this account, mostly by selfdestruct, we dont ask database for storage slots
     /// and asume they are U256::zero()
@@ -102,6 +249,148 @@ impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
             logs: Vec::default(),
             block_hashes: Map::new(),
             db,
+            limits: CacheLimits::default(),
+            lru: CacheLru::default(),
+            undo_log: None,
+        }
+    }
+
+    /// Create checkpoint that commits done after it can be reverted to.
+    ///
+    /// From first checkpoint, every commit records previous values of accounts it changes
+    /// until [CacheDB::clear_checkpoints] is called.
+    pub fn checkpoint(&mut self) -> CacheCheckpoint {
+        let undo_log = self.undo_log.get_or_insert_with(Vec::new);
+        CacheCheckpoint {
+            undo_i: undo_log.len(),
+        }
+    }
+
+    /// Revert all commits that happened after checkpoint. Checkpoints created after this one are invalidated.
+    pub fn rollback(&mut self, checkpoint: CacheCheckpoint) {
+        let mut undo_log = match self.undo_log.take() {
+            Some(undo_log) => undo_log,
+            None => return,
+        };
+        while undo_log.len() > checkpoint.undo_i {
+            match undo_log.pop().unwrap() {
+                CommitUndo::Account { address, previous } => {
+                    self.mark_dirty(address);
+                    match previous {
+                        Some(account) => self.accounts.insert(address, *account),
+                        None => self.accounts.remove(&address),
+                    };
+                }
+                CommitUndo::Contract { hash } => {
+                    self.contracts.remove(&hash);
+                }
+            }
+        }
+        self.undo_log = Some(undo_log);
+    }
+
+    /// Stop recording commits and drop all checkpoints.
+    pub fn clear_checkpoints(&mut self) {
+        self.undo_log = None;
+    }
+
+    /// Create CacheDB that evicts least recently used clean entries when limits are exceeded.
+    pub fn with_limits(db: ExtDB, limits: CacheLimits) -> Self {
+        let mut cache = Self::new(db);
+        cache.limits = limits;
+        cache
+    }
+
+    /// Return hit/miss counters and current size of the cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.lru.hits,
+            misses: self.lru.misses,
+            evicted_accounts: self.lru.evicted_accounts,
+            evicted_contracts: self.lru.evicted_contracts,
+            accounts: self.accounts.len(),
+            clean_accounts: self.lru.accounts.len(),
+            storage_slots: self.accounts.values().map(|acc| acc.storage.len()).sum(),
+            contracts: self.contracts.len(),
+        }
+    }
+
+    /// Cached accounts sorted by address. See [CacheDB::accounts_page] to read them in pages.
+    pub fn accounts_sorted(&self) -> Vec<(&H160, &DbAccount)> {
+        sorted_page(&self.accounts, .., usize::MAX)
+    }
+
+    /// At most `limit` cached accounts with address in `range`, sorted by address. Next page
+    /// starts after the last returned address.
+    pub fn accounts_page(
+        &self,
+        range: impl RangeBounds<H160>,
+        limit: usize,
+    ) -> Vec<(&H160, &DbAccount)> {
+        sorted_page(&self.accounts, range, limit)
+    }
+
+    /// Cached storage of account sorted by slot, empty if account is not cached.
+    pub fn storage_sorted(&self, address: H160) -> Vec<(&U256, &U256)> {
+        self.storage_page(address, .., usize::MAX)
+    }
+
+    /// At most `limit` cached slots of account in `range`, sorted by slot. Same as
+    /// [CacheDB::accounts_page].
+    pub fn storage_page(
+        &self,
+        address: H160,
+        range: impl RangeBounds<U256>,
+        limit: usize,
+    ) -> Vec<(&U256, &U256)> {
+        match self.accounts.get(&address) {
+            Some(account) => sorted_page(&account.storage, range, limit),
+            None => Vec::new(),
+        }
+    }
+
+    /// Mark account as modified so that it is never evicted.
+    fn mark_dirty(&mut self, address: H160) {
+        if self.lru.accounts.remove(&address) {
+            if let Some(account) = self.accounts.get(&address) {
+                self.lru.clean_storage_slots -= account.storage.len();
+            }
+        }
+    }
+
+    /// Evict least recently used clean entries until all limits are satisfied.
+    fn evict(&mut self) {
+        let limits = self.limits;
+        loop {
+            let accounts_over = limits
+                .max_accounts
+                .is_some_and(|max| self.lru.accounts.len() > max);
+            let storage_over = limits
+                .max_storage_slots
+                .is_some_and(|max| self.lru.clean_storage_slots > max);
+            if !accounts_over && !storage_over {
+                break;
+            }
+            match self.lru.accounts.pop_oldest() {
+                Some(address) => {
+                    if let Some(account) = self.accounts.remove(&address) {
+                        self.lru.clean_storage_slots -= account.storage.len();
+                    }
+                    self.lru.evicted_accounts += 1;
+                }
+                None => break,
+            }
+        }
+        if let Some(max) = limits.max_contracts {
+            while self.lru.contracts.len() > max {
+                match self.lru.contracts.pop_oldest() {
+                    Some(hash) => {
+                        self.contracts.remove(&hash);
+                        self.lru.evicted_contracts += 1;
+                    }
+                    None => break,
+                }
+            }
         }
     }
 
@@ -112,6 +401,8 @@ impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
                 self.contracts
                     .entry(account.code_hash)
                     .or_insert_with(|| code.clone());
+                // code is now owned by user or commit and should not be evicted
+                self.lru.contracts.remove(&account.code_hash);
             }
         }
         if account.code_hash.is_zero() {
@@ -122,10 +413,13 @@ impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
     /// Insert account info but not override storage
     pub fn insert_account_info(&mut self, address: H160, mut info: AccountInfo) {
         self.insert_contract(&mut info);
+        self.mark_dirty(address);
         self.accounts.entry(address).or_default().info = info;
     }
 
+    /// Load account that is going to be modified by user.
     fn load_account(&mut self, address: H160) -> Result<&mut DbAccount, ExtDB::Error> {
+        self.mark_dirty(address);
         let db = &self.db;
         match self.accounts.entry(address) {
             Entry::Occupied(entry) => Ok(entry.into_mut()),
@@ -152,6 +446,73 @@ impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
         Ok(())
     }
 
+    /// Load accounts and storage slots into cache so that execution does not need to fetch them
+    /// one by one. Access list can be predicted with [crate::analysis::predict_access_list].
+    pub fn prewarm(&mut self, access_list: &[(H160, Vec<U256>)]) -> Result<(), ExtDB::Error> {
+        for (address, slots) in access_list {
+            self.basic(*address)?;
+            for slot in slots {
+                self.storage(*address, *slot)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Load accounts and storage slots that are not cached yet in one batch of
+    /// [DatabaseRef::basic_batch] and one of [DatabaseRef::storage_batch]. Accounts of `slots`
+    /// are loaded too. Fetching from [super::Web3DB] is then done with concurrent requests.
+    ///
+    /// Addresses can be hinted by [crate::analysis::calldata_address_hints].
+    pub fn prefetch(
+        &mut self,
+        addresses: &[H160],
+        slots: &[(H160, U256)],
+    ) -> Result<(), ExtDB::Error> {
+        let mut seen_accounts = HashSet::new();
+        let mut missing_accounts = Vec::new();
+        for address in addresses
+            .iter()
+            .chain(slots.iter().map(|(address, _)| address))
+        {
+            if !self.accounts.contains_key(address) && seen_accounts.insert(*address) {
+                missing_accounts.push(*address);
+            }
+        }
+        let infos = self.db.basic_batch(&missing_accounts);
+        for (address, info) in missing_accounts.into_iter().zip(infos) {
+            self.lru.misses += 1;
+            self.lru.accounts.touch(address);
+            self.accounts.insert(address, info?.into());
+        }
+
+        let mut seen_slots = HashSet::new();
+        let mut missing_slots = Vec::new();
+        for (address, index) in slots {
+            let account = &self.accounts[address];
+            if account.storage.contains_key(index)
+                || matches!(
+                    account.account_state,
+                    AccountState::StorageCleared | AccountState::NotExisting
+                )
+                || !seen_slots.insert((*address, *index))
+            {
+                continue;
+            }
+            missing_slots.push((*address, *index));
+        }
+        let values = self.db.storage_batch(&missing_slots);
+        for ((address, index), value) in missing_slots.into_iter().zip(values) {
+            self.lru.misses += 1;
+            let account = self.accounts.get_mut(&address).unwrap();
+            account.storage.insert(index, value?);
+            if self.lru.accounts.refresh(&address) {
+                self.lru.clean_storage_slots += 1;
+            }
+        }
+        self.evict();
+        Ok(())
+    }
+
     /// replace account storage without overriding account info
     pub fn replace_account_storage(
         &mut self,
@@ -165,27 +526,59 @@ impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
     }
 }
 
-impl<ExtDB: DatabaseRef> DatabaseCommit for CacheDB<ExtDB> {
-    fn commit(&mut self, changes: Map<H160, Account>) {
+impl<ExtDB: DatabaseRef> CacheDB<ExtDB> {
+    /// Apply changes same as [DatabaseCommit::commit] and return summary of what was changed.
+    pub fn commit_with_report(&mut self, changes: Map<H160, Account>) -> CommitReport {
+        let mut report = CommitReport::default();
         for (address, mut account) in changes {
+            self.mark_dirty(address);
+            if let Some(undo_log) = self.undo_log.as_mut() {
+                undo_log.push(CommitUndo::Account {
+                    address,
+                    previous: self.accounts.get(&address).cloned().map(Box::new),
+                });
+            }
             if account.is_destroyed {
+                // balance of destroyed account is already transferred to beneficiary
+                // that is present in changes as touched account.
                 let db_account = self.accounts.entry(address).or_default();
                 db_account.storage.clear();
                 db_account.account_state = AccountState::NotExisting;
                 db_account.info = AccountInfo::default();
+                report.destroyed.push(address);
                 continue;
             }
+            let code_hash = account.info.code.as_ref().map(|code| code.hash());
+            // `Option::is_none_or` needs a newer toolchain than this crate targets.
+            #[allow(clippy::unnecessary_map_or)]
+            let had_code = code_hash.map_or(true, |hash| self.contracts.contains_key(&hash));
             self.insert_contract(&mut account.info);
+            if !had_code && self.contracts.contains_key(&account.info.code_hash) {
+                report.code_inserted.push(account.info.code_hash);
+                if let Some(undo_log) = self.undo_log.as_mut() {
+                    undo_log.push(CommitUndo::Contract {
+                        hash: account.info.code_hash,
+                    });
+                }
+            }
 
             let db_account = self.accounts.entry(address).or_default();
             db_account.info = account.info;
 
             db_account.account_state = if account.storage_cleared {
                 db_account.storage.clear();
+                report.created.push(address);
                 AccountState::StorageCleared
             } else {
                 AccountState::Touched
             };
+            report.slots_written.extend(
+                account
+                    .storage
+                    .iter()
+                    .filter(|(_, slot)| slot.is_changed())
+                    .map(|(key, _)| (address, *key)),
+            );
             db_account.storage.extend(
                 account
                     .storage
@@ -193,6 +586,29 @@ impl<ExtDB: DatabaseRef> DatabaseCommit for CacheDB<ExtDB> {
                     .map(|(key, value)| (key, value.present_value())),
             );
         }
+        report
+    }
+}
+
+/// Smallest `limit` entries of `map` with key in `range`, sorted by key. Only entries of the
+/// page are sorted.
+fn sorted_page<K: Ord, V>(
+    map: &Map<K, V>,
+    range: impl RangeBounds<K>,
+    limit: usize,
+) -> Vec<(&K, &V)> {
+    let mut entries: Vec<_> = map.iter().filter(|(key, _)| range.contains(key)).collect();
+    if limit < entries.len() {
+        entries.select_nth_unstable_by(limit, |a, b| a.0.cmp(b.0));
+        entries.truncate(limit);
+    }
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+impl<ExtDB: DatabaseRef> DatabaseCommit for CacheDB<ExtDB> {
+    fn commit(&mut self, changes: Map<H160, Account>) {
+        self.commit_with_report(changes);
     }
 }
 
@@ -212,44 +628,64 @@ impl<ExtDB: DatabaseRef> Database for CacheDB<ExtDB> {
 
     fn basic(&mut self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
         let basic = match self.accounts.entry(address) {
-            Entry::Occupied(entry) => entry.into_mut(),
-            Entry::Vacant(entry) => entry.insert(
-                self.db
-                    .basic(address)?
-                    .map(|info| DbAccount {
-                        info,
-                        ..Default::default()
-                    })
-                    .unwrap_or_else(DbAccount::new_not_existing),
-            ),
+            Entry::Occupied(entry) => {
+                self.lru.hits += 1;
+                self.lru.accounts.refresh(&address);
+                entry.into_mut().info()
+            }
+            Entry::Vacant(entry) => {
+                self.lru.misses += 1;
+                self.lru.accounts.touch(address);
+                let info = entry
+                    .insert(
+                        self.db
+                            .basic(address)?
+                            .map(|info| DbAccount {
+                                info,
+                                ..Default::default()
+                            })
+                            .unwrap_or_else(DbAccount::new_not_existing),
+                    )
+                    .info();
+                self.evict();
+                info
+            }
         };
-        Ok(basic.info())
+        Ok(basic)
     }
 
     /// Get the value in an account's storage slot.
     ///
     /// It is assumed that account is already loaded.
     fn storage(&mut self, address: H160, index: U256) -> Result<U256, Self::Error> {
-        match self.accounts.entry(address) {
+        let value = match self.accounts.entry(address) {
             Entry::Occupied(mut acc_entry) => {
                 let acc_entry = acc_entry.get_mut();
                 match acc_entry.storage.entry(index) {
-                    Entry::Occupied(entry) => Ok(*entry.get()),
+                    Entry::Occupied(entry) => {
+                        self.lru.hits += 1;
+                        self.lru.accounts.refresh(&address);
+                        return Ok(*entry.get());
+                    }
                     Entry::Vacant(entry) => {
                         if matches!(
                             acc_entry.account_state,
                             AccountState::StorageCleared | AccountState::NotExisting
                         ) {
-                            Ok(U256::zero())
-                        } else {
-                            let slot = self.db.storage(address, index)?;
-                            entry.insert(slot);
-                            Ok(slot)
+                            return Ok(U256::zero());
                         }
+                        self.lru.misses += 1;
+                        let slot = self.db.storage(address, index)?;
+                        entry.insert(slot);
+                        if self.lru.accounts.refresh(&address) {
+                            self.lru.clean_storage_slots += 1;
+                        }
+                        slot
                     }
                 }
             }
             Entry::Vacant(acc_entry) => {
+                self.lru.misses += 1;
                 // acc needs to be loaded for us to access slots.
                 let info = self.db.basic(address)?;
                 let (account, value) = if info.is_some() {
@@ -260,21 +696,53 @@ impl<ExtDB: DatabaseRef> Database for CacheDB<ExtDB> {
                 } else {
                     (info.into(), U256::zero())
                 };
+                self.lru.clean_storage_slots += account.storage.len();
+                self.lru.accounts.touch(address);
                 acc_entry.insert(account);
-                Ok(value)
+                value
             }
-        }
+        };
+        self.evict();
+        Ok(value)
     }
 
     fn code_by_hash(&mut self, code_hash: H256) -> Result<Bytecode, Self::Error> {
         match self.contracts.entry(code_hash) {
-            Entry::Occupied(entry) => Ok(entry.get().clone()),
+            Entry::Occupied(entry) => {
+                self.lru.hits += 1;
+                self.lru.contracts.refresh(&code_hash);
+                Ok(entry.get().clone())
+            }
             Entry::Vacant(entry) => {
+                self.lru.misses += 1;
                 // if you return code bytes when basic fn is called this function is not needed.
-                Ok(entry.insert(self.db.code_by_hash(code_hash)?).clone())
+                let code = entry.insert(self.db.code_by_hash(code_hash)?).clone();
+                self.lru.contracts.touch(code_hash);
+                self.evict();
+                Ok(code)
             }
         }
     }
+
+    /// Range of cached code, otherwise it is read from underlying database without caching.
+    fn code_range(
+        &mut self,
+        code_hash: H256,
+        offset: usize,
+        len: usize,
+    ) -> Result<Bytes, Self::Error> {
+        match self.contracts.get(&code_hash) {
+            Some(code) => Ok(code.range(offset, len)),
+            None => self.db.code_range(code_hash, offset, len),
+        }
+    }
+
+    fn code_size(&mut self, code_hash: H256) -> Result<usize, Self::Error> {
+        match self.contracts.get(&code_hash) {
+            Some(code) => Ok(code.len()),
+            None => self.db.code_size(code_hash),
+        }
+    }
 }
 
 impl<ExtDB: DatabaseRef> DatabaseRef for CacheDB<ExtDB> {
@@ -313,6 +781,20 @@ impl<ExtDB: DatabaseRef> DatabaseRef for CacheDB<ExtDB> {
         }
     }
 
+    fn code_range(&self, code_hash: H256, offset: usize, len: usize) -> Result<Bytes, Self::Error> {
+        match self.contracts.get(&code_hash) {
+            Some(code) => Ok(code.range(offset, len)),
+            None => self.db.code_range(code_hash, offset, len),
+        }
+    }
+
+    fn code_size(&self, code_hash: H256) -> Result<usize, Self::Error> {
+        match self.contracts.get(&code_hash) {
+            Some(code) => Ok(code.len()),
+            None => self.db.code_size(code_hash),
+        }
+    }
+
     fn block_hash(&self, number: U256) -> Result<H256, Self::Error> {
         match self.block_hashes.get(&number) {
             Some(entry) => Ok(*entry),
@@ -321,6 +803,42 @@ impl<ExtDB: DatabaseRef> DatabaseRef for CacheDB<ExtDB> {
     }
 }
 
+/// Source of block hashes of [BlockHashProviderDB].
+#[derive(Debug, Default, Clone)]
+pub enum BlockHashProvider {
+    /// Hash is keccak of big endian block number. Any BLOCKHASH read succeeds.
+    #[default]
+    KeccakOfNumber,
+    /// Hash is always zero, as if block was pruned from history.
+    Zero,
+    /// Every read fails with [BlockHashNotFound]. Useful for tests that don't expect BLOCKHASH.
+    Error,
+    /// Hashes are taken from map and reading missing block fails with [BlockHashNotFound].
+    Map(Map<U256, H256>),
+}
+
+impl BlockHashProvider {
+    pub fn block_hash(&self, number: U256) -> Result<H256, BlockHashNotFound> {
+        match self {
+            Self::KeccakOfNumber => {
+                let mut buffer: [u8; 4 * 8] = [0; 4 * 8];
+                number.to_big_endian(&mut buffer);
+                Ok(H256::from_slice(&Keccak256::digest(buffer)))
+            }
+            Self::Zero => Ok(H256::zero()),
+            Self::Error => Err(BlockHashNotFound(number)),
+            Self::Map(hashes) => hashes
+                .get(&number)
+                .copied()
+                .ok_or(BlockHashNotFound(number)),
+        }
+    }
+}
+
+/// Hash of block with this number is not available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHashNotFound(pub U256);
+
 /// An empty database that always returns default values when queried.
 #[derive(Debug, Default, Clone)]
 pub struct EmptyDB();
@@ -348,6 +866,43 @@ impl DatabaseRef for EmptyDB {
     }
 }
 
+/// Empty database like [EmptyDB] whose block hashes are returned by [BlockHashProvider], reads
+/// of strict providers can fail. See [super::CallbackDB] for database whose values are provided
+/// by closures.
+#[derive(Debug, Default, Clone)]
+pub struct BlockHashProviderDB {
+    pub block_hash_provider: BlockHashProvider,
+}
+
+impl BlockHashProviderDB {
+    pub fn new(block_hash_provider: BlockHashProvider) -> Self {
+        Self {
+            block_hash_provider,
+        }
+    }
+}
+
+impl DatabaseRef for BlockHashProviderDB {
+    type Error = BlockHashNotFound;
+    /// Get basic account information.
+    fn basic(&self, _address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+        Ok(None)
+    }
+    /// Get account code by its hash
+    fn code_by_hash(&self, _code_hash: H256) -> Result<Bytecode, Self::Error> {
+        Ok(Bytecode::new())
+    }
+    /// Get storage value of address at index.
+    fn storage(&self, _address: H160, _index: U256) -> Result<U256, Self::Error> {
+        Ok(U256::default())
+    }
+
+    // History related
+    fn block_hash(&self, number: U256) -> Result<H256, Self::Error> {
+        self.block_hash_provider.block_hash(number)
+    }
+}
+
 /// Custom benchmarking DB that only has account info for the zero address.
 ///
 /// Any other address will return an empty account.
@@ -394,11 +949,14 @@ impl Database for BenchmarkDB {
 
 #[cfg(test)]
 mod tests {
-    use primitive_types::H160;
+    use primitive_types::{H160, H256, U256};
 
     use crate::{AccountInfo, Database};
 
-    use super::{CacheDB, EmptyDB};
+    use super::{BlockHashProvider, CacheDB, CacheLimits, EmptyDB, InMemoryDB};
+    use crate::{Account, Bytecode, DatabaseCommit, StorageSlot};
+    use bytes::Bytes;
+    use hashbrown::HashMap as Map;
 
     #[test]
     pub fn test_insert_account_storage() {
@@ -445,4 +1003,270 @@ mod tests {
         assert_eq!(new_state.storage(account, key0), Ok(0.into()));
         assert_eq!(new_state.storage(account, key1), Ok(value1));
     }
+
+    #[test]
+    pub fn test_evict_clean_accounts() {
+        let limits = CacheLimits {
+            max_accounts: Some(2),
+            ..Default::default()
+        };
+        let mut state = CacheDB::with_limits(EmptyDB::default(), limits);
+        let dirty = H160::from_low_u64_be(100);
+        state.insert_account_info(dirty, AccountInfo::from_balance(1.into()));
+
+        for i in 1..=3 {
+            let _ = state.basic(H160::from_low_u64_be(i));
+        }
+        // first loaded account is least recently used and is evicted.
+        assert!(!state.accounts.contains_key(&H160::from_low_u64_be(1)));
+        assert!(state.accounts.contains_key(&H160::from_low_u64_be(2)));
+        assert!(state.accounts.contains_key(&H160::from_low_u64_be(3)));
+        // inserted account is never evicted.
+        assert!(state.accounts.contains_key(&dirty));
+
+        // touch account 2 so that 3 becomes oldest.
+        let _ = state.basic(H160::from_low_u64_be(2));
+        let _ = state.basic(H160::from_low_u64_be(4));
+        assert!(state.accounts.contains_key(&H160::from_low_u64_be(2)));
+        assert!(!state.accounts.contains_key(&H160::from_low_u64_be(3)));
+
+        let stats = state.cache_stats();
+        assert_eq!(stats.evicted_accounts, 2);
+        assert_eq!(stats.clean_accounts, 2);
+        assert_eq!(stats.accounts, 3);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 4);
+    }
+
+    #[test]
+    pub fn test_evict_by_storage_limit() {
+        let account = H160::from_low_u64_be(42);
+        let mut init_state = CacheDB::new(EmptyDB::default());
+        init_state.insert_account_info(account, AccountInfo::from_balance(1.into()));
+
+        let limits = CacheLimits {
+            max_storage_slots: Some(2),
+            ..Default::default()
+        };
+        let mut state = CacheDB::with_limits(init_state, limits);
+        let _ = state.basic(account);
+        let _ = state.storage(account, 1.into());
+        let _ = state.storage(account, 2.into());
+        assert!(state.accounts.contains_key(&account));
+        let _ = state.storage(account, 3.into());
+        assert!(!state.accounts.contains_key(&account));
+        // value is still readable from underlying database.
+        assert_eq!(state.basic(account).unwrap().unwrap().balance, 1.into());
+    }
+
+    #[test]
+    pub fn test_prefetch() {
+        let account = H160::from_low_u64_be(42);
+        let missing = H160::from_low_u64_be(43);
+        let mut remote = InMemoryDB::default();
+        remote.insert_account_info(account, AccountInfo::from_balance(1.into()));
+        remote
+            .insert_account_storage(account, 1.into(), 5.into())
+            .unwrap();
+
+        let mut state = CacheDB::new(crate::db::InstrumentedDB::new(remote));
+        let _ = state.storage(account, 2.into());
+        state.db.reset_stats();
+        let slots = [
+            (account, 1.into()),
+            (account, 2.into()),
+            (missing, 1.into()),
+        ];
+        state.prefetch(&[account, missing], &slots).unwrap();
+        // account and slot 2 were cached and storage of not existing account is not fetched.
+        let stats = state.db.reset_stats();
+        assert_eq!((stats.basic.calls, stats.storage.calls), (1, 1));
+
+        assert_eq!(state.storage(account, 1.into()), Ok(5.into()));
+        assert_eq!(state.basic(missing), Ok(None));
+        assert_eq!(state.db.stats().calls(), 0);
+        assert_eq!(state.cache_stats().storage_slots, 2);
+    }
+
+    #[test]
+    pub fn test_commit_report() {
+        let created = H160::from_low_u64_be(1);
+        let destroyed = H160::from_low_u64_be(2);
+        let beneficiary = H160::from_low_u64_be(3);
+        let mut state = CacheDB::new(EmptyDB::default());
+        state.insert_account_info(destroyed, AccountInfo::from_balance(10.into()));
+        state.insert_account_info(beneficiary, AccountInfo::from_balance(5.into()));
+
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x60, 0x00]));
+        let mut created_acc: Account = AccountInfo::new(0.into(), 1, code.clone()).into();
+        created_acc.storage_cleared = true;
+        created_acc
+            .storage
+            .insert(7.into(), StorageSlot::new_changed(0.into(), 9.into()));
+        let mut destroyed_acc: Account = AccountInfo::default().into();
+        destroyed_acc.is_destroyed = true;
+        let beneficiary_acc: Account = AccountInfo::from_balance(15.into()).into();
+
+        let report = state.commit_with_report(Map::from([
+            (created, created_acc),
+            (destroyed, destroyed_acc),
+            (beneficiary, beneficiary_acc),
+        ]));
+        assert_eq!(report.created, vec![created]);
+        assert_eq!(report.destroyed, vec![destroyed]);
+        assert_eq!(report.code_inserted, vec![code.hash()]);
+        assert_eq!(report.slots_written, vec![(created, 7.into())]);
+
+        assert_eq!(state.basic(destroyed).unwrap(), None);
+        assert_eq!(
+            state.basic(beneficiary).unwrap().unwrap().balance,
+            15.into()
+        );
+        assert_eq!(state.storage(created, 7.into()), Ok(9.into()));
+
+        // same code is not reported twice.
+        let again: Account = AccountInfo::new(0.into(), 1, code).into();
+        let report = state.commit_with_report(Map::from([(created, again)]));
+        assert!(report.code_inserted.is_empty());
+    }
+
+    #[test]
+    pub fn test_commit_rollback() {
+        let account = H160::from_low_u64_be(1);
+        let new_account = H160::from_low_u64_be(2);
+        let mut state = CacheDB::new(EmptyDB::default());
+        state.insert_account_info(account, AccountInfo::from_balance(10.into()));
+
+        let commit = |state: &mut CacheDB<EmptyDB>, address, balance: u64| {
+            let mut acc: Account = AccountInfo::from_balance(balance.into()).into();
+            acc.storage
+                .insert(1.into(), StorageSlot::new_changed(0.into(), balance.into()));
+            state.commit(Map::from([(address, acc)]));
+        };
+
+        let first = state.checkpoint();
+        commit(&mut state, account, 20);
+        let second = state.checkpoint();
+        commit(&mut state, account, 30);
+        commit(&mut state, new_account, 5);
+        assert_eq!(state.basic(account).unwrap().unwrap().balance, 30.into());
+
+        state.rollback(second);
+        assert_eq!(state.basic(account).unwrap().unwrap().balance, 20.into());
+        assert_eq!(state.storage(account, 1.into()), Ok(20.into()));
+        assert!(!state.accounts.contains_key(&new_account));
+
+        state.rollback(first);
+        assert_eq!(state.basic(account).unwrap().unwrap().balance, 10.into());
+        assert_eq!(state.storage(account, 1.into()), Ok(0.into()));
+    }
+
+    /// Execute BLOCKHASH of previous block and store result in slot 0.
+    fn blockhash<ExtDB: super::DatabaseRef>(db: CacheDB<ExtDB>) -> (crate::Return, U256) {
+        use crate::{opcode::*, TransactTo};
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = db;
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(
+                U256::zero(),
+                0,
+                Bytecode::new_raw(Bytes::from(vec![
+                    PUSH1, 9, BLOCKHASH, PUSH1, 0, SSTORE, STOP,
+                ])),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.block.number = 10.into();
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        let (result, state) = evm.transact();
+        let value = state
+            .get(&contract)
+            .and_then(|account| account.storage.get(&U256::zero()))
+            .map(|slot| slot.present_value())
+            .unwrap_or_default();
+        (result.exit_reason, value)
+    }
+
+    #[test]
+    fn test_block_hash_provider() {
+        let hash = H256::repeat_byte(0xaa);
+        assert_eq!(
+            BlockHashProvider::Map(Map::from([(9.into(), hash)])).block_hash(9.into()),
+            Ok(hash)
+        );
+        assert_eq!(
+            BlockHashProvider::Zero.block_hash(9.into()),
+            Ok(H256::zero())
+        );
+
+        let (exit, value) = blockhash(InMemoryDB::default());
+        assert_eq!(exit, crate::Return::Stop);
+        assert!(!value.is_zero());
+
+        // strict providers fail transaction on unexpected read.
+        let (exit, _) = blockhash(InMemoryDB::with_block_hash_provider(
+            BlockHashProvider::Error,
+        ));
+        assert_eq!(exit, crate::Return::FatalExternalError);
+        let (exit, _) = blockhash(InMemoryDB::with_block_hash_provider(
+            BlockHashProvider::Map(Map::new()),
+        ));
+        assert_eq!(exit, crate::Return::FatalExternalError);
+
+        // hashes inserted into cache are used before provider.
+        let mut db = InMemoryDB::with_block_hash_provider(BlockHashProvider::Error);
+        db.block_hashes.insert(9.into(), hash);
+        assert_eq!(
+            blockhash(db),
+            (crate::Return::Stop, U256::from_big_endian(hash.as_bytes()))
+        );
+    }
+
+    #[test]
+    pub fn test_sorted_pages() {
+        use core::ops::Bound;
+
+        let mut db = InMemoryDB::default();
+        for i in (1..=10u64).rev() {
+            let address = H160::from_low_u64_be(i);
+            db.insert_account_info(address, AccountInfo::from_balance(U256::from(i)));
+            db.insert_account_storage(address, U256::from(100 - i), U256::from(i))
+                .unwrap();
+            db.insert_account_storage(address, U256::from(i), U256::from(i))
+                .unwrap();
+        }
+        let addresses = |page: Vec<(&H160, &super::DbAccount)>| -> Vec<u64> {
+            page.iter().map(|(a, _)| a.to_low_u64_be()).collect()
+        };
+        assert_eq!(
+            addresses(db.accounts_sorted()),
+            (1..=10).collect::<Vec<_>>()
+        );
+
+        // read accounts in pages of 4.
+        let mut pages = Vec::new();
+        let mut start = Bound::Unbounded;
+        loop {
+            let page = db.accounts_page((start, Bound::Unbounded), 4);
+            let Some((last, _)) = page.last() else {
+                break;
+            };
+            start = Bound::Excluded(**last);
+            pages.push(addresses(page));
+        }
+        assert_eq!(pages, vec![vec![1, 2, 3, 4], vec![5, 6, 7, 8], vec![9, 10]]);
+
+        let slots: Vec<_> = db
+            .storage_sorted(H160::from_low_u64_be(3))
+            .into_iter()
+            .map(|(slot, value)| (slot.as_u64(), value.as_u64()))
+            .collect();
+        assert_eq!(slots, vec![(3, 3), (97, 3)]);
+        let page = db.storage_page(H160::from_low_u64_be(3), U256::from(4).., 10);
+        assert_eq!(page, vec![(&U256::from(97), &U256::from(3))]);
+        assert!(db.storage_sorted(H160::from_low_u64_be(11)).is_empty());
+    }
 }
@@ -0,0 +1,494 @@
+use super::{Database, DatabaseCommit, DatabaseRef};
+use crate::{interpreter::bytecode::Bytecode, Account, AccountInfo};
+use bytes::Bytes;
+use hashbrown::HashMap as Map;
+use primitive_types::{H160, H256, U256};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Number of buckets of [LatencyHistogram].
+pub const LATENCY_BUCKETS: usize = 32;
+
+/// Wrapper of [Database] or [DatabaseRef] that counts calls of every method, bytes they
+/// returned and their latency. Used to find out how many requests execution does to remote
+/// state and how well caches above it work.
+///
+/// ```ignore
+/// let db = InstrumentedDB::new(CacheDB::new(remote));
+/// // execute transactions ...
+/// let stats = db.stats();
+/// println!("{} storage reads, p99 {:?}", stats.storage.calls, stats.storage.latency.quantile(0.99));
+/// ```
+#[derive(Debug, Default)]
+pub struct InstrumentedDB<DB> {
+    db: DB,
+    stats: Mutex<DbStats>,
+}
+
+/// Statistics of every method of [Database].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DbStats {
+    pub basic: MethodStats,
+    pub code_by_hash: MethodStats,
+    pub storage: MethodStats,
+    pub block_hash: MethodStats,
+    pub code_range: MethodStats,
+    pub code_size: MethodStats,
+}
+
+impl DbStats {
+    /// Number of calls of all methods.
+    pub fn calls(&self) -> u64 {
+        self.methods().iter().map(|method| method.calls).sum()
+    }
+
+    /// Bytes returned by all methods.
+    pub fn bytes(&self) -> u64 {
+        self.methods().iter().map(|method| method.bytes).sum()
+    }
+
+    /// Time spent in all methods.
+    pub fn total_latency(&self) -> Duration {
+        self.methods()
+            .iter()
+            .map(|method| method.latency.total)
+            .sum()
+    }
+
+    fn methods(&self) -> [&MethodStats; 6] {
+        [
+            &self.basic,
+            &self.code_by_hash,
+            &self.storage,
+            &self.block_hash,
+            &self.code_range,
+            &self.code_size,
+        ]
+    }
+}
+
+/// Statistics of one method of [Database].
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct MethodStats {
+    pub calls: u64,
+    /// Calls that returned error.
+    pub errors: u64,
+    /// Size of returned values. Account info is 72 bytes (balance, nonce and code hash) or zero
+    /// if account doesn't exist, code and range of code are their length, code size is 8 bytes, storage value and block hash are 32 bytes.
+    pub bytes: u64,
+    pub latency: LatencyHistogram,
+}
+
+/// Histogram of call durations. Bucket `i` counts calls that took less than `2^i`
+/// microseconds and were not counted in previous bucket, last bucket counts all slower calls.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct LatencyHistogram {
+    pub buckets: [u64; LATENCY_BUCKETS],
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros();
+        let bucket = (128 - micros.leading_zeros()) as usize;
+        self.buckets[bucket.min(LATENCY_BUCKETS - 1)] += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+
+    /// Number of recorded calls.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count() {
+            0 => Duration::ZERO,
+            count => {
+                let nanos = self.total.as_nanos() / count as u128;
+                Duration::new(
+                    (nanos / 1_000_000_000) as u64,
+                    (nanos % 1_000_000_000) as u32,
+                )
+            }
+        }
+    }
+
+    /// Upper bound of latency that `quantile` of calls didn't exceed, `quantile` is between
+    /// zero and one. Precision is that of buckets, bounded by [LatencyHistogram::max].
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        let target = (quantile.clamp(0.0, 1.0) * self.count() as f64).ceil() as u64;
+        let mut seen = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target && seen != 0 {
+                return Duration::from_micros(1 << i).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+impl<DB> InstrumentedDB<DB> {
+    pub fn new(db: DB) -> Self {
+        Self {
+            db,
+            stats: Default::default(),
+        }
+    }
+
+    pub fn db(&self) -> &DB {
+        &self.db
+    }
+
+    pub fn db_mut(&mut self) -> &mut DB {
+        &mut self.db
+    }
+
+    pub fn into_inner(self) -> DB {
+        self.db
+    }
+
+    /// Statistics recorded since database was created or since last [InstrumentedDB::reset_stats].
+    pub fn stats(&self) -> DbStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Clear recorded statistics and return them.
+    pub fn reset_stats(&self) -> DbStats {
+        core::mem::take(&mut *self.stats.lock().unwrap())
+    }
+}
+
+/// Record call that started at `start` and returned `result`.
+fn record<T, E>(
+    stats: &mut MethodStats,
+    start: Instant,
+    result: &Result<T, E>,
+    bytes: impl FnOnce(&T) -> usize,
+) {
+    stats.latency.record(start.elapsed());
+    stats.calls += 1;
+    match result {
+        Ok(value) => stats.bytes += bytes(value) as u64,
+        Err(_) => stats.errors += 1,
+    }
+}
+
+fn basic_bytes(info: &Option<AccountInfo>) -> usize {
+    if info.is_some() {
+        72
+    } else {
+        0
+    }
+}
+
+impl<DB: Database> Database for InstrumentedDB<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+        let start = Instant::now();
+        let result = self.db.basic(address);
+        let stats = &mut self.stats.get_mut().unwrap().basic;
+        record(stats, start, &result, basic_bytes);
+        result
+    }
+
+    fn code_by_hash(&mut self, code_hash: H256) -> Result<Bytecode, Self::Error> {
+        let start = Instant::now();
+        let result = self.db.code_by_hash(code_hash);
+        let stats = &mut self.stats.get_mut().unwrap().code_by_hash;
+        record(stats, start, &result, Bytecode::len);
+        result
+    }
+
+    fn storage(&mut self, address: H160, index: U256) -> Result<U256, Self::Error> {
+        let start = Instant::now();
+        let result = self.db.storage(address, index);
+        let stats = &mut self.stats.get_mut().unwrap().storage;
+        record(stats, start, &result, |_| 32);
+        result
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<H256, Self::Error> {
+        let start = Instant::now();
+        let result = self.db.block_hash(number);
+        let stats = &mut self.stats.get_mut().unwrap().block_hash;
+        record(stats, start, &result, |_| 32);
+        result
+    }
+
+    fn code_range(
+        &mut self,
+        code_hash: H256,
+        offset: usize,
+        len: usize,
+    ) -> Result<Bytes, Self::Error> {
+        let start = Instant::now();
+        let result = self.db.code_range(code_hash, offset, len);
+        let stats = &mut self.stats.get_mut().unwrap().code_range;
+        record(stats, start, &result, Bytes::len);
+        result
+    }
+
+    fn code_size(&mut self, code_hash: H256) -> Result<usize, Self::Error> {
+        let start = Instant::now();
+        let result = self.db.code_size(code_hash);
+        let stats = &mut self.stats.get_mut().unwrap().code_size;
+        record(stats, start, &result, |_| 8);
+        result
+    }
+}
+
+impl<DB: DatabaseRef> DatabaseRef for InstrumentedDB<DB> {
+    type Error = DB::Error;
+
+    fn basic(&self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+        let start = Instant::now();
+        let result = self.db.basic(address);
+        let mut stats = self.stats.lock().unwrap();
+        record(&mut stats.basic, start, &result, basic_bytes);
+        result
+    }
+
+    fn code_by_hash(&self, code_hash: H256) -> Result<Bytecode, Self::Error> {
+        let start = Instant::now();
+        let result = self.db.code_by_hash(code_hash);
+        let mut stats = self.stats.lock().unwrap();
+        record(&mut stats.code_by_hash, start, &result, Bytecode::len);
+        result
+    }
+
+    fn storage(&self, address: H160, index: U256) -> Result<U256, Self::Error> {
+        let start = Instant::now();
+        let result = self.db.storage(address, index);
+        let mut stats = self.stats.lock().unwrap();
+        record(&mut stats.storage, start, &result, |_| 32);
+        result
+    }
+
+    fn block_hash(&self, number: U256) -> Result<H256, Self::Error> {
+        let start = Instant::now();
+        let result = self.db.block_hash(number);
+        let mut stats = self.stats.lock().unwrap();
+        record(&mut stats.block_hash, start, &result, |_| 32);
+        result
+    }
+
+    fn code_range(&self, code_hash: H256, offset: usize, len: usize) -> Result<Bytes, Self::Error> {
+        let start = Instant::now();
+        let result = self.db.code_range(code_hash, offset, len);
+        let mut stats = self.stats.lock().unwrap();
+        record(&mut stats.code_range, start, &result, Bytes::len);
+        result
+    }
+
+    fn code_size(&self, code_hash: H256) -> Result<usize, Self::Error> {
+        let start = Instant::now();
+        let result = self.db.code_size(code_hash);
+        let mut stats = self.stats.lock().unwrap();
+        record(&mut stats.code_size, start, &result, |_| 8);
+        result
+    }
+
+    /// Batch is forwarded to keep concurrent fetching of wrapped database. Every account is
+    /// recorded as one call that took as long as the whole batch.
+    fn basic_batch(&self, addresses: &[H160]) -> Vec<Result<Option<AccountInfo>, Self::Error>> {
+        let start = Instant::now();
+        let results = self.db.basic_batch(addresses);
+        let mut stats = self.stats.lock().unwrap();
+        for result in &results {
+            record(&mut stats.basic, start, result, basic_bytes);
+        }
+        results
+    }
+
+    /// Same as [InstrumentedDB::basic_batch], every slot is recorded as one call.
+    fn storage_batch(&self, slots: &[(H160, U256)]) -> Vec<Result<U256, Self::Error>> {
+        let start = Instant::now();
+        let results = self.db.storage_batch(slots);
+        let mut stats = self.stats.lock().unwrap();
+        for result in &results {
+            record(&mut stats.storage, start, result, |_| 32);
+        }
+        results
+    }
+}
+
+impl<DB: DatabaseCommit> DatabaseCommit for InstrumentedDB<DB> {
+    fn commit(&mut self, changes: Map<H160, Account>) {
+        self.db.commit(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::EmptyDB, Bytecode, InMemoryDB, TransactTo};
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), Duration::ZERO);
+        for micros in [0, 1, 3, 3, 100] {
+            histogram.record(Duration::from_micros(micros));
+        }
+        assert_eq!(histogram.buckets[..8], [1, 1, 2, 0, 0, 0, 0, 1]);
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.mean(), Duration::from_nanos(21_400));
+        assert_eq!(histogram.quantile(0.5), Duration::from_micros(4));
+        assert_eq!(histogram.quantile(1.0), Duration::from_micros(100));
+
+        // count that doesn't fit in u32.
+        let histogram = LatencyHistogram {
+            buckets: [1 << 32; LATENCY_BUCKETS],
+            total: Duration::from_secs((LATENCY_BUCKETS as u64) << 32),
+            max: Duration::from_secs(1),
+        };
+        assert_eq!(histogram.mean(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_instrumented_db() {
+        let contract = H160::from_low_u64_be(0xa);
+        // SLOAD of slot 0 and 1.
+        let code = [0x60, 0, 0x54, 0x60, 1, 0x54, 0x00];
+        let mut db = InMemoryDB::new(EmptyDB::default());
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(0.into(), 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(InstrumentedDB::new(db));
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        evm.transact();
+
+        let stats = evm.db().unwrap().reset_stats();
+        // caller, that is also coinbase, and contract are loaded. Caller doesn't exist.
+        assert_eq!((stats.basic.calls, stats.basic.bytes), (2, 72));
+        assert_eq!((stats.storage.calls, stats.storage.bytes), (2, 64));
+        assert_eq!(stats.code_by_hash.calls, 0);
+        assert_eq!(stats.calls(), 4);
+        assert_eq!(stats.basic.latency.count(), 2);
+        assert_eq!(evm.db().unwrap().stats(), DbStats::default());
+
+        // read through reference.
+        let db = InstrumentedDB::new(EmptyDB::default());
+        assert_eq!(
+            DatabaseRef::storage(&db, contract, U256::zero()),
+            Ok(U256::zero())
+        );
+        assert_eq!(db.stats().storage.calls, 1);
+    }
+
+    /// Database with large contract at 0xb whose code is not returned with account.
+    struct LargeCodeDB {
+        caller_code: Bytecode,
+        code: Bytecode,
+    }
+
+    impl Database for LargeCodeDB {
+        type Error = core::convert::Infallible;
+
+        fn basic(&mut self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+            Ok(match address.to_low_u64_be() {
+                0xa => Some(AccountInfo::new(0.into(), 1, self.caller_code.clone())),
+                0xb => Some(AccountInfo {
+                    balance: U256::zero(),
+                    nonce: 1,
+                    code_hash: self.code.hash(),
+                    code: None,
+                }),
+                _ => None,
+            })
+        }
+
+        fn code_by_hash(&mut self, _code_hash: H256) -> Result<Bytecode, Self::Error> {
+            Ok(self.code.clone())
+        }
+
+        fn storage(&mut self, _address: H160, _index: U256) -> Result<U256, Self::Error> {
+            Ok(U256::zero())
+        }
+
+        fn block_hash(&mut self, _number: U256) -> Result<H256, Self::Error> {
+            Ok(H256::zero())
+        }
+    }
+
+    #[test]
+    fn test_extcodecopy_code_range() {
+        let code: Vec<u8> = (0..24_000).map(|i| (i % 251) as u8).collect();
+        // EXTCODECOPY of `len` bytes of 0xb from offset 20000 and return them.
+        let caller_code = |len: u16| {
+            let [hi, lo] = len.to_be_bytes();
+            let code = [
+                0x61, hi, lo, 0x61, 0x4e, 0x20, 0x60, 0, 0x60, 0xb, 0x3c, // EXTCODECOPY
+                0x61, hi, lo, 0x60, 0, 0xf3, // RETURN
+            ];
+            Bytecode::new_raw(code.to_vec().into())
+        };
+        let run = |len: u16| {
+            let mut evm = crate::new();
+            evm.database(InstrumentedDB::new(LargeCodeDB {
+                caller_code: caller_code(len),
+                code: Bytecode::new_raw(code.clone().into()),
+            }));
+            evm.env.tx.transact_to = TransactTo::Call(H160::from_low_u64_be(0xa));
+            evm.env.tx.gas_limit = 100_000;
+            let (result, _) = evm.transact();
+            let out = match result.out {
+                crate::TransactOut::Call(out) => out,
+                _ => panic!("expected call output"),
+            };
+            (out, evm.db().unwrap().reset_stats())
+        };
+
+        // small copy reads only its range.
+        let (out, stats) = run(32);
+        assert_eq!(out[..], code[20_000..20_032]);
+        assert_eq!((stats.code_range.calls, stats.code_range.bytes), (1, 32));
+        assert_eq!(stats.code_by_hash.calls, 0);
+
+        // large copy loads whole code, part after its end is zero.
+        let (out, stats) = run(5_000);
+        assert_eq!(out[..4_000], code[20_000..]);
+        assert!(out[4_000..].iter().all(|byte| *byte == 0));
+        assert_eq!((stats.code_by_hash.calls, stats.code_range.calls), (1, 0));
+    }
+
+    #[test]
+    fn test_extcodesize_without_code() {
+        let code = Bytecode::new_raw(vec![0; 24_000].into());
+        // EXTCODESIZE and EXTCODEHASH of 0xb stored at 0 and 32 and returned.
+        let caller_code = [
+            0x60, 0xb, 0x3b, 0x60, 0, 0x52, // EXTCODESIZE
+            0x60, 0xb, 0x3f, 0x60, 32, 0x52, // EXTCODEHASH
+            0x60, 64, 0x60, 0, 0xf3,
+        ];
+        let mut evm = crate::new();
+        evm.database(InstrumentedDB::new(LargeCodeDB {
+            caller_code: Bytecode::new_raw(caller_code.to_vec().into()),
+            code: code.clone(),
+        }));
+        evm.env.cfg.spec_id = crate::SpecId::LONDON;
+        evm.env.tx.transact_to = TransactTo::Call(H160::from_low_u64_be(0xa));
+        evm.env.tx.gas_limit = 100_000;
+        let (result, _) = evm.transact();
+        let out = match result.out {
+            crate::TransactOut::Call(out) => out,
+            _ => panic!("expected call output"),
+        };
+        assert_eq!(U256::from_big_endian(&out[..32]), U256::from(24_000));
+        assert_eq!(H256::from_slice(&out[32..]), code.hash());
+
+        // neither opcode loads the code.
+        let stats = evm.db().unwrap().stats();
+        assert_eq!(stats.code_by_hash.calls, 0);
+        assert_eq!((stats.code_size.calls, stats.code_size.bytes), (1, 8));
+    }
+}
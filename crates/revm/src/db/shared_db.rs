@@ -0,0 +1,290 @@
+use super::{CacheDB, DatabaseRef};
+use crate::{interpreter::bytecode::Bytecode, AccountInfo};
+use bytes::Bytes;
+use hashbrown::HashMap as Map;
+use primitive_types::{H160, H256, U256};
+use std::{
+    hash::Hash,
+    sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+/// Per simulation database. Changes are committed into private `CacheDB` overlay
+/// while everything that is read from underlying database is shared between all overlays.
+pub type SharedCacheDB<ExtDB> = CacheDB<Arc<SharedCache<ExtDB>>>;
+
+/// Read cache that can be shared between many EVM instances running in parallel against same state.
+///
+/// Values are fetched from `db` only once. Map locks are held only to find entry of a key, so
+/// misses of different keys are fetched in parallel while concurrent misses of the same key
+/// wait for the first fetch to finish instead of going to `db` again. Failed or panicked fetch
+/// leaves entry empty and it is fetched again on next read.
+#[derive(Debug, Default)]
+pub struct SharedCache<ExtDB: DatabaseRef> {
+    accounts: RwLock<Map<H160, Slot<Option<AccountInfo>>>>,
+    storage: RwLock<Map<(H160, U256), Slot<U256>>>,
+    contracts: RwLock<Map<H256, Slot<Bytecode>>>,
+    block_hashes: RwLock<Map<U256, Slot<H256>>>,
+    db: ExtDB,
+}
+
+/// Entry of a key, empty until value is fetched. Its lock is held while fetching.
+type Slot<V> = Arc<Mutex<Option<V>>>;
+
+impl<ExtDB: DatabaseRef> SharedCache<ExtDB> {
+    pub fn new(db: ExtDB) -> Self {
+        Self {
+            accounts: Default::default(),
+            storage: Default::default(),
+            contracts: Default::default(),
+            block_hashes: Default::default(),
+            db,
+        }
+    }
+
+    /// Create new private overlay over this cache.
+    pub fn overlay(self: &Arc<Self>) -> SharedCacheDB<ExtDB> {
+        CacheDB::new(self.clone())
+    }
+
+    pub fn db(&self) -> &ExtDB {
+        &self.db
+    }
+
+    /// Number of accounts, storage slots and contracts that are cached.
+    pub fn len(&self) -> (usize, usize, usize) {
+        (
+            cached_len(&self.accounts),
+            cached_len(&self.storage),
+            cached_len(&self.contracts),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == (0, 0, 0)
+    }
+}
+
+// Values are inserted only after fetch succeeded, so maps and slots are consistent even if a
+// thread panicked while holding their lock and poisoning can be ignored.
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn cached_len<K, V>(map: &RwLock<Map<K, Slot<V>>>) -> usize {
+    read(map)
+        .values()
+        .filter(|slot| lock(slot).is_some())
+        .count()
+}
+
+/// Cached value of `key`, without fetching it.
+fn cached<K: Hash + Eq, V: Clone>(map: &RwLock<Map<K, Slot<V>>>, key: &K) -> Option<V> {
+    let slot = read(map).get(key)?.clone();
+    let value = lock(&slot).clone();
+    value
+}
+
+/// Get value from map or fetch it with `fetch` and insert it. Fetch is done under lock of the
+/// entry of `key` only.
+fn get_or_fetch<K: Hash + Eq, V: Clone, E>(
+    map: &RwLock<Map<K, Slot<V>>>,
+    key: K,
+    fetch: impl FnOnce() -> Result<V, E>,
+) -> Result<V, E> {
+    let existing = read(map).get(&key).cloned();
+    let slot = match existing {
+        Some(slot) => slot,
+        None => write(map).entry(key).or_default().clone(),
+    };
+    let mut value = lock(&slot);
+    // other thread could fetch it while we were waiting on lock.
+    if let Some(value) = &*value {
+        return Ok(value.clone());
+    }
+    let fetched = fetch()?;
+    *value = Some(fetched.clone());
+    Ok(fetched)
+}
+
+impl<ExtDB: DatabaseRef> DatabaseRef for SharedCache<ExtDB> {
+    type Error = ExtDB::Error;
+
+    fn basic(&self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+        get_or_fetch(&self.accounts, address, || self.db.basic(address))
+    }
+
+    fn code_by_hash(&self, code_hash: H256) -> Result<Bytecode, Self::Error> {
+        get_or_fetch(&self.contracts, code_hash, || {
+            self.db.code_by_hash(code_hash)
+        })
+    }
+
+    fn storage(&self, address: H160, index: U256) -> Result<U256, Self::Error> {
+        get_or_fetch(&self.storage, (address, index), || {
+            self.db.storage(address, index)
+        })
+    }
+
+    fn block_hash(&self, number: U256) -> Result<H256, Self::Error> {
+        get_or_fetch(&self.block_hashes, number, || self.db.block_hash(number))
+    }
+
+    /// Range of cached code, otherwise it is read from `db` without caching.
+    fn code_range(&self, code_hash: H256, offset: usize, len: usize) -> Result<Bytes, Self::Error> {
+        if let Some(code) = cached(&self.contracts, &code_hash) {
+            return Ok(code.range(offset, len));
+        }
+        self.db.code_range(code_hash, offset, len)
+    }
+
+    /// Size of cached code, otherwise it is read from `db` without caching.
+    fn code_size(&self, code_hash: H256) -> Result<usize, Self::Error> {
+        if let Some(code) = cached(&self.contracts, &code_hash) {
+            return Ok(code.len());
+        }
+        self.db.code_size(code_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SharedCache;
+    use crate::{
+        db::{DatabaseRef, EmptyDB},
+        AccountInfo, Bytecode, DatabaseCommit,
+    };
+    use core::convert::Infallible;
+    use hashbrown::HashMap as Map;
+    use primitive_types::{H160, H256, U256};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[derive(Default)]
+    struct CountingDB {
+        basic_calls: AtomicUsize,
+    }
+
+    impl DatabaseRef for CountingDB {
+        type Error = Infallible;
+        fn basic(&self, _address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+            self.basic_calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(AccountInfo::from_balance(10.into())))
+        }
+        fn code_by_hash(&self, code_hash: H256) -> Result<Bytecode, Self::Error> {
+            EmptyDB().code_by_hash(code_hash)
+        }
+        fn storage(&self, address: H160, index: U256) -> Result<U256, Self::Error> {
+            EmptyDB().storage(address, index)
+        }
+        fn block_hash(&self, number: U256) -> Result<H256, Self::Error> {
+            EmptyDB().block_hash(number)
+        }
+    }
+
+    #[test]
+    fn test_shared_cache_overlays() {
+        let address = H160::from_low_u64_be(1);
+        let shared = Arc::new(SharedCache::new(CountingDB::default()));
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let shared = shared.clone();
+                std::thread::spawn(move || {
+                    let mut overlay = shared.overlay();
+                    let info = overlay.basic(address).unwrap().unwrap();
+                    assert_eq!(info.balance, 10.into());
+
+                    // commit only changes private overlay.
+                    let mut account: crate::Account = AccountInfo::from_balance(i.into()).into();
+                    account.is_touched = true;
+                    overlay.commit(Map::from([(address, account)]));
+                    assert_eq!(overlay.basic(address).unwrap().unwrap().balance, i.into());
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(shared.db().basic_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(shared.basic(address).unwrap().unwrap().balance, 10.into());
+    }
+
+    /// Fetch of address 1 signals `started` and waits until address 2 is fetched,
+    /// address 3 panics.
+    struct GatedDB {
+        started: std::sync::Mutex<std::sync::mpsc::Sender<()>>,
+        fetched_2: (std::sync::Mutex<bool>, std::sync::Condvar),
+    }
+
+    impl DatabaseRef for GatedDB {
+        type Error = Infallible;
+        fn basic(&self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+            let (fetched_2, condvar) = &self.fetched_2;
+            match address.to_low_u64_be() {
+                1 => {
+                    self.started.lock().unwrap().send(()).unwrap();
+                    let timeout = std::time::Duration::from_secs(10);
+                    let guard = fetched_2.lock().unwrap();
+                    let (_guard, wait) = condvar
+                        .wait_timeout_while(guard, timeout, |fetched| !*fetched)
+                        .unwrap();
+                    assert!(!wait.timed_out(), "fetches are serialized");
+                }
+                2 => {
+                    *fetched_2.lock().unwrap() = true;
+                    condvar.notify_all();
+                }
+                _ => panic!("backend failure"),
+            }
+            Ok(Some(AccountInfo::from_balance(
+                address.to_low_u64_be().into(),
+            )))
+        }
+        fn code_by_hash(&self, _code_hash: H256) -> Result<Bytecode, Self::Error> {
+            Ok(Bytecode::new())
+        }
+        fn storage(&self, _address: H160, _index: U256) -> Result<U256, Self::Error> {
+            Ok(U256::zero())
+        }
+        fn block_hash(&self, _number: U256) -> Result<H256, Self::Error> {
+            Ok(H256::zero())
+        }
+    }
+
+    #[test]
+    fn test_shared_cache_fetch_without_map_lock() {
+        let address = H160::from_low_u64_be;
+        let (started, fetch_started) = std::sync::mpsc::channel();
+        let shared = Arc::new(SharedCache::new(GatedDB {
+            started: std::sync::Mutex::new(started),
+            fetched_2: Default::default(),
+        }));
+        let waiting = {
+            let shared = shared.clone();
+            std::thread::spawn(move || shared.basic(address(1)).unwrap())
+        };
+        fetch_started.recv().unwrap();
+        // fetched while other thread is fetching address 1.
+        assert_eq!(shared.basic(address(2)).unwrap().unwrap().balance, 2.into());
+        assert_eq!(waiting.join().unwrap().unwrap().balance, 1.into());
+
+        // panicked fetch doesn't make cache unusable.
+        let panicked =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| shared.basic(address(3))));
+        assert!(panicked.is_err());
+        assert_eq!(shared.len(), (2, 0, 0));
+        assert_eq!(shared.basic(address(1)).unwrap().unwrap().balance, 1.into());
+    }
+}
@@ -1,5 +1,7 @@
+use super::{CacheDB, DatabaseRef};
 use crate::{interpreter::bytecode::Bytecode, AccountInfo, Database, KECCAK_EMPTY};
 use bytes::Bytes;
+use futures::stream::{self, StreamExt};
 use primitive_types::{H160, H256, U256};
 use tokio::runtime::{Handle, Runtime};
 use web3::{
@@ -8,6 +10,13 @@ use web3::{
     Web3,
 };
 
+/// [Web3DB] with cache of fetched state. Use [CacheDB::prefetch] to fetch state that
+/// transaction is going to need with concurrent requests before it is executed.
+pub type ForkDB = CacheDB<Web3DB>;
+
+/// Maximal number of requests that batch fetches have in flight at once.
+pub const MAX_CONCURRENT_FETCHES: usize = 16;
+
 pub struct Web3DB {
     web3: Web3<Http>,
     runtime: Option<Runtime>,
@@ -45,22 +54,16 @@ impl Web3DB {
             None => futures::executor::block_on(f),
         }
     }
-}
-
-impl Database for Web3DB {
-    type Error = ();
 
-    fn basic(&mut self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+    /// Fetch nonce, balance and code of account concurrently.
+    async fn fetch_basic(&self, address: H160) -> AccountInfo {
         let add = wH160(address.0);
-        let f = async {
-            let nonce = self.web3.eth().transaction_count(add, self.block_number);
-            let balance = self.web3.eth().balance(add, self.block_number);
-            let code = self.web3.eth().code(add, self.block_number);
-            tokio::join!(nonce, balance, code)
-        };
-        let (nonce, balance, code) = self.block_on(f);
+        let nonce = self.web3.eth().transaction_count(add, self.block_number);
+        let balance = self.web3.eth().balance(add, self.block_number);
+        let code = self.web3.eth().code(add, self.block_number);
+        let (nonce, balance, code) = tokio::join!(nonce, balance, code);
         // panic on not getting data?
-        Ok(Some(AccountInfo::new(
+        AccountInfo::new(
             U256(
                 balance
                     .unwrap_or_else(|e| panic!("web3 get balance error:{:?}", e))
@@ -73,37 +76,39 @@ impl Database for Web3DB {
                 code.unwrap_or_else(|e| panic!("web3 get node error:{:?}", e))
                     .0,
             )),
-        )))
+        )
     }
 
-    fn code_by_hash(&mut self, _code_hash: primitive_types::H256) -> Result<Bytecode, Self::Error> {
+    async fn fetch_storage(&self, address: H160, index: U256) -> U256 {
+        let add = wH160(address.0);
+        let index = wU256(index.0);
+        let storage = self
+            .web3
+            .eth()
+            .storage(add, index, self.block_number)
+            .await
+            .unwrap();
+        U256::from_big_endian(storage.as_bytes())
+    }
+}
+
+impl DatabaseRef for Web3DB {
+    type Error = ();
+
+    fn basic(&self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+        Ok(Some(self.block_on(self.fetch_basic(address))))
+    }
+
+    fn code_by_hash(&self, _code_hash: H256) -> Result<Bytecode, Self::Error> {
         panic!("Should not be called. Code is already loaded");
         // not needed because we already load code with basic info
     }
 
-    fn storage(
-        &mut self,
-        address: primitive_types::H160,
-        index: primitive_types::U256,
-    ) -> Result<primitive_types::U256, Self::Error> {
-        let add = wH160(address.0);
-        let index = wU256(index.0);
-        let f = async {
-            let storage = self
-                .web3
-                .eth()
-                .storage(add, index, self.block_number)
-                .await
-                .unwrap();
-            U256::from_big_endian(storage.as_bytes())
-        };
-        Ok(self.block_on(f))
+    fn storage(&self, address: H160, index: U256) -> Result<U256, Self::Error> {
+        Ok(self.block_on(self.fetch_storage(address, index)))
     }
 
-    fn block_hash(
-        &mut self,
-        number: primitive_types::U256,
-    ) -> Result<primitive_types::H256, Self::Error> {
+    fn block_hash(&self, number: U256) -> Result<H256, Self::Error> {
         if number > U256::from(u64::MAX) {
             return Ok(KECCAK_EMPTY);
         }
@@ -125,4 +130,48 @@ impl Database for Web3DB {
         };
         Ok(H256(self.block_on(f).unwrap().hash.unwrap().0))
     }
+
+    /// Accounts are fetched concurrently, at most [MAX_CONCURRENT_FETCHES] at once.
+    fn basic_batch(&self, addresses: &[H160]) -> Vec<Result<Option<AccountInfo>, Self::Error>> {
+        let fetches = addresses.iter().map(|address| self.fetch_basic(*address));
+        let infos: Vec<_> = self.block_on(
+            stream::iter(fetches)
+                .buffered(MAX_CONCURRENT_FETCHES)
+                .collect(),
+        );
+        infos.into_iter().map(|info| Ok(Some(info))).collect()
+    }
+
+    /// Slots are fetched concurrently, at most [MAX_CONCURRENT_FETCHES] at once.
+    fn storage_batch(&self, slots: &[(H160, U256)]) -> Vec<Result<U256, Self::Error>> {
+        let fetches = slots
+            .iter()
+            .map(|(address, index)| self.fetch_storage(*address, *index));
+        let values: Vec<_> = self.block_on(
+            stream::iter(fetches)
+                .buffered(MAX_CONCURRENT_FETCHES)
+                .collect(),
+        );
+        values.into_iter().map(Ok).collect()
+    }
+}
+
+impl Database for Web3DB {
+    type Error = ();
+
+    fn basic(&mut self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+        DatabaseRef::basic(self, address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: H256) -> Result<Bytecode, Self::Error> {
+        DatabaseRef::code_by_hash(self, code_hash)
+    }
+
+    fn storage(&mut self, address: H160, index: U256) -> Result<U256, Self::Error> {
+        DatabaseRef::storage(self, address, index)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<H256, Self::Error> {
+        DatabaseRef::block_hash(self, number)
+    }
 }
@@ -1,10 +1,12 @@
 use crate::{
-    db::{Database, DatabaseCommit, DatabaseRef, RefDBWrapper},
+    db::{Database, DatabaseCommit, DatabaseRef, WrapDatabaseRef},
     evm_impl::{EVMImpl, Transact},
     journaled_state::State,
-    specification, Env, ExecutionResult, Inspector, NoOpInspector,
+    specification, AnalysisCache, BlockHashes, CreateAddressHook, Env, ExecutionResult, Inspector,
+    InvalidTransaction, NoOpInspector, PrecompileRegistry, PreparedTx, SpecId, SpecOutcome,
+    SpecReport, TxEnv, TxFeeHook,
 };
-use alloc::boxed::Box;
+use alloc::{boxed::Box, sync::Arc};
 use revm_precompiles::Precompiles;
 
 /// Struct that takes Database and enabled transact to update state directly to database.
@@ -28,6 +30,17 @@ use revm_precompiles::Precompiles;
 pub struct EVM<DB> {
     pub env: Env,
     pub db: Option<DB>,
+    /// Fee charged in addition to gas. See [TxFeeHook].
+    pub fee_hook: Option<Arc<dyn TxFeeHook>>,
+    /// Precompiles used instead of the ones of the spec. Can be used to add custom precompiles
+    /// with [Precompiles::insert].
+    pub precompiles: Option<Precompiles>,
+    /// Cache of analysed bytecode that can be shared with other EVMs. See [AnalysisCache].
+    pub analysis_cache: Option<Arc<dyn AnalysisCache>>,
+    /// Derivation of created contract addresses. See [CreateAddressHook].
+    pub create_address_hook: Option<Arc<dyn CreateAddressHook>>,
+    /// Hashes of recent blocks shared with other EVMs. See [BlockHashes].
+    pub block_hashes: Option<Arc<dyn BlockHashes>>,
 }
 
 pub fn new<DB>() -> EVM<DB> {
@@ -58,13 +71,30 @@ impl<DB: Database + DatabaseCommit> EVM<DB> {
 impl<DB: Database> EVM<DB> {
     /// Execute transaction without writing to DB, return change state.
     pub fn transact(&mut self) -> (ExecutionResult, State) {
-        if let Some(db) = self.db.as_mut() {
-            let mut noop = NoOpInspector {};
-            let out = evm_inner::<DB, false>(&mut self.env, db, &mut noop).transact();
-            out
-        } else {
-            panic!("Database needs to be set");
-        }
+        let (env, db, hooks) = self.parts_mut();
+        let mut noop = NoOpInspector {};
+        let out = evm_inner::<DB, false>(env, db, &mut noop, hooks).transact();
+        out
+    }
+
+    /// Replace transaction of environment with `tx` and execute it without writing to DB.
+    /// Fields of previous transaction can't leak into this one.
+    pub fn transact_with(&mut self, tx: TxEnv) -> (ExecutionResult, State) {
+        self.env.tx = tx;
+        self.transact()
+    }
+
+    /// Execute transaction prepared by [EVM::prepare] without writing to DB. Environment is
+    /// replaced with the prepared one and intrinsic gas and access list warm set of preparation
+    /// are used instead of computing them again.
+    pub fn transact_prepared(&mut self, prepared: PreparedTx) -> (ExecutionResult, State) {
+        let (env, intrinsic_gas, warm_set) = prepared.into_parts();
+        self.env = env;
+        let (env, db, hooks) = self.parts_mut();
+        let mut noop = NoOpInspector {};
+        let out = evm_inner::<DB, false>(env, db, &mut noop, hooks)
+            .transact_prepared(intrinsic_gas, &warm_set);
+        out
     }
 
     /// Execute transaction with given inspector, without wring to DB. Return change state.
@@ -72,42 +102,64 @@ impl<DB: Database> EVM<DB> {
         &mut self,
         mut inspector: INSP,
     ) -> (ExecutionResult, State) {
-        if let Some(db) = self.db.as_mut() {
-            evm_inner::<DB, true>(&mut self.env, db, &mut inspector).transact()
-        } else {
-            panic!("Database needs to be set");
-        }
+        let (env, db, hooks) = self.parts_mut();
+        evm_inner::<DB, true>(env, db, &mut inspector, hooks).transact()
     }
 }
 
-impl<'a, DB: DatabaseRef> EVM<DB> {
+impl<DB: DatabaseRef> EVM<DB> {
     /// Execute transaction without writing to DB, return change state.
     pub fn transact_ref(&self) -> (ExecutionResult, State) {
         if let Some(db) = self.db.as_ref() {
             let mut noop = NoOpInspector {};
-            let mut db = RefDBWrapper::new(db);
-            let db = &mut db;
-            let out =
-                evm_inner::<RefDBWrapper<DB::Error>, false>(&mut self.env.clone(), db, &mut noop)
-                    .transact();
+            let out = evm_inner::<WrapDatabaseRef<DB>, false>(
+                &mut self.env.clone(),
+                &mut WrapDatabaseRef(db),
+                &mut noop,
+                self.hooks(),
+            )
+            .transact();
             out
         } else {
             panic!("Database needs to be set");
         }
     }
 
+    /// Execute transaction under every [SpecId], without writing to DB. Custom spec is ignored.
+    ///
+    /// Useful to see how hardforks change behavior of transaction and to catch opcodes
+    /// that are not gated by the right spec.
+    pub fn transact_all_specs(&self) -> SpecReport {
+        let db = self.db.as_ref().expect("Database needs to be set");
+        let mut outcomes = alloc::vec::Vec::with_capacity(SpecId::ALL.len());
+        for spec_id in SpecId::ALL {
+            let mut env = self.env.clone();
+            env.cfg.spec_id = spec_id;
+            env.cfg.custom_spec = None;
+            let mut noop = NoOpInspector {};
+            let (result, _) = evm_inner::<WrapDatabaseRef<DB>, false>(
+                &mut env,
+                &mut WrapDatabaseRef(db),
+                &mut noop,
+                self.hooks(),
+            )
+            .transact();
+            outcomes.push(SpecOutcome::new(spec_id, &result));
+        }
+        SpecReport { outcomes }
+    }
+
     /// Execute transaction with given inspector, without wring to DB. Return change state.
-    pub fn inspect_ref<INSP: Inspector<RefDBWrapper<'a, DB::Error>>>(
+    pub fn inspect_ref<'a, INSP: Inspector<WrapDatabaseRef<'a, DB>>>(
         &'a self,
         mut inspector: INSP,
     ) -> (ExecutionResult, State) {
         if let Some(db) = self.db.as_ref() {
-            let mut db = RefDBWrapper::new(db);
-            let db = &mut db;
-            let out = evm_inner::<RefDBWrapper<DB::Error>, true>(
+            let out = evm_inner::<WrapDatabaseRef<DB>, true>(
                 &mut self.env.clone(),
-                db,
+                &mut WrapDatabaseRef(db),
                 &mut inspector,
+                self.hooks(),
             )
             .transact();
             out
@@ -117,14 +169,54 @@ impl<'a, DB: DatabaseRef> EVM<DB> {
     }
 }
 
+/// Hooks and precompiles of [EVM] borrowed for execution of one transaction by [evm_inner].
+#[derive(Clone, Copy, Default)]
+pub struct EVMHooks<'a> {
+    pub fee_hook: Option<&'a dyn TxFeeHook>,
+    /// Precompiles of the spec are used if not set.
+    pub precompiles: Option<&'a Precompiles>,
+    pub analysis_cache: Option<&'a dyn AnalysisCache>,
+    pub create_address_hook: Option<&'a dyn CreateAddressHook>,
+    pub block_hashes: Option<&'a dyn BlockHashes>,
+}
+
 impl<DB> EVM<DB> {
     pub fn new() -> Self {
         Self {
             env: Env::default(),
             db: None,
+            fee_hook: None,
+            precompiles: None,
+            analysis_cache: None,
+            create_address_hook: None,
+            block_hashes: None,
+        }
+    }
+
+    /// Borrow hooks of this EVM, see [EVMHooks].
+    pub fn hooks(&self) -> EVMHooks<'_> {
+        EVMHooks {
+            fee_hook: self.fee_hook.as_deref(),
+            precompiles: self.precompiles.as_ref(),
+            analysis_cache: self.analysis_cache.as_deref(),
+            create_address_hook: self.create_address_hook.as_deref(),
+            block_hashes: self.block_hashes.as_deref(),
         }
     }
 
+    /// Borrow environment, database and hooks at once, for execution with [evm_inner].
+    fn parts_mut(&mut self) -> (&mut Env, &mut DB, EVMHooks<'_>) {
+        let hooks = EVMHooks {
+            fee_hook: self.fee_hook.as_deref(),
+            precompiles: self.precompiles.as_ref(),
+            analysis_cache: self.analysis_cache.as_deref(),
+            create_address_hook: self.create_address_hook.as_deref(),
+            block_hashes: self.block_hashes.as_deref(),
+        };
+        let db = self.db.as_mut().expect("Database needs to be set");
+        (&mut self.env, db, hooks)
+    }
+
     pub fn database(&mut self, db: DB) {
         self.db = Some(db);
     }
@@ -136,15 +228,58 @@ impl<DB> EVM<DB> {
     pub fn take_db(&mut self) -> DB {
         core::mem::take(&mut self.db).unwrap()
     }
+
+    /// Set [EVM::precompiles] to the ones registered in `registry` for chain id and spec of
+    /// the environment, or to precompiles of the spec if chain has none. Needs to be called
+    /// again if chain id or spec is changed.
+    pub fn select_precompiles(&mut self, registry: &PrecompileRegistry) {
+        self.precompiles = registry.precompiles_for_env(&self.env);
+    }
+
+    /// Validate transaction of environment and derive state it reads, without executing it.
+    /// Prepared transaction can be prefetched and executed later, on this or other EVM, with
+    /// [EVM::transact_prepared]. See [PreparedTx].
+    pub fn prepare(&self) -> Result<PreparedTx, InvalidTransaction> {
+        PreparedTx::new(self.env.clone(), self.create_address_hook.as_deref())
+    }
+}
+
+/// Object safe interface of EVM over database `DB`.
+///
+/// Unlike [EVM] it can be used as `Box<dyn Evm<DB>>`, so EVMs with different configuration
+/// or mock EVMs used in tests can be stored together without generics leaking to the caller.
+pub trait Evm<DB: Database> {
+    fn env(&self) -> &Env;
+    fn env_mut(&mut self) -> &mut Env;
+    /// Execute transaction of environment without writing to DB, return change state.
+    fn transact(&mut self) -> (ExecutionResult, State);
+    /// Execute transaction of environment with given inspector, without writing to DB.
+    fn inspect(&mut self, inspector: &mut dyn Inspector<DB>) -> (ExecutionResult, State);
+}
+
+impl<DB: Database> Evm<DB> for EVM<DB> {
+    fn env(&self) -> &Env {
+        &self.env
+    }
+
+    fn env_mut(&mut self) -> &mut Env {
+        &mut self.env
+    }
+
+    fn transact(&mut self) -> (ExecutionResult, State) {
+        EVM::transact(self)
+    }
+
+    fn inspect(&mut self, inspector: &mut dyn Inspector<DB>) -> (ExecutionResult, State) {
+        let (env, db, hooks) = self.parts_mut();
+        evm_inner::<DB, true>(env, db, inspector, hooks).transact()
+    }
 }
 
 macro_rules! create_evm {
-    ($spec:ident, $db:ident,$env:ident,$inspector:ident) => {
+    ($spec:ident, $db:ident, $env:ident, $inspector:ident, $hooks:ident) => {
         Box::new(EVMImpl::<'a, $spec, DB, INSPECT>::new(
-            $db,
-            $env,
-            $inspector,
-            Precompiles::new(SpecId::to_precompile_id($spec::SPEC_ID)).clone(),
+            $db, $env, $inspector, $hooks,
         )) as Box<dyn Transact + 'a>
     };
 }
@@ -153,21 +288,462 @@ pub fn evm_inner<'a, DB: Database, const INSPECT: bool>(
     env: &'a mut Env,
     db: &'a mut DB,
     insp: &'a mut dyn Inspector<DB>,
+    hooks: EVMHooks<'a>,
 ) -> Box<dyn Transact + 'a> {
     use specification::*;
+    if env.cfg.custom_spec.is_some() {
+        return create_evm!(CustomSpec, db, env, insp, hooks);
+    }
     match env.cfg.spec_id {
-        SpecId::FRONTIER | SpecId::FRONTIER_THAWING => create_evm!(FrontierSpec, db, env, insp),
-        SpecId::HOMESTEAD | SpecId::DAO_FORK => create_evm!(HomesteadSpec, db, env, insp),
-        SpecId::TANGERINE => create_evm!(TangerineSpec, db, env, insp),
-        SpecId::SPURIOUS_DRAGON => create_evm!(SpuriousDragonSpec, db, env, insp),
-        SpecId::BYZANTIUM => create_evm!(ByzantiumSpec, db, env, insp),
-        SpecId::PETERSBURG | SpecId::CONSTANTINOPLE => create_evm!(PetersburgSpec, db, env, insp),
-        SpecId::ISTANBUL | SpecId::MUIR_GLACIER => create_evm!(IstanbulSpec, db, env, insp),
-        SpecId::BERLIN => create_evm!(BerlinSpec, db, env, insp),
+        SpecId::FRONTIER | SpecId::FRONTIER_THAWING => {
+            create_evm!(FrontierSpec, db, env, insp, hooks)
+        }
+        SpecId::HOMESTEAD | SpecId::DAO_FORK => create_evm!(HomesteadSpec, db, env, insp, hooks),
+        SpecId::TANGERINE => create_evm!(TangerineSpec, db, env, insp, hooks),
+        SpecId::SPURIOUS_DRAGON => create_evm!(SpuriousDragonSpec, db, env, insp, hooks),
+        SpecId::BYZANTIUM => create_evm!(ByzantiumSpec, db, env, insp, hooks),
+        SpecId::PETERSBURG | SpecId::CONSTANTINOPLE => {
+            create_evm!(PetersburgSpec, db, env, insp, hooks)
+        }
+        SpecId::ISTANBUL | SpecId::MUIR_GLACIER => create_evm!(IstanbulSpec, db, env, insp, hooks),
+        SpecId::BERLIN => create_evm!(BerlinSpec, db, env, insp, hooks),
         SpecId::LONDON | SpecId::ARROW_GLACIER | SpecId::GRAY_GLACIER => {
-            create_evm!(LondonSpec, db, env, insp)
+            create_evm!(LondonSpec, db, env, insp, hooks)
+        }
+        SpecId::MERGE => create_evm!(MergeSpec, db, env, insp, hooks),
+        SpecId::CANCUN => create_evm!(CancunSpec, db, env, insp, hooks),
+        SpecId::LATEST => create_evm!(LatestSpec, db, env, insp, hooks),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::InMemoryDB,
+        opcode::*,
+        precompiles::{
+            self, KzgSettings, Log, Precompile, PrecompileContext, PrecompileOutput,
+            PrecompileResult,
+        },
+        AccountInfo, Bytecode, PrecompileDelegation, Return, TransactOut, TransactTo, KECCAK_EMPTY,
+    };
+    use alloc::{sync::Arc, vec::Vec};
+    use bytes::Bytes;
+    use primitive_types::{H160, H256, U256};
+    use revm_precompiles::Precompiles;
+
+    /// Emit log with address of the caller as data.
+    fn logging_precompile(
+        _input: &[u8],
+        _gas_limit: u64,
+        ctx: &PrecompileContext,
+    ) -> PrecompileResult {
+        Ok(PrecompileOutput {
+            cost: 100,
+            output: Vec::new(),
+            logs: vec![Log {
+                address: ctx.address,
+                topics: Vec::new(),
+                data: Bytes::copy_from_slice(ctx.caller.as_bytes()),
+            }],
+        })
+    }
+
+    fn call(op: u8, address: u8) -> Vec<u8> {
+        let value: &[u8] = if op == CALL { &[PUSH1, 0] } else { &[] };
+        [
+            &[PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 0][..],
+            value,
+            &[PUSH2, address, 0, GAS, op],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_evm_is_send_and_sync() {
+        // EVM with hooks set can be moved to and shared with other threads.
+        fn assert_send<T: Send + Sync>() {}
+        assert_send::<crate::EVM<InMemoryDB>>();
+    }
+
+    #[test]
+    fn test_custom_precompile_logs() {
+        let precompile = H160::from_low_u64_be(0x100);
+        let reverting = H160::from_low_u64_be(0x1000);
+        let caller = H160::from_low_u64_be(0x2000);
+        let static_caller = H160::from_low_u64_be(0x3000);
+        let codes = [
+            // logs of the reverted call are dropped.
+            (
+                reverting,
+                [call(CALL, 0x01), vec![PUSH1, 0, DUP1, REVERT]].concat(),
+            ),
+            (
+                caller,
+                [call(CALL, 0x01), vec![POP], call(CALL, 0x10), vec![STOP]].concat(),
+            ),
+            // precompile can't emit logs in static call, return success of the call.
+            (
+                static_caller,
+                [
+                    call(STATICCALL, 0x01),
+                    vec![PUSH1, 0, MSTORE, PUSH1, 32, PUSH1, 0, RETURN],
+                ]
+                .concat(),
+            ),
+        ];
+        let mut db = InMemoryDB::default();
+        for (address, code) in codes {
+            db.insert_account_info(
+                address,
+                AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code.into())),
+            );
+        }
+        let mut precompiles = Precompiles::latest().clone();
+        precompiles.insert(precompile, Precompile::Custom(logging_precompile));
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.precompiles = Some(precompiles);
+        evm.env.tx.gas_limit = 100_000;
+
+        evm.env.tx.transact_to = TransactTo::Call(caller);
+        let (result, _) = evm.transact();
+        assert_eq!(result.exit_reason, Return::Stop);
+        assert_eq!(
+            result.logs,
+            vec![crate::Log {
+                address: precompile,
+                topics: Vec::new(),
+                data: Bytes::copy_from_slice(caller.as_bytes()),
+            }]
+        );
+
+        evm.env.tx.transact_to = TransactTo::Call(static_caller);
+        let (result, _) = evm.transact();
+        assert_eq!(result.exit_reason, Return::Return);
+        assert!(matches!(result.out, TransactOut::Call(out) if out.as_ref() == [0; 32]));
+        assert!(result.logs.is_empty());
+    }
+
+    #[test]
+    fn test_custom_precompile_warm() {
+        let precompile = H160::from_low_u64_be(0x100);
+        let mut precompiles = Precompiles::latest().clone();
+        precompiles.insert(precompile, Precompile::Custom(logging_precompile));
+        // address that follows standard precompiles is not one.
+        let after_standard = Precompiles::latest().len() as u8 + 1;
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut evm = crate::new();
+        evm.precompiles = Some(precompiles);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        let mut balance_gas = |address: &[u8]| {
+            let code = [&[PUSH2], address, &[BALANCE, STOP]].concat();
+            let mut db = InMemoryDB::default();
+            db.insert_account_info(
+                contract,
+                AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code.into())),
+            );
+            evm.database(db);
+            let (result, _) = evm.transact();
+            assert_eq!(result.exit_reason, Return::Stop);
+            result.gas_used - 21_000 - 3
+        };
+        // EIP-2929: precompiles are warm.
+        assert_eq!(balance_gas(&[0x01, 0x00]), 100);
+        assert_eq!(balance_gas(&[0x00, 0x01]), 100);
+        assert_eq!(balance_gas(&[0x00, after_standard]), 2600);
+    }
+
+    /// Return apparent value of the call.
+    fn value_precompile(
+        _input: &[u8],
+        _gas_limit: u64,
+        ctx: &PrecompileContext,
+    ) -> PrecompileResult {
+        let mut output = vec![0; 32];
+        ctx.value.to_big_endian(&mut output);
+        Ok(PrecompileOutput {
+            cost: 100,
+            output,
+            logs: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn test_precompile_delegation() {
+        let precompile = H160::from_low_u64_be(0x100);
+        let contract = H160::from_low_u64_be(0x1000);
+        // CALLCODE with value 5 and DELEGATECALL of precompile, return outputs and success flags.
+        // Calls get fixed gas as rejected call consumes all of it.
+        let code = [
+            &[PUSH1, 32, PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 5][..],
+            &[PUSH2, 1, 0, PUSH2, 0x10, 0, CALLCODE, PUSH1, 32, MSTORE],
+            &[PUSH1, 32, PUSH1, 64, PUSH1, 0, PUSH1, 0],
+            &[PUSH2, 1, 0, PUSH2, 0x10, 0, DELEGATECALL, PUSH1, 96, MSTORE],
+            &[PUSH1, 128, PUSH1, 0, RETURN],
+        ]
+        .concat();
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(H160::zero(), AccountInfo::from_balance(100.into()));
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(10.into(), 0, Bytecode::new_raw(code.into())),
+        );
+        let mut precompiles = Precompiles::latest().clone();
+        precompiles.insert(precompile, Precompile::Custom(value_precompile));
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.precompiles = Some(precompiles);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.value = 3.into();
+        evm.env.tx.gas_limit = 100_000;
+
+        let mut run = |delegation: PrecompileDelegation| {
+            evm.env.cfg.precompile_delegation = delegation;
+            let (result, _) = evm.transact();
+            assert_eq!(result.exit_reason, Return::Return);
+            match result.out {
+                TransactOut::Call(out) => out
+                    .chunks(32)
+                    .map(|word| U256::from_big_endian(word).as_u64())
+                    .collect::<Vec<_>>(),
+                _ => unreachable!("call returns call output"),
+            }
+        };
+        assert_eq!(run(PrecompileDelegation::Execute), vec![5, 1, 3, 1]);
+        assert_eq!(run(PrecompileDelegation::NoCode), vec![0, 1, 0, 1]);
+        assert_eq!(run(PrecompileDelegation::Reject), vec![0, 0, 0, 0]);
+    }
+
+    /// Tiny setup that accepts proof equal to commitment.
+    #[derive(Debug)]
+    struct TinySetup;
+
+    impl KzgSettings for TinySetup {
+        fn verify_kzg_proof(
+            &self,
+            commitment: &[u8; 48],
+            _z: &[u8; 32],
+            _y: &[u8; 32],
+            proof: &[u8; 48],
+        ) -> bool {
+            commitment == proof
         }
-        SpecId::MERGE => create_evm!(MergeSpec, db, env, insp),
-        SpecId::LATEST => create_evm!(LatestSpec, db, env, insp),
+    }
+
+    /// Verify proof of input `commitment | z | y | proof` and return one byte of result.
+    fn kzg_precompile(input: &[u8], _gas_limit: u64, ctx: &PrecompileContext) -> PrecompileResult {
+        let settings = ctx
+            .kzg_settings
+            .as_ref()
+            .ok_or(precompiles::Return::Other("missing KZG settings".into()))?;
+        let field = |range: core::ops::Range<usize>| input.get(range).unwrap_or(&[0; 48][..]);
+        let valid = settings.verify_kzg_proof(
+            field(0..48).try_into().unwrap(),
+            field(48..80).try_into().unwrap(),
+            field(80..112).try_into().unwrap(),
+            field(112..160).try_into().unwrap(),
+        );
+        Ok(PrecompileOutput::without_logs(50_000, vec![valid as u8]))
+    }
+
+    #[test]
+    fn test_kzg_settings() {
+        let precompile = H160::from_low_u64_be(0x100);
+        let mut precompiles = Precompiles::latest().clone();
+        precompiles.insert(precompile, Precompile::Custom(kzg_precompile));
+        let mut evm = crate::new();
+        evm.database(InMemoryDB::default());
+        evm.precompiles = Some(precompiles);
+        evm.env.tx.transact_to = TransactTo::Call(precompile);
+        evm.env.tx.gas_limit = 100_000;
+        let mut input = vec![0; 160];
+        input[0] = 1;
+        evm.env.tx.data = input.into();
+
+        // precompile fails without settings.
+        assert_eq!(evm.transact().0.exit_reason, Return::PrecompileError);
+
+        let settings: Arc<dyn KzgSettings> = Arc::new(TinySetup);
+        evm.env.cfg.kzg_settings = Some(settings.clone());
+        let output = |evm: &mut crate::EVM<InMemoryDB>| match evm.transact().0.out {
+            TransactOut::Call(out) => out.to_vec(),
+            _ => unreachable!("call returns call output"),
+        };
+        assert_eq!(output(&mut evm), vec![0]);
+        let mut input = vec![0; 160];
+        input[0] = 1;
+        input[112] = 1;
+        evm.env.tx.data = input.into();
+        assert_eq!(output(&mut evm), vec![1]);
+        assert_eq!(evm.env.cfg.kzg_settings, Some(settings));
+    }
+
+    #[test]
+    fn test_precompile_account() {
+        let identity = H160::from_low_u64_be(4);
+        let contract = H160::from_low_u64_be(0x1000);
+        // return code hashes of identity precompile before and after sending 5 wei to it.
+        let code_hash = |offset: u8| [PUSH1, 4, EXTCODEHASH, PUSH1, offset, MSTORE];
+        let code = [
+            &code_hash(0)[..],
+            &[
+                PUSH1, 0, DUP1, DUP1, DUP1, PUSH1, 5, PUSH1, 4, GAS, CALL, POP,
+            ],
+            &code_hash(32),
+            &[PUSH1, 64, PUSH1, 0, RETURN],
+        ]
+        .concat();
+        let run = |precompile_balance: u64| {
+            let mut db = InMemoryDB::default();
+            db.insert_account_info(
+                contract,
+                AccountInfo::new(10.into(), 0, Bytecode::new_raw(code.clone().into())),
+            );
+            if precompile_balance != 0 {
+                db.insert_account_info(
+                    identity,
+                    AccountInfo::from_balance(precompile_balance.into()),
+                );
+            }
+            let mut evm = crate::new();
+            evm.database(db);
+            evm.env.tx.transact_to = TransactTo::Call(contract);
+            evm.env.tx.gas_limit = 100_000;
+            let (result, state) = evm.transact();
+            let hashes = match result.out {
+                TransactOut::Call(out) => [&out[..32], &out[32..]].map(H256::from_slice),
+                _ => unreachable!("call returns call output"),
+            };
+            (hashes, state[&identity].info.balance)
+        };
+        // precompile without balance is empty account.
+        assert_eq!(run(0), ([H256::zero(), KECCAK_EMPTY], 5.into()));
+        // balance of precompile is loaded from database and not counted twice.
+        assert_eq!(run(10), ([KECCAK_EMPTY, KECCAK_EMPTY], 15.into()));
+    }
+
+    #[test]
+    fn test_evm_trait_object() {
+        use super::{Evm, State};
+        use crate::{EVMData, Env, ExecutionResult, Inspector, Interpreter};
+
+        /// Returns the same result for every transaction.
+        #[derive(Default)]
+        struct MockEvm(Env);
+        impl Evm<InMemoryDB> for MockEvm {
+            fn env(&self) -> &Env {
+                &self.0
+            }
+            fn env_mut(&mut self) -> &mut Env {
+                &mut self.0
+            }
+            fn transact(&mut self) -> (ExecutionResult, State) {
+                (
+                    ExecutionResult::new_with_reason(Return::Revert),
+                    State::new(),
+                )
+            }
+            fn inspect(
+                &mut self,
+                _inspector: &mut dyn Inspector<InMemoryDB>,
+            ) -> (ExecutionResult, State) {
+                self.transact()
+            }
+        }
+
+        #[derive(Default)]
+        struct Steps(usize);
+        impl Inspector<InMemoryDB> for Steps {
+            fn step(
+                &mut self,
+                _interp: &mut Interpreter,
+                _data: &mut EVMData<'_, InMemoryDB>,
+                _is_static: bool,
+            ) -> Return {
+                self.0 += 1;
+                Return::Continue
+            }
+        }
+
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(
+                U256::zero(),
+                0,
+                Bytecode::new_raw(vec![PUSH1, 1, POP, STOP].into()),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        let mut evms: Vec<Box<dyn Evm<InMemoryDB>>> =
+            vec![Box::new(evm), Box::new(MockEvm::default())];
+        for evm in evms.iter_mut() {
+            evm.env_mut().tx.transact_to = TransactTo::Call(contract);
+            evm.env_mut().tx.gas_limit = 100_000;
+        }
+
+        let mut steps = Steps::default();
+        assert_eq!(evms[0].inspect(&mut steps).0.exit_reason, Return::Stop);
+        assert_eq!(steps.0, 3);
+        assert_eq!(evms[0].transact().0.exit_reason, Return::Stop);
+        assert_eq!(evms[1].transact().0.exit_reason, Return::Revert);
+        assert_eq!(evms[1].env().tx.gas_limit, 100_000);
+    }
+
+    #[test]
+    fn test_inspect_ref() {
+        use crate::{
+            db::{Database, WrapDatabaseRef},
+            EVMData, Inspector, Interpreter,
+        };
+
+        #[derive(Default)]
+        struct Steps(usize);
+        impl<DB: Database> Inspector<DB> for Steps {
+            fn step(
+                &mut self,
+                _interp: &mut Interpreter,
+                _data: &mut EVMData<'_, DB>,
+                _is_static: bool,
+            ) -> Return {
+                self.0 += 1;
+                Return::Continue
+            }
+        }
+
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(
+                U256::zero(),
+                0,
+                Bytecode::new_raw(vec![PUSH1, 1, PUSH1, 0, SSTORE, STOP].into()),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+
+        let mut steps = Steps::default();
+        let (result, state) = evm.inspect_ref(&mut steps);
+        assert_eq!(result.exit_reason, Return::Stop);
+        assert_eq!(steps.0, 4);
+        assert_eq!(
+            state[&contract].storage[&U256::zero()].present_value(),
+            U256::one()
+        );
+        // nothing is written to database.
+        assert_eq!(evm.transact_ref().1, state);
+
+        // error of wrapped database is not erased.
+        let db = evm.db.as_ref().unwrap();
+        let _: Result<H256, core::convert::Infallible> =
+            WrapDatabaseRef(db).block_hash(U256::zero());
     }
 }
@@ -1,34 +1,123 @@
 use crate::{
-    db::Database,
-    gas,
+    db::{Database, CODE_RANGE_MAX_LEN},
     interpreter::{self, bytecode::Bytecode},
     interpreter::{Contract, Interpreter},
-    journaled_state::{Account, JournaledState, State},
+    journaled_state::{Account, JournalCheckpoint, JournaledState, State, StateLoad},
     models::SelfDestructResult,
-    return_ok, return_revert, AnalysisKind, CallContext, CallInputs, CallScheme, CreateInputs,
-    CreateScheme, Env, ExecutionResult, Gas, Inspector, Log, Return, Spec,
+    return_ok, return_revert, tx_preprocess, AccessListWarmSet, AnalysisKind, BytecodeState,
+    CallContext, CallInputs, CallScheme, CreateCollision, CreateInputs, CreateScheme, Deployment,
+    EVMHooks, Env, ExecutionResult, FeeSettlement, FrameUsage, Gas, GasBreakdown, Inspector, Log,
+    PrecompileDelegation, Return, Spec,
     SpecId::{self, *},
-    TransactOut, TransactTo, Transfer, KECCAK_EMPTY,
+    SpecRules, TransactOut, TransactTo, Transfer, ZeroValueTouch, KECCAK_EMPTY,
 };
-use alloc::vec::Vec;
+use alloc::{collections::BTreeMap, vec::Vec};
 use bytes::Bytes;
-use core::{cmp::min, marker::PhantomData};
+use core::{cmp::min, marker::PhantomData, mem};
 use hashbrown::HashMap as Map;
 use primitive_types::{H160, H256, U256};
-use revm_precompiles::{Precompile, PrecompileOutput, Precompiles};
+use revm_precompiles::{Precompile, PrecompileContext, PrecompileOutput, Precompiles};
 use sha3::{Digest, Keccak256};
 
 pub struct EVMData<'a, DB: Database> {
+    /// Environment of transaction. Changes done directly are kept if call reverts, use
+    /// [EVMData::env_mut] for changes that are reverted together with state.
     pub env: &'a mut Env,
+    /// Env at the start of every frame that changed it through [EVMData::env_mut], one entry
+    /// per checkpoint.
+    env_journal: Vec<Option<Env>>,
     pub journaled_state: JournaledState,
     pub db: &'a mut DB,
     pub error: Option<DB::Error>,
+    /// Precompiles that are active for current spec. Standard ones are borrowed from static
+    /// table of the spec, see [Precompiles::new].
+    pub precompiles: &'a Precompiles,
+    /// Hashes of blocks read by BLOCKHASH, by block number. Reads of reverted frames are kept.
+    pub block_hashes: BTreeMap<U256, H256>,
+    /// Creates that collided with existing account, see [ExecutionResult::create_collisions].
+    pub create_collisions: Vec<CreateCollision>,
+    /// Statistics of frames executed so far.
+    #[cfg(feature = "execution_stats")]
+    pub stats: crate::ExecutionStats,
+}
+
+impl<'a, DB: Database> EVMData<'a, DB> {
+    /// Whether address is one of active precompiles.
+    pub fn is_precompile(&self, address: &H160) -> bool {
+        self.precompiles.contains(address)
+    }
+
+    /// Env that can be changed in the middle of transaction, for example by cheatcodes that
+    /// set block number or timestamp. Change is journaled and reverted if current call reverts.
+    pub fn env_mut(&mut self) -> &mut Env {
+        if let Some(snapshot @ None) = self.env_journal.last_mut() {
+            *snapshot = Some(self.env.clone());
+        }
+        self.env
+    }
+
+    /// Create checkpoint of state and env at the start of frame.
+    pub fn checkpoint(&mut self) -> JournalCheckpoint {
+        self.env_journal.push(None);
+        self.journaled_state.checkpoint()
+    }
+
+    /// Commit frame. Env it started with becomes the one to revert to in parent frame, if
+    /// parent didn't change env before.
+    pub fn checkpoint_commit(&mut self) {
+        if let Some(env) = self.env_journal.pop().flatten() {
+            if let Some(parent @ None) = self.env_journal.last_mut() {
+                *parent = Some(env);
+            }
+        }
+        self.journaled_state.checkpoint_commit();
+    }
+
+    /// Revert state and env to checkpoint.
+    pub fn checkpoint_revert(&mut self, checkpoint: JournalCheckpoint) {
+        if let Some(env) = self.env_journal.pop().flatten() {
+            *self.env = env;
+        }
+        self.journaled_state.checkpoint_revert(checkpoint);
+    }
+
+    /// Set balance of account. Change is journaled and reverted if current call reverts.
+    pub fn set_balance(&mut self, address: H160, balance: U256) -> Result<(), DB::Error> {
+        self.journaled_state.set_balance(address, balance, self.db)
+    }
+
+    /// Set nonce of account. Change is journaled and reverted if current call reverts.
+    pub fn set_nonce(&mut self, address: H160, nonce: u64) -> Result<(), DB::Error> {
+        self.journaled_state.set_nonce(address, nonce, self.db)
+    }
+
+    /// Set code of account. Change is journaled and reverted if current call reverts.
+    pub fn set_code(&mut self, address: H160, code: Bytecode) -> Result<(), DB::Error> {
+        self.journaled_state.load_account(address, self.db)?;
+        self.journaled_state.set_code(address, code);
+        Ok(())
+    }
+
+    /// Set storage slot of account. Change is journaled and reverted if current call reverts.
+    pub fn set_storage(&mut self, address: H160, key: U256, value: U256) -> Result<(), DB::Error> {
+        self.journaled_state.load_account(address, self.db)?;
+        self.journaled_state.sstore(address, key, value, self.db)?;
+        Ok(())
+    }
 }
 
 pub struct EVMImpl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> {
     data: EVMData<'a, DB>,
-    precompiles: Precompiles,
     inspector: &'a mut dyn Inspector<DB>,
+    hooks: EVMHooks<'a>,
+    /// Gas spent on code deposit of created contracts.
+    code_deposit_gas: u64,
+    /// Code deposit gas charged by the last create frame that stored its code. After create
+    /// transaction succeeds it is the one charged for the deployed contract.
+    last_code_deposit_gas: u64,
+    /// Hardforks and gas prices of `GSPEC`, flags of [crate::CustomSpec] are taken from
+    /// [crate::CfgEnv::custom_spec].
+    spec: SpecRules,
     _phantomdata: PhantomData<GSPEC>,
 }
 
@@ -36,46 +125,82 @@ pub trait Transact {
     /// Do transaction.
     /// Return Return, Output for call or Address if we are creating contract, gas spend, gas refunded, State that needs to be applied.
     fn transact(&mut self) -> (ExecutionResult, State);
+
+    /// Do transaction with intrinsic gas and access list warm set computed before, by
+    /// [crate::PreparedTx]. Stateless checks of environment are skipped as they were done then.
+    fn transact_prepared(
+        &mut self,
+        intrinsic_gas: u64,
+        warm_set: &AccessListWarmSet,
+    ) -> (ExecutionResult, State);
 }
 
 impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> Transact
     for EVMImpl<'a, GSPEC, DB, INSPECT>
 {
     fn transact(&mut self) -> (ExecutionResult, State) {
-        let caller = self.data.env.tx.caller;
-        let value = self.data.env.tx.value;
-        let data = self.data.env.tx.data.clone();
-        let gas_limit = self.data.env.tx.gas_limit;
-        let exit = |reason: Return| (ExecutionResult::new_with_reason(reason), State::new());
+        self.transact_inspected(None)
+    }
 
-        if GSPEC::enabled(LONDON) {
-            if let Some(priority_fee) = self.data.env.tx.gas_priority_fee {
-                if priority_fee > self.data.env.tx.gas_price {
-                    // or gas_max_fee for eip1559
-                    return exit(Return::GasMaxFeeGreaterThanPriorityFee);
-                }
-            }
-            let effective_gas_price = self.data.env.effective_gas_price();
-            let basefee = self.data.env.block.basefee;
+    fn transact_prepared(
+        &mut self,
+        intrinsic_gas: u64,
+        warm_set: &AccessListWarmSet,
+    ) -> (ExecutionResult, State) {
+        self.transact_inspected(Some((intrinsic_gas, warm_set)))
+    }
+}
 
-            // check minimal cost against basefee
-            // TODO maybe do this checks when creating evm. We already have all data there
-            // or should be move effective_gas_price inside transact fn
-            if effective_gas_price < basefee {
-                return exit(Return::GasPriceLessThenBasefee);
-            }
-            // check if priority fee is lower then max fee
+impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB, INSPECT> {
+    fn transact_inspected(
+        &mut self,
+        prepared: Option<(u64, &AccessListWarmSet)>,
+    ) -> (ExecutionResult, State) {
+        if INSPECT {
+            self.inspector.transact_start(self.data.env);
         }
-        // unusual to be found here, but check if gas_limit is more then block_gas_limit
-        if U256::from(gas_limit) > self.data.env.block.gas_limit {
-            return exit(Return::CallerGasLimitMoreThenBlock);
+        let (result, state) = self.transact_inner(prepared);
+        if INSPECT {
+            self.inspector.transact_end(&result, &state);
         }
+        (result, state)
+    }
+
+    fn transact_inner(
+        &mut self,
+        prepared: Option<(u64, &AccessListWarmSet)>,
+    ) -> (ExecutionResult, State) {
+        let caller = self.data.env.tx.caller;
+        let value = self.data.env.tx.value;
+        let data = self.data.env.tx.data.clone();
+        let gas_limit = self.data.env.tx.gas_limit;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            target: "revm::transact",
+            "transact",
+            ?caller,
+            gas_limit,
+            spec = ?GSPEC::SPEC_ID
+        )
+        .entered();
+        let exit = |reason: Return| {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(target: "revm::transact", ?reason, "transaction rejected");
+            (ExecutionResult::new_with_reason(reason), State::new())
+        };
 
         let mut gas = Gas::new(gas_limit);
         // record initial gas cost. if not using gas metering init will return 0
-        if !gas.record_cost(self.initialization::<GSPEC>()) {
-            return exit(Return::OutOfGas);
-        }
+        let initial_gas = match self.initialization::<GSPEC>(prepared) {
+            Ok(initial_gas) if gas.record_cost(initial_gas) => {
+                #[cfg(feature = "gas_accounting")]
+                gas.accounting_mut()
+                    .add(crate::gas::GasCategory::Intrinsic, initial_gas);
+                initial_gas
+            }
+            Ok(_) => return exit(Return::OutOfGas),
+            Err(reason) => return exit(reason),
+        };
 
         // load acc
         if self
@@ -94,9 +219,17 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> Transact
             return exit(Return::RejectCallerWithCode);
         }
 
-        // substract gas_limit*gas_price from current account.
-        if let Some(payment_value) =
-            U256::from(gas_limit).checked_mul(self.data.env.effective_gas_price())
+        // additional fee charged together with gas payment.
+        let extra_fee = self
+            .hooks
+            .fee_hook
+            .map(|hook| hook.fee(self.data.env))
+            .unwrap_or_default();
+
+        // substract gas_limit*gas_price and extra fee from current account.
+        if let Some(payment_value) = U256::from(gas_limit)
+            .checked_mul(self.data.env.effective_gas_price())
+            .and_then(|payment| payment.checked_add(extra_fee))
         {
             let balance = &mut self
                 .data
@@ -127,6 +260,7 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> Transact
         }
 
         // call inner handling of call/create
+        let mut deployment = None;
         let (exit_reason, ret_gas, out) = match self.data.env.tx.transact_to {
             TransactTo::Call(address) => {
                 if self.data.journaled_state.inc_nonce(caller).is_none() {
@@ -150,11 +284,16 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> Transact
                     input: data,
                     gas_limit,
                     context,
+                    is_static: false,
                 };
                 let (exit, gas, bytes) = self.call_inner::<GSPEC>(&mut call_input);
                 (exit, gas, TransactOut::Call(bytes))
             }
             TransactTo::Create(scheme) => {
+                // EIP-2681: Limit account nonce to 2^64-1
+                if self.data.journaled_state.account(caller).info.nonce == u64::MAX {
+                    return exit(Return::NonceOverflow);
+                }
                 let mut create_input = CreateInputs {
                     caller,
                     scheme,
@@ -163,6 +302,9 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> Transact
                     gas_limit,
                 };
                 let (exit, address, ret_gas, bytes) = self.create_inner::<GSPEC>(&mut create_input);
+                if let (return_ok!(), Some(address)) = (exit, address) {
+                    deployment = Some(self.deployment(address));
+                }
                 (exit, ret_gas, TransactOut::Create(bytes, address))
             }
         };
@@ -173,24 +315,71 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> Transact
                     gas.erase_cost(ret_gas.remaining());
                     gas.record_refund(ret_gas.refunded());
                 }
-                return_revert!() => {
+                // nonce overflow of created address fails deployment without consuming its gas.
+                return_revert!() | Return::NonceOverflow => {
                     gas.erase_cost(ret_gas.remaining());
                 }
                 _ => {}
             }
+            #[cfg(feature = "gas_accounting")]
+            gas.accounting_mut().add_frame(
+                &ret_gas,
+                matches!(
+                    exit_reason,
+                    return_ok!() | return_revert!() | Return::NonceOverflow
+                ),
+            );
         }
 
-        let (state, logs, gas_used, gas_refunded) = self.finalize::<GSPEC>(caller, &gas);
-        (
-            ExecutionResult {
-                exit_reason,
-                out,
-                gas_used,
-                gas_refunded,
-                logs,
-            },
-            state,
-        )
+        let (state, logs, gas_used, gas_refunded, fees) =
+            match self.finalize::<GSPEC>(caller, &gas, extra_fee) {
+                Ok(finalized) => finalized,
+                Err(err) => {
+                    self.data.error = Some(err);
+                    return exit(Return::FatalExternalError);
+                }
+            };
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            target: "revm::transact",
+            ?exit_reason,
+            gas_used,
+            gas_refunded,
+            "transaction executed"
+        );
+        gas.set_refund(gas_refunded as i64);
+        // finalized state has only touched accounts.
+        let mut touched: Vec<H160> = state.keys().copied().collect();
+        touched.sort_unstable();
+        let gas_breakdown = GasBreakdown {
+            intrinsic: initial_gas,
+            execution: gas
+                .spend()
+                .saturating_sub(initial_gas + self.code_deposit_gas),
+            code_deposit: self.code_deposit_gas,
+        };
+        let mut result = ExecutionResult {
+            exit_reason,
+            out,
+            gas_used,
+            gas_refunded,
+            logs,
+            truncated: Default::default(),
+            gas,
+            gas_breakdown,
+            fees,
+            deployment,
+            block_hashes: mem::take(&mut self.data.block_hashes).into_iter().collect(),
+            touched,
+            create_collisions: mem::take(&mut self.data.create_collisions),
+            #[cfg(feature = "execution_stats")]
+            stats: Some(self.data.stats),
+            #[cfg(not(feature = "execution_stats"))]
+            stats: None,
+        };
+        let cfg = &self.data.env.cfg;
+        result.truncate(cfg.limit_result_output_size, cfg.limit_result_log_data_size);
+        (result, state)
     }
 }
 
@@ -199,154 +388,187 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         db: &'a mut DB,
         env: &'a mut Env,
         inspector: &'a mut dyn Inspector<DB>,
-        precompiles: Precompiles,
+        hooks: EVMHooks<'a>,
     ) -> Self {
-        let journaled_state = if GSPEC::enabled(SpecId::SPURIOUS_DRAGON) {
-            JournaledState::new(precompiles.len())
+        let spec = SpecRules::of::<GSPEC>(env.cfg.custom_spec);
+        let precompiles = hooks
+            .precompiles
+            .unwrap_or_else(|| Precompiles::new(spec.flags().to_precompile_id()));
+        let precompile_addresses = precompiles.addresses().into_iter().copied().collect();
+        let mut journaled_state = if spec.enabled::<GSPEC>(SpecId::SPURIOUS_DRAGON) {
+            JournaledState::with_precompiles(precompile_addresses)
         } else {
-            JournaledState::new_legacy(precompiles.len())
+            JournaledState::with_precompiles_legacy(precompile_addresses)
         };
+        journaled_state.touch_zero_value = env.cfg.zero_value_touch == ZeroValueTouch::Touch;
         Self {
             data: EVMData {
                 env,
+                env_journal: Vec::new(),
                 journaled_state,
                 db,
                 error: None,
+                precompiles,
+                block_hashes: BTreeMap::new(),
+                create_collisions: Vec::new(),
+                #[cfg(feature = "execution_stats")]
+                stats: Default::default(),
             },
-            precompiles,
             inspector,
+            hooks,
+            code_deposit_gas: 0,
+            last_code_deposit_gas: 0,
+            spec,
             _phantomdata: PhantomData {},
         }
     }
 
+    /// Pay back unused gas and refund to caller, pay coinbase and distribute fee of
+    /// [TxFeeHook]. Fails if one of paid accounts can't be loaded.
+    #[allow(clippy::type_complexity)]
     fn finalize<SPEC: Spec>(
         &mut self,
         caller: H160,
         gas: &Gas,
-    ) -> (Map<H160, Account>, Vec<Log>, u64, u64) {
+        extra_fee: U256,
+    ) -> Result<(Map<H160, Account>, Vec<Log>, u64, u64, FeeSettlement), DB::Error> {
         let coinbase = self.data.env.block.coinbase;
+        let mut fees = FeeSettlement::default();
         let (gas_used, gas_refunded) = if crate::USE_GAS {
             let effective_gas_price = self.data.env.effective_gas_price();
             let basefee = self.data.env.block.basefee;
-            let max_refund_quotient = if SPEC::enabled(LONDON) { 5 } else { 2 }; // EIP-3529: Reduction in refunds
+            // EIP-3529: Reduction in refunds
+            let max_refund_quotient = self.spec.gas_table::<SPEC>().max_refund_quotient;
 
-            let gas_refunded = min(gas.refunded() as u64, gas.spend() / max_refund_quotient);
-            let acc_caller = self.data.journaled_state.state().get_mut(&caller).unwrap();
-            acc_caller.info.balance = acc_caller
-                .info
-                .balance
-                .saturating_add(effective_gas_price * (gas.remaining() + gas_refunded));
+            // refund counter can't be negative on transaction level, but clamp it to be safe.
+            let gas_refunded = min(
+                gas.refunded().max(0) as u64,
+                gas.spend() / max_refund_quotient,
+            );
+            let gas_used = gas.spend() - gas_refunded;
+            fees.caller_refund = effective_gas_price * (gas.remaining() + gas_refunded);
+            self.credit(caller, fees.caller_refund)?;
 
             // EIP-1559
-            let coinbase_gas_price = if SPEC::enabled(LONDON) {
+            let coinbase_gas_price = if self.spec.enabled::<SPEC>(LONDON) {
                 effective_gas_price.saturating_sub(basefee)
             } else {
                 effective_gas_price
             };
-
-            // TODO
-            let _ = self
-                .data
-                .journaled_state
-                .load_account(coinbase, self.data.db);
-            self.data.journaled_state.touch(&coinbase);
-            let acc_coinbase = self
-                .data
-                .journaled_state
-                .state()
-                .get_mut(&coinbase)
-                .unwrap();
-            acc_coinbase.info.balance = acc_coinbase
-                .info
-                .balance
-                .saturating_add(coinbase_gas_price * (gas.spend() - gas_refunded));
-            (gas.spend() - gas_refunded, gas_refunded)
+            fees.coinbase_reward = coinbase_gas_price * gas_used;
+            fees.burned = (effective_gas_price - coinbase_gas_price) * gas_used;
+            self.credit(coinbase, fees.coinbase_reward)?;
+            (gas_used, gas_refunded)
         } else {
-            // touch coinbase
-            // TODO return
-            let _ = self
-                .data
-                .journaled_state
-                .load_account(coinbase, self.data.db);
-            self.data.journaled_state.touch(&coinbase);
+            // coinbase is touched even if it is not paid.
+            self.credit(coinbase, U256::zero())?;
             (0, 0)
         };
-        let (mut new_state, logs) = self.data.journaled_state.finalize();
-        // precompiles are special case. If there is precompiles in finalized Map that means some balance is
-        // added to it, we need now to load precompile address from db and add this amount to it so that we
-        // will have sum.
-        if self.data.env.cfg.perf_all_precompiles_have_balance {
-            for address in self.precompiles.addresses() {
-                if let Some(precompile) = new_state.get_mut(address) {
-                    // we found it.
-                    precompile.info.balance += self
-                        .data
-                        .db
-                        .basic(*address)
-                        .ok()
-                        .flatten()
-                        .map(|acc| acc.balance)
-                        .unwrap_or_default();
-                }
+        if let Some(hook) = self.hooks.fee_hook {
+            for (address, amount) in hook.distribute(self.data.env, extra_fee, gas_used) {
+                // hook can't distribute more than was charged.
+                let amount = min(amount, extra_fee - fees.distributed);
+                self.credit(address, amount)?;
+                fees.distributed += amount;
             }
+            fees.burned += extra_fee - fees.distributed;
         }
+        let (new_state, logs) = self.data.journaled_state.finalize();
+        Ok((new_state, logs, gas_used, gas_refunded, fees))
+    }
 
-        (new_state, logs, gas_used, gas_refunded)
+    /// Load and touch account and add `amount` to its balance.
+    fn credit(&mut self, address: H160, amount: U256) -> Result<(), DB::Error> {
+        self.data
+            .journaled_state
+            .load_account(address, self.data.db)?;
+        if !amount.is_zero() || self.data.journaled_state.touch_zero_value {
+            self.data.journaled_state.touch(&address);
+        }
+        let account = self.data.journaled_state.state().get_mut(&address).unwrap();
+        account.info.balance = account.info.balance.saturating_add(amount);
+        Ok(())
     }
 
-    fn initialization<SPEC: Spec>(&mut self) -> u64 {
-        let is_create = matches!(self.data.env.tx.transact_to, TransactTo::Create(_));
-        let input = &self.data.env.tx.data;
+    /// Code deployed at `address` by create transaction and gas that was paid for it.
+    fn deployment(&self, address: H160) -> Deployment {
+        let code = match &self.data.journaled_state.account(address).info.code {
+            Some(code) => code.bytes().slice(..code.len()),
+            None => Bytes::new(),
+        };
+        Deployment {
+            code,
+            code_deposit_gas: self.last_code_deposit_gas,
+        }
+    }
 
-        if crate::USE_GAS {
-            let zero_data_len = input.iter().filter(|v| **v == 0).count() as u64;
-            let non_zero_data_len = (input.len() as u64 - zero_data_len) as u64;
-            let (accessed_accounts, accessed_slots) = {
-                if SPEC::enabled(BERLIN) {
-                    let mut accessed_slots = 0_u64;
-
-                    for (address, slots) in self.data.env.tx.access_list.iter() {
-                        // TODO return
-                        let _ = self
-                            .data
-                            .journaled_state
-                            .load_account(*address, self.data.db);
-                        accessed_slots += slots.len() as u64;
-                        // TODO return
-                        for slot in slots {
-                            let _ = self
-                                .data
-                                .journaled_state
-                                .sload(*address, *slot, self.data.db);
-                        }
+    /// Run [tx_preprocess] stages of transaction. Returns intrinsic gas of transaction.
+    fn initialization<SPEC: Spec>(
+        &mut self,
+        prepared: Option<(u64, &AccessListWarmSet)>,
+    ) -> Result<u64, Return> {
+        if prepared.is_none() {
+            tx_preprocess::validate_env(self.spec.flags(), self.data.env)?;
+        }
+        if crate::USE_GAS && self.spec.enabled::<SPEC>(BERLIN) {
+            let EVMData {
+                env,
+                journaled_state,
+                db,
+                error,
+                ..
+            } = &mut self.data;
+            tx_preprocess::warm_addresses(journaled_state, *db, &env.cfg.warm_addresses)
+                .and_then(|_| match prepared {
+                    Some((_, warm_set)) => {
+                        tx_preprocess::warm_access_list_set(journaled_state, *db, warm_set)
                     }
-                    (self.data.env.tx.access_list.len() as u64, accessed_slots)
-                } else {
-                    (0, 0)
-                }
-            };
-
-            let transact = if is_create {
-                if SPEC::enabled(HOMESTEAD) {
-                    // EIP-2: Homestead Hard-fork Changes
-                    53000
-                } else {
-                    21000
-                }
-            } else {
-                21000
-            };
+                    None => {
+                        tx_preprocess::warm_access_list(journaled_state, *db, &env.tx.access_list)
+                    }
+                })
+                .map_err(|err| {
+                    *error = Some(err);
+                    Return::FatalExternalError
+                })?;
+        }
+        Ok(match prepared {
+            Some((intrinsic_gas, _)) if crate::USE_GAS => intrinsic_gas,
+            _ => tx_preprocess::intrinsic_gas(self.spec.gas_table::<SPEC>(), self.data.env),
+        })
+    }
 
-            // EIP-2028: Transaction data gas cost reduction
-            let gas_transaction_non_zero_data = if SPEC::enabled(ISTANBUL) { 16 } else { 68 };
+    /// Analyse bytecode using analysis cache if it is set. Without the cache bytecode is analysed
+    /// when contract is created.
+    fn analysed<SPEC: Spec>(&self, bytecode: Bytecode) -> Bytecode {
+        let spec_id = match SPEC::SPEC_ID {
+            Some(spec_id) => spec_id,
+            // analysis depends on flags that are not part of cache key.
+            None => return self.custom_analysed::<SPEC>(bytecode),
+        };
+        let cache = match self.hooks.analysis_cache {
+            Some(cache)
+                if !bytecode.is_empty()
+                    && !matches!(bytecode.state(), BytecodeState::Analysed { .. }) =>
+            {
+                cache
+            }
+            _ => return bytecode,
+        };
+        if let Some(analysed) = cache.get(spec_id, bytecode.hash()) {
+            return analysed;
+        }
+        let analysed = bytecode.to_analysed::<SPEC>();
+        cache.insert(spec_id, analysed.clone());
+        analysed
+    }
 
-            transact
-                + zero_data_len * gas::TRANSACTION_ZERO_DATA
-                + non_zero_data_len * gas_transaction_non_zero_data
-                + accessed_accounts * gas::ACCESS_LIST_ADDRESS
-                + accessed_slots * gas::ACCESS_LIST_STORAGE_KEY
-        } else {
-            0
+    /// Analyse bytecode of [crate::CustomSpec] with its flags, before it is locked by [Contract]
+    /// that analyses it for `SPEC` only. Bytecode of standard specs is returned as it is.
+    fn custom_analysed<SPEC: Spec>(&self, bytecode: Bytecode) -> Bytecode {
+        match SPEC::SPEC_ID {
+            Some(_) => bytecode,
+            None => bytecode.to_analysed_with_flags(self.spec.flags()),
         }
     }
 
@@ -354,6 +576,10 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         &mut self,
         inputs: &mut CreateInputs,
     ) -> (Return, Option<H160>, Gas, Bytes) {
+        #[cfg(feature = "execution_stats")]
+        {
+            self.data.stats.calls += 1;
+        }
         // Call inspector
         if INSPECT {
             let (ret, address, gas, out) = self.inspector.create(&mut self.data, inputs);
@@ -364,6 +590,17 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
             }
         }
 
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!(
+            target: "revm::call",
+            "create",
+            caller = ?inputs.caller,
+            depth = self.data.journaled_state.depth(),
+            gas_limit = inputs.gas_limit,
+            init_code_len = inputs.init_code.len()
+        )
+        .entered();
+
         let gas = Gas::new(inputs.gas_limit);
         self.load_account(inputs.caller);
 
@@ -371,9 +608,16 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         if self.data.journaled_state.depth() > interpreter::CALL_STACK_LIMIT {
             return (Return::CallTooDeep, None, gas, Bytes::new());
         }
+        if let Some(limit) = self.data.env.cfg.limit_initcode_size {
+            if inputs.init_code.len() > limit {
+                return (Return::CreateInitcodeSizeLimit, None, gas, Bytes::new());
+            }
+        }
         // Check balance of caller and value. Do this before increasing nonce
         match self.balance(inputs.caller) {
-            Some(i) if i.0 < inputs.value => return (Return::OutOfFund, None, gas, Bytes::new()),
+            Some(i) if i.data < inputs.value => {
+                return (Return::OutOfFund, None, gas, Bytes::new())
+            }
             Some(_) => (),
             _ => return (Return::FatalExternalError, None, gas, Bytes::new()),
         }
@@ -388,33 +632,53 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
 
         // Create address
         let code_hash = H256::from_slice(Keccak256::digest(&inputs.init_code).as_slice());
-        let created_address = match inputs.scheme {
-            CreateScheme::Create => create_address(inputs.caller, old_nonce),
-            CreateScheme::Create2 { salt } => create2_address(inputs.caller, code_hash, salt),
+        let created_address = match (self.hooks.create_address_hook, inputs.scheme) {
+            (Some(hook), scheme) => {
+                hook.create_address(inputs.caller, old_nonce, scheme, code_hash)
+            }
+            (None, CreateScheme::Create) => create_address(inputs.caller, old_nonce),
+            (None, CreateScheme::Create2 { salt }) => {
+                create2_address(inputs.caller, code_hash, salt)
+            }
         };
         let ret = Some(created_address);
 
         // Load account so that it will be hot
-        self.load_account(created_address);
+        let was_warm = self
+            .load_account(created_address)
+            .is_some_and(|load| !load.is_cold);
+        if INSPECT {
+            self.inspector
+                .create_address(&mut self.data, inputs, created_address, old_nonce);
+        }
 
         // Enter subroutine
-        let checkpoint = self.data.journaled_state.checkpoint();
+        let checkpoint = self.data.checkpoint();
 
         // Create contract account and check for collision
         match self.data.journaled_state.create_account(
             created_address,
-            self.precompiles.contains(&created_address),
+            self.data.precompiles.contains(&created_address),
             self.data.db,
         ) {
-            Ok(false) => {
-                self.data.journaled_state.checkpoint_revert(checkpoint);
+            Ok(Err(reason)) => {
+                let info = &self.data.journaled_state.state[&created_address].info;
+                let collision = CreateCollision {
+                    address: created_address,
+                    reason,
+                    nonce: info.nonce,
+                    code_hash: info.code_hash,
+                    was_warm,
+                };
+                self.data.create_collisions.push(collision);
+                self.data.checkpoint_revert(checkpoint);
                 return (Return::CreateCollision, ret, gas, Bytes::new());
             }
             Err(err) => {
                 self.data.error = Some(err);
                 return (Return::FatalExternalError, ret, gas, Bytes::new());
             }
-            Ok(true) => (),
+            Ok(Ok(())) => (),
         }
 
         // Transfer value to contract address
@@ -424,27 +688,27 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
             inputs.value,
             self.data.db,
         ) {
-            self.data.journaled_state.checkpoint_revert(checkpoint);
+            self.data.checkpoint_revert(checkpoint);
             return (e, ret, gas, Bytes::new());
         }
 
         // EIP-161: State trie clearing (invariant-preserving alternative)
-        if SPEC::enabled(SPURIOUS_DRAGON)
+        if self.spec.enabled::<SPEC>(SPURIOUS_DRAGON)
             && self
                 .data
                 .journaled_state
                 .inc_nonce(created_address)
                 .is_none()
         {
-            // overflow
-            self.data.journaled_state.checkpoint_revert(checkpoint);
-            return (Return::Return, None, gas, Bytes::new());
+            // overflow, deployment fails but gas is returned as on revert.
+            self.data.checkpoint_revert(checkpoint);
+            return (Return::NonceOverflow, ret, gas, Bytes::new());
         }
 
         // Create new interpreter and execute initcode
         let contract = Contract::new::<SPEC>(
             Bytes::new(),
-            Bytecode::new_raw(inputs.init_code.clone()),
+            self.custom_analysed::<SPEC>(Bytecode::new_raw(inputs.init_code.clone())),
             created_address,
             inputs.caller,
             inputs.value,
@@ -454,61 +718,84 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         let mut interp = Interpreter::new_with_memory_limit::<SPEC>(
             contract,
             gas.limit(),
+            false,
             self.data.env.cfg.memory_limit,
         );
 
         #[cfg(not(feature = "memory_limit"))]
-        let mut interp = Interpreter::new::<SPEC>(contract, gas.limit());
+        let mut interp = Interpreter::new::<SPEC>(contract, gas.limit(), false);
+
+        interp.spec = self.spec;
 
         if Self::INSPECT {
+            interp.step_opcodes = self.inspector.step_opcodes();
+            // create is never executed in static context.
             self.inspector
-                .initialize_interp(&mut interp, &mut self.data, SPEC::IS_STATIC_CALL);
+                .initialize_interp(&mut interp, &mut self.data, false);
         }
         let exit_reason = interp.run::<Self, SPEC>(self);
+        #[cfg(feature = "execution_stats")]
+        self.data.stats.add_frame(&interp);
+        if INSPECT {
+            let usage = FrameUsage::new(&interp, self.data.journaled_state.depth());
+            self.inspector.frame_usage(&mut self.data, &usage);
+        }
 
         // Host error if present on execution\
         let (ret, address, gas, out) = match exit_reason {
             return_ok!() => {
                 let b = Bytes::new();
                 // if ok, check contract creation limit and calculate gas deduction on output len.
-                let mut bytes = interp.return_value();
+                let mut bytes = interp.take_return_value();
 
                 // EIP-3541: Reject new contract code starting with the 0xEF byte
-                if SPEC::enabled(LONDON) && !bytes.is_empty() && bytes.first() == Some(&0xEF) {
-                    self.data.journaled_state.checkpoint_revert(checkpoint);
+                if self.spec.enabled::<SPEC>(LONDON)
+                    && !bytes.is_empty()
+                    && bytes.first() == Some(&0xEF)
+                {
+                    self.data.checkpoint_revert(checkpoint);
                     return (Return::CreateContractWithEF, ret, interp.gas, b);
                 }
 
                 // EIP-170: Contract code size limit
                 // By default limit is 0x6000 (~25kb)
-                if SPEC::enabled(SPURIOUS_DRAGON)
-                    && bytes.len() > self.data.env.cfg.limit_contract_code_size.unwrap_or(0x6000)
+                if self.spec.enabled::<SPEC>(SPURIOUS_DRAGON)
+                    && bytes.len() > self.data.env.cfg.max_code_size()
                 {
-                    self.data.journaled_state.checkpoint_revert(checkpoint);
+                    self.data.checkpoint_revert(checkpoint);
                     return (Return::CreateContractLimit, ret, interp.gas, b);
                 }
                 if crate::USE_GAS {
                     let gas_for_code = bytes.len() as u64 * crate::gas::CODEDEPOSIT;
-                    if !interp.gas.record_cost(gas_for_code) {
+                    if interp.gas.record_cost(gas_for_code) {
+                        #[cfg(feature = "gas_accounting")]
+                        interp
+                            .gas
+                            .accounting_mut()
+                            .add(crate::gas::GasCategory::CodeDeposit, gas_for_code);
+                        self.code_deposit_gas += gas_for_code;
+                        self.last_code_deposit_gas = gas_for_code;
+                    } else {
                         // record code deposit gas cost and check if we are out of gas.
                         // EIP-2 point 3: If contract creation does not have enough gas to pay for the
                         // final gas fee for adding the contract code to the state, the contract
                         //  creation fails (i.e. goes out-of-gas) rather than leaving an empty contract.
-                        if SPEC::enabled(HOMESTEAD) {
-                            self.data.journaled_state.checkpoint_revert(checkpoint);
+                        if self.spec.enabled::<SPEC>(HOMESTEAD) {
+                            self.data.checkpoint_revert(checkpoint);
                             return (Return::OutOfGas, ret, interp.gas, b);
                         } else {
                             bytes = Bytes::new();
+                            self.last_code_deposit_gas = 0;
                         }
                     }
                 }
                 // if we have enought gas
-                self.data.journaled_state.checkpoint_commit();
+                self.data.checkpoint_commit();
                 // Do analasis of bytecode streight away.
                 let bytecode = match self.data.env.cfg.perf_analyse_created_bytecodes {
                     AnalysisKind::Raw => Bytecode::new_raw(bytes),
                     AnalysisKind::Check => Bytecode::new_raw(bytes).to_checked(),
-                    AnalysisKind::Analyse => Bytecode::new_raw(bytes).to_analysed::<SPEC>(),
+                    AnalysisKind::Analyse => self.analysed::<SPEC>(Bytecode::new_raw(bytes)),
                 };
 
                 self.data
@@ -517,8 +804,8 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
                 (Return::Continue, ret, interp.gas, b)
             }
             _ => {
-                self.data.journaled_state.checkpoint_revert(checkpoint);
-                (exit_reason, ret, interp.gas, interp.return_value())
+                self.data.checkpoint_revert(checkpoint);
+                (exit_reason, ret, interp.gas, interp.take_return_value())
             }
         };
 
@@ -531,11 +818,15 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
     }
 
     fn call_inner<SPEC: Spec>(&mut self, inputs: &mut CallInputs) -> (Return, Gas, Bytes) {
+        #[cfg(feature = "execution_stats")]
+        {
+            self.data.stats.calls += 1;
+        }
         // Call the inspector
         if INSPECT {
             let (ret, gas, out) = self
                 .inspector
-                .call(&mut self.data, inputs, SPEC::IS_STATIC_CALL);
+                .call(&mut self.data, inputs, inputs.is_static);
             if ret != Return::Continue {
                 return self.inspector.call_end(
                     &mut self.data,
@@ -543,15 +834,26 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
                     gas,
                     ret,
                     out,
-                    SPEC::IS_STATIC_CALL,
+                    inputs.is_static,
                 );
             }
         }
 
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!(
+            target: "revm::call",
+            "call",
+            address = ?inputs.contract,
+            caller = ?inputs.context.caller,
+            depth = self.data.journaled_state.depth(),
+            gas_limit = inputs.gas_limit
+        )
+        .entered();
+
         let mut gas = Gas::new(inputs.gas_limit);
         // Load account and get code. Account is now hot.
-        let bytecode = if let Some((bytecode, _)) = self.code(inputs.contract) {
-            bytecode
+        let bytecode = if let Some(load) = self.code(inputs.contract) {
+            self.analysed::<SPEC>(load.data)
         } else {
             return (Return::FatalExternalError, gas, Bytes::new());
         };
@@ -566,7 +868,31 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
                     gas,
                     ret,
                     out,
-                    SPEC::IS_STATIC_CALL,
+                    inputs.is_static,
+                );
+            } else {
+                return (ret, gas, out);
+            }
+        }
+
+        let is_delegated = matches!(
+            inputs.context.scheme,
+            CallScheme::DelegateCall | CallScheme::CallCode
+        );
+        let precompile_delegation = self.data.env.cfg.precompile_delegation;
+        if is_delegated
+            && precompile_delegation == PrecompileDelegation::Reject
+            && self.data.precompiles.contains(&inputs.contract)
+        {
+            let (ret, gas, out) = (Return::PrecompileDelegation, gas, Bytes::new());
+            if Self::INSPECT {
+                return self.inspector.call_end(
+                    &mut self.data,
+                    inputs,
+                    gas,
+                    ret,
+                    out,
+                    inputs.is_static,
                 );
             } else {
                 return (ret, gas, out);
@@ -574,10 +900,10 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
         }
 
         // Create subroutine checkpoint
-        let checkpoint = self.data.journaled_state.checkpoint();
+        let checkpoint = self.data.checkpoint();
 
         // Touch address. For "EIP-158 State Clear", this will erase empty accounts.
-        if inputs.transfer.value.is_zero() {
+        if inputs.transfer.value.is_zero() && self.data.journaled_state.touch_zero_value {
             self.load_account(inputs.context.address);
             self.data.journaled_state.touch(&inputs.context.address);
         }
@@ -589,7 +915,7 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
             inputs.transfer.value,
             self.data.db,
         ) {
-            self.data.journaled_state.checkpoint_revert(checkpoint);
+            self.data.checkpoint_revert(checkpoint);
             let (ret, gas, out) = (e, gas, Bytes::new());
             if Self::INSPECT {
                 return self.inspector.call_end(
@@ -598,39 +924,75 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
                     gas,
                     ret,
                     out,
-                    SPEC::IS_STATIC_CALL,
+                    inputs.is_static,
                 );
             } else {
                 return (ret, gas, out);
             }
         }
 
-        // Call precompiles
-        let (ret, gas, out) = if let Some(precompile) = self.precompiles.get(&inputs.contract) {
+        // Call precompiles. With `NoCode` delegated precompile executes its (usually empty) code.
+        let precompile =
+            self.data.precompiles.get(&inputs.contract).filter(|_| {
+                !is_delegated || precompile_delegation == PrecompileDelegation::Execute
+            });
+        let (ret, gas, out) = if let Some(precompile) = precompile {
             let out = match precompile {
                 Precompile::Standard(fun) => fun(inputs.input.as_ref(), inputs.gas_limit),
-                Precompile::Custom(fun) => fun(inputs.input.as_ref(), inputs.gas_limit),
+                Precompile::Custom(fun) => fun(
+                    inputs.input.as_ref(),
+                    inputs.gas_limit,
+                    &PrecompileContext {
+                        address: inputs.context.address,
+                        caller: inputs.context.caller,
+                        value: inputs.context.apparent_value,
+                        is_static: inputs.is_static,
+                        kzg_settings: self.data.env.cfg.kzg_settings.clone(),
+                    },
+                ),
             };
-            match out {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(
+                target: "revm::precompile",
+                address = ?inputs.contract,
+                input_len = inputs.input.len(),
+                cost = ?out.as_ref().map(|out| out.cost),
+                "precompile"
+            );
+            let ret = match out {
                 Ok(PrecompileOutput { output, cost, logs }) => {
-                    if !crate::USE_GAS || gas.record_cost(cost) {
-                        logs.into_iter().for_each(|l| {
-                            self.data.journaled_state.log(Log {
-                                address: l.address,
-                                topics: l.topics,
-                                data: l.data,
-                            })
-                        });
-                        self.data.journaled_state.checkpoint_commit();
-                        (Return::Continue, gas, Bytes::from(output))
+                    let charged = !crate::USE_GAS || gas.record_cost(cost);
+                    #[cfg(feature = "gas_accounting")]
+                    if crate::USE_GAS && charged {
+                        gas.accounting_mut()
+                            .add(crate::gas::GasCategory::Precompile, cost);
+                    }
+                    if !charged {
+                        Err(Return::OutOfGas)
+                    } else if inputs.is_static && !logs.is_empty() {
+                        Err(Return::StateChangeDuringStaticCall)
                     } else {
-                        self.data.journaled_state.checkpoint_revert(checkpoint);
-                        (Return::OutOfGas, gas, Bytes::new())
+                        // logs are part of the call checkpoint and are dropped if it is reverted.
+                        for log in logs {
+                            self.data.journaled_state.log(Log {
+                                address: log.address,
+                                topics: log.topics,
+                                data: log.data,
+                            });
+                        }
+                        Ok(Bytes::from(output))
                     }
                 }
-                Err(_e) => {
-                    self.data.journaled_state.checkpoint_revert(checkpoint); //TODO check if we are discarding or reverting
-                    (Return::PrecompileError, gas, Bytes::new())
+                Err(_e) => Err(Return::PrecompileError),
+            };
+            match ret {
+                Ok(output) => {
+                    self.data.checkpoint_commit();
+                    (Return::Continue, gas, output)
+                }
+                Err(ret) => {
+                    self.data.checkpoint_revert(checkpoint);
+                    (ret, gas, Bytes::new())
                 }
             }
         } else {
@@ -642,30 +1004,41 @@ impl<'a, GSPEC: Spec, DB: Database, const INSPECT: bool> EVMImpl<'a, GSPEC, DB,
             let mut interp = Interpreter::new_with_memory_limit::<SPEC>(
                 contract,
                 gas.limit(),
+                inputs.is_static,
                 self.data.env.cfg.memory_limit,
             );
 
             #[cfg(not(feature = "memory_limit"))]
-            let mut interp = Interpreter::new::<SPEC>(contract, gas.limit());
+            let mut interp = Interpreter::new::<SPEC>(contract, gas.limit(), inputs.is_static);
+
+            interp.spec = self.spec;
 
             if Self::INSPECT {
-                // create is always no static call.
+                interp.step_opcodes = self.inspector.step_opcodes();
                 self.inspector
-                    .initialize_interp(&mut interp, &mut self.data, false);
+                    .initialize_interp(&mut interp, &mut self.data, inputs.is_static);
             }
             let exit_reason = interp.run::<Self, SPEC>(self);
+            #[cfg(feature = "execution_stats")]
+            self.data.stats.add_frame(&interp);
+            if INSPECT {
+                let usage = FrameUsage::new(&interp, self.data.journaled_state.depth());
+                self.inspector.frame_usage(&mut self.data, &usage);
+            }
             if matches!(exit_reason, return_ok!()) {
-                self.data.journaled_state.checkpoint_commit();
+                self.data.checkpoint_commit();
             } else {
-                self.data.journaled_state.checkpoint_revert(checkpoint);
+                self.data.checkpoint_revert(checkpoint);
             }
 
-            (exit_reason, interp.gas, interp.return_value())
+            (exit_reason, interp.gas, interp.take_return_value())
         };
+        #[cfg(feature = "tracing")]
+        tracing::trace!(target: "revm::call", ?ret, gas_used = gas.spend(), "call finished");
 
         if INSPECT {
             self.inspector
-                .call_end(&mut self.data, inputs, gas, ret, out, SPEC::IS_STATIC_CALL)
+                .call_end(&mut self.data, inputs, gas, ret, out, inputs.is_static)
         } else {
             (ret, gas, out)
         }
@@ -692,14 +1065,29 @@ impl<'a, GSPEC: Spec, DB: Database + 'a, const INSPECT: bool> Host
     }
 
     fn block_hash(&mut self, number: U256) -> Option<H256> {
-        self.data
-            .db
-            .block_hash(number)
-            .map_err(|e| self.data.error = Some(e))
-            .ok()
+        #[cfg(feature = "tracing")]
+        tracing::trace!(target: "revm::db", %number, "block_hash");
+        if let Some(hash) = self
+            .hooks
+            .block_hashes
+            .and_then(|hashes| hashes.block_hash(number))
+        {
+            self.data.block_hashes.insert(number, hash);
+            return Some(hash);
+        }
+        match self.data.db.block_hash(number) {
+            Ok(hash) => {
+                self.data.block_hashes.insert(number, hash);
+                Some(hash)
+            }
+            Err(e) => {
+                self.data.error = Some(e);
+                None
+            }
+        }
     }
 
-    fn load_account(&mut self, address: H160) -> Option<(bool, bool)> {
+    fn load_account(&mut self, address: H160) -> Option<StateLoad<bool>> {
         self.data
             .journaled_state
             .load_account_exist(address, self.data.db)
@@ -707,53 +1095,108 @@ impl<'a, GSPEC: Spec, DB: Database + 'a, const INSPECT: bool> Host
             .ok()
     }
 
-    fn balance(&mut self, address: H160) -> Option<(U256, bool)> {
+    fn balance(&mut self, address: H160) -> Option<StateLoad<U256>> {
         let db = &mut self.data.db;
         let journal = &mut self.data.journaled_state;
         let error = &mut self.data.error;
         journal
-            .load_account(address, db)
+            .balance(address, db)
             .map_err(|e| *error = Some(e))
             .ok()
-            .map(|(acc, is_cold)| (acc.info.balance, is_cold))
     }
 
-    fn code(&mut self, address: H160) -> Option<(Bytecode, bool)> {
+    fn self_balance(&mut self, address: H160) -> Option<U256> {
+        // executing contract is loaded when its frame starts, database is read only if host is
+        // called for other address.
+        if let Some(account) = self.data.journaled_state.state.get(&address) {
+            return Some(account.info.balance);
+        }
+        self.balance(address).map(|balance| balance.data)
+    }
+
+    fn code(&mut self, address: H160) -> Option<StateLoad<Bytecode>> {
         let journal = &mut self.data.journaled_state;
         let db = &mut self.data.db;
         let error = &mut self.data.error;
 
-        let (acc, is_cold) = journal
+        let load = journal
             .load_code(address, db)
             .map_err(|e| *error = Some(e))
             .ok()?;
-        Some((acc.info.code.clone().unwrap(), is_cold))
+        Some(load.map(|acc| acc.info.code.clone().unwrap()))
     }
 
-    /// Get code hash of address.
-    fn code_hash(&mut self, address: H160) -> Option<(H256, bool)> {
+    /// Small range of code that is not loaded yet is read with [Database::code_range], without
+    /// loading the whole code into the account.
+    fn code_range(&mut self, address: H160, offset: usize, len: usize) -> Option<StateLoad<Bytes>> {
         let journal = &mut self.data.journaled_state;
         let db = &mut self.data.db;
         let error = &mut self.data.error;
 
-        let (acc, is_cold) = journal
-            .load_code(address, db)
+        let StateLoad { data: acc, is_cold } = journal
+            .load_account(address, db)
             .map_err(|e| *error = Some(e))
             .ok()?;
-        //asume that all precompiles have some balance
-        let is_precompile = self.precompiles.contains(&address);
-        if is_precompile && self.data.env.cfg.perf_all_precompiles_have_balance {
-            return Some((KECCAK_EMPTY, is_cold));
+        let info = &acc.info;
+        if info.code.is_none() && info.code_hash != KECCAK_EMPTY && len <= CODE_RANGE_MAX_LEN {
+            let range = db
+                .code_range(info.code_hash, offset, len)
+                .map_err(|e| *error = Some(e))
+                .ok()?;
+            return Some(StateLoad::new(range, is_cold));
         }
+        // account is loaded, so it is cold only on the first access.
+        let load = journal
+            .load_code(address, db)
+            .map_err(|e| *error = Some(e))
+            .ok()?;
+        let code = load.data.info.code.as_ref().unwrap();
+        Some(StateLoad::new(code.range(offset, len), is_cold))
+    }
+
+    /// Size of code that is not loaded yet is read with [Database::code_size], without loading
+    /// the code into the account.
+    fn code_size(&mut self, address: H160) -> Option<StateLoad<usize>> {
+        let journal = &mut self.data.journaled_state;
+        let db = &mut self.data.db;
+        let error = &mut self.data.error;
+
+        let StateLoad { data: acc, is_cold } = journal
+            .load_account(address, db)
+            .map_err(|e| *error = Some(e))
+            .ok()?;
+        let size = match &acc.info.code {
+            Some(code) => code.len(),
+            None if acc.info.code_hash == KECCAK_EMPTY => 0,
+            None => db
+                .code_size(acc.info.code_hash)
+                .map_err(|e| *error = Some(e))
+                .ok()?,
+        };
+        Some(StateLoad::new(size, is_cold))
+    }
+
+    /// Get code hash of address.
+    fn code_hash(&mut self, address: H160) -> Option<StateLoad<H256>> {
+        let journal = &mut self.data.journaled_state;
+        let db = &mut self.data.db;
+        let error = &mut self.data.error;
+
+        // hash is part of account info, code is not loaded.
+        let StateLoad { data: acc, is_cold } = journal
+            .load_account(address, db)
+            .map_err(|e| *error = Some(e))
+            .ok()?;
+        // precompiles are accounts without code, their hash is zero until they get balance.
         if acc.is_empty() {
             // TODO check this for pre tangerine fork
-            return Some((H256::zero(), is_cold));
+            return Some(StateLoad::new(H256::zero(), is_cold));
         }
 
-        Some((acc.info.code_hash, is_cold))
+        Some(StateLoad::new(acc.info.code_hash, is_cold))
     }
 
-    fn sload(&mut self, address: H160, index: U256) -> Option<(U256, bool)> {
+    fn sload(&mut self, address: H160, index: U256) -> Option<StateLoad<U256>> {
         // account is always hot. reference on that statement https://eips.ethereum.org/EIPS/eip-2929 see `Note 2:`
         self.data
             .journaled_state
@@ -788,14 +1231,16 @@ impl<'a, GSPEC: Spec, DB: Database + 'a, const INSPECT: bool> Host
     }
 
     fn selfdestruct(&mut self, address: H160, target: H160) -> Option<SelfDestructResult> {
-        if INSPECT {
-            self.inspector.selfdestruct();
-        }
-        self.data
+        let result = self
+            .data
             .journaled_state
             .selfdestruct(address, target, self.data.db)
             .map_err(|e| self.data.error = Some(e))
-            .ok()
+            .ok()?;
+        if INSPECT {
+            self.inspector.selfdestruct(address, target, &result);
+        }
+        Some(result)
     }
 
     fn create<SPEC: Spec>(
@@ -844,18 +1289,32 @@ pub trait Host {
 
     fn env(&mut self) -> &mut Env;
 
-    /// load account. Returns (is_cold,is_new_account)
-    fn load_account(&mut self, address: H160) -> Option<(bool, bool)>;
+    /// load account. Returns if account exists.
+    fn load_account(&mut self, address: H160) -> Option<StateLoad<bool>>;
     /// Get environmental block hash.
     fn block_hash(&mut self, number: U256) -> Option<H256>;
     /// Get balance of address.
-    fn balance(&mut self, address: H160) -> Option<(U256, bool)>;
+    fn balance(&mut self, address: H160) -> Option<StateLoad<U256>>;
+    /// Get balance of currently executing contract. It is loaded and warm while its frame runs,
+    /// so hosts can return it without database access.
+    fn self_balance(&mut self, address: H160) -> Option<U256> {
+        Some(self.balance(address)?.data)
+    }
     /// Get code of address.
-    fn code(&mut self, address: H160) -> Option<(Bytecode, bool)>;
+    fn code(&mut self, address: H160) -> Option<StateLoad<Bytecode>>;
+    /// Get at most `len` bytes of code of address starting at `offset`, fewer if code ends
+    /// before.
+    fn code_range(&mut self, address: H160, offset: usize, len: usize) -> Option<StateLoad<Bytes>> {
+        Some(self.code(address)?.map(|code| code.range(offset, len)))
+    }
+    /// Get size of code of address.
+    fn code_size(&mut self, address: H160) -> Option<StateLoad<usize>> {
+        Some(self.code(address)?.map(|code| code.len()))
+    }
     /// Get code hash of address.
-    fn code_hash(&mut self, address: H160) -> Option<(H256, bool)>;
+    fn code_hash(&mut self, address: H160) -> Option<StateLoad<H256>>;
     /// Get storage value of address at index.
-    fn sload(&mut self, address: H160, index: U256) -> Option<(U256, bool)>;
+    fn sload(&mut self, address: H160, index: U256) -> Option<StateLoad<U256>>;
     /// Set storage value of address at index. Return if slot is cold/hot access.
     fn sstore(
         &mut self,
@@ -0,0 +1,105 @@
+use crate::{alloc::vec::Vec, Env};
+use primitive_types::{H160, U256};
+
+/// Fee charged from transaction caller on top of gas, like L1 data fee of rollups
+/// or custom operator fee.
+///
+/// Fee is calculated before execution, checked against caller balance and subtracted
+/// together with gas limit payment. After execution it is distributed in finalization.
+pub trait TxFeeHook: Send + Sync {
+    /// Fee charged from caller before execution.
+    fn fee(&self, env: &Env) -> U256;
+
+    /// Distribute charged `fee` after execution that used `gas_used` gas.
+    /// Returns accounts and amounts that are added to their balances.
+    /// Part of the fee that is not distributed is burned. Amounts over the rest of `fee` are
+    /// clamped to it, so distribution can't create ether.
+    fn distribute(&self, env: &Env, fee: U256, gas_used: u64) -> Vec<(H160, U256)>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::InMemoryDB, AccountInfo, Return, TransactTo};
+    use alloc::{sync::Arc, vec};
+
+    /// Charges fee for every byte of input and sends it to the vault.
+    struct DataFee {
+        vault: H160,
+    }
+
+    impl TxFeeHook for DataFee {
+        fn fee(&self, env: &Env) -> U256 {
+            U256::from(env.tx.data.len() * 100)
+        }
+
+        fn distribute(&self, _env: &Env, fee: U256, _gas_used: u64) -> Vec<(H160, U256)> {
+            vec![(self.vault, fee)]
+        }
+    }
+
+    /// Tries to distribute twice the charged fee.
+    struct Minting {
+        vault: H160,
+    }
+
+    impl TxFeeHook for Minting {
+        fn fee(&self, _env: &Env) -> U256 {
+            100.into()
+        }
+
+        fn distribute(&self, _env: &Env, fee: U256, _gas_used: u64) -> Vec<(H160, U256)> {
+            vec![(self.vault, fee), (self.vault, fee)]
+        }
+    }
+
+    #[test]
+    fn test_tx_fee_hook() {
+        let caller = H160::from_low_u64_be(0x1000);
+        let vault = H160::from_low_u64_be(0x2000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(caller, AccountInfo::from_balance(1000.into()));
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.fee_hook = Some(Arc::new(DataFee { vault }));
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(H160::from_low_u64_be(0x3000));
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.data = vec![1; 4].into();
+
+        let (result, state) = evm.transact();
+        assert_eq!(result.exit_reason, Return::Stop);
+        assert_eq!(state[&caller].info.balance, 600.into());
+        assert_eq!(state[&vault].info.balance, 400.into());
+        assert_eq!(result.fees.distributed, 400.into());
+        assert_eq!(result.fees.burned, 0.into());
+
+        // fee is part of balance check.
+        evm.env.tx.data = vec![1; 11].into();
+        let (result, _) = evm.transact();
+        assert_eq!(result.exit_reason, Return::LackOfFundForGasLimit);
+    }
+
+    #[test]
+    fn test_tx_fee_hook_clamps_distribution() {
+        let caller = H160::from_low_u64_be(0x1000);
+        let vault = H160::from_low_u64_be(0x2000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(caller, AccountInfo::from_balance(1000.into()));
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.fee_hook = Some(Arc::new(Minting { vault }));
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(H160::from_low_u64_be(0x3000));
+        evm.env.tx.gas_limit = 100_000;
+
+        let (result, state) = evm.transact();
+        assert_eq!(result.exit_reason, Return::Stop);
+        assert_eq!(state[&caller].info.balance, 900.into());
+        assert_eq!(state[&vault].info.balance, 100.into());
+        assert_eq!(result.fees.distributed, 100.into());
+        assert_eq!(result.fees.burned, 0.into());
+    }
+}
@@ -0,0 +1,159 @@
+//! Fee market arithmetic shared with block building code.
+//!
+//! Contains EIP-1559 basefee calculation and EIP-4844 blob gas price calculation.
+use primitive_types::U256;
+
+/// EIP-1559: Bound divisor of the basefee change.
+pub const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
+/// EIP-1559: Gas target is gas limit divided by elasticity multiplier.
+pub const ELASTICITY_MULTIPLIER: u64 = 2;
+
+/// EIP-4844: Gas consumed by single blob.
+pub const GAS_PER_BLOB: u64 = 1 << 17;
+/// EIP-4844: Target blob gas consumed by block.
+pub const TARGET_BLOB_GAS_PER_BLOCK: u64 = 3 * GAS_PER_BLOB;
+/// EIP-4844: Maximum blob gas consumed by block.
+pub const MAX_BLOB_GAS_PER_BLOCK: u64 = 6 * GAS_PER_BLOB;
+/// EIP-4844: Minimum price of blob gas.
+pub const MIN_BLOB_GASPRICE: u64 = 1;
+/// EIP-4844: Controls the maximum rate of change of blob gas price.
+pub const BLOB_GASPRICE_UPDATE_FRACTION: u64 = 3338477;
+
+/// Calculate basefee of next block from parent gas usage, gas limit and basefee as defined in EIP-1559.
+pub fn calc_next_block_basefee(gas_used: u64, gas_limit: u64, basefee: U256) -> U256 {
+    let gas_target = gas_limit / ELASTICITY_MULTIPLIER;
+    if gas_target == 0 || gas_used == gas_target {
+        return basefee;
+    }
+    let denominator = U256::from(gas_target) * U256::from(BASE_FEE_MAX_CHANGE_DENOMINATOR);
+    if gas_used > gas_target {
+        let delta = basefee * U256::from(gas_used - gas_target) / denominator;
+        basefee + core::cmp::max(delta, U256::one())
+    } else {
+        let delta = basefee * U256::from(gas_target - gas_used) / denominator;
+        basefee.saturating_sub(delta)
+    }
+}
+
+/// Calculate excess blob gas of next block from parent excess blob gas and parent blob gas used.
+/// Saturates at `u64::MAX` instead of overflowing.
+pub fn calc_excess_blob_gas(parent_excess_blob_gas: u64, parent_blob_gas_used: u64) -> u64 {
+    parent_excess_blob_gas
+        .saturating_add(parent_blob_gas_used)
+        .saturating_sub(TARGET_BLOB_GAS_PER_BLOCK)
+}
+
+/// Calculate blob gas price from excess blob gas as defined in EIP-4844. Price that overflows
+/// is `u128::MAX`, see [fake_exponential].
+pub fn calc_blob_gasprice(excess_blob_gas: u64) -> u128 {
+    fake_exponential(
+        MIN_BLOB_GASPRICE,
+        excess_blob_gas,
+        BLOB_GASPRICE_UPDATE_FRACTION,
+    )
+}
+
+/// Approximate `factor * e ** (numerator / denominator)` using Taylor expansion, as defined in EIP-4844.
+///
+/// If calculation overflows `u128` result is `u128::MAX`.
+///
+/// Panics if denominator is zero.
+pub fn fake_exponential(factor: u64, numerator: u64, denominator: u64) -> u128 {
+    assert_ne!(denominator, 0, "attempt to divide by zero");
+    let factor = factor as u128;
+    let numerator = numerator as u128;
+    let denominator = denominator as u128;
+
+    let mut i: u128 = 1;
+    let mut output: u128 = 0;
+    // product of two u64 fits in u128.
+    let mut numerator_accum = factor * denominator;
+    while numerator_accum > 0 {
+        output = match output.checked_add(numerator_accum) {
+            Some(output) => output,
+            None => return u128::MAX,
+        };
+        // Denominator is asserted as not zero at the start of the function.
+        let (Some(product), Some(divisor)) = (
+            numerator_accum.checked_mul(numerator),
+            denominator.checked_mul(i),
+        ) else {
+            return u128::MAX;
+        };
+        numerator_accum = product / divisor;
+        i += 1;
+    }
+    output / denominator
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_exponential() {
+        for (factor, numerator, denominator, expected) in [
+            (1u64, 0u64, 1u64, 1u128),
+            (38493, 0, 1000, 38493),
+            (0, 1234, 2345, 0),
+            (1, 2, 1, 6),
+            (1, 4, 2, 6),
+            (1, 3, 1, 16),
+            (1, 6, 2, 18),
+            (1, 4, 1, 49),
+            (1, 8, 2, 50),
+            (10, 8, 2, 542),
+            (11, 8, 2, 596),
+            (1, 5, 1, 136),
+            (1, 5, 2, 11),
+            (2, 5, 2, 23),
+        ] {
+            assert_eq!(fake_exponential(factor, numerator, denominator), expected);
+        }
+        // accumulator overflows u128.
+        assert_eq!(fake_exponential(u64::MAX, u64::MAX, 1), u128::MAX);
+        assert_eq!(
+            fake_exponential(1, 87, 1),
+            60752274383134776695503461174634082613
+        );
+        assert_eq!(fake_exponential(1, 88, 1), u128::MAX);
+    }
+
+    #[test]
+    fn test_blob_gasprice() {
+        assert_eq!(calc_blob_gasprice(0), 1);
+        assert_eq!(calc_blob_gasprice(BLOB_GASPRICE_UPDATE_FRACTION), 2);
+        assert_eq!(
+            calc_excess_blob_gas(0, MAX_BLOB_GAS_PER_BLOCK),
+            TARGET_BLOB_GAS_PER_BLOCK
+        );
+        assert_eq!(
+            calc_excess_blob_gas(u64::MAX, MAX_BLOB_GAS_PER_BLOCK),
+            u64::MAX - TARGET_BLOB_GAS_PER_BLOCK
+        );
+        assert_eq!(calc_blob_gasprice(u64::MAX), u128::MAX);
+        assert_eq!(calc_excess_blob_gas(GAS_PER_BLOB, GAS_PER_BLOB), 0);
+    }
+
+    #[test]
+    fn test_next_block_basefee() {
+        let basefee = U256::from(1_000_000_000u64);
+        assert_eq!(
+            calc_next_block_basefee(15_000_000, 30_000_000, basefee),
+            basefee
+        );
+        assert_eq!(
+            calc_next_block_basefee(30_000_000, 30_000_000, basefee),
+            U256::from(1_125_000_000u64)
+        );
+        assert_eq!(
+            calc_next_block_basefee(0, 30_000_000, basefee),
+            U256::from(875_000_000u64)
+        );
+        // increase is at least one wei
+        assert_eq!(
+            calc_next_block_basefee(15_000_001, 30_000_000, U256::from(7)),
+            U256::from(8)
+        );
+    }
+}
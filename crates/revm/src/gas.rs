@@ -1,8 +1,18 @@
+//! Gas calculation.
+//!
+//! Costs are calculated with the same functions the interpreter uses, so tools that estimate gas
+//! off-chain get exactly the same numbers. Functions that depend on hardfork take [crate::Spec].
+#[cfg(feature = "gas_accounting")]
+mod accounting;
 mod calc;
 mod constants;
+mod table;
 
+#[cfg(feature = "gas_accounting")]
+pub use accounting::*;
 pub use calc::*;
 pub use constants::*;
+pub use table::GasTable;
 #[derive(Clone, Copy, Debug)]
 pub struct Gas {
     limit: u64,
@@ -10,6 +20,8 @@ pub struct Gas {
     memory: u64,
     refunded: i64,
     all_used_gas: u64,
+    #[cfg(feature = "gas_accounting")]
+    accounting: GasAccounting,
 }
 impl Gas {
     pub fn new(limit: u64) -> Self {
@@ -19,6 +31,8 @@ impl Gas {
             memory: 0,
             refunded: 0,
             all_used_gas: 0,
+            #[cfg(feature = "gas_accounting")]
+            accounting: GasAccounting::default(),
         }
     }
 
@@ -30,10 +44,27 @@ impl Gas {
         self.memory
     }
 
+    /// Refund counter of this frame. It is signed as SSTORE can remove refund that was
+    /// added previously in the transaction.
+    ///
+    /// Refunds are not journaled, every frame has its own counter that is added to the
+    /// caller only if frame finished successfully, so refunds of reverted frames are dropped.
     pub fn refunded(&self) -> i64 {
         self.refunded
     }
 
+    /// Gas charged by this frame and frames it called, per category. Categories add up to
+    /// [Gas::spend].
+    #[cfg(feature = "gas_accounting")]
+    pub fn accounting(&self) -> &GasAccounting {
+        &self.accounting
+    }
+
+    #[cfg(feature = "gas_accounting")]
+    pub fn accounting_mut(&mut self) -> &mut GasAccounting {
+        &mut self.accounting
+    }
+
     pub fn spend(&self) -> u64 {
         self.all_used_gas
     }
@@ -71,14 +102,129 @@ impl Gas {
             if overflow || self.limit < all_used_gas {
                 return false;
             }
+            #[cfg(feature = "gas_accounting")]
+            self.accounting
+                .add(GasCategory::Memory, gas_memory - self.memory);
             self.memory = gas_memory;
             self.all_used_gas = all_used_gas;
         }
         true
     }
 
+    /// Set refund counter. Used at the end of transaction to record refund that was applied.
+    pub fn set_refund(&mut self, refund: i64) {
+        self.refunded = refund;
+    }
+
     /// used in gas_refund! macro
     pub fn gas_refund(&mut self, refund: i64) {
         self.refunded += refund;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{db::InMemoryDB, AccountInfo, Bytecode, Return, SpecId, TransactTo};
+    use alloc::vec::Vec;
+    use bytes::Bytes;
+    use primitive_types::{H160, U256};
+
+    /// Call `to` forwarding all gas and pop the result.
+    fn call(to: u64) -> Vec<u8> {
+        let to = (to as u16).to_be_bytes();
+        vec![
+            0x60, 0, 0x60, 0, 0x60, 0, 0x60, 0, 0x60, 0, 0x61, to[0], to[1], 0x5a, 0xf1, 0x50,
+        ]
+    }
+
+    fn contract(db: &mut InMemoryDB, address: u64, code: Vec<u8>) {
+        db.insert_account_info(
+            H160::from_low_u64_be(address),
+            AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(Bytes::from(code))),
+        );
+    }
+
+    fn refunded(db: InMemoryDB, entry: u64) -> u64 {
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.spec_id = SpecId::ISTANBUL;
+        evm.env.tx.transact_to = TransactTo::Call(H160::from_low_u64_be(entry));
+        evm.env.tx.gas_limit = 1_000_000;
+        let (result, _) = evm.transact();
+        assert_eq!(result.exit_reason, Return::Stop);
+        result.gas_refunded
+    }
+
+    #[test]
+    fn test_sstore_refund_in_reverted_call() {
+        let mut db = InMemoryDB::default();
+        // clear slot 0 and revert
+        contract(
+            &mut db,
+            0x1000,
+            vec![0x60, 0, 0x60, 0, 0x55, 0x60, 0, 0x60, 0, 0xfd],
+        );
+        // clear slot 0 and stop
+        contract(&mut db, 0x1001, vec![0x60, 0, 0x60, 0, 0x55, 0x00]);
+        for address in [0x1000, 0x1001] {
+            db.insert_account_storage(H160::from_low_u64_be(address), U256::zero(), U256::one())
+                .unwrap();
+        }
+        contract(&mut db, 0x2000, [call(0x1000), call(0x1001)].concat());
+
+        // only refund of successful call is counted.
+        assert_eq!(refunded(db, 0x2000), super::REFUND_SSTORE_CLEARS as u64);
+    }
+
+    #[test]
+    fn test_selfdestruct_refund_in_reverted_call() {
+        let mut db = InMemoryDB::default();
+        // selfdestruct to zero address
+        contract(&mut db, 0x1000, vec![0x60, 0, 0xff]);
+        // call selfdestructing contract and revert
+        contract(
+            &mut db,
+            0x1001,
+            [call(0x1000), vec![0x60, 0, 0x60, 0, 0xfd]].concat(),
+        );
+        // set storage to raise gas spend so that refund is not capped.
+        contract(
+            &mut db,
+            0x2000,
+            [vec![0x60, 1, 0x60, 0, 0x55], call(0x1001), call(0x1000)].concat(),
+        );
+
+        // reverted selfdestruct is not counted and does not mark account as destroyed.
+        assert_eq!(refunded(db, 0x2000), super::SELFDESTRUCT as u64);
+    }
+
+    #[test]
+    fn test_call_gas_forwarding() {
+        use crate::{SpecFlags, SpecId};
+        let london = &super::GasTable::new(SpecFlags::new(SpecId::LONDON));
+        let frontier = &super::GasTable::new(SpecFlags::new(SpecId::FRONTIER));
+        // EIP-150: 100_000 - 100_000 / 64
+        assert_eq!(super::all_but_one_64th(100_000), 98_438);
+        assert_eq!(
+            super::call_gas(london, 100_000, u64::MAX, false),
+            (98_438, 98_438)
+        );
+        // requested gas is used if lower than limit.
+        assert_eq!(
+            super::call_gas(london, 100_000, 50_000, true),
+            (50_000, 52_300)
+        );
+        // stipend is added on top of all forwarded gas.
+        assert_eq!(
+            super::call_gas(london, 100_000, u64::MAX, true),
+            (98_438, 100_738)
+        );
+        // before EIP-150 requested gas is forwarded, caller runs out of gas if it can't pay it.
+        assert_eq!(
+            super::call_gas(frontier, 100_000, 200_000, false),
+            (200_000, 200_000)
+        );
+        assert_eq!(super::create_gas(london, 64), 63);
+        assert_eq!(super::create_gas(frontier, 64), 64);
+    }
+}
@@ -0,0 +1,337 @@
+//! Accounting of gas by its source, enabled with `gas_accounting` feature.
+//!
+//! Every charge of the interpreter is tagged with a [GasCategory] and summed per frame in
+//! [GasAccounting] of its [Gas]. Frames add their accounting to the caller when they finish, so
+//! accounting of [crate::ExecutionResult::gas] covers the whole transaction and its categories
+//! add up to [Gas::spend]. Charge that is not tagged breaks that sum, which makes it easy to
+//! find when costs of new hardfork are added.
+use super::{constants::*, Gas, GasTable};
+use crate::models::SelfDestructResult;
+use primitive_types::U256;
+
+/// Source of gas charge.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum GasCategory {
+    /// Gas paid before execution, see [crate::initial_tx_gas].
+    Intrinsic,
+    /// Static cost of opcodes, charged per block of instructions.
+    Opcode,
+    /// Memory expansion.
+    Memory,
+    /// EIP-2929 cost of cold account or storage slot access on top of warm access.
+    ColdAccess,
+    /// Warm or pre Berlin cost of BALANCE.
+    AccountAccess,
+    /// Warm or pre Berlin cost of SLOAD.
+    StorageRead,
+    /// Warm or pre Berlin cost of SSTORE.
+    StorageWrite,
+    /// CALLDATACOPY, CODECOPY, RETURNDATACOPY and EXTCODECOPY, base cost and copied words.
+    Copy,
+    /// SHA3 and hashing of CREATE2 init code.
+    Hashing,
+    /// EXP, base cost and exponent bytes.
+    Exp,
+    /// LOG, base cost, topics and data.
+    Log,
+    /// Warm or pre Berlin cost of CALL-like opcodes.
+    Call,
+    /// Value transfer of CALL and CALLCODE, less call stipend that is spent by the callee and
+    /// accounted in its categories.
+    ValueTransfer,
+    /// New account created by CALL or SELFDESTRUCT.
+    NewAccount,
+    /// CREATE and CREATE2 base cost.
+    Create,
+    /// SELFDESTRUCT base cost.
+    SelfDestruct,
+    /// Cost of precompile.
+    Precompile,
+    /// Storing code of created contract.
+    CodeDeposit,
+    /// Remaining gas of frames that halted with error, it is not returned to the caller.
+    Burned,
+}
+
+impl GasCategory {
+    pub const COUNT: usize = 19;
+
+    pub const ALL: [GasCategory; Self::COUNT] = [
+        Self::Intrinsic,
+        Self::Opcode,
+        Self::Memory,
+        Self::ColdAccess,
+        Self::AccountAccess,
+        Self::StorageRead,
+        Self::StorageWrite,
+        Self::Copy,
+        Self::Hashing,
+        Self::Exp,
+        Self::Log,
+        Self::Call,
+        Self::ValueTransfer,
+        Self::NewAccount,
+        Self::Create,
+        Self::SelfDestruct,
+        Self::Precompile,
+        Self::CodeDeposit,
+        Self::Burned,
+    ];
+
+    /// EIP or rule that defines the charge, for gas breakdowns shown to users.
+    pub const fn source(&self) -> &'static str {
+        match self {
+            Self::Intrinsic => "intrinsic gas (EIP-2, EIP-2028, EIP-2930)",
+            Self::Opcode => "static opcode cost",
+            Self::Memory => "memory expansion",
+            Self::ColdAccess => "EIP-2929 cold access",
+            Self::AccountAccess => "account access (EIP-150, EIP-1884)",
+            Self::StorageRead => "SLOAD (EIP-150, EIP-1884)",
+            Self::StorageWrite => "SSTORE (EIP-2200)",
+            Self::Copy => "copy",
+            Self::Hashing => "keccak256",
+            Self::Exp => "EXP (EIP-160)",
+            Self::Log => "LOG",
+            Self::Call => "call (EIP-150)",
+            Self::ValueTransfer => "value transfer",
+            Self::NewAccount => "new account (EIP-161)",
+            Self::Create => "create",
+            Self::SelfDestruct => "SELFDESTRUCT (EIP-150)",
+            Self::Precompile => "precompile",
+            Self::CodeDeposit => "code deposit",
+            Self::Burned => "exceptional halt",
+        }
+    }
+}
+
+/// Gas charged per [GasCategory].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GasAccounting {
+    charged: [u64; GasCategory::COUNT],
+}
+
+impl GasAccounting {
+    pub fn get(&self, category: GasCategory) -> u64 {
+        self.charged[category as usize]
+    }
+
+    /// Sum of all categories.
+    pub fn total(&self) -> u64 {
+        self.charged.iter().sum()
+    }
+
+    /// Categories with non zero gas.
+    pub fn iter(&self) -> impl Iterator<Item = (GasCategory, u64)> + '_ {
+        GasCategory::ALL
+            .iter()
+            .map(|category| (*category, self.get(*category)))
+            .filter(|(_, gas)| *gas != 0)
+    }
+
+    pub fn add(&mut self, category: GasCategory, gas: u64) {
+        self.charged[category as usize] += gas;
+    }
+
+    /// Add accounting of `frame` that finished. Remaining gas of frame that is not `returned`
+    /// to the caller is [GasCategory::Burned].
+    pub fn add_frame(&mut self, frame: &Gas, returned: bool) {
+        for (charged, frame) in self.charged.iter_mut().zip(frame.accounting().charged) {
+            *charged += frame;
+        }
+        if !returned {
+            self.add(GasCategory::Burned, frame.remaining());
+        }
+    }
+}
+
+/// Split of [super::call_cost]. Value transfer is without call stipend.
+pub fn call_cost_parts(
+    table: &GasTable,
+    value: U256,
+    is_new: bool,
+    is_cold: bool,
+    is_call_or_callcode: bool,
+    is_call_or_staticcall: bool,
+) -> [(GasCategory, u64); 4] {
+    let transfers_value = !value.is_zero();
+    let cold = table.cold_account_additional_cost(is_cold);
+    let value_transfer = if is_call_or_callcode && transfers_value {
+        CALLVALUE - CALL_STIPEND
+    } else {
+        0
+    };
+    let new_account = super::calc::new_cost(table, is_call_or_staticcall, is_new, transfers_value);
+    let access = table.account_access_cost(is_cold, table.call);
+    [
+        (GasCategory::ColdAccess, cold),
+        (GasCategory::Call, access - cold),
+        (GasCategory::ValueTransfer, value_transfer),
+        (GasCategory::NewAccount, new_account),
+    ]
+}
+
+/// Split of [super::selfdestruct_cost].
+pub fn selfdestruct_cost_parts(
+    table: &GasTable,
+    res: SelfDestructResult,
+) -> [(GasCategory, u64); 3] {
+    let cold = if table.cold_warm_access && res.is_cold {
+        COLD_ACCOUNT_ACCESS_COST
+    } else {
+        0
+    };
+    let new_account = super::selfdestruct_cost(table, res) - cold - table.selfdestruct;
+    [
+        (GasCategory::ColdAccess, cold),
+        (GasCategory::SelfDestruct, table.selfdestruct),
+        (GasCategory::NewAccount, new_account),
+    ]
+}
+
+/// EIP-2929 part of [super::sload_cost].
+pub fn cold_sload_cost(table: &GasTable, is_cold: bool) -> u64 {
+    table.sload_cost(is_cold) - table.sload_cost(false)
+}
+
+/// EIP-2929 part of [super::sstore_cost].
+pub fn cold_sstore_cost(table: &GasTable, is_cold: bool) -> u64 {
+    if table.cold_warm_access && is_cold {
+        COLD_SLOAD_COST
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::InMemoryDB, opcode::*, AccountInfo, Bytecode, ExecutionResult, Return, SpecId,
+        TransactTo,
+    };
+    use alloc::vec::Vec;
+    use bytes::Bytes;
+    use primitive_types::H160;
+
+    /// CALL of `to` with `value` and 0x1000 gas, its success is dropped.
+    fn call(to: u16, value: u8) -> Vec<u8> {
+        let [to0, to1] = to.to_be_bytes();
+        vec![
+            PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, value, PUSH2, to0, to1, PUSH2, 0x10, 0,
+            CALL, POP,
+        ]
+    }
+
+    fn transact(spec_id: SpecId) -> ExecutionResult {
+        let mut db = InMemoryDB::default();
+        let code = [
+            // cold and warm SLOAD, SSTORE to warm slot.
+            vec![PUSH1, 0, SLOAD, POP, PUSH1, 0, SLOAD, POP],
+            vec![PUSH1, 1, PUSH1, 0, SSTORE],
+            vec![PUSH1, 32, PUSH1, 0, LOG0],
+            // value to new account, call of halting contract and identity precompile.
+            call(0x3000, 1),
+            call(0x4000, 0),
+            call(0x0004, 0),
+            vec![STOP],
+        ]
+        .concat();
+        db.insert_account_info(
+            H160::from_low_u64_be(0x1000),
+            AccountInfo::new(U256::one(), 0, Bytecode::new_raw(Bytes::from(code))),
+        );
+        db.insert_account_info(
+            H160::from_low_u64_be(0x4000),
+            AccountInfo::new(
+                U256::zero(),
+                0,
+                Bytecode::new_raw(Bytes::from(vec![INVALID])),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.spec_id = spec_id;
+        evm.env.tx.transact_to = TransactTo::Call(H160::from_low_u64_be(0x1000));
+        evm.env.tx.gas_limit = 1_000_000;
+        let (result, _) = evm.transact();
+        assert_eq!(result.exit_reason, Return::Stop);
+        result
+    }
+
+    #[test]
+    fn test_gas_accounting() {
+        let result = transact(SpecId::LONDON);
+        let accounting = result.gas.accounting();
+        assert_eq!(accounting.total(), result.gas.spend());
+        assert_eq!(accounting.get(GasCategory::Intrinsic), 21_000);
+        // EIP-2929: one cold slot and two cold accounts, precompiles are always warm.
+        assert_eq!(
+            accounting.get(GasCategory::ColdAccess),
+            (COLD_SLOAD_COST - WARM_STORAGE_READ_COST)
+                + 2 * (COLD_ACCOUNT_ACCESS_COST - WARM_STORAGE_READ_COST)
+        );
+        assert_eq!(
+            accounting.get(GasCategory::StorageRead),
+            2 * WARM_STORAGE_READ_COST
+        );
+        assert_eq!(accounting.get(GasCategory::StorageWrite), SSTORE_SET);
+        assert_eq!(accounting.get(GasCategory::Log), LOG + 32 * LOGDATA);
+        assert_eq!(accounting.get(GasCategory::Memory), 3);
+        assert_eq!(
+            accounting.get(GasCategory::Call),
+            3 * WARM_STORAGE_READ_COST
+        );
+        assert_eq!(
+            accounting.get(GasCategory::ValueTransfer),
+            super::CALLVALUE - CALL_STIPEND
+        );
+        assert_eq!(accounting.get(GasCategory::NewAccount), NEWACCOUNT);
+        assert_eq!(accounting.get(GasCategory::Precompile), 15);
+        // all gas given to halting contract is lost.
+        assert!(accounting.get(GasCategory::Burned) > 0);
+        assert_eq!(accounting.get(GasCategory::CodeDeposit), 0);
+        assert!(accounting
+            .iter()
+            .all(|(category, gas)| gas != 0 && !category.source().is_empty()));
+
+        // every spec charges only what it accounts for.
+        for spec_id in [
+            SpecId::FRONTIER,
+            SpecId::HOMESTEAD,
+            SpecId::TANGERINE,
+            SpecId::SPURIOUS_DRAGON,
+            SpecId::ISTANBUL,
+            SpecId::BERLIN,
+            SpecId::LATEST,
+        ] {
+            let result = transact(spec_id);
+            let accounting = result.gas.accounting();
+            assert_eq!(accounting.total(), result.gas.spend(), "{:?}", spec_id);
+            assert_eq!(
+                accounting.get(GasCategory::ColdAccess) == 0,
+                !SpecId::enabled(spec_id, SpecId::BERLIN),
+                "{:?}",
+                spec_id
+            );
+        }
+    }
+
+    #[test]
+    fn test_gas_accounting_of_create() {
+        let mut evm = crate::new();
+        evm.database(InMemoryDB::default());
+        // init code returns one byte of memory.
+        evm.env.tx.data = Bytes::from(vec![PUSH1, 1, PUSH1, 0, RETURN]);
+        evm.env.tx.transact_to = TransactTo::create();
+        evm.env.tx.gas_limit = 1_000_000;
+        let (result, _) = evm.transact();
+        assert_eq!(result.exit_reason, Return::Continue);
+        let accounting = result.gas.accounting();
+        assert_eq!(accounting.total(), result.gas.spend());
+        assert_eq!(accounting.get(GasCategory::CodeDeposit), CODEDEPOSIT);
+        assert_eq!(
+            accounting.get(GasCategory::Intrinsic),
+            result.gas_breakdown.intrinsic
+        );
+    }
+}
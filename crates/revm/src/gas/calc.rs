@@ -1,16 +1,18 @@
-use super::constants::*;
-use crate::{models::SelfDestructResult, Spec, SpecId::*};
-use primitive_types::U256;
+use super::{constants::*, GasTable};
+use crate::models::SelfDestructResult;
+use alloc::vec::Vec;
+use core::cmp::min;
+use primitive_types::{H160, U256};
 
+/// Refund of SSTORE that changes slot with `original` value at the start of transaction from
+/// `current` to `new` value. Negative if refund given by previous SSTORE is taken back.
+///
+/// EIP-2200 net gas metering since Istanbul, EIP-2929 prices since Berlin and
+/// EIP-3529 reduced clear refund since London.
 #[allow(clippy::collapsible_else_if)]
-pub fn sstore_refund<SPEC: Spec>(original: U256, current: U256, new: U256) -> i64 {
-    if SPEC::enabled(ISTANBUL) {
-        // EIP-3529: Reduction in refunds
-        let sstore_clears_schedule = if SPEC::enabled(LONDON) {
-            (SSTORE_RESET - COLD_SLOAD_COST + ACCESS_LIST_STORAGE_KEY) as i64
-        } else {
-            REFUND_SSTORE_CLEARS
-        };
+pub fn sstore_refund(table: &GasTable, original: U256, current: U256, new: U256) -> i64 {
+    if table.sstore_net_metering {
+        let sstore_clears_schedule = table.sstore_clears_refund;
         if current == new {
             0
         } else {
@@ -28,15 +30,10 @@ pub fn sstore_refund<SPEC: Spec>(original: U256, current: U256, new: U256) -> i6
                 }
 
                 if original == new {
-                    let (gas_sstore_reset, gas_sload) = if SPEC::enabled(BERLIN) {
-                        (SSTORE_RESET - COLD_SLOAD_COST, WARM_STORAGE_READ_COST)
-                    } else {
-                        (SSTORE_RESET, sload_cost::<SPEC>(false))
-                    };
                     if original.is_zero() {
-                        refund += (SSTORE_SET - gas_sload) as i64;
+                        refund += (SSTORE_SET - table.sload) as i64;
                     } else {
-                        refund += (gas_sstore_reset - gas_sload) as i64;
+                        refund += (table.sstore_reset - table.sload) as i64;
                     }
                 }
 
@@ -52,6 +49,8 @@ pub fn sstore_refund<SPEC: Spec>(original: U256, current: U256, new: U256) -> i6
     }
 }
 
+/// Cost of CREATE2 with init code of `len` bytes: CREATE cost and hashing of init code.
+/// None on overflow.
 pub fn create2_cost(len: usize) -> Option<u64> {
     let base = CREATE;
     // ceil(len / 32.0)
@@ -82,15 +81,13 @@ fn log2floor(value: U256) -> u64 {
     l
 }
 
-pub fn exp_cost<SPEC: Spec>(power: U256) -> Option<u64> {
+/// Cost of EXP with exponent `power`. Cost per byte of exponent is 50 since Spurious Dragon
+/// (EIP-160). None on overflow.
+pub fn exp_cost(table: &GasTable, power: U256) -> Option<u64> {
     if power.is_zero() {
         Some(EXP)
     } else {
-        let gas_byte = U256::from(if SPEC::enabled(SPURIOUS_DRAGON) {
-            50
-        } else {
-            10
-        }); // EIP-160: EXP cost increase
+        let gas_byte = U256::from(table.exp_byte);
         let gas = U256::from(EXP)
             .checked_add(gas_byte.checked_mul(U256::from(log2floor(power) / 8 + 1))?)?;
 
@@ -102,86 +99,78 @@ pub fn exp_cost<SPEC: Spec>(power: U256) -> Option<u64> {
     }
 }
 
+/// Cost of CALLDATACOPY, CODECOPY and RETURNDATACOPY of `len` bytes, without memory expansion.
+/// None on overflow.
 pub fn verylowcopy_cost(len: u64) -> Option<u64> {
     let wordd = len / 32;
     let wordr = len % 32;
     VERYLOW.checked_add(COPY.checked_mul(if wordr == 0 { wordd } else { wordd + 1 })?)
 }
 
-pub fn extcodecopy_cost<SPEC: Spec>(len: u64, is_cold: bool) -> Option<u64> {
+/// Cost of copying `len` bytes by EXTCODECOPY in addition to its base cost, including cold
+/// account access (EIP-2929). None on overflow.
+pub fn extcodecopy_cost(table: &GasTable, len: u64, is_cold: bool) -> Option<u64> {
     let wordd = len / 32;
     let wordr = len % 32;
 
-    let base_gas: u64 = if SPEC::enabled(BERLIN) && is_cold {
-        // WARM_STORAGE_READ_COST is already calculated
-        COLD_ACCOUNT_ACCESS_COST - WARM_STORAGE_READ_COST
-    } else {
-        0
-    };
+    let base_gas = table.cold_account_additional_cost(is_cold);
     base_gas.checked_add(COPY.checked_mul(if wordr == 0 { wordd } else { wordd + 1 })?)
 }
 
-pub fn account_access_gas<SPEC: Spec>(is_cold: bool) -> u64 {
-    if SPEC::enabled(BERLIN) {
-        if is_cold {
-            COLD_ACCOUNT_ACCESS_COST
-        } else {
-            WARM_STORAGE_READ_COST
-        }
-    } else if SPEC::enabled(ISTANBUL) {
-        700
-    } else {
-        20
-    }
+/// EIP-2929: additional cost of cold account access for opcodes whose warm
+/// access cost (WARM_STORAGE_READ_COST) is already calculated in gas block.
+pub fn cold_account_additional_cost(table: &GasTable, is_cold: bool) -> u64 {
+    table.cold_account_additional_cost(is_cold)
+}
+
+/// Cost of account access by BALANCE, EXTCODESIZE and EXTCODEHASH, per EIP-1884 since Istanbul and
+/// EIP-2929 since Berlin.
+pub fn account_access_gas(table: &GasTable, is_cold: bool) -> u64 {
+    table.account_access_cost(is_cold, table.account_access)
 }
 
+/// Cost of BALANCE, per EIP-150 since Tangerine, EIP-1884 since Istanbul and EIP-2929 since Berlin.
+pub fn balance_cost(table: &GasTable, is_cold: bool) -> u64 {
+    table.account_access_cost(is_cold, table.balance)
+}
+
+/// Cost of LOG with `n` topics and `len` bytes of data, without memory expansion.
+/// None on overflow.
 pub fn log_cost(n: u8, len: u64) -> Option<u64> {
     LOG.checked_add(LOGDATA.checked_mul(len)?)?
         .checked_add(LOGTOPIC * n as u64)
 }
 
+/// Cost of SHA3 over `len` bytes, without memory expansion. None on overflow.
 pub fn sha3_cost(len: u64) -> Option<u64> {
     let wordd = len / 32;
     let wordr = len % 32;
     SHA3.checked_add(SHA3WORD.checked_mul(if wordr == 0 { wordd } else { wordd + 1 })?)
 }
 
-pub fn sload_cost<SPEC: Spec>(is_cold: bool) -> u64 {
-    if SPEC::enabled(BERLIN) {
-        if is_cold {
-            COLD_SLOAD_COST
-        } else {
-            WARM_STORAGE_READ_COST
-        }
-    } else if SPEC::enabled(ISTANBUL) {
-        // EIP-1884: Repricing for trie-size-dependent opcodes
-        800
-    } else if SPEC::enabled(TANGERINE) {
-        // EIP-150: Gas cost changes for IO-heavy operations
-        200
-    } else {
-        50
-    }
+/// Cost of SLOAD, per EIP-150 since Tangerine, EIP-1884 since Istanbul and EIP-2929 since Berlin.
+pub fn sload_cost(table: &GasTable, is_cold: bool) -> u64 {
+    table.sload_cost(is_cold)
 }
 
+/// Cost of SSTORE that changes slot with `original` value at the start of transaction from
+/// `current` to `new` value. `gas` is remaining gas, since Istanbul SSTORE fails (None is
+/// returned) if it is not more than call stipend (EIP-2200).
 #[allow(clippy::collapsible_else_if)]
-pub fn sstore_cost<SPEC: Spec>(
+pub fn sstore_cost(
+    table: &GasTable,
     original: U256,
     current: U256,
     new: U256,
     gas: u64,
     is_cold: bool,
 ) -> Option<u64> {
-    // TODO untangle this mess and make it more elegant
-    let (gas_sload, gas_sstore_reset) = if SPEC::enabled(BERLIN) {
-        (WARM_STORAGE_READ_COST, SSTORE_RESET - COLD_SLOAD_COST)
-    } else {
-        (sload_cost::<SPEC>(is_cold), SSTORE_RESET)
-    };
+    // warm SLOAD since Berlin.
+    let gas_sload = table.sload;
 
     // https://eips.ethereum.org/EIPS/eip-2200
     // It’s a combined version of EIP-1283 and EIP-1706
-    let gas_cost = if SPEC::enabled(ISTANBUL) {
+    let gas_cost = if table.sstore_net_metering {
         // EIP-1706
         if gas <= CALL_STIPEND {
             return None;
@@ -195,7 +184,7 @@ pub fn sstore_cost<SPEC: Spec>(
                 if original.is_zero() {
                     SSTORE_SET
                 } else {
-                    gas_sstore_reset
+                    table.sstore_reset
                 }
             } else {
                 gas_sload
@@ -205,42 +194,41 @@ pub fn sstore_cost<SPEC: Spec>(
         if current.is_zero() && !new.is_zero() {
             SSTORE_SET
         } else {
-            gas_sstore_reset
+            table.sstore_reset
         }
     };
     // In EIP-2929 we charge extra if the slot has not been used yet in this transaction
-    if SPEC::enabled(BERLIN) && is_cold {
+    if table.cold_warm_access && is_cold {
         Some(gas_cost + COLD_SLOAD_COST)
     } else {
         Some(gas_cost)
     }
 }
 
-pub fn selfdestruct_cost<SPEC: Spec>(res: SelfDestructResult) -> u64 {
+/// Cost of SELFDESTRUCT, including new account cost of the target (EIP-150, EIP-161) and
+/// cold access of the target (EIP-2929).
+pub fn selfdestruct_cost(table: &GasTable, res: SelfDestructResult) -> u64 {
     // EIP-161: State trie clearing (invariant-preserving alternative)
-    let should_charge_topup = if SPEC::enabled(SPURIOUS_DRAGON) {
+    let should_charge_topup = if table.new_account_needs_value {
         res.had_value && !res.target_exists
     } else {
         !res.target_exists
     };
 
-    let selfdestruct_gas_topup = if SPEC::enabled(TANGERINE) && should_charge_topup {
-        //EIP-150: Gas cost changes for IO-heavy operations
-        25000
-    } else {
-        0
-    };
-
-    let selfdestruct_gas = if SPEC::enabled(TANGERINE) { 5000 } else { 0 }; //EIP-150: Gas cost changes for IO-heavy operations
-
-    let mut gas = selfdestruct_gas + selfdestruct_gas_topup;
-    if SPEC::enabled(BERLIN) && res.is_cold {
+    let mut gas = table.selfdestruct;
+    if should_charge_topup {
+        gas += table.selfdestruct_new_account;
+    }
+    if table.cold_warm_access && res.is_cold {
         gas += COLD_ACCOUNT_ACCESS_COST
     }
     gas
 }
 
-pub fn call_cost<SPEC: Spec>(
+/// Cost of CALL-like opcode, without memory expansion and gas forwarded to the callee
+/// (see [call_gas]). It includes account access, value transfer and new account cost.
+pub fn call_cost(
+    table: &GasTable,
     value: U256,
     is_new: bool,
     is_cold: bool,
@@ -249,36 +237,86 @@ pub fn call_cost<SPEC: Spec>(
 ) -> u64 {
     let transfers_value = value != U256::default();
 
-    let call_gas = if SPEC::enabled(BERLIN) {
-        if is_cold {
-            COLD_ACCOUNT_ACCESS_COST
-        } else {
-            WARM_STORAGE_READ_COST
-        }
-    } else if SPEC::enabled(TANGERINE) {
-        // EIP-150: Gas cost changes for IO-heavy operations
-        700
+    table.account_access_cost(is_cold, table.call)
+        + xfer_cost(is_call_or_callcode, transfers_value)
+        + new_cost(table, is_call_or_staticcall, is_new, transfers_value)
+}
+
+/// EIP-150: Maximum gas that can be forwarded to new frame is all but one 64th of remaining gas.
+pub fn all_but_one_64th(gas: u64) -> u64 {
+    gas - gas / 64
+}
+
+/// Gas forwarded by CALL-like opcode. `remaining` is gas of the caller after call cost is charged
+/// and `requested` is gas argument of the opcode.
+///
+/// Returns gas that is deducted from the caller and gas limit of the new frame, which includes
+/// stipend if call transfers value.
+pub fn call_gas(
+    table: &GasTable,
+    remaining: u64,
+    requested: u64,
+    transfers_value: bool,
+) -> (u64, u64) {
+    let forwarded = if table.all_but_one_64th {
+        min(all_but_one_64th(remaining), requested)
     } else {
-        40
+        requested
     };
-
-    call_gas
-        + xfer_cost(is_call_or_callcode, transfers_value)
-        + new_cost::<SPEC>(is_call_or_staticcall, is_new, transfers_value)
+    let gas_limit = if transfers_value {
+        forwarded.saturating_add(CALL_STIPEND)
+    } else {
+        forwarded
+    };
+    (forwarded, gas_limit)
 }
 
-pub fn hot_cold_cost<SPEC: Spec>(is_cold: bool, regular_value: u64) -> u64 {
-    if SPEC::enabled(BERLIN) {
-        if is_cold {
-            COLD_ACCOUNT_ACCESS_COST
-        } else {
-            WARM_STORAGE_READ_COST
-        }
+/// Gas forwarded by CREATE and CREATE2. `remaining` is gas of the caller after create cost is
+/// charged. All of it is forwarded before EIP-150.
+pub fn create_gas(table: &GasTable, remaining: u64) -> u64 {
+    if table.all_but_one_64th {
+        all_but_one_64th(remaining)
     } else {
-        regular_value
+        remaining
     }
 }
 
+/// Intrinsic gas of transaction that is charged before execution starts. It covers base cost,
+/// calldata and access list (EIP-2930).
+pub fn initial_tx_gas(
+    table: &GasTable,
+    input: &[u8],
+    is_create: bool,
+    access_list: &[(H160, Vec<U256>)],
+) -> u64 {
+    let zero_data_len = input.iter().filter(|v| **v == 0).count() as u64;
+    let non_zero_data_len = input.len() as u64 - zero_data_len;
+    // EIP-2: Homestead Hard-fork Changes
+    let transact = if is_create { table.tx_create } else { 21000 };
+
+    transact
+        + zero_data_len * TRANSACTION_ZERO_DATA
+        // EIP-2028: Transaction data gas cost reduction
+        + non_zero_data_len * table.tx_data_non_zero
+        + access_list_gas(access_list, table)
+}
+
+/// EIP-2930: Intrinsic gas of access list, zero if access lists are not enabled in `table`.
+/// Every listed address and slot is charged, including duplicates.
+pub fn access_list_gas(access_list: &[(H160, Vec<U256>)], table: &GasTable) -> u64 {
+    if !table.cold_warm_access {
+        return 0;
+    }
+    let accessed_slots: usize = access_list.iter().map(|(_, slots)| slots.len()).sum();
+    access_list.len() as u64 * ACCESS_LIST_ADDRESS + accessed_slots as u64 * ACCESS_LIST_STORAGE_KEY
+}
+
+/// Cost of access to account that is `regular_value` before Berlin and depends on whether account
+/// is cold since Berlin (EIP-2929).
+pub fn hot_cold_cost(table: &GasTable, is_cold: bool, regular_value: u64) -> u64 {
+    table.account_access_cost(is_cold, regular_value)
+}
+
 fn xfer_cost(is_call_or_callcode: bool, transfers_value: bool) -> u64 {
     if is_call_or_callcode && transfers_value {
         CALLVALUE
@@ -287,28 +325,251 @@ fn xfer_cost(is_call_or_callcode: bool, transfers_value: bool) -> u64 {
     }
 }
 
-fn new_cost<SPEC: Spec>(is_call_or_staticcall: bool, is_new: bool, transfers_value: bool) -> u64 {
-    if is_call_or_staticcall {
-        // EIP-161: State trie clearing (invariant-preserving alternative)
-        if SPEC::enabled(SPURIOUS_DRAGON) {
-            if transfers_value && is_new {
-                NEWACCOUNT
-            } else {
-                0
-            }
-        } else if is_new {
-            NEWACCOUNT
-        } else {
-            0
-        }
+pub(super) fn new_cost(
+    table: &GasTable,
+    is_call_or_staticcall: bool,
+    is_new: bool,
+    transfers_value: bool,
+) -> u64 {
+    // EIP-161: State trie clearing (invariant-preserving alternative)
+    let needs_value = table.new_account_needs_value;
+    if is_call_or_staticcall && is_new && (transfers_value || !needs_value) {
+        NEWACCOUNT
     } else {
         0
     }
 }
 
+/// Total cost of memory of `a` 32 byte words: `3 * a + a * a / 512`, or u64::MAX if it doesn't
+/// fit. Calculated in u128 as saturated `a * a` would make cost of memory above 2^32 words
+/// lower than its real cost.
 pub fn memory_gas(a: usize) -> u64 {
-    let a = a as u64;
-    MEMORY
-        .saturating_mul(a)
-        .saturating_add(a.saturating_mul(a) / 512)
+    let a = a as u128;
+    let gas = MEMORY as u128 * a + a * a / 512;
+    gas.try_into().unwrap_or(u64::MAX)
+}
+
+/// Cost of expanding memory from `current_len` to `new_len` bytes. Sizes are rounded up to
+/// 32 byte words and nothing is charged if memory does not grow.
+pub fn memory_expansion_cost(current_len: usize, new_len: usize) -> u64 {
+    let words = |len: usize| len / 32 + usize::from(len % 32 != 0);
+    memory_gas(words(new_len)).saturating_sub(memory_gas(words(current_len)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SpecId::{self, *};
+
+    fn table(spec_id: SpecId) -> GasTable {
+        GasTable::new(spec_id.into())
+    }
+
+    #[test]
+    fn test_exp_cost_eip160() {
+        assert_eq!(exp_cost(&table(FRONTIER), U256::zero()), Some(10));
+        assert_eq!(exp_cost(&table(FRONTIER), U256::one()), Some(20));
+        assert_eq!(exp_cost(&table(SPURIOUS_DRAGON), U256::one()), Some(60));
+        assert_eq!(exp_cost(&table(SPURIOUS_DRAGON), 256.into()), Some(110));
+        assert_eq!(
+            exp_cost(&table(SPURIOUS_DRAGON), U256::MAX),
+            Some(10 + 50 * 32)
+        );
+    }
+
+    #[test]
+    fn test_memory_and_copy_cost() {
+        assert_eq!(memory_gas(1), 3);
+        assert_eq!(memory_gas(32), 32 * 3 + 2);
+        assert_eq!(memory_expansion_cost(0, 1), 3);
+        assert_eq!(memory_expansion_cost(0, 32), 3);
+        assert_eq!(memory_expansion_cost(32, 64), 3);
+        assert_eq!(memory_expansion_cost(64, 32), 0);
+        assert_eq!(memory_expansion_cost(0, 1024), 32 * 3 + 2);
+
+        assert_eq!(verylowcopy_cost(0), Some(3));
+        assert_eq!(verylowcopy_cost(33), Some(3 + 2 * 3));
+        assert_eq!(sha3_cost(32), Some(30 + 6));
+        assert_eq!(log_cost(2, 10), Some(375 + 10 * 8 + 2 * 375));
+        assert_eq!(create2_cost(33), Some(32000 + 2 * 6));
+
+        // costs of sizes around 2^64 saturate or overflow instead of wrapping.
+        assert_eq!(memory_gas(usize::MAX), u64::MAX);
+        assert_eq!(memory_gas(1 << 32), 3 * (1 << 32) + (1 << 55));
+        assert_eq!(memory_expansion_cost(0, usize::MAX), u64::MAX);
+        assert_eq!(memory_expansion_cost(usize::MAX, usize::MAX), 0);
+        let max_words = u64::MAX / 32 + 1;
+        assert_eq!(verylowcopy_cost(u64::MAX), Some(3 + 3 * max_words));
+        assert_eq!(sha3_cost(u64::MAX), Some(30 + 6 * max_words));
+        assert_eq!(create2_cost(usize::MAX), Some(32000 + 6 * max_words));
+        assert_eq!(log_cost(4, u64::MAX / 8), None);
+    }
+
+    #[test]
+    fn test_sload_cost() {
+        assert_eq!(sload_cost(&table(FRONTIER), true), 50);
+        // EIP-150
+        assert_eq!(sload_cost(&table(TANGERINE), true), 200);
+        // EIP-1884
+        assert_eq!(sload_cost(&table(ISTANBUL), true), 800);
+        // EIP-2929
+        assert_eq!(sload_cost(&table(BERLIN), true), 2100);
+        assert_eq!(sload_cost(&table(BERLIN), false), 100);
+    }
+
+    #[test]
+    fn test_sstore_cost_and_refund() {
+        let (zero, one, two) = (U256::zero(), U256::one(), U256::from(2));
+        assert_eq!(
+            sstore_cost(&table(FRONTIER), zero, zero, one, 0, false),
+            Some(20000)
+        );
+        assert_eq!(
+            sstore_cost(&table(FRONTIER), one, one, two, 0, false),
+            Some(5000)
+        );
+        // EIP-2200
+        assert_eq!(
+            sstore_cost(&table(ISTANBUL), zero, zero, one, 10000, false),
+            Some(20000)
+        );
+        assert_eq!(
+            sstore_cost(&table(ISTANBUL), one, two, one, 10000, false),
+            Some(800)
+        );
+        assert_eq!(
+            sstore_cost(&table(ISTANBUL), zero, zero, one, 2300, false),
+            None
+        );
+        // EIP-2929
+        assert_eq!(
+            sstore_cost(&table(BERLIN), one, one, two, 10000, true),
+            Some(5000)
+        );
+        assert_eq!(
+            sstore_cost(&table(BERLIN), one, one, two, 10000, false),
+            Some(2900)
+        );
+        assert_eq!(
+            sstore_cost(&table(BERLIN), one, one, one, 10000, false),
+            Some(100)
+        );
+
+        assert_eq!(sstore_refund(&table(FRONTIER), one, one, zero), 15000);
+        assert_eq!(sstore_refund(&table(ISTANBUL), one, one, zero), 15000);
+        // restoring original value refunds reset cost.
+        assert_eq!(sstore_refund(&table(BERLIN), one, two, one), 2900 - 100);
+        // taking back refund of cleared slot.
+        assert_eq!(sstore_refund(&table(BERLIN), one, zero, two), -15000);
+        // EIP-3529
+        assert_eq!(sstore_refund(&table(LONDON), one, one, zero), 4800);
+    }
+
+    #[test]
+    fn test_call_cost_and_gas() {
+        assert_eq!(
+            call_cost(&table(FRONTIER), U256::zero(), false, true, true, true),
+            40
+        );
+        // EIP-150
+        assert_eq!(
+            call_cost(&table(TANGERINE), U256::zero(), true, true, true, true),
+            700 + 25000
+        );
+        // EIP-161: new account is charged only if value is transferred.
+        assert_eq!(
+            call_cost(
+                &table(SPURIOUS_DRAGON),
+                U256::zero(),
+                true,
+                true,
+                true,
+                true
+            ),
+            700
+        );
+        assert_eq!(
+            call_cost(&table(SPURIOUS_DRAGON), U256::one(), true, true, true, true),
+            700 + 9000 + 25000
+        );
+        // EIP-2929
+        assert_eq!(
+            call_cost(&table(BERLIN), U256::zero(), false, true, true, true),
+            2600
+        );
+        assert_eq!(
+            call_cost(&table(BERLIN), U256::zero(), false, false, false, false),
+            100
+        );
+
+        assert_eq!(
+            call_gas(&table(FRONTIER), 6400, u64::MAX, false),
+            (u64::MAX, u64::MAX)
+        );
+        assert_eq!(
+            call_gas(&table(TANGERINE), 6400, u64::MAX, false),
+            (6300, 6300)
+        );
+        assert_eq!(
+            call_gas(&table(TANGERINE), 6400, 1000, true),
+            (1000, 1000 + 2300)
+        );
+        assert_eq!(create_gas(&table(FRONTIER), 6400), 6400);
+        assert_eq!(create_gas(&table(TANGERINE), 6400), 6300);
+    }
+
+    #[test]
+    fn test_selfdestruct_cost() {
+        let result = |had_value, target_exists, is_cold| SelfDestructResult {
+            had_value,
+            target_exists,
+            is_cold,
+            ..Default::default()
+        };
+        assert_eq!(
+            selfdestruct_cost(&table(FRONTIER), result(true, false, true)),
+            0
+        );
+        // EIP-150
+        assert_eq!(
+            selfdestruct_cost(&table(TANGERINE), result(false, false, true)),
+            5000 + 25000
+        );
+        // EIP-161
+        assert_eq!(
+            selfdestruct_cost(&table(SPURIOUS_DRAGON), result(false, false, true)),
+            5000
+        );
+        // EIP-2929
+        assert_eq!(
+            selfdestruct_cost(&table(BERLIN), result(false, true, true)),
+            5000 + 2600
+        );
+    }
+
+    #[test]
+    fn test_initial_tx_gas() {
+        let access_list = [(H160::zero(), vec![U256::zero(), U256::one()])];
+        assert_eq!(
+            initial_tx_gas(&table(FRONTIER), &[0, 1], false, &[]),
+            21000 + 4 + 68
+        );
+        // EIP-2
+        assert_eq!(initial_tx_gas(&table(FRONTIER), &[], true, &[]), 21000);
+        assert_eq!(initial_tx_gas(&table(HOMESTEAD), &[], true, &[]), 53000);
+        // EIP-2028
+        assert_eq!(
+            initial_tx_gas(&table(ISTANBUL), &[0, 1], false, &[]),
+            21000 + 4 + 16
+        );
+        // EIP-2930
+        assert_eq!(
+            initial_tx_gas(&table(ISTANBUL), &[], false, &access_list),
+            21000
+        );
+        assert_eq!(
+            initial_tx_gas(&table(BERLIN), &[], false, &access_list),
+            21000 + 2400 + 2 * 1900
+        );
+    }
 }
@@ -0,0 +1,194 @@
+use super::constants::*;
+use crate::{SpecFlags, SpecId::*};
+
+/// Gas prices that changed between hardforks, resolved once per spec so that gas of executed
+/// opcodes is calculated without checking enabled hardforks.
+///
+/// Tables of standard specs are evaluated at compile time, see [crate::Spec::GAS_TABLE].
+/// Table of [crate::CustomSpec] is built from its flags when EVM is created, see
+/// [crate::SpecRules].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct GasTable {
+    /// EIP-2929 and EIP-2930: Accounts and storage slots are cold on first access and access
+    /// list is part of intrinsic gas.
+    pub cold_warm_access: bool,
+    /// Cost of BALANCE, or of its warm access since EIP-2929. Changed by EIP-150 and EIP-1884.
+    pub balance: u64,
+    /// Cost of SLOAD, or of its warm access since EIP-2929. Changed by EIP-150 and EIP-1884.
+    pub sload: u64,
+    /// Cost of EXTCODESIZE and EXTCODEHASH, or of their warm access since EIP-2929. Changed by
+    /// EIP-1884.
+    pub account_access: u64,
+    /// Cost of CALL-like opcodes, or of their warm access since EIP-2929. Changed by EIP-150.
+    pub call: u64,
+    /// Cost of EXP per byte of exponent, changed by EIP-160.
+    pub exp_byte: u64,
+    /// EIP-2200: Net gas metering of SSTORE.
+    pub sstore_net_metering: bool,
+    /// Cost of SSTORE that changes non zero slot. EIP-2929 subtracted cold access from it.
+    pub sstore_reset: u64,
+    /// Refund of SSTORE that clears slot, reduced by EIP-3529.
+    pub sstore_clears_refund: i64,
+    /// Cost of SELFDESTRUCT, added by EIP-150.
+    pub selfdestruct: u64,
+    /// Cost of SELFDESTRUCT to new account, added by EIP-150.
+    pub selfdestruct_new_account: u64,
+    /// Refund of SELFDESTRUCT, removed by EIP-3529.
+    pub selfdestruct_refund: i64,
+    /// EIP-161: New account is charged only if value is transferred to it.
+    pub new_account_needs_value: bool,
+    /// EIP-150: Only all but one 64th of remaining gas can be forwarded to new frame.
+    pub all_but_one_64th: bool,
+    /// Intrinsic gas of create transaction, raised by EIP-2.
+    pub tx_create: u64,
+    /// Intrinsic gas of non zero byte of transaction data, reduced by EIP-2028.
+    pub tx_data_non_zero: u64,
+    /// Refund is limited to gas spent divided by this quotient, raised by EIP-3529.
+    pub max_refund_quotient: u64,
+}
+
+impl GasTable {
+    /// Gas prices of hardforks enabled in `flags`.
+    pub const fn new(flags: SpecFlags) -> Self {
+        let homestead = flags.enabled(HOMESTEAD);
+        let tangerine = flags.enabled(TANGERINE);
+        let spurious_dragon = flags.enabled(SPURIOUS_DRAGON);
+        let istanbul = flags.enabled(ISTANBUL);
+        let berlin = flags.enabled(BERLIN);
+        let london = flags.enabled(LONDON);
+        Self {
+            cold_warm_access: berlin,
+            balance: if berlin {
+                WARM_STORAGE_READ_COST
+            } else if istanbul {
+                700
+            } else if tangerine {
+                400
+            } else {
+                20
+            },
+            sload: if berlin {
+                WARM_STORAGE_READ_COST
+            } else if istanbul {
+                800
+            } else if tangerine {
+                200
+            } else {
+                50
+            },
+            account_access: if berlin {
+                WARM_STORAGE_READ_COST
+            } else if istanbul {
+                700
+            } else {
+                20
+            },
+            call: if berlin {
+                WARM_STORAGE_READ_COST
+            } else if tangerine {
+                700
+            } else {
+                40
+            },
+            exp_byte: if spurious_dragon { 50 } else { 10 },
+            sstore_net_metering: istanbul,
+            sstore_reset: if berlin {
+                SSTORE_RESET - COLD_SLOAD_COST
+            } else {
+                SSTORE_RESET
+            },
+            sstore_clears_refund: if london {
+                (SSTORE_RESET - COLD_SLOAD_COST + ACCESS_LIST_STORAGE_KEY) as i64
+            } else {
+                REFUND_SSTORE_CLEARS
+            },
+            selfdestruct: if tangerine { 5000 } else { 0 },
+            selfdestruct_new_account: if tangerine { NEWACCOUNT } else { 0 },
+            selfdestruct_refund: if london { 0 } else { SELFDESTRUCT },
+            new_account_needs_value: spurious_dragon,
+            all_but_one_64th: tangerine,
+            tx_create: if homestead { 53000 } else { 21000 },
+            tx_data_non_zero: if istanbul {
+                TRANSACTION_NON_ZERO_DATA_INIT
+            } else {
+                TRANSACTION_NON_ZERO_DATA_FRONTIER
+            },
+            max_refund_quotient: if london { 5 } else { 2 },
+        }
+    }
+
+    /// Cost of access to account that is `legacy_cost` before EIP-2929.
+    #[inline(always)]
+    pub const fn account_access_cost(&self, is_cold: bool, legacy_cost: u64) -> u64 {
+        if !self.cold_warm_access {
+            legacy_cost
+        } else if is_cold {
+            COLD_ACCOUNT_ACCESS_COST
+        } else {
+            WARM_STORAGE_READ_COST
+        }
+    }
+
+    /// EIP-2929: Cost of cold account access on top of warm access.
+    #[inline(always)]
+    pub const fn cold_account_additional_cost(&self, is_cold: bool) -> u64 {
+        if self.cold_warm_access && is_cold {
+            COLD_ACCOUNT_ACCESS_COST - WARM_STORAGE_READ_COST
+        } else {
+            0
+        }
+    }
+
+    /// Cost of SLOAD.
+    #[inline(always)]
+    pub const fn sload_cost(&self, is_cold: bool) -> u64 {
+        if self.cold_warm_access && is_cold {
+            COLD_SLOAD_COST
+        } else {
+            self.sload
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CustomSpec, LondonSpec, SpecId, SpecRules};
+
+    #[test]
+    fn test_gas_table() {
+        for spec_id in SpecId::ALL {
+            let table = GasTable::new(SpecFlags::new(spec_id));
+            let enabled = |other| SpecId::enabled(spec_id, other);
+            assert_eq!(table.cold_warm_access, enabled(BERLIN));
+            assert_eq!(table.all_but_one_64th, enabled(TANGERINE));
+            assert_eq!(table.selfdestruct_refund == 0, enabled(LONDON));
+        }
+        let istanbul = GasTable::new(SpecFlags::new(ISTANBUL));
+        assert_eq!(
+            (istanbul.balance, istanbul.sload, istanbul.call),
+            (700, 800, 700)
+        );
+        assert_eq!(istanbul.sload_cost(true), 800);
+        assert_eq!(istanbul.account_access_cost(true, 700), 700);
+        assert_eq!(istanbul.cold_account_additional_cost(true), 0);
+
+        let berlin = GasTable::new(SpecFlags::new(BERLIN));
+        assert_eq!(berlin.sload_cost(true), 2100);
+        assert_eq!(berlin.sload_cost(false), 100);
+        assert_eq!(berlin.account_access_cost(true, 700), 2600);
+        assert_eq!(berlin.cold_account_additional_cost(true), 2500);
+
+        // table of custom spec follows its flags.
+        let flags = SpecFlags::new(LONDON).disable(BERLIN);
+        let rules = SpecRules::of::<CustomSpec>(Some(flags));
+        assert_eq!(rules.gas_table::<CustomSpec>(), &GasTable::new(flags));
+        // standard spec doesn't read the rules.
+        assert_eq!(
+            rules.gas_table::<LondonSpec>(),
+            &GasTable::new(SpecFlags::new(LONDON))
+        );
+        assert_eq!(GasTable::new(flags).sload, 800);
+        assert_eq!(GasTable::new(flags).sstore_clears_refund, 4800);
+    }
+}
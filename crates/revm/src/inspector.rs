@@ -2,11 +2,16 @@ use bytes::Bytes;
 use primitive_types::{H160, H256};
 
 use crate::{
-    evm_impl::EVMData, opcode, spec_opcode_gas, CallInputs, CreateInputs, Database, Gas,
-    Interpreter, Return,
+    custom_opcode_gas, evm_impl::EVMData, opcode, spec_opcode_gas, CallInputs, CreateInputs,
+    Database, Env, ExecutionResult, Gas, Interpreter, OpcodeSet, Return, SelfDestructResult, State,
 };
 use auto_impl::auto_impl;
 
+/// EVM hooks called during execution.
+///
+/// Inspector can observe execution and alter control flow of calls and creates, but it does
+/// not configure the EVM. Limits and spec overrides (contract code size, memory limit, custom
+/// spec) are set in [crate::CfgEnv] and apply with and without inspector.
 #[auto_impl(&mut, Box)]
 pub trait Inspector<DB: Database> {
     /// Called Before the interpreter is initialized.
@@ -39,6 +44,15 @@ pub trait Inspector<DB: Database> {
         Return::Continue
     }
 
+    /// Opcodes for which [Inspector::step] and [Inspector::step_end] are called, all by default.
+    ///
+    /// Tracers that are interested only in few opcodes should return them here, steps of other
+    /// opcodes then don't call into inspector at all. It is read when interpreter of a call or
+    /// create is created.
+    fn step_opcodes(&self) -> OpcodeSet {
+        OpcodeSet::ALL
+    }
+
     /// Called when a log is emitted.
     fn log(
         &mut self,
@@ -101,6 +115,20 @@ pub trait Inspector<DB: Database> {
         (Return::Continue, None, Gas::new(0), Bytes::default())
     }
 
+    /// Called when address of the created contract is derived, before the account is created
+    /// and init code is executed. `nonce` is nonce of the caller used for CREATE, salt of CREATE2
+    /// is in `inputs`.
+    ///
+    /// State changes done here, like funding the address, are not reverted if the create fails.
+    fn create_address(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CreateInputs,
+        _address: H160,
+        _nonce: u64,
+    ) {
+    }
+
     /// Called when a contract has been created.
     ///
     /// Returning anything other than the values passed to this function (`(ret, remaining_gas,
@@ -117,14 +145,58 @@ pub trait Inspector<DB: Database> {
         (ret, address, remaining_gas, out)
     }
 
-    /// Called when a contract has been self-destructed.
-    fn selfdestruct(&mut self) {}
+    /// Called when interpreter of a call or create frame stops, before [Inspector::call_end]
+    /// or [Inspector::create_end], with resources the frame used. It is not called for
+    /// precompiles and for frames that were not executed.
+    fn frame_usage(&mut self, _data: &mut EVMData<'_, DB>, _usage: &FrameUsage) {}
+
+    /// Called when contract at `address` has been self-destructed with `target` as beneficiary.
+    /// Swept balance is [SelfDestructResult::value], together with whether target was cold or
+    /// is created by it. If `target` is `address`, balance is burned and is found in
+    /// [SelfDestructResult::burned_balance].
+    fn selfdestruct(&mut self, _address: H160, _target: H160, _result: &SelfDestructResult) {}
+
+    /// Called when transaction starts, before it is validated. Tracers use it to open output of
+    /// the transaction or to reset state left from previous one.
+    fn transact_start(&mut self, _env: &Env) {}
+
+    /// Called when transaction ends, with the final result and state changes that are returned
+    /// from it. It is also called for transaction that was rejected, with empty state.
+    fn transact_end(&mut self, _result: &ExecutionResult, _state: &State) {}
+}
+
+/// Resources used by a single call or create frame, see [Inspector::frame_usage].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FrameUsage {
+    /// Address of the executed contract, created address for create.
+    pub address: H160,
+    /// Call depth of the frame, starting with 1 for the frame of transaction.
+    pub depth: u64,
+    /// Highest number of items on the stack.
+    pub max_stack_depth: usize,
+    /// Memory expanded by the frame, in 32 byte words.
+    pub memory_words: usize,
+}
+
+impl FrameUsage {
+    pub(crate) fn new(interp: &Interpreter, depth: u64) -> Self {
+        Self {
+            address: interp.contract.address,
+            depth,
+            max_stack_depth: interp.max_stack_depth,
+            memory_words: interp.memory.len() / 32,
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
 pub struct NoOpInspector();
 
-impl<DB: Database> Inspector<DB> for NoOpInspector {}
+impl<DB: Database> Inspector<DB> for NoOpInspector {
+    fn step_opcodes(&self) -> OpcodeSet {
+        OpcodeSet::NONE
+    }
+}
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct GasInspector {
@@ -167,7 +239,14 @@ impl<DB: Database> Inspector<DB> for GasInspector {
         let op = interp.current_opcode();
 
         // calculate gas_block
-        let infos = spec_opcode_gas(data.env.cfg.spec_id);
+        let custom_infos;
+        let infos = match data.env.cfg.custom_spec {
+            Some(flags) => {
+                custom_infos = custom_opcode_gas(flags);
+                &custom_infos
+            }
+            None => spec_opcode_gas(data.env.cfg.spec_id),
+        };
         let info = &infos[op as usize];
 
         let pc = interp.program_counter();
@@ -241,10 +320,10 @@ impl<DB: Database> Inspector<DB> for GasInspector {
 
 #[cfg(test)]
 mod tests {
-    use crate::db::BenchmarkDB;
+    use crate::db::{BenchmarkDB, InMemoryDB};
     use crate::{
-        opcode, Bytecode, CallInputs, CreateInputs, Database, EVMData, Gas, GasInspector,
-        Inspector, Interpreter, OpCode, Return, TransactTo,
+        opcode, AccountInfo, Bytecode, CallInputs, CreateInputs, Database, EVMData, Gas,
+        GasInspector, Inspector, Interpreter, NoOpInspector, OpCode, OpcodeSet, Return, TransactTo,
     };
     use bytes::Bytes;
     use core::str::FromStr;
@@ -395,4 +474,527 @@ mod tests {
             );
         }
     }
+
+    #[derive(Default)]
+    struct StaticInspector {
+        /// Called contract, its return and static flag of every finished call.
+        calls: Vec<(H160, Return, bool)>,
+    }
+
+    impl<DB: Database> Inspector<DB> for StaticInspector {
+        fn call_end(
+            &mut self,
+            _data: &mut EVMData<'_, DB>,
+            inputs: &CallInputs,
+            remaining_gas: Gas,
+            ret: Return,
+            out: Bytes,
+            is_static: bool,
+        ) -> (Return, Gas, Bytes) {
+            assert_eq!(is_static, inputs.is_static);
+            self.calls.push((inputs.contract, ret, is_static));
+            (ret, remaining_gas, out)
+        }
+    }
+
+    /// Bytecode that calls `to` with `gas * 256` gas, zero value and empty input/output.
+    fn call_code(call: u8, to: u8, gas: u8) -> Vec<u8> {
+        let mut code = vec![opcode::PUSH1, 0, opcode::PUSH1, 0, opcode::PUSH1, 0];
+        code.extend([opcode::PUSH1, 0]);
+        if call == opcode::CALL {
+            // value
+            code.extend([opcode::PUSH1, 0]);
+        }
+        code.extend([opcode::PUSH1, to, opcode::PUSH2, gas, 0, call]);
+        code
+    }
+
+    #[test]
+    fn test_nested_static_calls() {
+        let addr = H160::from_low_u64_be;
+        let mut sstore = vec![opcode::PUSH1, 1, opcode::PUSH1, 0, opcode::SSTORE];
+        sstore.push(opcode::STOP);
+        let log = vec![
+            opcode::PUSH1,
+            0,
+            opcode::PUSH1,
+            0,
+            opcode::LOG0,
+            opcode::STOP,
+        ];
+        // 0xb calls 0xc and 0xd
+        let mut inner = call_code(opcode::CALL, 0xc, 0x10);
+        inner.extend(call_code(opcode::CALL, 0xd, 0x10));
+        inner.push(opcode::STOP);
+        // 0xa staticcalls 0xb and then calls 0xc
+        let mut outer = call_code(opcode::STATICCALL, 0xb, 0x80);
+        outer.extend(call_code(opcode::CALL, 0xc, 0x80));
+        outer.push(opcode::STOP);
+
+        let mut db = InMemoryDB::default();
+        for (address, code) in [(0xa, outer), (0xb, inner), (0xc, sstore), (0xd, log)] {
+            db.insert_account_info(
+                addr(address),
+                AccountInfo::new(0.into(), 1, Bytecode::new_raw(code.into())),
+            );
+        }
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = addr(0x1000);
+        evm.env.tx.transact_to = TransactTo::Call(addr(0xa));
+        evm.env.tx.gas_limit = 1_000_000;
+
+        let mut inspector = StaticInspector::default();
+        let (result, state) = evm.inspect(&mut inspector);
+        assert_eq!(result.exit_reason, Return::Stop);
+        assert_eq!(
+            inspector.calls,
+            vec![
+                (addr(0xc), Return::StateChangeDuringStaticCall, true),
+                (addr(0xd), Return::StateChangeDuringStaticCall, true),
+                (addr(0xb), Return::Stop, true),
+                (addr(0xc), Return::Stop, false),
+                (addr(0xa), Return::Stop, false),
+            ]
+        );
+        // only non static call to 0xc changed storage.
+        assert_eq!(
+            state[&addr(0xc)].storage[&0.into()].present_value(),
+            1.into()
+        );
+    }
+
+    #[derive(Default)]
+    struct PrecompileInspector {
+        /// Called contract and whether it is precompile.
+        calls: Vec<(H160, bool)>,
+    }
+
+    impl<DB: Database> Inspector<DB> for PrecompileInspector {
+        fn call(
+            &mut self,
+            data: &mut EVMData<'_, DB>,
+            inputs: &mut CallInputs,
+            _is_static: bool,
+        ) -> (Return, Gas, Bytes) {
+            assert_eq!(data.precompiles.spec(), revm_precompiles::SpecId::CANCUN);
+            // static table of the spec is borrowed, not copied.
+            let table = revm_precompiles::Precompiles::new(revm_precompiles::SpecId::CANCUN);
+            assert!(core::ptr::eq(data.precompiles, table));
+            self.calls
+                .push((inputs.contract, data.is_precompile(&inputs.contract)));
+            (Return::Continue, Gas::new(0), Bytes::new())
+        }
+    }
+
+    #[test]
+    fn test_precompiles_in_evm_data() {
+        let addr = H160::from_low_u64_be;
+        // 0xa calls identity precompile and 0xb
+        let mut code = call_code(opcode::CALL, 0x4, 0x10);
+        code.extend(call_code(opcode::CALL, 0xb, 0x10));
+        code.push(opcode::STOP);
+
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            addr(0xa),
+            AccountInfo::new(0.into(), 1, Bytecode::new_raw(code.into())),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(addr(0xa));
+        evm.env.tx.gas_limit = 1_000_000;
+
+        let mut inspector = PrecompileInspector::default();
+        let (result, _) = evm.inspect(&mut inspector);
+        assert_eq!(result.exit_reason, Return::Stop);
+        assert_eq!(
+            inspector.calls,
+            vec![(addr(0xa), false), (addr(0x4), true), (addr(0xb), false)]
+        );
+    }
+
+    /// Records opcodes of steps.
+    #[derive(Default)]
+    struct SstoreInspector {
+        steps: Vec<u8>,
+        step_ends: usize,
+    }
+
+    impl<DB: Database> Inspector<DB> for SstoreInspector {
+        fn step_opcodes(&self) -> OpcodeSet {
+            OpcodeSet::new(&[opcode::SSTORE])
+        }
+
+        fn step(
+            &mut self,
+            interp: &mut Interpreter,
+            _data: &mut EVMData<'_, DB>,
+            _is_static: bool,
+        ) -> Return {
+            self.steps.push(interp.current_opcode());
+            Return::Continue
+        }
+
+        fn step_end(
+            &mut self,
+            _interp: &mut Interpreter,
+            _data: &mut EVMData<'_, DB>,
+            _is_static: bool,
+            _eval: Return,
+        ) -> Return {
+            self.step_ends += 1;
+            Return::Continue
+        }
+    }
+
+    #[test]
+    fn test_step_opcodes() {
+        let set = OpcodeSet::new(&[opcode::STOP, opcode::SSTORE, opcode::SELFDESTRUCT]);
+        assert!(set.contains(opcode::STOP) && set.contains(opcode::SELFDESTRUCT));
+        assert!(!set.contains(opcode::SLOAD));
+        assert_eq!(set.union(OpcodeSet::ALL), OpcodeSet::ALL);
+        assert!(OpcodeSet::NONE.is_empty() && !set.is_empty());
+
+        let sstore = [opcode::PUSH1, 1, opcode::PUSH1, 0, opcode::SSTORE];
+        let code = [&sstore[..], &sstore, &[opcode::STOP]].concat();
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            H160::from_low_u64_be(0xa),
+            AccountInfo::new(0.into(), 1, Bytecode::new_raw(code.into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(H160::from_low_u64_be(0xa));
+        evm.env.tx.gas_limit = 1_000_000;
+
+        let mut inspector = SstoreInspector::default();
+        let (result, _) = evm.inspect(&mut inspector);
+        assert_eq!(result.exit_reason, Return::Stop);
+        assert_eq!(inspector.steps, vec![opcode::SSTORE, opcode::SSTORE]);
+        assert_eq!(inspector.step_ends, 2);
+
+        // inspector without steps runs the same loop as transaction without inspector.
+        let (noop_result, _) = evm.inspect(NoOpInspector());
+        assert_eq!(noop_result.gas_used, result.gas_used);
+    }
+
+    /// Funds created contract before its init code runs.
+    #[derive(Default)]
+    struct FundingInspector {
+        derived: Option<(H160, u64)>,
+        created: Option<H160>,
+    }
+
+    impl<DB: Database> Inspector<DB> for FundingInspector {
+        fn create_address(
+            &mut self,
+            data: &mut EVMData<'_, DB>,
+            _inputs: &CreateInputs,
+            address: H160,
+            nonce: u64,
+        ) {
+            self.derived = Some((address, nonce));
+            data.set_balance(address, 7.into()).ok().unwrap();
+        }
+
+        fn create_end(
+            &mut self,
+            _data: &mut EVMData<'_, DB>,
+            _inputs: &CreateInputs,
+            ret: Return,
+            address: Option<H160>,
+            remaining_gas: Gas,
+            out: Bytes,
+        ) -> (Return, Option<H160>, Gas, Bytes) {
+            self.created = address;
+            (ret, address, remaining_gas, out)
+        }
+    }
+
+    #[test]
+    fn test_create_address() {
+        let caller = H160::from_low_u64_be(0xa);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(caller, AccountInfo::new(0.into(), 3, Bytecode::new()));
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::create();
+        // store balance of created contract.
+        evm.env.tx.data = Bytes::from(vec![
+            opcode::SELFBALANCE,
+            opcode::PUSH1,
+            0,
+            opcode::SSTORE,
+            opcode::STOP,
+        ]);
+        evm.env.tx.gas_limit = 1_000_000;
+
+        let mut inspector = FundingInspector::default();
+        let (result, state) = evm.inspect(&mut inspector);
+        assert_eq!(result.exit_reason, Return::Continue);
+        let address = crate::create_address(caller, 3);
+        assert_eq!(inspector.derived, Some((address, 3)));
+        assert_eq!(inspector.created, Some(address));
+        assert_eq!(state[&address].info.balance, 7.into());
+        assert_eq!(state[&address].storage[&0.into()].present_value(), 7.into());
+    }
+
+    /// Sets block number when 0xc starts, like `roll` cheatcode.
+    struct RollInspector;
+
+    impl<DB: Database> Inspector<DB> for RollInspector {
+        fn step(
+            &mut self,
+            interp: &mut Interpreter,
+            data: &mut EVMData<'_, DB>,
+            _is_static: bool,
+        ) -> Return {
+            if interp.contract.address == H160::from_low_u64_be(0xc)
+                && interp.program_counter() == 0
+            {
+                data.env_mut().block.number = 100.into();
+            }
+            Return::Continue
+        }
+    }
+
+    #[test]
+    fn test_env_revert() {
+        let addr = H160::from_low_u64_be;
+        // 0xa calls 0xb and stores block number, 0xb calls 0xc and ends with `end`.
+        let mut code = call_code(opcode::CALL, 0xb, 0x40);
+        code.extend([
+            opcode::NUMBER,
+            opcode::PUSH1,
+            0,
+            opcode::SSTORE,
+            opcode::STOP,
+        ]);
+        let run = |end: &[u8]| {
+            let mut inner = call_code(opcode::CALL, 0xc, 0x10);
+            inner.extend(end);
+            let mut db = InMemoryDB::default();
+            for (address, code) in [(0xa, &code), (0xb, &inner), (0xc, &vec![opcode::STOP])] {
+                db.insert_account_info(
+                    addr(address),
+                    AccountInfo::new(0.into(), 1, Bytecode::new_raw(code.clone().into())),
+                );
+            }
+            let mut evm = crate::new();
+            evm.database(db);
+            evm.env.block.number = 7.into();
+            evm.env.tx.transact_to = TransactTo::Call(addr(0xa));
+            evm.env.tx.gas_limit = 1_000_000;
+            let (result, state) = evm.inspect(RollInspector);
+            assert_eq!(result.exit_reason, Return::Stop);
+            let number = state[&addr(0xa)].storage[&0.into()].present_value();
+            assert_eq!(number, evm.env.block.number);
+            number
+        };
+
+        // change made by 0xc is committed into 0xb and reverted with it.
+        let revert = [opcode::PUSH1, 0, opcode::PUSH1, 0, opcode::REVERT];
+        assert_eq!(run(&revert), 7.into());
+        assert_eq!(run(&[opcode::STOP]), 100.into());
+    }
+
+    /// Frames output of every transaction and counts calls in it.
+    #[derive(Default)]
+    struct FramingInspector {
+        calls: usize,
+        output: Vec<String>,
+    }
+
+    impl<DB: Database> Inspector<DB> for FramingInspector {
+        fn call(
+            &mut self,
+            _data: &mut EVMData<'_, DB>,
+            _inputs: &mut CallInputs,
+            _is_static: bool,
+        ) -> (Return, Gas, Bytes) {
+            self.calls += 1;
+            (Return::Continue, Gas::new(0), Bytes::new())
+        }
+
+        fn transact_start(&mut self, env: &crate::Env) {
+            self.calls = 0;
+            self.output.push(format!("start {}", env.tx.gas_limit));
+        }
+
+        fn transact_end(&mut self, result: &crate::ExecutionResult, state: &crate::State) {
+            self.output.push(format!(
+                "end {:?} calls {} accounts {}",
+                result.exit_reason,
+                self.calls,
+                state.len()
+            ));
+        }
+    }
+
+    #[test]
+    fn test_transact_hooks() {
+        let contract = H160::from_low_u64_be(0xa);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(
+                0.into(),
+                1,
+                Bytecode::new_raw(call_code(opcode::CALL, 0xb, 0x10).into()),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+
+        let mut inspector = FramingInspector::default();
+        evm.inspect(&mut inspector);
+        // rejected transaction is framed too.
+        evm.env.tx.gas_limit = 20_000;
+        evm.inspect(&mut inspector);
+        assert_eq!(
+            inspector.output,
+            vec![
+                "start 100000",
+                "end Stop calls 2 accounts 3",
+                "start 20000",
+                "end OutOfGas calls 0 accounts 0",
+            ]
+        );
+    }
+
+    /// Records where input and output of every call are stored.
+    #[derive(Default)]
+    struct BufferInspector {
+        inputs: Vec<*const u8>,
+        outputs: Vec<*const u8>,
+    }
+
+    impl<DB: Database> Inspector<DB> for BufferInspector {
+        fn call(
+            &mut self,
+            _data: &mut EVMData<'_, DB>,
+            inputs: &mut CallInputs,
+            _is_static: bool,
+        ) -> (Return, Gas, Bytes) {
+            self.inputs.push(inputs.input.as_ptr());
+            (Return::Continue, Gas::new(0), Bytes::new())
+        }
+
+        fn call_end(
+            &mut self,
+            _data: &mut EVMData<'_, DB>,
+            _inputs: &CallInputs,
+            remaining_gas: Gas,
+            ret: Return,
+            out: Bytes,
+            _is_static: bool,
+        ) -> (Return, Gas, Bytes) {
+            self.outputs.push(out.as_ptr());
+            (ret, remaining_gas, out)
+        }
+    }
+
+    #[test]
+    fn test_proxy_buffers_are_shared() {
+        use opcode::*;
+        let proxy = H160::from_low_u64_be(0xa);
+        // forward calldata with DELEGATECALL to 0xb and return its output.
+        let proxy_code = [
+            &[CALLDATASIZE, PUSH1, 0, PUSH1, 0, CALLDATACOPY][..],
+            &[PUSH1, 0, PUSH1, 0, CALLDATASIZE, PUSH1, 0, PUSH1, 0xb, GAS],
+            &[DELEGATECALL, POP],
+            &[RETURNDATASIZE, PUSH1, 0, PUSH1, 0, RETURNDATACOPY],
+            &[RETURNDATASIZE, PUSH1, 0, RETURN],
+        ]
+        .concat();
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            proxy,
+            AccountInfo::new(0.into(), 1, Bytecode::new_raw(proxy_code.into())),
+        );
+        db.insert_account_info(
+            H160::from_low_u64_be(0xb),
+            AccountInfo::new(
+                0.into(),
+                1,
+                Bytecode::new_raw(vec![PUSH1, 64, PUSH1, 0, RETURN].into()),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(proxy);
+        evm.env.tx.data = vec![7; 36].into();
+        evm.env.tx.gas_limit = 100_000;
+
+        let mut inspector = BufferInspector::default();
+        let (result, _) = evm.inspect(&mut inspector);
+        assert_eq!(result.exit_reason, Return::Return);
+        let output = result.output().unwrap();
+        assert_eq!(output.as_ref(), [0; 64]);
+        // calldata is forwarded and output is returned without copies.
+        assert_eq!(inspector.inputs, vec![evm.env.tx.data.as_ptr(); 2]);
+        assert_eq!(inspector.outputs, vec![output.as_ptr(); 2]);
+    }
+
+    #[derive(Default)]
+    struct UsageInspector {
+        frames: Vec<super::FrameUsage>,
+    }
+
+    impl<DB: Database> Inspector<DB> for UsageInspector {
+        fn frame_usage(&mut self, _data: &mut EVMData<'_, DB>, usage: &super::FrameUsage) {
+            self.frames.push(*usage);
+        }
+    }
+
+    #[test]
+    fn test_frame_usage() {
+        let addr = H160::from_low_u64_be;
+        // 0xa calls identity precompile and 0xb, leaving success flags on the stack.
+        let mut code = call_code(opcode::CALL, 0x4, 0x10);
+        code.extend(call_code(opcode::CALL, 0xb, 0x10));
+        code.push(opcode::STOP);
+        // 0xb stores word at offset 64.
+        let mstore = vec![
+            opcode::PUSH1,
+            1,
+            opcode::PUSH1,
+            64,
+            opcode::MSTORE,
+            opcode::STOP,
+        ];
+
+        let mut db = InMemoryDB::default();
+        for (address, code) in [(0xa, code), (0xb, mstore)] {
+            db.insert_account_info(
+                addr(address),
+                AccountInfo::new(0.into(), 1, Bytecode::new_raw(code.into())),
+            );
+        }
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(addr(0xa));
+        evm.env.tx.gas_limit = 1_000_000;
+
+        let mut inspector = UsageInspector::default();
+        let (result, _) = evm.inspect(&mut inspector);
+        assert_eq!(result.exit_reason, Return::Stop);
+        let usage = |address, depth, max_stack_depth, memory_words| super::FrameUsage {
+            address: addr(address),
+            depth,
+            max_stack_depth,
+            memory_words,
+        };
+        // precompile has no frame, inner frame ends first.
+        assert_eq!(
+            inspector.frames,
+            vec![usage(0xb, 2, 2, 3), usage(0xa, 1, 8, 0)]
+        );
+    }
 }
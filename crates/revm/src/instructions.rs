@@ -15,6 +15,7 @@ pub use opcode::{OpCode, OPCODE_JUMPMAP};
 
 use crate::{interpreter::Interpreter, CallScheme, Host, Spec, SpecId::*};
 use core::ops::{BitAnd, BitOr, BitXor};
+use num_enum::TryFromPrimitive;
 use primitive_types::U256;
 
 #[macro_export]
@@ -32,7 +33,7 @@ macro_rules! return_revert {
 }
 
 #[repr(u8)]
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, TryFromPrimitive)]
 #[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Return {
     //success codes
@@ -72,6 +73,17 @@ pub enum Return {
     CreateContractLimit,
     /// Error on created contract that begins with EF
     CreateContractWithEF,
+    /// SSTORE, LOG, CREATE or SELFDESTRUCT executed in static context.
+    StateChangeDuringStaticCall,
+    /// EIP-155: Transaction chain id does not match chain id of environment.
+    InvalidChainId,
+    /// Init code exceeds configured limit.
+    CreateInitcodeSizeLimit,
+    /// Output of call or create exceeds configured limit.
+    ReturnDataLimit,
+    /// DELEGATECALL or CALLCODE of precompile that is rejected by
+    /// [crate::CfgEnv::precompile_delegation].
+    PrecompileDelegation,
 }
 
 #[inline(always)]
@@ -87,7 +99,7 @@ pub fn eval<H: Host, S: Spec>(opcode: u8, interp: &mut Interpreter, host: &mut H
         251_u8..=252_u8 => Return::OpcodeNotFound,*/
         opcode::STOP => Return::Stop,
         opcode::ADD => op2_u256_tuple!(interp, overflowing_add),
-        opcode::MUL => op2_u256_tuple!(interp, overflowing_mul),
+        opcode::MUL => op2_u256_fn!(interp, arithmetic::mul),
         opcode::SUB => op2_u256_tuple!(interp, overflowing_sub),
         opcode::DIV => op2_u256_fn!(interp, arithmetic::div),
         opcode::SDIV => op2_u256_fn!(interp, arithmetic::sdiv),
@@ -111,17 +123,17 @@ pub fn eval<H: Host, S: Spec>(opcode: u8, interp: &mut Interpreter, host: &mut H
         opcode::SHL => op2_u256_fn!(
             interp,
             bitwise::shl,
-            S::enabled(CONSTANTINOPLE) // EIP-145: Bitwise shifting instructions in EVM
+            interp.spec.enabled::<S>(CONSTANTINOPLE) // EIP-145: Bitwise shifting instructions in EVM
         ),
         opcode::SHR => op2_u256_fn!(
             interp,
             bitwise::shr,
-            S::enabled(CONSTANTINOPLE) // EIP-145: Bitwise shifting instructions in EVM
+            interp.spec.enabled::<S>(CONSTANTINOPLE) // EIP-145: Bitwise shifting instructions in EVM
         ),
         opcode::SAR => op2_u256_fn!(
             interp,
             bitwise::sar,
-            S::enabled(CONSTANTINOPLE) // EIP-145: Bitwise shifting instructions in EVM
+            interp.spec.enabled::<S>(CONSTANTINOPLE) // EIP-145: Bitwise shifting instructions in EVM
         ),
         opcode::SHA3 => system::sha3(interp),
 
@@ -230,11 +242,11 @@ pub fn eval<H: Host, S: Spec>(opcode: u8, interp: &mut Interpreter, host: &mut H
         opcode::SLOAD => host::sload::<H, S>(interp, host),
         opcode::SSTORE => host::sstore::<H, S>(interp, host),
         opcode::GAS => system::gas(interp),
-        opcode::LOG0 => host::log::<H, S>(interp, 0, host),
-        opcode::LOG1 => host::log::<H, S>(interp, 1, host),
-        opcode::LOG2 => host::log::<H, S>(interp, 2, host),
-        opcode::LOG3 => host::log::<H, S>(interp, 3, host),
-        opcode::LOG4 => host::log::<H, S>(interp, 4, host),
+        opcode::LOG0 => host::log::<H>(interp, 0, host),
+        opcode::LOG1 => host::log::<H>(interp, 1, host),
+        opcode::LOG2 => host::log::<H>(interp, 2, host),
+        opcode::LOG3 => host::log::<H>(interp, 3, host),
+        opcode::LOG4 => host::log::<H>(interp, 4, host),
         opcode::SELFDESTRUCT => host::selfdestruct::<H, S>(interp, host),
         opcode::CREATE => host::create::<H, S>(interp, false, host), //check
         opcode::CREATE2 => host::create::<H, S>(interp, true, host), //check
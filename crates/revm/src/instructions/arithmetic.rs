@@ -4,9 +4,35 @@ use super::i256::{i256_div, i256_mod};
 use core::{convert::TryInto, ops::Rem};
 use primitive_types::{U256, U512};
 
+/// Value of `v` if it fits in one limb. Operands of most opcodes are counters, offsets and
+/// amounts that fit, so multiplication, division and modulo are done with native integers.
+/// ADD, SUB and comparisons don't have fast path, their 256-bit versions are as fast.
+#[inline(always)]
+fn as_word(v: &U256) -> Option<u64> {
+    if (v.0[1] | v.0[2] | v.0[3]) == 0 {
+        Some(v.0[0])
+    } else {
+        None
+    }
+}
+
+#[inline(always)]
+fn as_words(op1: &U256, op2: &U256) -> Option<(u64, u64)> {
+    Some((as_word(op1)?, as_word(op2)?))
+}
+
+pub fn mul(op1: U256, op2: U256) -> U256 {
+    match as_words(&op1, &op2) {
+        Some((a, b)) => U256::from(a as u128 * b as u128),
+        None => op1.overflowing_mul(op2).0,
+    }
+}
+
 pub fn div(op1: U256, op2: U256) -> U256 {
     if op2.is_zero() {
         U256::zero()
+    } else if let Some((a, b)) = as_words(&op1, &op2) {
+        U256::from(a / b)
     } else {
         //op1 / op2
         super::i256::div_u256::div_mod(op1, op2).0
@@ -20,6 +46,8 @@ pub fn sdiv(op1: U256, op2: U256) -> U256 {
 pub fn rem(op1: U256, op2: U256) -> U256 {
     if op2.is_zero() {
         U256::zero()
+    } else if let Some((a, b)) = as_words(&op1, &op2) {
+        U256::from(a % b)
     } else {
         op1.rem(op2)
     }
@@ -36,6 +64,8 @@ pub fn smod(op1: U256, op2: U256) -> U256 {
 pub fn addmod(op1: U256, op2: U256, op3: U256) -> U256 {
     if op3.is_zero() {
         U256::zero()
+    } else if let (Some((a, b)), Some(n)) = (as_words(&op1, &op2), as_word(&op3)) {
+        U256::from((a as u128 + b as u128) % n as u128)
     } else {
         let op1: U512 = op1.into();
         let op2: U512 = op2.into();
@@ -49,6 +79,8 @@ pub fn addmod(op1: U256, op2: U256, op3: U256) -> U256 {
 pub fn mulmod(op1: U256, op2: U256, op3: U256) -> U256 {
     if op3.is_zero() {
         U256::zero()
+    } else if let (Some((a, b)), Some(n)) = (as_words(&op1, &op2), as_word(&op3)) {
+        U256::from((a as u128 * b as u128) % n as u128)
     } else {
         let op1: U512 = op1.into();
         let op2: U512 = op2.into();
@@ -76,7 +108,11 @@ pub fn exp(op1: U256, op2: U256) -> U256 {
 
 pub fn eval_exp<SPEC: Spec>(interp: &mut Interpreter) -> Return {
     pop!(interp, op1, op2);
-    gas_or_fail!(interp, gas::exp_cost::<SPEC>(op2));
+    gas_or_fail!(
+        interp,
+        gas::exp_cost(interp.spec.gas_table::<SPEC>(), op2),
+        Exp
+    );
     let ret = exp(op1, op2);
     push!(interp, ret);
 
@@ -119,7 +155,8 @@ pub fn signextend(op1: U256, op2: U256) -> U256 {
 mod tests {
     use alloc::vec;
 
-    use super::{signextend, U256};
+    use super::{addmod, div, mul, mulmod, rem, signextend, U256, U512};
+    use core::convert::TryInto;
 
     /// Test to ensure new (optimized) `signextend` implementation is equivalent to the previous
     /// implementation.
@@ -173,4 +210,48 @@ mod tests {
             ret
         }
     }
+
+    /// Fast paths of single limb operands give same results as 256-bit arithmetic.
+    #[test]
+    fn test_word_fast_paths() {
+        let mut values = vec![
+            U256::zero(),
+            U256::one(),
+            U256::from(u32::MAX),
+            U256::from(u64::MAX),
+            U256::from(u64::MAX) + 1,
+            U256::MAX,
+        ];
+        // xorshift, operands are mostly single limb with some wider ones.
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        for i in 0..200 {
+            let word = next() >> (next() % 64);
+            values.push(if i % 8 == 0 {
+                U256([word, next(), 0, 0])
+            } else {
+                U256::from(word)
+            });
+        }
+
+        let wide = |v: U256| -> U512 { v.into() };
+        let narrow = |v: U512| -> U256 { v.try_into().unwrap() };
+        for a in values.iter().copied() {
+            for b in values.iter().copied() {
+                assert_eq!(mul(a, b), a.overflowing_mul(b).0);
+                if b.is_zero() {
+                    continue;
+                }
+                assert_eq!(div(a, b), a / b);
+                assert_eq!(rem(a, b), a % b);
+                assert_eq!(addmod(a, a, b), narrow((wide(a) + wide(a)) % wide(b)));
+                assert_eq!(mulmod(a, a, b), narrow((wide(a) * wide(a)) % wide(b)));
+            }
+        }
+    }
 }
@@ -6,9 +6,9 @@ pub fn jump(interp: &mut Interpreter) -> Return {
     pop!(interp, dest);
     let dest = as_usize_or_fail!(dest, Return::InvalidJump);
     if interp.contract.is_valid_jump(dest) {
-        // Safety: In analysis we are checking create our jump table and we do check above to be
+        // In analysis we are checking create our jump table and we do check above to be
         // sure that jump is safe to execute.
-        interp.instruction_pointer = unsafe { interp.contract.bytecode.as_ptr().add(dest) };
+        interp.jump_to(dest);
         Return::Continue
     } else {
         Return::InvalidJump
@@ -21,9 +21,9 @@ pub fn jumpi(interp: &mut Interpreter) -> Return {
     if !value.is_zero() {
         let dest = as_usize_or_fail!(dest, Return::InvalidJump);
         if interp.contract.is_valid_jump(dest) {
-            // Safety: In analysis we are checking if jump is valid destination and
-            // this `if` makes this unsafe block safe.
-            interp.instruction_pointer = unsafe { interp.contract.bytecode.as_ptr().add(dest) };
+            // In analysis we are checking if jump is valid destination and
+            // this `if` makes this jump safe.
+            interp.jump_to(dest);
             Return::Continue
         } else {
             Return::InvalidJump
@@ -35,7 +35,7 @@ pub fn jumpi(interp: &mut Interpreter) -> Return {
 }
 
 pub fn jumpdest(interp: &mut Interpreter) -> Return {
-    gas!(interp, gas::JUMPDEST);
+    gas!(interp, gas::JUMPDEST, Opcode);
     interp.add_next_gas_block(interp.program_counter() - 1)
 }
 
@@ -62,7 +62,7 @@ pub fn ret(interp: &mut Interpreter) -> Return {
 pub fn revert<SPEC: Spec>(interp: &mut Interpreter) -> Return {
     // zero gas cost gas!(interp,gas::ZERO);
     // EIP-140: REVERT instruction
-    check!(SPEC::enabled(BYZANTIUM));
+    check!(interp.spec.enabled::<SPEC>(BYZANTIUM));
     pop!(interp, start, len);
     let len = as_usize_or_fail!(len, Return::OutOfGas);
     if len == 0 {
@@ -1,10 +1,6 @@
 use crate::{
-    alloc::vec::Vec,
-    gas::{self, COLD_ACCOUNT_ACCESS_COST, WARM_STORAGE_READ_COST},
-    interpreter::Interpreter,
-    return_ok, return_revert, CallContext, CallInputs, CallScheme, CreateInputs, CreateScheme,
-    Host, Return, Spec,
-    SpecId::*,
+    alloc::vec::Vec, gas, interpreter::Interpreter, return_ok, return_revert, CallContext,
+    CallInputs, CallScheme, CreateInputs, CreateScheme, Host, Return, Spec, SpecId::*, StateLoad,
     Transfer,
 };
 use bytes::Bytes;
@@ -17,19 +13,15 @@ pub fn balance<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H) -> R
     if ret.is_none() {
         return Return::FatalExternalError;
     }
-    let (balance, is_cold) = ret.unwrap();
+    let balance = ret.unwrap();
     gas!(
         interp,
-        if SPEC::enabled(ISTANBUL) {
-            // EIP-1884: Repricing for trie-size-dependent opcodes
-            gas::account_access_gas::<SPEC>(is_cold)
-        } else if SPEC::enabled(TANGERINE) {
-            400
-        } else {
-            20
-        }
+        gas::balance_cost(interp.spec.gas_table::<SPEC>(), balance.is_cold),
+        AccountAccess,
+        ColdAccess =
+            gas::cold_account_additional_cost(interp.spec.gas_table::<SPEC>(), balance.is_cold)
     );
-    push!(interp, balance);
+    push!(interp, balance.data);
 
     Return::Continue
 }
@@ -37,47 +29,48 @@ pub fn balance<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H) -> R
 pub fn selfbalance<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H) -> Return {
     // gas!(interp, gas::LOW);
     // EIP-1884: Repricing for trie-size-dependent opcodes
-    check!(SPEC::enabled(ISTANBUL));
-    let ret = host.balance(interp.contract.address);
+    check!(interp.spec.enabled::<SPEC>(ISTANBUL));
+    let ret = host.self_balance(interp.contract.address);
     if ret.is_none() {
         return Return::FatalExternalError;
     }
-    let (balance, _) = ret.unwrap();
-    push!(interp, balance);
+    push!(interp, ret.unwrap());
 
     Return::Continue
 }
 
 pub fn extcodesize<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H) -> Return {
     pop_address!(interp, address);
-    let ret = host.code(address);
+    let ret = host.code_size(address);
     if ret.is_none() {
         return Return::FatalExternalError;
     }
-    let (code, is_cold) = ret.unwrap();
-    if SPEC::enabled(BERLIN) && is_cold {
-        // WARM_STORAGE_READ_COST is already calculated in gas block
-        gas!(interp, COLD_ACCOUNT_ACCESS_COST - WARM_STORAGE_READ_COST);
-    }
+    let size = ret.unwrap();
+    gas!(
+        interp,
+        size.cold_account_additional_gas(interp.spec.gas_table::<SPEC>()),
+        ColdAccess
+    );
 
-    push!(interp, U256::from(code.len()));
+    push!(interp, U256::from(size.data));
 
     Return::Continue
 }
 
 pub fn extcodehash<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H) -> Return {
-    check!(SPEC::enabled(CONSTANTINOPLE)); // EIP-1052: EXTCODEHASH opcode
+    check!(interp.spec.enabled::<SPEC>(CONSTANTINOPLE)); // EIP-1052: EXTCODEHASH opcode
     pop_address!(interp, address);
     let ret = host.code_hash(address);
     if ret.is_none() {
         return Return::FatalExternalError;
     }
-    let (code_hash, is_cold) = ret.unwrap();
-    if SPEC::enabled(BERLIN) && is_cold {
-        // WARM_STORAGE_READ_COST is already calculated in gas block
-        gas!(interp, COLD_ACCOUNT_ACCESS_COST - WARM_STORAGE_READ_COST);
-    }
-    push_h256!(interp, code_hash);
+    let code_hash = ret.unwrap();
+    gas!(
+        interp,
+        code_hash.cold_account_additional_gas(interp.spec.gas_table::<SPEC>()),
+        ColdAccess
+    );
+    push_h256!(interp, code_hash.data);
 
     Return::Continue
 }
@@ -86,25 +79,32 @@ pub fn extcodecopy<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H)
     pop_address!(interp, address);
     pop!(interp, memory_offset, code_offset, len_u256);
 
-    let ret = host.code(address);
+    let len = as_usize_or_fail!(len_u256, Return::OutOfGas);
+    let code_offset = as_usize_saturated!(code_offset);
+    // only copied part of code is read.
+    let ret = host.code_range(address, code_offset, len);
     if ret.is_none() {
         return Return::FatalExternalError;
     }
-    let (code, is_cold) = ret.unwrap();
+    let StateLoad {
+        data: code,
+        is_cold,
+    } = ret.unwrap();
 
-    let len = as_usize_or_fail!(len_u256, Return::OutOfGas);
-    gas_or_fail!(interp, gas::extcodecopy_cost::<SPEC>(len as u64, is_cold));
+    gas_or_fail!(
+        interp,
+        gas::extcodecopy_cost(interp.spec.gas_table::<SPEC>(), len as u64, is_cold),
+        Copy,
+        ColdAccess = gas::cold_account_additional_cost(interp.spec.gas_table::<SPEC>(), is_cold)
+    );
     if len == 0 {
         return Return::Continue;
     }
     let memory_offset = as_usize_or_fail!(memory_offset, Return::OutOfGas);
-    let code_offset = min(as_usize_saturated!(code_offset), code.len());
     memory_resize!(interp, memory_offset, len);
 
     // Safety: set_data is unsafe function and memory_resize ensures us that it is safe to call it
-    interp
-        .memory
-        .set_data(memory_offset, code_offset, len, code.bytes());
+    interp.memory.set_data(memory_offset, 0, len, &code);
     Return::Continue
 }
 
@@ -135,14 +135,19 @@ pub fn sload<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H) -> Ret
     if ret.is_none() {
         return Return::FatalExternalError;
     }
-    let (value, is_cold) = ret.unwrap();
-    gas!(interp, gas::sload_cost::<SPEC>(is_cold));
-    push!(interp, value);
+    let value = ret.unwrap();
+    gas!(
+        interp,
+        value.sload_gas(interp.spec.gas_table::<SPEC>()),
+        StorageRead,
+        ColdAccess = gas::cold_sload_cost(interp.spec.gas_table::<SPEC>(), value.is_cold)
+    );
+    push!(interp, value.data);
     Return::Continue
 }
 
 pub fn sstore<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H) -> Return {
-    check!(!SPEC::IS_STATIC_CALL);
+    check_static!(interp);
 
     pop!(interp, index, value);
     let ret = host.sstore(interp.contract.address, index, value);
@@ -150,20 +155,35 @@ pub fn sstore<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H) -> Re
         return Return::FatalExternalError;
     }
     let (original, old, new, is_cold) = ret.unwrap();
-    gas_or_fail!(interp, {
-        let remaining_gas = interp.gas.remaining();
-        gas::sstore_cost::<SPEC>(original, old, new, remaining_gas, is_cold)
-    });
-    refund!(interp, gas::sstore_refund::<SPEC>(original, old, new));
+    gas_or_fail!(
+        interp,
+        {
+            let remaining_gas = interp.gas.remaining();
+            gas::sstore_cost(
+                interp.spec.gas_table::<SPEC>(),
+                original,
+                old,
+                new,
+                remaining_gas,
+                is_cold,
+            )
+        },
+        StorageWrite,
+        ColdAccess = gas::cold_sstore_cost(interp.spec.gas_table::<SPEC>(), is_cold)
+    );
+    refund!(
+        interp,
+        gas::sstore_refund(interp.spec.gas_table::<SPEC>(), original, old, new)
+    );
     interp.add_next_gas_block(interp.program_counter() - 1)
 }
 
-pub fn log<H: Host, SPEC: Spec>(interp: &mut Interpreter, n: u8, host: &mut H) -> Return {
-    check!(!SPEC::IS_STATIC_CALL);
+pub fn log<H: Host>(interp: &mut Interpreter, n: u8, host: &mut H) -> Return {
+    check_static!(interp);
 
     pop!(interp, offset, len);
     let len = as_usize_or_fail!(len, Return::OutOfGas);
-    gas_or_fail!(interp, gas::log_cost(n, len as u64));
+    gas_or_fail!(interp, gas::log_cost(n, len as u64), Log);
     let data = if len == 0 {
         Bytes::new()
     } else {
@@ -189,7 +209,7 @@ pub fn log<H: Host, SPEC: Spec>(interp: &mut Interpreter, n: u8, host: &mut H) -
 }
 
 pub fn selfdestruct<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H) -> Return {
-    check!(!SPEC::IS_STATIC_CALL);
+    check_static!(interp);
     pop_address!(interp, target);
 
     let res = host.selfdestruct(interp.contract.address, target);
@@ -199,10 +219,14 @@ pub fn selfdestruct<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H)
     let res = res.unwrap();
 
     // EIP-3529: Reduction in refunds
-    if !SPEC::enabled(LONDON) && !res.previously_destroyed {
-        refund!(interp, gas::SELFDESTRUCT)
+    if !res.previously_destroyed {
+        refund!(interp, interp.spec.gas_table::<SPEC>().selfdestruct_refund)
     }
-    gas!(interp, gas::selfdestruct_cost::<SPEC>(res));
+    gas!(
+        interp,
+        gas::selfdestruct_cost(interp.spec.gas_table::<SPEC>(), res),
+        parts = gas::selfdestruct_cost_parts(interp.spec.gas_table::<SPEC>(), res)
+    );
 
     Return::SelfDestruct
 }
@@ -212,13 +236,13 @@ pub fn create<H: Host, SPEC: Spec>(
     is_create2: bool,
     host: &mut H,
 ) -> Return {
-    check!(!SPEC::IS_STATIC_CALL);
+    check_static!(interp);
     if is_create2 {
         // EIP-1014: Skinny CREATE2
-        check!(SPEC::enabled(PETERSBURG));
+        check!(interp.spec.enabled::<SPEC>(CONSTANTINOPLE));
     }
 
-    interp.return_data_buffer = Bytes::new();
+    interp.return_data_buffer.clear();
 
     pop!(interp, value, code_offset, len);
     let len = as_usize_or_fail!(len, Return::OutOfGas);
@@ -233,20 +257,20 @@ pub fn create<H: Host, SPEC: Spec>(
 
     let scheme = if is_create2 {
         pop!(interp, salt);
-        gas_or_fail!(interp, gas::create2_cost(len));
+        gas_or_fail!(
+            interp,
+            gas::create2_cost(len),
+            Hashing,
+            Create = gas::CREATE
+        );
         CreateScheme::Create2 { salt }
     } else {
-        gas!(interp, gas::CREATE);
+        gas!(interp, gas::CREATE, Create);
         CreateScheme::Create
     };
 
-    let mut gas_limit = interp.gas().remaining();
-
     // EIP-150: Gas cost changes for IO-heavy operations
-    if SPEC::enabled(TANGERINE) {
-        // take remaining gas and deduce l64 part of it.
-        gas_limit -= gas_limit / 64
-    }
+    let gas_limit = gas::create_gas(interp.spec.gas_table::<SPEC>(), interp.gas().remaining());
     gas!(interp, gas_limit);
 
     let mut create_input = CreateInputs {
@@ -258,7 +282,18 @@ pub fn create<H: Host, SPEC: Spec>(
     };
 
     let (return_reason, address, gas, return_data) = host.create::<SPEC>(&mut create_input);
-    interp.return_data_buffer = return_data;
+    #[cfg(feature = "gas_accounting")]
+    interp.gas.accounting_mut().add_frame(
+        &gas,
+        matches!(
+            return_reason,
+            return_ok!() | return_revert!() | Return::NonceOverflow
+        ),
+    );
+    let limit = host.env().cfg.limit_return_data_size;
+    if let Err(err) = interp.return_data_buffer.set(return_data, limit) {
+        return err;
+    }
 
     match return_reason {
         return_ok!() => {
@@ -266,7 +301,8 @@ pub fn create<H: Host, SPEC: Spec>(
             interp.gas.erase_cost(gas.remaining());
             interp.gas.record_refund(gas.refunded());
         }
-        return_revert!() => {
+        // nonce overflow of created address fails deployment without consuming its gas.
+        return_revert!() | Return::NonceOverflow => {
             push_h256!(interp, H256::default());
             interp.gas.erase_cost(gas.remaining());
         }
@@ -284,11 +320,11 @@ pub fn call<H: Host, SPEC: Spec>(
     host: &mut H,
 ) -> Return {
     match scheme {
-        CallScheme::DelegateCall => check!(SPEC::enabled(HOMESTEAD)), // EIP-7: DELEGATECALL
-        CallScheme::StaticCall => check!(SPEC::enabled(BYZANTIUM)), // EIP-214: New opcode STATICCALL
+        CallScheme::DelegateCall => check!(interp.spec.enabled::<SPEC>(HOMESTEAD)), // EIP-7: DELEGATECALL
+        CallScheme::StaticCall => check!(interp.spec.enabled::<SPEC>(BYZANTIUM)), // EIP-214: New opcode STATICCALL
         _ => (),
     }
-    interp.return_data_buffer = Bytes::new();
+    interp.return_data_buffer.clear();
 
     pop!(interp, local_gas_limit);
     pop_address!(interp, to);
@@ -305,7 +341,7 @@ pub fn call<H: Host, SPEC: Spec>(
         }
         CallScheme::Call => {
             pop!(interp, value);
-            if SPEC::IS_STATIC_CALL && !value.is_zero() {
+            if interp.is_static && !value.is_zero() {
                 return Return::CallNotAllowedInsideStatic;
             }
             value
@@ -319,7 +355,7 @@ pub fn call<H: Host, SPEC: Spec>(
     let input = if in_len != 0 {
         let in_offset = as_usize_or_fail!(in_offset, Return::OutOfGas);
         memory_resize!(interp, in_offset, in_len);
-        Bytes::copy_from_slice(interp.memory.get_slice(in_offset, in_len))
+        call_input(interp, in_offset, in_len)
     } else {
         Bytes::new()
     };
@@ -383,51 +419,62 @@ pub fn call<H: Host, SPEC: Spec>(
     if res.is_none() {
         return Return::FatalExternalError;
     }
-    let (is_cold, exist) = res.unwrap();
+    let StateLoad {
+        data: exist,
+        is_cold,
+    } = res.unwrap();
     let is_new = !exist;
 
+    let is_call_or_callcode = matches!(scheme, CallScheme::Call | CallScheme::CallCode);
+    let is_call_or_staticcall = matches!(scheme, CallScheme::Call | CallScheme::StaticCall);
     gas!(
         interp,
-        gas::call_cost::<SPEC>(
+        gas::call_cost(
+            interp.spec.gas_table::<SPEC>(),
             value,
             is_new,
             is_cold,
-            matches!(scheme, CallScheme::Call | CallScheme::CallCode),
-            matches!(scheme, CallScheme::Call | CallScheme::StaticCall),
+            is_call_or_callcode,
+            is_call_or_staticcall
+        ),
+        parts = gas::call_cost_parts(
+            interp.spec.gas_table::<SPEC>(),
+            value,
+            is_new,
+            is_cold,
+            is_call_or_callcode,
+            is_call_or_staticcall
         )
     );
 
-    // take l64 part of gas_limit
-    let mut gas_limit = if SPEC::enabled(TANGERINE) {
-        //EIP-150: Gas cost changes for IO-heavy operations
-        let gas = interp.gas().remaining();
-        min(gas - gas / 64, local_gas_limit)
-    } else {
-        local_gas_limit
-    };
-
-    gas!(interp, gas_limit);
-
-    // add call stipend if there is value to be transferred.
-    if matches!(scheme, CallScheme::Call | CallScheme::CallCode) && !transfer.value.is_zero() {
-        gas_limit = gas_limit.saturating_add(gas::CALL_STIPEND);
-    }
-    let is_static = matches!(scheme, CallScheme::StaticCall);
-
+    // EIP-150: Gas cost changes for IO-heavy operations
+    // call stipend is added if there is value to be transferred.
+    let (forwarded, gas_limit) = gas::call_gas(
+        interp.spec.gas_table::<SPEC>(),
+        interp.gas().remaining(),
+        local_gas_limit,
+        is_call_or_callcode && !transfer.value.is_zero(),
+    );
+    gas!(interp, forwarded);
     let mut call_input = CallInputs {
         contract: to,
         transfer,
         input,
         gas_limit,
         context,
+        // static context is inherited by all nested calls.
+        is_static: interp.is_static || scheme == CallScheme::StaticCall,
     };
-    // CALL CONTRACT, with static or ordinary spec.
-    let (reason, gas, return_data) = if is_static {
-        host.call::<SPEC::STATIC>(&mut call_input)
-    } else {
-        host.call::<SPEC>(&mut call_input)
-    };
-    interp.return_data_buffer = return_data;
+    let (reason, gas, return_data) = host.call::<SPEC>(&mut call_input);
+    #[cfg(feature = "gas_accounting")]
+    interp
+        .gas
+        .accounting_mut()
+        .add_frame(&gas, matches!(reason, return_ok!() | return_revert!()));
+    let limit = host.env().cfg.limit_return_data_size;
+    if let Err(err) = interp.return_data_buffer.set(return_data, limit) {
+        return err;
+    }
 
     let target_len = min(out_len, interp.return_data_buffer.len());
 
@@ -438,14 +485,14 @@ pub fn call<H: Host, SPEC: Spec>(
             interp.gas.record_refund(gas.refunded());
             interp
                 .memory
-                .set(out_offset, &interp.return_data_buffer[..target_len]);
+                .set(out_offset, &interp.return_data_buffer.data()[..target_len]);
             push!(interp, U256::one());
         }
         return_revert!() => {
             interp.gas.erase_cost(gas.remaining());
             interp
                 .memory
-                .set(out_offset, &interp.return_data_buffer[..target_len]);
+                .set(out_offset, &interp.return_data_buffer.data()[..target_len]);
             push!(interp, U256::zero());
         }
         Return::FatalExternalError => return Return::FatalExternalError,
@@ -455,3 +502,15 @@ pub fn call<H: Host, SPEC: Spec>(
     }
     interp.add_next_gas_block(interp.program_counter() - 1)
 }
+
+/// Input of call from memory region. Proxies forward their own input or output of previous
+/// call, these are shared instead of copied if memory region is equal to them.
+fn call_input(interp: &Interpreter, offset: usize, len: usize) -> Bytes {
+    let input = interp.memory.get_slice(offset, len);
+    for shared in [&interp.contract.input, interp.return_data_buffer.data()] {
+        if shared.len() == len && shared[..] == *input {
+            return shared.clone();
+        }
+    }
+    Bytes::copy_from_slice(input)
+}
@@ -4,8 +4,8 @@ use primitive_types::H256;
 pub fn chainid<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H) -> Return {
     // gas!(interp, gas::BASE);
     // EIP-1344: ChainID opcode
-    check!(SPEC::enabled(ISTANBUL));
-    push!(interp, host.env().cfg.chain_id);
+    check!(interp.spec.enabled::<SPEC>(ISTANBUL));
+    push!(interp, host.env().chain_id());
     Return::Continue
 }
 
@@ -48,14 +48,14 @@ pub fn gasprice<H: Host>(interp: &mut Interpreter, host: &mut H) -> Return {
 pub fn basefee<H: Host, SPEC: Spec>(interp: &mut Interpreter, host: &mut H) -> Return {
     // gas!(interp, gas::BASE);
     // EIP-3198: BASEFEE opcode
-    check!(SPEC::enabled(LONDON));
+    check!(interp.spec.enabled::<SPEC>(LONDON));
     push!(interp, host.env().block.basefee);
     Return::Continue
 }
 
 pub fn origin<H: Host>(interp: &mut Interpreter, host: &mut H) -> Return {
     // gas!(interp, gas::BASE);
-    let ret = H256::from(host.env().tx.caller);
+    let ret = H256::from(host.env().origin());
     push_h256!(interp, ret);
     Return::Continue
 }
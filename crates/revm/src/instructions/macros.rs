@@ -8,14 +8,56 @@ macro_rules! check {
     };
 }
 
+macro_rules! check_static {
+    ($interp:expr) => {
+        if $interp.is_static {
+            return Return::StateChangeDuringStaticCall;
+        }
+    };
+}
+
+/// Charge gas. With `gas_accounting` feature charge is added to [crate::gas::GasCategory] that
+/// follows the cost, optionally with `Other = part` of it added to other category. Charge
+/// without category is gas forwarded to new frame, it is accounted by that frame.
 macro_rules! gas {
     ($interp:expr, $gas:expr) => {
         if crate::USE_GAS {
-            if !$interp.gas.record_cost(($gas)) {
+            if !$interp.gas.record_cost($gas) {
                 return Return::OutOfGas;
             }
         }
     };
+    ($interp:expr, $gas:expr, $category:ident $(, $other:ident = $part:expr)?) => {
+        if crate::USE_GAS {
+            let gas = $gas;
+            if !$interp.gas.record_cost(gas) {
+                return Return::OutOfGas;
+            }
+            #[cfg(feature = "gas_accounting")]
+            {
+                let accounting = $interp.gas.accounting_mut();
+                #[allow(unused_mut)]
+                let mut rest = gas;
+                $(
+                    let part = $part;
+                    accounting.add(crate::gas::GasCategory::$other, part);
+                    rest -= part;
+                )?
+                accounting.add(crate::gas::GasCategory::$category, rest);
+            }
+        }
+    };
+    ($interp:expr, $gas:expr, parts = $parts:expr) => {
+        if crate::USE_GAS {
+            if !$interp.gas.record_cost($gas) {
+                return Return::OutOfGas;
+            }
+            #[cfg(feature = "gas_accounting")]
+            for (category, part) in $parts {
+                $interp.gas.accounting_mut().add(category, part);
+            }
+        }
+    };
 }
 
 macro_rules! refund {
@@ -27,22 +69,25 @@ macro_rules! refund {
 }
 
 macro_rules! gas_or_fail {
-    ($interp:expr, $gas:expr) => {
+    ($interp:expr, $gas:expr $(, $category:ident $(, $other:ident = $part:expr)?)?) => {
         if crate::USE_GAS {
             match $gas {
-                Some(gas_used) => gas!($interp, gas_used),
+                Some(gas_used) => gas!($interp, gas_used $(, $category $(, $other = $part)?)?),
                 None => return Return::OutOfGas,
             }
         }
     };
 }
 
+/// Expand memory to cover `len` bytes at `offset` and charge gas for it. Offset overflowing
+/// usize is out of gas as its gas can't be paid. Callers must not expand memory if `len` is zero.
 macro_rules! memory_resize {
     ($interp:expr, $offset:expr, $len:expr) => {{
         let len: usize = $len;
         let offset: usize = $offset;
-        if let Some(new_size) =
-            crate::interpreter::memory::next_multiple_of_32(offset.saturating_add(len))
+        if let Some(new_size) = offset
+            .checked_add(len)
+            .and_then(crate::interpreter::memory::next_multiple_of_32)
         {
             #[cfg(feature = "memory_limit")]
             if new_size > ($interp.memory_limit as usize) {
@@ -267,9 +312,10 @@ macro_rules! op3_u256_fn {
     }};
 }
 
+/// Value as usize or usize::MAX if it doesn't fit, on 32-bit targets too.
 macro_rules! as_usize_saturated {
     ( $v:expr ) => {{
-        if $v.0[1] != 0 || $v.0[2] != 0 || $v.0[3] != 0 {
+        if $v.0[1] != 0 || $v.0[2] != 0 || $v.0[3] != 0 || $v.0[0] > usize::MAX as u64 {
             usize::MAX
         } else {
             $v.0[0] as usize
@@ -277,17 +323,15 @@ macro_rules! as_usize_saturated {
     }};
 }
 
+/// Value as usize or return from instruction if it doesn't fit. Values that don't fit are
+/// memory offsets and lengths whose gas can't be paid, so default reason is OutOfGas.
 macro_rules! as_usize_or_fail {
     ( $v:expr ) => {{
-        if $v.0[1] != 0 || $v.0[2] != 0 || $v.0[3] != 0 {
-            return Return::OutOfGas;
-        }
-
-        $v.0[0] as usize
+        as_usize_or_fail!($v, Return::OutOfGas)
     }};
 
     ( $v:expr, $reason:expr ) => {{
-        if $v.0[1] != 0 || $v.0[2] != 0 || $v.0[3] != 0 {
+        if $v.0[1] != 0 || $v.0[2] != 0 || $v.0[3] != 0 || $v.0[0] > usize::MAX as u64 {
             return $reason;
         }
 
@@ -1,5 +1,5 @@
 use crate::gas;
-use crate::SpecId;
+use crate::{SpecFlags, SpecId};
 
 pub struct OpCode(u8);
 
@@ -169,6 +169,54 @@ impl OpCode {
     }
 }
 
+/// Set of opcodes, stored as 256 bit mask.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct OpcodeSet([u64; 4]);
+
+impl Default for OpcodeSet {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl OpcodeSet {
+    pub const ALL: OpcodeSet = OpcodeSet([u64::MAX; 4]);
+    pub const NONE: OpcodeSet = OpcodeSet([0; 4]);
+
+    pub const fn new(opcodes: &[u8]) -> Self {
+        let mut set = Self::NONE;
+        let mut i = 0;
+        while i < opcodes.len() {
+            set = set.with(opcodes[i]);
+            i += 1;
+        }
+        set
+    }
+
+    pub const fn with(mut self, opcode: u8) -> Self {
+        self.0[(opcode >> 6) as usize] |= 1 << (opcode & 63);
+        self
+    }
+
+    pub const fn union(mut self, other: OpcodeSet) -> Self {
+        let mut i = 0;
+        while i < 4 {
+            self.0[i] |= other.0[i];
+            i += 1;
+        }
+        self
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0[0] | self.0[1] | self.0[2] | self.0[3] == 0
+    }
+
+    #[inline(always)]
+    pub const fn contains(&self, opcode: u8) -> bool {
+        self.0[(opcode >> 6) as usize] & (1 << (opcode & 63)) != 0
+    }
+}
+
 const JUMP_MASK: u32 = 0x80000000;
 const GAS_BLOCK_END_MASK: u32 = 0x40000000;
 const IS_PUSH_MASK: u32 = 0x20000000;
@@ -231,7 +279,10 @@ impl OpInfo {
 
 macro_rules! gas_opcodee {
     ($name:ident, $spec_id:expr) => {
-        const $name: &'static [OpInfo; 256] = &[
+const $name: &'static [OpInfo; 256] = &gas_opcodee!(@array SpecId::enabled, $spec_id);
+    };
+    (@array $enabled:path, $spec:expr) => {
+        [
             /* 0x00  STOP */ OpInfo::gas_block_end(0),
             /* 0x01  ADD */ OpInfo::gas(gas::VERYLOW),
             /* 0x02  MUL */ OpInfo::gas(gas::LOW),
@@ -260,19 +311,19 @@ macro_rules! gas_opcodee {
             /* 0x19  NOT */ OpInfo::gas(gas::VERYLOW),
             /* 0x1a  BYTE */ OpInfo::gas(gas::VERYLOW),
             /* 0x1b  SHL */
-            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::CONSTANTINOPLE) {
+            OpInfo::gas(if $enabled($spec, SpecId::CONSTANTINOPLE) {
                 gas::VERYLOW
             } else {
                 0
             }),
             /* 0x1c  SHR */
-            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::CONSTANTINOPLE) {
+            OpInfo::gas(if $enabled($spec, SpecId::CONSTANTINOPLE) {
                 gas::VERYLOW
             } else {
                 0
             }),
             /* 0x1d  SAR */
-            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::CONSTANTINOPLE) {
+            OpInfo::gas(if $enabled($spec, SpecId::CONSTANTINOPLE) {
                 gas::VERYLOW
             } else {
                 0
@@ -307,34 +358,34 @@ macro_rules! gas_opcodee {
             /* 0x39  CODECOPY */ OpInfo::dynamic_gas(),
             /* 0x3a  GASPRICE */ OpInfo::gas(gas::BASE),
             /* 0x3b  EXTCODESIZE */
-            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::BERLIN) {
+            OpInfo::gas(if $enabled($spec, SpecId::BERLIN) {
                 gas::WARM_STORAGE_READ_COST // add only part of gas
-            } else if SpecId::enabled($spec_id, SpecId::TANGERINE) {
+            } else if $enabled($spec, SpecId::TANGERINE) {
                 700
             } else {
                 20
             }),
             /* 0x3c  EXTCODECOPY */
-            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::BERLIN) {
+            OpInfo::gas(if $enabled($spec, SpecId::BERLIN) {
                 gas::WARM_STORAGE_READ_COST // add only part of gas
-            } else if SpecId::enabled($spec_id, SpecId::TANGERINE) {
+            } else if $enabled($spec, SpecId::TANGERINE) {
                 700
             } else {
                 20
             }),
             /* 0x3d  RETURNDATASIZE */
-            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::BYZANTIUM) {
+            OpInfo::gas(if $enabled($spec, SpecId::BYZANTIUM) {
                 gas::BASE
             } else {
                 0
             }),
             /* 0x3e  RETURNDATACOPY */ OpInfo::dynamic_gas(),
             /* 0x3f  EXTCODEHASH */
-            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::BERLIN) {
+            OpInfo::gas(if $enabled($spec, SpecId::BERLIN) {
                 gas::WARM_STORAGE_READ_COST // add only part of gas
-            } else if SpecId::enabled($spec_id, SpecId::ISTANBUL) {
+            } else if $enabled($spec, SpecId::ISTANBUL) {
                 700
-            } else if SpecId::enabled($spec_id, SpecId::PETERSBURG) {
+            } else if $enabled($spec, SpecId::PETERSBURG) {
                 // constantinople
                 400
             } else {
@@ -347,19 +398,19 @@ macro_rules! gas_opcodee {
             /* 0x44  DIFFICULTY */ OpInfo::gas(gas::BASE),
             /* 0x45  GASLIMIT */ OpInfo::gas(gas::BASE),
             /* 0x46  CHAINID */
-            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::ISTANBUL) {
+            OpInfo::gas(if $enabled($spec, SpecId::ISTANBUL) {
                 gas::BASE
             } else {
                 0
             }),
             /* 0x47  SELFBALANCE */
-            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::ISTANBUL) {
+            OpInfo::gas(if $enabled($spec, SpecId::ISTANBUL) {
                 gas::LOW
             } else {
                 0
             }),
             /* 0x48  BASEFEE */
-            OpInfo::gas(if SpecId::enabled($spec_id, SpecId::LONDON) {
+            OpInfo::gas(if $enabled($spec, SpecId::LONDON) {
                 gas::BASE
             } else {
                 0
@@ -549,10 +600,15 @@ macro_rules! gas_opcodee {
             /* 0xfd  REVERT */ OpInfo::gas_block_end(0),
             /* 0xfe  INVALID */ OpInfo::gas_block_end(0),
             /* 0xff  SELFDESTRUCT */ OpInfo::gas_block_end(0),
-        ];
+        ]
     };
 }
 
+/// Opcode gas table of runtime defined spec. See [crate::CustomSpec].
+pub const fn custom_opcode_gas(flags: SpecFlags) -> [OpInfo; 256] {
+    gas_opcodee!(@array SpecFlags::enabled, flags)
+}
+
 pub const fn spec_opcode_gas(spec_id: SpecId) -> &'static [OpInfo; 256] {
     match spec_id {
         SpecId::FRONTIER => {
@@ -619,6 +675,10 @@ pub const fn spec_opcode_gas(spec_id: SpecId) -> &'static [OpInfo; 256] {
             gas_opcodee!(MERGE, SpecId::MERGE);
             MERGE
         }
+        SpecId::CANCUN => {
+            gas_opcodee!(CANCUN, SpecId::CANCUN);
+            CANCUN
+        }
         SpecId::LATEST => {
             gas_opcodee!(LATEST, SpecId::LATEST);
             LATEST
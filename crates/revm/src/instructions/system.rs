@@ -8,7 +8,7 @@ use sha3::{Digest, Keccak256};
 pub fn sha3(interp: &mut Interpreter) -> Return {
     pop!(interp, from, len);
     let len = as_usize_or_fail!(len, Return::OutOfGas);
-    gas_or_fail!(interp, gas::sha3_cost(len as u64));
+    gas_or_fail!(interp, gas::sha3_cost(len as u64), Hashing);
     let h256 = if len == 0 {
         KECCAK_EMPTY
     } else {
@@ -45,7 +45,7 @@ pub fn codesize(interp: &mut Interpreter) -> Return {
 pub fn codecopy(interp: &mut Interpreter) -> Return {
     pop!(interp, memory_offset, code_offset, len);
     let len = as_usize_or_fail!(len, Return::OutOfGas);
-    gas_or_fail!(interp, gas::verylowcopy_cost(len as u64));
+    gas_or_fail!(interp, gas::verylowcopy_cost(len as u64), Copy);
     if len == 0 {
         return Return::Continue;
     }
@@ -99,7 +99,7 @@ pub fn callvalue(interp: &mut Interpreter) -> Return {
 pub fn calldatacopy(interp: &mut Interpreter) -> Return {
     pop!(interp, memory_offset, data_offset, len);
     let len = as_usize_or_fail!(len, Return::OutOfGas);
-    gas_or_fail!(interp, gas::verylowcopy_cost(len as u64));
+    gas_or_fail!(interp, gas::verylowcopy_cost(len as u64), Copy);
     if len == 0 {
         return Return::Continue;
     }
@@ -117,7 +117,7 @@ pub fn calldatacopy(interp: &mut Interpreter) -> Return {
 pub fn returndatasize<SPEC: Spec>(interp: &mut Interpreter) -> Return {
     // gas!(interp, gas::BASE);
     // EIP-211: New opcodes: RETURNDATASIZE and RETURNDATACOPY
-    check!(SPEC::enabled(BYZANTIUM));
+    check!(interp.spec.enabled::<SPEC>(BYZANTIUM));
     let size = U256::from(interp.return_data_buffer.len());
     push!(interp, size);
     Return::Continue
@@ -125,21 +125,22 @@ pub fn returndatasize<SPEC: Spec>(interp: &mut Interpreter) -> Return {
 
 pub fn returndatacopy<SPEC: Spec>(interp: &mut Interpreter) -> Return {
     // EIP-211: New opcodes: RETURNDATASIZE and RETURNDATACOPY
-    check!(SPEC::enabled(BYZANTIUM));
+    check!(interp.spec.enabled::<SPEC>(BYZANTIUM));
     pop!(interp, memory_offset, offset, len);
     let len = as_usize_or_fail!(len, Return::OutOfGas);
-    gas_or_fail!(interp, gas::verylowcopy_cost(len as u64));
-    let memory_offset = as_usize_or_fail!(memory_offset, Return::OutOfGas);
+    gas_or_fail!(interp, gas::verylowcopy_cost(len as u64), Copy);
     let data_offset = as_usize_saturated!(offset);
-    memory_resize!(interp, memory_offset, len);
-    let (data_end, overflow) = data_offset.overflowing_add(len);
-    if overflow || data_end > interp.return_data_buffer.len() {
-        return Return::OutOfOffset;
+    // reading out of return data fails even if nothing is copied.
+    let data = match interp.return_data_buffer.slice(data_offset, len) {
+        Ok(data) => data,
+        Err(err) => return err,
+    };
+    if len == 0 {
+        return Return::Continue;
     }
-    interp.memory.set(
-        memory_offset,
-        &interp.return_data_buffer[data_offset..data_end],
-    );
+    let memory_offset = as_usize_or_fail!(memory_offset, Return::OutOfGas);
+    memory_resize!(interp, memory_offset, len);
+    interp.memory.set(memory_offset, data);
     Return::Continue
 }
 
@@ -148,3 +149,87 @@ pub fn gas(interp: &mut Interpreter) -> Return {
     push!(interp, U256::from(interp.gas.remaining()));
     interp.add_next_gas_block(interp.program_counter() - 1)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{opcode::*, AccountInfo, Bytecode, InMemoryDB, Return, TransactTo};
+    use primitive_types::{H160, U256};
+
+    /// PUSH32 of `value`.
+    fn push(value: U256) -> Vec<u8> {
+        let mut code = vec![PUSH32; 33];
+        value.to_big_endian(&mut code[1..]);
+        code
+    }
+
+    /// Run `code` with its arguments pushed in reverse order, so that first argument is on top
+    /// of the stack. Returns exit reason and gas used.
+    fn run(args: &[U256], op: u8) -> (Return, u64) {
+        let mut code: Vec<u8> = args.iter().rev().flat_map(|arg| push(*arg)).collect();
+        code.extend([op, STOP]);
+        let contract = H160::from_low_u64_be(0xa);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code.into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 1_000_000;
+        let result = evm.transact().0;
+        (result.exit_reason, result.gas_used)
+    }
+
+    /// Offsets and lengths around 2^64 fail with out of gas instead of wrapping or panicking.
+    /// Cases are taken from fuzzer findings of other clients.
+    #[test]
+    fn test_memory_offset_overflow() {
+        let u64_max = U256::from(u64::MAX);
+        let oog = Return::OutOfGas;
+        let cases = [
+            // offset + len overflows u64.
+            (vec![u64_max, 0.into(), 1.into()], CALLDATACOPY, oog),
+            (vec![U256::one() << 63, U256::one() << 63], SHA3, oog),
+            (vec![u64_max - 31, 1.into()], MSTORE, oog),
+            (vec![u64_max, 1.into()], MSTORE8, oog),
+            (vec![U256::MAX], MLOAD, oog),
+            // offset doesn't fit u64.
+            (vec![u64_max + 1, 0.into(), 1.into()], CODECOPY, oog),
+            (vec![u64_max + 1, 1.into()], RETURN, oog),
+            // copy cost of length overflows.
+            (vec![0.into(), 0.into(), u64_max], CALLDATACOPY, oog),
+            (vec![0.into(), 0.into(), u64_max], RETURNDATACOPY, oog),
+            (vec![0.into(), u64_max], LOG0, oog),
+            // memory that fits in usize but its gas can't be paid.
+            (vec![U256::one() << 32, 1.into()], MSTORE8, oog),
+            (vec![0.into(), u64_max - 32], REVERT, oog),
+        ];
+        for (args, op, reason) in cases {
+            assert_eq!(run(&args, op).0, reason, "opcode {:#x}", op);
+        }
+    }
+
+    /// Zero length copies don't expand memory no matter how large the offset is.
+    #[test]
+    fn test_zero_length_offsets() {
+        let (_, base) = run(&[], STOP);
+        // gas of pushes, copy and STOP without memory expansion.
+        let copy_gas = |args: usize| base + args as u64 * 3 + 3;
+        for op in [CALLDATACOPY, CODECOPY, RETURNDATACOPY] {
+            let args = [U256::MAX, 0.into(), 0.into()];
+            assert_eq!(run(&args, op), (Return::Stop, copy_gas(3)));
+        }
+        // data offset past end of calldata and code copies zeros.
+        let args = [0.into(), U256::MAX, 32.into()];
+        assert_eq!(run(&args, CALLDATACOPY).0, Return::Stop);
+        assert_eq!(run(&args, CODECOPY).0, Return::Stop);
+        assert_eq!(run(&[U256::MAX, 0.into()], LOG0).0, Return::Stop);
+        assert_eq!(run(&[U256::MAX, 0.into()], SHA3).0, Return::Stop);
+        assert_eq!(run(&[U256::MAX, 0.into()], RETURN).0, Return::Return);
+
+        // reading out of return data fails even if nothing is copied.
+        let args = [0.into(), 1.into(), 0.into()];
+        assert_eq!(run(&args, RETURNDATACOPY).0, Return::OutOfOffset);
+    }
+}
@@ -1,16 +1,18 @@
 pub mod bytecode;
 mod contract;
 pub(crate) mod memory;
+mod return_data;
 mod stack;
 
 pub use bytecode::{Bytecode, BytecodeLocked, BytecodeState};
 pub use contract::Contract;
 pub use memory::Memory;
+pub use return_data::ReturnData;
 pub use stack::Stack;
 
 use crate::{
     instructions::{eval, Return},
-    Gas, Host, Spec, USE_GAS,
+    Gas, Host, OpcodeSet, Spec, SpecRules, USE_GAS,
 };
 use bytes::Bytes;
 use core::ops::Range;
@@ -21,7 +23,11 @@ pub const CALL_STACK_LIMIT: u64 = 1024;
 pub struct Interpreter {
     /// Contract information and invoking data
     pub contract: Contract,
-    /// Instruction pointer.
+    /// Instruction pointer. It points into padded bytecode of the contract.
+    ///
+    /// By default it is read and moved with unchecked pointer arithmetic that relies on padding
+    /// added by analysis, with bounds verified only by debug assertions. With `checked_interpreter`
+    /// feature every access is bounds checked and done without `unsafe`.
     pub instruction_pointer: *const u8,
     /// Memory.
     pub memory: Memory,
@@ -30,28 +36,49 @@ pub struct Interpreter {
     /// left gas. Memory gas can be found in Memory field.
     pub gas: Gas,
     /// After call returns, its return data is saved here.
-    pub return_data_buffer: Bytes,
+    pub return_data_buffer: ReturnData,
     /// Return value.
     pub return_range: Range<usize>,
+    /// Whether this frame is executed in static context. State changes are not allowed in it.
+    pub is_static: bool,
+    /// Hardforks and gas prices of executed spec. Set by EVM from [crate::CfgEnv::custom_spec]
+    /// when [crate::CustomSpec] is executed.
+    pub spec: SpecRules,
+    /// Opcodes for which inspector `step` and `step_end` are called, see
+    /// [crate::Inspector::step_opcodes].
+    pub step_opcodes: OpcodeSet,
     /// Memory limit. See [`crate::CfgEnv`].
     #[cfg(feature = "memory_limit")]
     pub memory_limit: u64,
+    /// Highest stack depth reached in this frame. It is tracked only when executed with
+    /// inspector, see [crate::Inspector::frame_usage], or with `execution_stats` feature.
+    pub max_stack_depth: usize,
+    /// Instructions of this frame. Stack depth is in [Self::max_stack_depth], memory and
+    /// calls are not counted here.
+    #[cfg(feature = "execution_stats")]
+    pub stats: crate::ExecutionStats,
 }
 
 impl Interpreter {
     pub fn current_opcode(&self) -> u8 {
-        unsafe { *self.instruction_pointer }
+        self.code_slice(1)[0]
     }
     #[cfg(not(feature = "memory_limit"))]
-    pub fn new<SPEC: Spec>(contract: Contract, gas_limit: u64) -> Self {
+    pub fn new<SPEC: Spec>(contract: Contract, gas_limit: u64, is_static: bool) -> Self {
         Self {
             instruction_pointer: contract.bytecode.as_ptr(),
             return_range: Range::default(),
             memory: Memory::new(),
             stack: Stack::new(),
-            return_data_buffer: Bytes::new(),
+            return_data_buffer: ReturnData::new(),
             contract,
             gas: Gas::new(gas_limit),
+            is_static,
+            spec: SpecRules::of::<SPEC>(None),
+            step_opcodes: OpcodeSet::ALL,
+            max_stack_depth: 0,
+            #[cfg(feature = "execution_stats")]
+            stats: Default::default(),
         }
     }
 
@@ -59,6 +86,7 @@ impl Interpreter {
     pub fn new_with_memory_limit<SPEC: Spec>(
         contract: Contract,
         gas_limit: u64,
+        is_static: bool,
         memory_limit: u64,
     ) -> Self {
         Self {
@@ -66,10 +94,16 @@ impl Interpreter {
             return_range: Range::default(),
             memory: Memory::new(),
             stack: Stack::new(),
-            return_data_buffer: Bytes::new(),
+            return_data_buffer: ReturnData::new(),
             contract,
             gas: Gas::new(gas_limit),
+            is_static,
+            spec: SpecRules::of::<SPEC>(None),
+            step_opcodes: OpcodeSet::ALL,
             memory_limit,
+            max_stack_depth: 0,
+            #[cfg(feature = "execution_stats")]
+            stats: Default::default(),
         }
     }
 
@@ -92,11 +126,17 @@ impl Interpreter {
             if !self.gas.record_cost(gas_block) {
                 return Return::OutOfGas;
             }
+            #[cfg(feature = "gas_accounting")]
+            self.gas
+                .accounting_mut()
+                .add(crate::gas::GasCategory::Opcode, gas_block);
         }
         Return::Continue
     }
 
     /// Return a reference of the program counter.
+    #[cfg(not(feature = "checked_interpreter"))]
+    #[inline(always)]
     pub fn program_counter(&self) -> usize {
         // Safety: this is just subtraction of pointers, it is safe to do.
         unsafe {
@@ -105,31 +145,126 @@ impl Interpreter {
         }
     }
 
+    /// Return a reference of the program counter.
+    #[cfg(feature = "checked_interpreter")]
+    #[inline(always)]
+    pub fn program_counter(&self) -> usize {
+        self.instruction_pointer as usize - self.contract.bytecode.as_ptr() as usize
+    }
+
+    /// Bytes of padded bytecode starting at instruction pointer.
+    #[inline(always)]
+    pub(crate) fn code_slice(&self, len: usize) -> &[u8] {
+        Self::code_at(&self.contract.bytecode, self.instruction_pointer, len)
+    }
+
+    /// Bytes of `bytecode` starting at `pointer`. Takes fields separately so that
+    /// stack can be borrowed mutably while slice is used.
+    #[cfg(not(feature = "checked_interpreter"))]
+    #[inline(always)]
+    pub(crate) fn code_at(bytecode: &BytecodeLocked, pointer: *const u8, len: usize) -> &[u8] {
+        // Safety: analysis pads bytecode so that last opcode is STOP and all PUSH immediates
+        // are in bounds.
+        unsafe {
+            debug_assert!(
+                pointer.offset_from(bytecode.as_ptr()) as usize + len <= bytecode.bytecode().len()
+            );
+            core::slice::from_raw_parts(pointer, len)
+        }
+    }
+
+    /// Bytes of `bytecode` starting at `pointer`. Takes fields separately so that
+    /// stack can be borrowed mutably while slice is used.
+    #[cfg(feature = "checked_interpreter")]
+    #[inline(always)]
+    pub(crate) fn code_at(bytecode: &BytecodeLocked, pointer: *const u8, len: usize) -> &[u8] {
+        let pc = pointer as usize - bytecode.as_ptr() as usize;
+        &bytecode.bytecode()[pc..pc + len]
+    }
+
+    /// Move instruction pointer `n` bytes forward.
+    #[cfg(not(feature = "checked_interpreter"))]
+    #[inline(always)]
+    pub(crate) fn advance(&mut self, n: usize) {
+        debug_assert!(self.program_counter() + n <= self.contract.bytecode.bytecode().len());
+        // Safety: padding of bytecode ensures that pointer stays in bounds, see `code_slice`.
+        self.instruction_pointer = unsafe { self.instruction_pointer.add(n) };
+    }
+
+    /// Move instruction pointer `n` bytes forward.
+    #[cfg(feature = "checked_interpreter")]
+    #[inline(always)]
+    pub(crate) fn advance(&mut self, n: usize) {
+        self.jump_to(self.program_counter() + n);
+    }
+
+    /// Set instruction pointer to `pc`. Jump destination needs to be validated before.
+    #[cfg(not(feature = "checked_interpreter"))]
+    #[inline(always)]
+    pub(crate) fn jump_to(&mut self, pc: usize) {
+        debug_assert!(pc < self.contract.bytecode.bytecode().len());
+        // Safety: valid jump destinations are in bounds of bytecode.
+        self.instruction_pointer = unsafe { self.contract.bytecode.as_ptr().add(pc) };
+    }
+
+    /// Set instruction pointer to `pc`. Jump destination needs to be validated before.
+    #[cfg(feature = "checked_interpreter")]
+    #[inline(always)]
+    pub(crate) fn jump_to(&mut self, pc: usize) {
+        assert!(pc <= self.contract.bytecode.bytecode().len());
+        self.instruction_pointer = self.contract.bytecode.as_ptr().wrapping_add(pc);
+    }
+
     /// loop steps until we are finished with execution
     pub fn run<H: Host, SPEC: Spec>(&mut self, host: &mut H) -> Return {
+        // inspector that is not interested in steps gets the same loop as no inspector at all.
+        if H::INSPECT && !self.step_opcodes.is_empty() {
+            self.run_steps::<H, SPEC, true>(host)
+        } else {
+            self.run_steps::<H, SPEC, false>(host)
+        }
+    }
+
+    #[inline(always)]
+    fn run_steps<H: Host, SPEC: Spec, const STEP: bool>(&mut self, host: &mut H) -> Return {
         //let timer = std::time::Instant::now();
         let mut ret = Return::Continue;
         // add first gas_block
-        if USE_GAS && !self.gas.record_cost(self.contract.first_gas_block()) {
-            return Return::OutOfGas;
+        if USE_GAS {
+            let gas_block = self.contract.first_gas_block();
+            if !self.gas.record_cost(gas_block) {
+                return Return::OutOfGas;
+            }
+            #[cfg(feature = "gas_accounting")]
+            self.gas
+                .accounting_mut()
+                .add(crate::gas::GasCategory::Opcode, gas_block);
         }
         while ret == Return::Continue {
             // step
-            if H::INSPECT {
-                let ret = host.step(self, SPEC::IS_STATIC_CALL);
+            let inspected = STEP && self.step_opcodes.contains(self.current_opcode());
+            if inspected {
+                let ret = host.step(self, self.is_static);
                 if ret != Return::Continue {
                     return ret;
                 }
             }
-            let opcode = unsafe { *self.instruction_pointer };
-            // Safety: In analysis we are doing padding of bytecode so that we are sure that last.
+            let opcode = self.current_opcode();
+            // In analysis we are doing padding of bytecode so that we are sure that last.
             // byte instruction is STOP so we are safe to just increment program_counter bcs on last instruction
             // it will do noop and just stop execution of this contract
-            self.instruction_pointer = unsafe { self.instruction_pointer.offset(1) };
+            self.advance(1);
             ret = eval::<H, SPEC>(opcode, self, host);
+            if H::INSPECT || cfg!(feature = "execution_stats") {
+                self.max_stack_depth = self.max_stack_depth.max(self.stack.len());
+            }
+            #[cfg(feature = "execution_stats")]
+            {
+                self.stats.instructions += 1;
+            }
 
-            if H::INSPECT {
-                let ret = host.step_end(self, SPEC::IS_STATIC_CALL, ret);
+            if inspected {
+                let ret = host.step_end(self, self.is_static, ret);
                 if ret != Return::Continue {
                     return ret;
                 }
@@ -138,6 +273,24 @@ impl Interpreter {
         ret
     }
 
+    /// Return value of the interp without copying it, memory is left empty. Output that is
+    /// equal to return data of last call, as proxies return it, is shared with it, otherwise
+    /// see [Memory::take_slice].
+    pub fn take_return_value(&mut self) -> Bytes {
+        if self.return_range.start == usize::MAX {
+            return Bytes::new();
+        }
+        let (offset, len) = (
+            self.return_range.start,
+            self.return_range.end - self.return_range.start,
+        );
+        let return_data = self.return_data_buffer.data();
+        if return_data.len() == len && return_data[..] == *self.memory.get_slice(offset, len) {
+            return return_data.clone();
+        }
+        self.memory.take_slice(offset, len)
+    }
+
     /// Copy and get the return value of the interp, if any.
     pub fn return_value(&self) -> Bytes {
         // if start is usize max it means that our return len is zero and we need to return empty
@@ -1,5 +1,9 @@
 use super::contract::{AnalysisData, ValidJumpAddress};
-use crate::{opcode, spec_opcode_gas, Spec, KECCAK_EMPTY};
+use crate::{
+    custom_opcode_gas,
+    opcode::{self, OpInfo},
+    spec_opcode_gas, Spec, SpecFlags, SpecRules, KECCAK_EMPTY,
+};
 use bytes::Bytes;
 use primitive_types::H256;
 use sha3::{Digest, Keccak256};
@@ -18,6 +22,17 @@ pub enum BytecodeState {
     },
 }
 
+/// Number of zero bytes appended to checked and analysed bytecode. Enough for data of PUSH32
+/// at the last byte of code and STOP after it.
+const PADDING: usize = 33;
+
+/// Code of contract.
+///
+/// Code is executed as if it was followed by infinite zero bytes, same as on mainnet: PUSH
+/// whose data is cut by the end of code pushes the missing bytes as zeros (`0x6101` pushes
+/// `0x0100`) and execution that runs past the end of code stops as on STOP. Bytes of PUSH data
+/// are never valid jump destinations and neither is any position past the end of code.
+/// Checked and analysed bytecode is padded with zeros so that this doesn't need bound checks.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bytecode {
@@ -75,7 +90,9 @@ impl Bytecode {
     ///
     /// # Safety
     /// Bytecode need to end with STOP (0x00) opcode as checked bytecode assumes
-    /// that it is safe to iterate over bytecode without checking lengths
+    /// that it is safe to iterate over bytecode without checking lengths.
+    /// Bytecode that is not followed by 33 zero bytes after `len` is padded again when it is
+    /// analysed.
     pub unsafe fn new_checked(bytecode: Bytes, len: usize, hash: Option<H256>) -> Self {
         let hash = match hash {
             None if len == 0 => KECCAK_EMPTY,
@@ -93,7 +110,8 @@ impl Bytecode {
     ///
     /// # Safety
     /// Same as new_checked, bytecode needs to end with STOP (0x00) opcode as checked bytecode assumes
-    /// that it is safe to iterate over bytecode without checking length.
+    /// that it is safe to iterate over bytecode without checking length. Bytecode needs to be
+    /// followed by 33 zero bytes after `len` so that PUSH at the end of code reads padding.
     /// And that ValidJumpAddress is valid.
     pub unsafe fn new_analysed(
         bytecode: Bytes,
@@ -141,14 +159,49 @@ impl Bytecode {
         }
     }
 
+    /// At most `len` bytes of original code starting at `offset`. Shorter or empty if code ends
+    /// before.
+    pub fn range(&self, offset: usize, len: usize) -> Bytes {
+        let code_len = self.len();
+        let start = offset.min(code_len);
+        let end = start.saturating_add(len).min(code_len);
+        self.bytecode.slice(start..end)
+    }
+
+    /// Position of PUSH at the end of code whose data is cut by the end of code. Missing bytes
+    /// of its data are pushed as zeros.
+    pub fn trailing_push(&self) -> Option<usize> {
+        let code = &self.bytecode[..self.len().min(self.bytecode.len())];
+        let mut index = 0;
+        while index < code.len() {
+            let opcode = code[index];
+            if (opcode::PUSH1..=opcode::PUSH32).contains(&opcode) {
+                let end = index + (opcode - opcode::PUSH1) as usize + 2;
+                if end > code.len() {
+                    return Some(index);
+                }
+                index = end;
+            } else {
+                index += 1;
+            }
+        }
+        None
+    }
+
+    /// Copy of first `len` bytes of `bytecode` followed by [PADDING] zeros.
+    fn padded(bytecode: &[u8], len: usize) -> Bytes {
+        let mut padded = Vec::with_capacity(len + PADDING);
+        padded.extend_from_slice(&bytecode[..len.min(bytecode.len())]);
+        padded.resize(len + PADDING, 0);
+        padded.into()
+    }
+
     pub fn to_checked(self) -> Self {
         match self.state {
             BytecodeState::Raw => {
                 let len = self.bytecode.len();
-                let mut bytecode: Vec<u8> = Vec::from(self.bytecode.as_ref());
-                bytecode.resize(len + 33, 0);
                 Self {
-                    bytecode: bytecode.into(),
+                    bytecode: Self::padded(&self.bytecode, len),
                     hash: self.hash,
                     state: BytecodeState::Checked { len },
                 }
@@ -157,7 +210,21 @@ impl Bytecode {
         }
     }
 
+    /// Analyse bytecode for `SPEC`. Hardforks of [crate::CustomSpec] are known only at runtime,
+    /// use [Bytecode::to_analysed_with_flags] for it.
     pub fn to_analysed<SPEC: Spec>(self) -> Self {
+        match SPEC::SPEC_ID {
+            Some(spec_id) => self.analysed_with(spec_opcode_gas(spec_id)),
+            None => self.to_analysed_with_flags(SpecRules::of::<SPEC>(None).flags()),
+        }
+    }
+
+    /// Analyse bytecode for [crate::CustomSpec] with hardforks enabled in `flags`.
+    pub fn to_analysed_with_flags(self, flags: SpecFlags) -> Self {
+        self.analysed_with(&custom_opcode_gas(flags))
+    }
+
+    fn analysed_with(self, opcode_gas: &[OpInfo; 256]) -> Self {
         let hash = self.hash;
         let (bytecode, len) = match self.state {
             BytecodeState::Raw => {
@@ -165,10 +232,19 @@ impl Bytecode {
                 let checked = self.to_checked();
                 (checked.bytecode, len)
             }
-            BytecodeState::Checked { len } => (self.bytecode, len),
+            BytecodeState::Checked { len } => {
+                // padding of unsafely created bytecode is not trusted, truncated PUSH at the
+                // end of code would read past it.
+                let padding = self.bytecode.get(len..len + PADDING);
+                if padding.is_some_and(|padding| padding.iter().all(|byte| *byte == 0)) {
+                    (self.bytecode, len)
+                } else {
+                    (Self::padded(&self.bytecode, len), len)
+                }
+            }
             _ => return self,
         };
-        let jumptable = Self::analyze::<SPEC>(bytecode.as_ref());
+        let jumptable = Self::analyze(bytecode.as_ref(), opcode_gas);
 
         Self {
             bytecode,
@@ -196,9 +272,7 @@ impl Bytecode {
     }
 
     /// Analyze bytecode to get jumptable and gas blocks.
-    fn analyze<SPEC: Spec>(code: &[u8]) -> ValidJumpAddress {
-        let opcode_gas = spec_opcode_gas(SPEC::SPEC_ID);
-
+    fn analyze(code: &[u8], opcode_gas: &[OpInfo; 256]) -> ValidJumpAddress {
         let mut analysis = ValidJumpAddress {
             first_gas_block: 0,
             analysis: Arc::new(vec![AnalysisData::none(); code.len()]),
@@ -309,3 +383,87 @@ impl BytecodeLocked {
         &self.jumptable
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::InMemoryDB, opcode::*, AccountInfo, Database, EVMData, Inspector, Interpreter, Return,
+        TransactTo,
+    };
+    use primitive_types::{H160, U256};
+
+    /// Stack at the first STOP.
+    #[derive(Default)]
+    struct StackAtStop(Option<Vec<U256>>);
+
+    impl<DB: Database> Inspector<DB> for StackAtStop {
+        fn step(
+            &mut self,
+            interp: &mut Interpreter,
+            _data: &mut EVMData<'_, DB>,
+            _is_static: bool,
+        ) -> Return {
+            if interp.current_opcode() == STOP && self.0.is_none() {
+                self.0 = Some(interp.stack().data().clone());
+            }
+            Return::Continue
+        }
+    }
+
+    fn run(bytecode: Bytecode) -> (Return, Option<Vec<U256>>) {
+        let contract = H160::from_low_u64_be(0xa);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(contract, AccountInfo::new(U256::zero(), 0, bytecode));
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        let mut inspector = StackAtStop::default();
+        let result = evm.inspect(&mut inspector).0;
+        (result.exit_reason, inspector.0)
+    }
+
+    #[test]
+    fn test_truncated_push() {
+        // missing bytes of PUSH data are zeros on the right.
+        let code = Bytecode::new_raw(vec![PUSH1, 1, PUSH3, 0xaa].into());
+        assert_eq!(code.trailing_push(), Some(2));
+        let (ret, stack) = run(code);
+        assert_eq!(ret, Return::Stop);
+        assert_eq!(stack, Some(vec![1.into(), 0xaa0000.into()]));
+
+        let (_, stack) = run(Bytecode::new_raw(vec![PUSH32].into()));
+        assert_eq!(stack, Some(vec![U256::zero()]));
+
+        assert_eq!(
+            Bytecode::new_raw(vec![PUSH2, 1, 2].into()).trailing_push(),
+            None
+        );
+        assert_eq!(Bytecode::new().trailing_push(), None);
+    }
+
+    #[test]
+    fn test_jumps_into_truncated_push() {
+        // JUMPDEST byte in data of truncated PUSH is not jump destination.
+        let code = vec![PUSH1, 4, JUMP, PUSH2, JUMPDEST];
+        assert_eq!(run(Bytecode::new_raw(code.into())).0, Return::InvalidJump);
+        // neither is padding after the end of code.
+        let code = vec![PUSH1, 3, JUMP];
+        assert_eq!(run(Bytecode::new_raw(code.into())).0, Return::InvalidJump);
+        let code = vec![PUSH1, 4, JUMP, STOP, JUMPDEST, PUSH2];
+        assert_eq!(run(Bytecode::new_raw(code.into())).0, Return::Stop);
+    }
+
+    #[test]
+    fn test_checked_bytecode_padding() {
+        // checked bytecode with only one STOP after truncated PUSH32 is padded again.
+        let code = unsafe { Bytecode::new_checked(vec![PUSH1, 1, PUSH32, 7, 0].into(), 4, None) };
+        let analysed = code.to_analysed::<crate::LatestSpec>();
+        assert_eq!(analysed.bytes().len(), 4 + PADDING);
+        assert_eq!(analysed.len(), 4);
+        let (ret, stack) = run(analysed);
+        assert_eq!(ret, Return::Stop);
+        assert_eq!(stack, Some(vec![1.into(), U256::from(7) << 248]));
+    }
+}
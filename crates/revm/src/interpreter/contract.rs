@@ -1,5 +1,5 @@
 use super::bytecode::{Bytecode, BytecodeLocked};
-use crate::{alloc::vec::Vec, CallContext, Spec};
+use crate::{alloc::vec::Vec, CallContext, CallScheme, Spec};
 use bytes::Bytes;
 use primitive_types::{H160, U256};
 use std::sync::Arc;
@@ -102,6 +102,51 @@ impl Contract {
             call_context.apparent_value,
         )
     }
+
+    /// Create contract of DELEGATECALL frame. Code loaded from `code_address` runs on state of `address`,
+    /// while caller and apparent value are inherited from the parent frame.
+    pub fn new_delegatecall<SPEC: Spec>(
+        input: Bytes,
+        bytecode: Bytecode,
+        code_address: H160,
+        address: H160,
+        caller: H160,
+        apparent_value: U256,
+    ) -> Self {
+        Self::new_with_context::<SPEC>(
+            input,
+            bytecode,
+            &CallContext {
+                address,
+                caller,
+                code_address,
+                apparent_value,
+                scheme: CallScheme::DelegateCall,
+            },
+        )
+    }
+
+    /// Create contract of CALLCODE frame. Code loaded from `code_address` runs on state of `address`,
+    /// which is also the caller, with `value` as apparent value.
+    pub fn new_callcode<SPEC: Spec>(
+        input: Bytes,
+        bytecode: Bytecode,
+        code_address: H160,
+        address: H160,
+        value: U256,
+    ) -> Self {
+        Self::new_with_context::<SPEC>(
+            input,
+            bytecode,
+            &CallContext {
+                address,
+                caller: address,
+                code_address,
+                apparent_value: value,
+                scheme: CallScheme::CallCode,
+            },
+        )
+    }
 }
 
 /// Mapping of valid jump destination from code.
@@ -150,7 +195,10 @@ impl ValidJumpAddress {
 
 #[cfg(test)]
 mod tests {
-    use super::AnalysisData;
+    use super::{AnalysisData, Contract};
+    use crate::{Bytecode, LondonSpec};
+    use bytes::Bytes;
+    use primitive_types::{H160, U256};
 
     #[test]
     pub fn test_jump_set() {
@@ -174,4 +222,35 @@ mod tests {
         assert!(jump.is_jump());
         assert_eq!(jump.gas_block(), 350);
     }
+
+    #[test]
+    pub fn test_delegatecall_and_callcode_context() {
+        let proxy = H160::from_low_u64_be(1);
+        let implementation = H160::from_low_u64_be(2);
+        let user = H160::from_low_u64_be(3);
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x00]));
+
+        let contract = Contract::new_delegatecall::<LondonSpec>(
+            Bytes::new(),
+            code.clone(),
+            implementation,
+            proxy,
+            user,
+            U256::from(10),
+        );
+        assert_eq!(contract.address, proxy);
+        assert_eq!(contract.caller, user);
+        assert_eq!(contract.value, U256::from(10));
+
+        let contract = Contract::new_callcode::<LondonSpec>(
+            Bytes::new(),
+            code,
+            implementation,
+            proxy,
+            U256::from(5),
+        );
+        assert_eq!(contract.address, proxy);
+        assert_eq!(contract.caller, proxy);
+        assert_eq!(contract.value, U256::from(5));
+    }
 }
@@ -1,6 +1,8 @@
 use crate::alloc::vec::Vec;
+use bytes::Bytes;
 use core::{
     cmp::min,
+    mem,
     ops::{BitAnd, Not},
 };
 use primitive_types::U256;
@@ -63,6 +65,22 @@ impl Memory {
         &self.data[offset..offset + size]
     }
 
+    /// Take memory region at given offset out of memory, that is left empty. Dont check offset
+    /// and size.
+    ///
+    /// Used for output of the frame, memory is not used after it. Buffer of the memory is moved
+    /// into returned [Bytes] without copying if region is at least half of it, otherwise region is
+    /// copied so that small output doesn't keep large memory alive.
+    pub fn take_slice(&mut self, offset: usize, size: usize) -> Bytes {
+        if size == 0 {
+            Bytes::new()
+        } else if size * 2 >= self.data.len() {
+            Bytes::from(mem::take(&mut self.data)).slice(offset..offset + size)
+        } else {
+            Bytes::copy_from_slice(self.get_slice(offset, size))
+        }
+    }
+
     /// Set memory region at given offset
     ///
     /// # Safety
@@ -117,7 +135,22 @@ pub(crate) fn next_multiple_of_32(x: usize) -> Option<usize> {
 
 #[cfg(test)]
 mod tests {
-    use super::next_multiple_of_32;
+    use super::*;
+
+    #[test]
+    fn test_take_slice() {
+        let mut memory = Memory::new();
+        memory.resize(64);
+        memory.set(0, &[1; 64]);
+        // small region is copied.
+        assert_eq!(memory.take_slice(0, 2), Bytes::from_static(&[1, 1]));
+        assert_eq!(memory.len(), 64);
+        // memory buffer is moved into output.
+        let output = memory.take_slice(16, 48);
+        assert_eq!(output, Bytes::from(vec![1; 48]));
+        assert!(memory.is_empty());
+        assert!(memory.take_slice(0, 0).is_empty());
+    }
 
     #[test]
     fn test_next_multiple_of_32() {
@@ -0,0 +1,124 @@
+use crate::Return;
+use bytes::Bytes;
+
+/// Output of the last call or create executed by the frame. It is read by RETURNDATASIZE and
+/// RETURNDATACOPY (EIP-211).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReturnData {
+    data: Bytes,
+}
+
+impl ReturnData {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+
+    /// Clear buffer. Called when new call or create starts.
+    pub fn clear(&mut self) {
+        self.data = Bytes::new();
+    }
+
+    /// Set output of finished call or create. If output is larger than `limit`
+    /// [Return::ReturnDataLimit] is returned and buffer is left empty.
+    pub fn set(&mut self, data: Bytes, limit: Option<usize>) -> Result<(), Return> {
+        if matches!(limit, Some(limit) if data.len() > limit) {
+            self.clear();
+            return Err(Return::ReturnDataLimit);
+        }
+        self.data = data;
+        Ok(())
+    }
+
+    /// Region copied by RETURNDATACOPY. Unlike CALLDATACOPY and CODECOPY, reading out of
+    /// bounds is not padded with zeroes but fails with [Return::OutOfOffset], even if `len`
+    /// is zero. Offset that does not fit in usize is expected to be saturated.
+    pub fn slice(&self, offset: usize, len: usize) -> Result<&[u8], Return> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.data.len() => Ok(&self.data[offset..end]),
+            _ => Err(Return::OutOfOffset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_return_data_slice() {
+        let mut buffer = ReturnData::new();
+        buffer.set(Bytes::from_static(&[1, 2, 3]), None).unwrap();
+        assert_eq!(buffer.slice(0, 3), Ok(&[1, 2, 3][..]));
+        assert_eq!(buffer.slice(1, 2), Ok(&[2, 3][..]));
+        // zero length copy at the end of buffer is allowed.
+        assert_eq!(buffer.slice(3, 0), Ok(&[][..]));
+        // but not past it.
+        assert_eq!(buffer.slice(4, 0), Err(Return::OutOfOffset));
+        assert_eq!(buffer.slice(2, 2), Err(Return::OutOfOffset));
+        assert_eq!(buffer.slice(usize::MAX, 0), Err(Return::OutOfOffset));
+        assert_eq!(buffer.slice(1, usize::MAX), Err(Return::OutOfOffset));
+
+        buffer.clear();
+        assert_eq!(buffer.slice(0, 0), Ok(&[][..]));
+        assert_eq!(buffer.slice(0, 1), Err(Return::OutOfOffset));
+    }
+
+    #[test]
+    fn test_return_data_limit() {
+        let mut buffer = ReturnData::new();
+        buffer.set(Bytes::from_static(&[1, 2]), Some(2)).unwrap();
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(
+            buffer.set(Bytes::from_static(&[1, 2, 3]), Some(2)),
+            Err(Return::ReturnDataLimit)
+        );
+        assert!(buffer.is_empty());
+    }
+
+    /// Call identity precompile with 32 bytes and then copy `len` bytes of return data from `offset`.
+    fn returndatacopy(offset: u8, len: u8, limit: Option<usize>) -> Return {
+        use crate::{db::InMemoryDB, opcode::*, AccountInfo, Bytecode, TransactTo};
+        use primitive_types::{H160, U256};
+
+        let code = [
+            // CALL identity precompile with memory[0..32] as input
+            &[PUSH1, 0, PUSH1, 0, PUSH1, 32, PUSH1, 0, PUSH1, 0][..],
+            &[PUSH1, 4, GAS, CALL, POP],
+            &[PUSH1, len, PUSH1, offset, PUSH1, 0, RETURNDATACOPY, STOP],
+        ]
+        .concat();
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code.into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.limit_return_data_size = limit;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        evm.transact().0.exit_reason
+    }
+
+    #[test]
+    fn test_returndatacopy_bounds() {
+        assert_eq!(returndatacopy(0, 32, None), Return::Stop);
+        assert_eq!(returndatacopy(32, 0, None), Return::Stop);
+        assert_eq!(returndatacopy(0, 33, None), Return::OutOfOffset);
+        assert_eq!(returndatacopy(33, 0, None), Return::OutOfOffset);
+        assert_eq!(returndatacopy(0, 0, Some(16)), Return::ReturnDataLimit);
+    }
+}
@@ -4,6 +4,12 @@ use primitive_types::{H256, U256};
 pub const STACK_LIMIT: usize = 1024;
 
 /// EVM stack.
+///
+/// Safe API ([Stack::peek], [Stack::peek_n], [Stack::pop], [Stack::try_pop], [Stack::push],
+/// [Stack::set]) checks stack bounds and returns [Return::StackUnderflow] or
+/// [Return::StackOverflow] leaving stack unchanged. It is intended for inspectors and custom
+/// instructions. Functions with `_unsafe` suffix are fast paths used by interpreter after
+/// stack length is checked and should not be used outside of it.
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Stack {
@@ -79,16 +85,31 @@ impl Stack {
         self.data.pop().ok_or(Return::StackUnderflow)
     }
 
+    #[inline]
+    /// Pop `N` values from the stack, first element is top of the stack. If the stack has less
+    /// than `N` values, returns the `StackUnderflow` error and leaves the stack unchanged.
+    pub fn try_pop<const N: usize>(&mut self) -> Result<[U256; N], Return> {
+        let values = self.peek_n::<N>()?;
+        self.data.truncate(self.data.len() - N);
+        Ok(values)
+    }
+
+    #[inline]
+    /// Number of values that can be pushed before `StackOverflow` error.
+    pub fn remaining(&self) -> usize {
+        STACK_LIMIT - self.data.len()
+    }
+
     #[inline(always)]
     /// Pops a value from the stack, returning it.
     ///
     /// # Safety
     /// The caller is responsible to check length of array
     pub unsafe fn pop_unsafe(&mut self) -> U256 {
-        let mut len = self.data.len();
-        len -= 1;
+        let len = self.data.len() - 1;
+        let value = *self.data.get_unchecked(len);
         self.data.set_len(len);
-        *self.data.get_unchecked(len)
+        value
     }
 
     #[inline(always)]
@@ -136,13 +157,13 @@ impl Stack {
     /// # Safety
     /// The caller is responsible to check length of array
     pub unsafe fn pop2_unsafe(&mut self) -> (U256, U256) {
-        let mut len = self.data.len();
-        len -= 2;
-        self.data.set_len(len);
-        (
+        let len = self.data.len() - 2;
+        let values = (
             *self.data.get_unchecked(len + 1),
             *self.data.get_unchecked(len),
-        )
+        );
+        self.data.set_len(len);
+        values
     }
 
     #[inline(always)]
@@ -151,14 +172,14 @@ impl Stack {
     /// # Safety
     /// The caller is responsible to check length of array
     pub unsafe fn pop3_unsafe(&mut self) -> (U256, U256, U256) {
-        let mut len = self.data.len();
-        len -= 3;
-        self.data.set_len(len);
-        (
+        let len = self.data.len() - 3;
+        let values = (
             *self.data.get_unchecked(len + 2),
             *self.data.get_unchecked(len + 1),
             *self.data.get_unchecked(len),
-        )
+        );
+        self.data.set_len(len);
+        values
     }
 
     #[inline(always)]
@@ -167,15 +188,15 @@ impl Stack {
     /// # Safety
     /// The caller is responsible to check length of array
     pub unsafe fn pop4_unsafe(&mut self) -> (U256, U256, U256, U256) {
-        let mut len = self.data.len();
-        len -= 4;
-        self.data.set_len(len);
-        (
+        let len = self.data.len() - 4;
+        let values = (
             *self.data.get_unchecked(len + 3),
             *self.data.get_unchecked(len + 2),
             *self.data.get_unchecked(len + 1),
             *self.data.get_unchecked(len),
-        )
+        );
+        self.data.set_len(len);
+        values
     }
 
     #[inline]
@@ -212,6 +233,21 @@ impl Stack {
         }
     }
 
+    #[inline]
+    /// Peek `N` values from the top of the stack, first element is top of the stack.
+    /// If the stack has less than `N` values, `StackUnderflow` is returned.
+    pub fn peek_n<const N: usize>(&self) -> Result<[U256; N], Return> {
+        let len = self.data.len();
+        if len < N {
+            return Err(Return::StackUnderflow);
+        }
+        let mut values = [U256::zero(); N];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = self.data[len - i - 1];
+        }
+        Ok(values)
+    }
+
     #[inline(always)]
     pub fn dup<const N: usize>(&mut self) -> Return {
         let len = self.data.len();
@@ -222,7 +258,8 @@ impl Stack {
         } else {
             // Safety: check for out of bounds is done above and it makes this safe to do.
             unsafe {
-                *self.data.get_unchecked_mut(len) = *self.data.get_unchecked(len - N);
+                let value = *self.data.get_unchecked(len - N);
+                self.data.as_mut_ptr().add(len).write(value);
                 self.data.set_len(len + 1);
             }
             Return::Continue
@@ -306,3 +343,34 @@ impl Stack {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_stack_api() {
+        let mut stack = Stack::new();
+        assert_eq!(stack.peek(0), Err(Return::StackUnderflow));
+        assert_eq!(stack.try_pop::<1>(), Err(Return::StackUnderflow));
+        for i in 1..=3u64 {
+            stack.push(i.into()).unwrap();
+        }
+        assert_eq!(stack.len(), 3);
+        assert_eq!(stack.remaining(), STACK_LIMIT - 3);
+        assert_eq!(stack.peek(2), Ok(1.into()));
+        assert_eq!(stack.peek_n::<2>(), Ok([3.into(), 2.into()]));
+
+        // underflow leaves stack unchanged.
+        assert_eq!(stack.try_pop::<4>(), Err(Return::StackUnderflow));
+        assert_eq!(stack.len(), 3);
+        assert_eq!(stack.try_pop::<2>(), Ok([3.into(), 2.into()]));
+        assert_eq!(stack.data(), &vec![U256::one()]);
+
+        while stack.remaining() > 0 {
+            stack.push(U256::zero()).unwrap();
+        }
+        assert_eq!(stack.push(U256::zero()), Err(Return::StackOverflow));
+        assert_eq!(stack.len(), STACK_LIMIT);
+    }
+}
@@ -1,8 +1,14 @@
-use crate::{interpreter::bytecode::Bytecode, models::SelfDestructResult, Return, KECCAK_EMPTY};
+use crate::{
+    gas::{self, GasTable},
+    interpreter::bytecode::Bytecode,
+    models::{CollisionReason, SelfDestructResult},
+    storage_map::{self, StorageMap},
+    Return, KECCAK_EMPTY,
+};
 use alloc::{vec, vec::Vec};
 use core::mem::{self};
-use hashbrown::{hash_map::Entry, HashMap as Map};
-use primitive_types::{H160, U256};
+use hashbrown::{hash_map::Entry, HashMap as Map, HashSet};
+use primitive_types::{H160, H256, U256};
 
 use crate::{db::Database, AccountInfo, Log};
 
@@ -13,6 +19,9 @@ pub struct JournaledState {
     pub state: State,
     /// logs
     pub logs: Vec<Log>,
+    /// Number of logs at the start of each frame that is not yet committed or reverted.
+    /// Logs are reverted by truncating them to this number.
+    frame_logs: Vec<usize>,
     /// how deep are we in call stack.
     pub depth: usize,
     /// journal with changes that happened between calls.
@@ -21,21 +30,85 @@ pub struct JournaledState {
     /// so we need to take care of that difference. Set this to false if you are handling
     /// legacy transactions
     pub is_before_spurious_dragon: bool,
-    /// It is assumed that precompiles start from 0x1 address and spand next N addresses.
-    /// we are using that assumption here
-    pub num_of_precompiles: usize,
+    /// Whether transfer of zero value touches accounts, see [crate::ZeroValueTouch].
+    pub touch_zero_value: bool,
+    /// Addresses of precompiles. They are warm from the start of transaction (EIP-2929).
+    pub precompiles: HashSet<H160>,
 }
 
 pub type State = Map<H160, Account>;
 pub type Storage = Map<U256, StorageSlot>;
 
+/// Accounts of `state` sorted by address.
+///
+/// Iteration order of [State] depends on the hasher, use this where output needs to be
+/// reproducible, for example in golden tests. Storage can be sorted by [Account::sorted_storage].
+pub fn sorted_accounts(state: &State) -> Vec<(&H160, &Account)> {
+    let mut accounts: Vec<_> = state.iter().collect();
+    accounts.sort_unstable_by_key(|(address, _)| **address);
+    accounts
+}
+
+/// Merge state of transaction into `block_state` that holds changes of previous transactions.
+///
+/// Slots keep original value from the first transaction that accessed them and present value
+/// from the last one, so changed slots of merged state are storage change set of all merged
+/// transactions. Use [reset_storage_originals] to start a new block.
+pub fn merge_state(block_state: &mut State, tx_state: State) {
+    for (address, mut tx_account) in tx_state {
+        if tx_account.is_destroyed {
+            // destroyed account has no storage, also slots written before selfdestruct.
+            tx_account.storage_cleared = true;
+            for slot in tx_account.storage.values_mut() {
+                slot.present_value = U256::zero();
+            }
+        }
+        let account = match block_state.entry(address) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(tx_account);
+                continue;
+            }
+        };
+        if tx_account.storage_cleared {
+            // slots of previous transactions that are not accessed anymore are cleared, by
+            // creation or by selfdestruct.
+            account.storage_cleared = true;
+            for slot in account.storage.values_mut() {
+                slot.present_value = U256::zero();
+            }
+        }
+        for (key, tx_slot) in tx_account.storage {
+            match account.storage.entry(key) {
+                storage_map::Entry::Occupied(mut entry) => {
+                    entry.get_mut().present_value = tx_slot.present_value
+                }
+                storage_map::Entry::Vacant(entry) => {
+                    entry.insert(tx_slot);
+                }
+            }
+        }
+        account.info = tx_account.info;
+        account.is_destroyed = tx_account.is_destroyed;
+        account.is_not_existing = tx_account.is_not_existing;
+        account.is_touched |= tx_account.is_touched;
+    }
+}
+
+/// Set original value of every slot to its present value, see [StorageSlot::reset_original].
+pub fn reset_storage_originals(state: &mut State) {
+    for account in state.values_mut() {
+        account.reset_storage_originals();
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 #[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Account {
     /// Balance of the account.
     pub info: AccountInfo,
     /// storage cache
-    pub storage: Map<U256, StorageSlot>,
+    pub storage: StorageMap,
     /// If account is newly created, we will not ask database for storage values
     pub storage_cleared: bool,
     /// if account is destroyed it will be scheduled for removal.
@@ -51,10 +124,24 @@ impl Account {
     pub fn is_empty(&self) -> bool {
         self.info.is_empty()
     }
+
+    /// Set original value of every slot to its present value.
+    pub fn reset_storage_originals(&mut self) {
+        for slot in self.storage.values_mut() {
+            slot.reset_original();
+        }
+    }
+
+    /// Storage slots sorted by key.
+    pub fn sorted_storage(&self) -> Vec<(&U256, &StorageSlot)> {
+        let mut storage: Vec<_> = self.storage.iter().collect();
+        storage.sort_unstable_by_key(|(key, _)| **key);
+        storage
+    }
     pub fn new_not_existing() -> Self {
         Self {
             info: AccountInfo::default(),
-            storage: Map::new(),
+            storage: StorageMap::new(),
             storage_cleared: false,
             is_destroyed: false,
             is_touched: false,
@@ -67,7 +154,7 @@ impl From<AccountInfo> for Account {
     fn from(info: AccountInfo) -> Self {
         Self {
             info,
-            storage: Map::new(),
+            storage: StorageMap::new(),
             storage_cleared: false,
             is_destroyed: false,
             is_touched: false,
@@ -76,6 +163,15 @@ impl From<AccountInfo> for Account {
     }
 }
 
+/// Value of storage slot in [Account::storage].
+///
+/// State returned by transaction contains every slot that transaction read or wrote, not only
+/// changed ones. `original_value` is value of the slot before transaction, or zero if account
+/// storage was cleared by its creation (see [Account::storage_cleared]), as EIP-2200 gas is
+/// computed from it. `present_value` is value after transaction, slot is changed if they
+/// differ. When states of more transactions are merged with [merge_state] original value
+/// is the one before the first merged transaction until it is reset with
+/// [StorageSlot::reset_original], usually at block boundary.
 #[derive(Debug, Clone, Default, Eq, PartialEq)]
 #[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StorageSlot {
@@ -92,6 +188,13 @@ impl StorageSlot {
         }
     }
 
+    pub fn new_changed(original: U256, present: U256) -> Self {
+        Self {
+            original_value: original,
+            present_value: present,
+        }
+    }
+
     /// Returns true if the present value differs from the original value
     pub fn is_changed(&self) -> bool {
         self.original_value != self.present_value
@@ -104,6 +207,44 @@ impl StorageSlot {
     pub fn present_value(&self) -> U256 {
         self.present_value
     }
+
+    /// Make present value the original one, slot is not changed after it.
+    pub fn reset_original(&mut self) {
+        self.original_value = self.present_value;
+    }
+}
+
+/// Result of loading account or storage slot, with information whether it was cold loaded (EIP-2929).
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct StateLoad<T> {
+    pub data: T,
+    pub is_cold: bool,
+}
+
+impl<T> StateLoad<T> {
+    pub fn new(data: T, is_cold: bool) -> Self {
+        Self { data, is_cold }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> StateLoad<U> {
+        StateLoad::new(f(self.data), self.is_cold)
+    }
+
+    /// Gas of account access: 2600 if cold and 100 if warm. Before Berlin it is `legacy_cost`.
+    pub fn account_access_gas(&self, table: &GasTable, legacy_cost: u64) -> u64 {
+        gas::hot_cold_cost(table, self.is_cold, legacy_cost)
+    }
+
+    /// Additional gas of cold account access, for opcodes that have warm access cost
+    /// already calculated in gas block.
+    pub fn cold_account_additional_gas(&self, table: &GasTable) -> u64 {
+        gas::cold_account_additional_cost(table, self.is_cold)
+    }
+
+    /// Gas of storage load: 2100 if cold and 100 if warm. Before Berlin it is fixed per spec.
+    pub fn sload_gas(&self, table: &GasTable) -> u64 {
+        gas::sload_cost(table, self.is_cold)
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -148,8 +289,21 @@ pub enum JournalEntry {
     },
     /// Code changed
     /// Action: Account code changed
-    /// Revert: Revert to previous bytecode.
-    CodeChange { address: H160, had_code: Bytecode },
+    /// Revert: Revert to previous bytecode and its hash. Code that was not loaded is `None`
+    /// and stays not loaded.
+    CodeChange {
+        address: H160,
+        had_code: Option<Bytecode>,
+        had_code_hash: H256,
+    },
+    /// Balance set directly, not by transfer
+    /// Action: Set balance
+    /// Revert: Revert to previous balance
+    BalanceChange { address: H160, had_balance: U256 },
+    /// Nonce set directly, not by increment
+    /// Action: Set nonce
+    /// Revert: Revert to previous nonce
+    NonceSet { address: H160, had_nonce: u64 },
 }
 
 /// SubRoutine checkpoint that will help us to go back from this
@@ -159,19 +313,43 @@ pub struct JournalCheckpoint {
 }
 
 impl JournaledState {
+    /// Journal with precompiles at addresses from 0x1 to `num_of_precompiles`. Use
+    /// [JournaledState::with_precompiles] for precompiles at other addresses.
     pub fn new(num_of_precompiles: usize) -> JournaledState {
+        Self::with_precompiles(
+            (1..=num_of_precompiles as u64)
+                .map(|num| {
+                    let mut address = H160::zero();
+                    address[12..].copy_from_slice(&num.to_be_bytes());
+                    address
+                })
+                .collect(),
+        )
+    }
+
+    pub fn new_legacy(num_of_precompiles: usize) -> JournaledState {
+        let mut journal = Self::new(num_of_precompiles);
+        journal.is_before_spurious_dragon = true;
+        journal
+    }
+
+    /// Journal with precompiles at `precompiles` addresses.
+    pub fn with_precompiles(precompiles: HashSet<H160>) -> JournaledState {
         Self {
             state: Map::new(),
             logs: Vec::new(),
+            frame_logs: Vec::new(),
             journal: vec![vec![]],
             depth: 0,
             is_before_spurious_dragon: false,
-            num_of_precompiles,
+            touch_zero_value: true,
+            precompiles,
         }
     }
 
-    pub fn new_legacy(num_of_precompiles: usize) -> JournaledState {
-        let mut journal = Self::new(num_of_precompiles);
+    /// Journal of spec before Spurious Dragon with precompiles at `precompiles` addresses.
+    pub fn with_precompiles_legacy(precompiles: HashSet<H160>) -> JournaledState {
+        let mut journal = Self::with_precompiles(precompiles);
         journal.is_before_spurious_dragon = true;
         journal
     }
@@ -194,15 +372,26 @@ impl JournaledState {
     }
 
     /// do cleanup and return modified state
+    ///
+    /// Since Spurious Dragon touched accounts that are empty are marked as destroyed (EIP-161:
+    /// State trie clearing). Before it they are kept, touched account exists even if it is empty.
     pub fn finalize(&mut self) -> (State, Vec<Log>) {
         let state = mem::take(&mut self.state);
+        let is_before_spurious_dragon = self.is_before_spurious_dragon;
 
         let state = state
             .into_iter()
             .filter(|(_, account)| account.is_touched)
+            .map(|(address, mut account)| {
+                if !is_before_spurious_dragon && account.is_empty() {
+                    account.is_destroyed = true;
+                }
+                (address, account)
+            })
             .collect();
 
         let logs = mem::take(&mut self.logs);
+        self.frame_logs.clear();
         self.journal = vec![vec![]];
         self.depth = 0;
         (state, logs)
@@ -228,13 +417,58 @@ impl JournaledState {
             .unwrap()
             .push(JournalEntry::CodeChange {
                 address,
-                had_code: code.clone(),
+                had_code: account.info.code.clone(),
+                had_code_hash: account.info.code_hash,
             });
 
         account.info.code_hash = code.hash();
         account.info.code = Some(code);
     }
 
+    /// Set balance of account, loading it if needed. Change is journaled and reverted
+    /// together with checkpoint it is made in.
+    pub fn set_balance<DB: Database>(
+        &mut self,
+        address: H160,
+        balance: U256,
+        db: &mut DB,
+    ) -> Result<(), DB::Error> {
+        self.load_account(address, db)?;
+        let account = self.state.get_mut(&address).unwrap();
+        Self::touch_account(self.journal.last_mut().unwrap(), &address, account);
+        self.journal
+            .last_mut()
+            .unwrap()
+            .push(JournalEntry::BalanceChange {
+                address,
+                had_balance: account.info.balance,
+            });
+        account.info.balance = balance;
+        Ok(())
+    }
+
+    /// Set nonce of account, loading it if needed. Change is journaled and reverted
+    /// together with checkpoint it is made in.
+    pub fn set_nonce<DB: Database>(
+        &mut self,
+        address: H160,
+        nonce: u64,
+        db: &mut DB,
+    ) -> Result<(), DB::Error> {
+        self.load_account(address, db)?;
+        let account = self.state.get_mut(&address).unwrap();
+        Self::touch_account(self.journal.last_mut().unwrap(), &address, account);
+        self.journal
+            .last_mut()
+            .unwrap()
+            .push(JournalEntry::NonceSet {
+                address,
+                had_nonce: account.info.nonce,
+            });
+        account.info.nonce = nonce;
+        Ok(())
+    }
+
     pub fn inc_nonce(&mut self, address: H160) -> Option<u64> {
         let account = self.state.get_mut(&address).unwrap();
         // Check if nonce is going to overflow.
@@ -260,13 +494,19 @@ impl JournaledState {
         db: &mut DB,
     ) -> Result<(bool, bool), Return> {
         // load accounts
-        let (_, from_is_cold) = self
+        let from_is_cold = self
             .load_account(*from, db)
-            .map_err(|_| Return::FatalExternalError)?;
+            .map_err(|_| Return::FatalExternalError)?
+            .is_cold;
 
-        let (_, to_is_cold) = self
+        let to_is_cold = self
             .load_account(*to, db)
-            .map_err(|_| Return::FatalExternalError)?;
+            .map_err(|_| Return::FatalExternalError)?
+            .is_cold;
+
+        if balance.is_zero() && !self.touch_zero_value {
+            return Ok((from_is_cold, to_is_cold));
+        }
 
         // sub balance from
         let from_account = &mut self.state.get_mut(from).unwrap();
@@ -295,29 +535,29 @@ impl JournaledState {
         Ok((from_is_cold, to_is_cold))
     }
 
-    /// return if it has collision of addresses
+    /// Create account at `address` or return why it collides with existing one.
     pub fn create_account<DB: Database>(
         &mut self,
         address: H160,
         is_precompile: bool,
         db: &mut DB,
-    ) -> Result<bool, DB::Error> {
-        let (acc, _) = self.load_code(address, db)?;
+    ) -> Result<Result<(), CollisionReason>, DB::Error> {
+        let acc = self.load_code(address, db)?.data;
 
         // Check collision. Bytecode needs to be empty.
         if let Some(ref code) = acc.info.code {
             if !code.is_empty() {
-                return Ok(false);
+                return Ok(Err(CollisionReason::Code));
             }
         }
         // Check collision. Nonce is not zero
         if acc.info.nonce != 0 {
-            return Ok(false);
+            return Ok(Err(CollisionReason::Nonce));
         }
 
         // Check collision. New account address is precompile.
         if is_precompile {
-            return Ok(false);
+            return Ok(Err(CollisionReason::Precompile));
         }
         acc.storage_cleared = true;
 
@@ -336,7 +576,7 @@ impl JournaledState {
             .last_mut()
             .unwrap()
             .push(JournalEntry::AccountTouched { address });
-        Ok(true)
+        Ok(Ok(()))
     }
 
     fn journal_revert(
@@ -370,8 +610,11 @@ impl JournaledState {
                     account.is_destroyed = was_destroyed;
                     account.info.balance += had_balance;
 
-                    let target = state.get_mut(&target).unwrap();
-                    target.info.balance -= had_balance;
+                    // burned balance was not given to target if account is its own target.
+                    if target != address {
+                        let target = state.get_mut(&target).unwrap();
+                        target.info.balance -= had_balance;
+                    }
                 }
                 JournalEntry::BalanceTransfer { from, to, balance } => {
                     // we dont need to check overflow and underflow when adding sub subtracting the balance.
@@ -395,15 +638,54 @@ impl JournaledState {
                         storage.remove(&key);
                     }
                 }
-                JournalEntry::CodeChange { address, had_code } => {
+                JournalEntry::CodeChange {
+                    address,
+                    had_code,
+                    had_code_hash,
+                } => {
                     let acc = state.get_mut(&address).unwrap();
-                    acc.info.code_hash = had_code.hash();
-                    acc.info.code = Some(had_code);
+                    acc.info.code_hash = had_code_hash;
+                    acc.info.code = had_code;
+                }
+                JournalEntry::BalanceChange {
+                    address,
+                    had_balance,
+                } => {
+                    state.get_mut(&address).unwrap().info.balance = had_balance;
+                }
+                JournalEntry::NonceSet { address, had_nonce } => {
+                    state.get_mut(&address).unwrap().info.nonce = had_nonce;
                 }
             }
         }
     }
 
+    /// Remove all but the first storage change of every slot from the entries of one frame.
+    ///
+    /// Entries are reverted from last to first so value of the slot at the start of the frame
+    /// is the one from the first entry and later ones are redundant. Entries of other kinds
+    /// are kept as they are.
+    fn compact_journal(entries: &mut Vec<JournalEntry>) {
+        let mut journaled = HashSet::new();
+        entries.retain(|entry| match entry {
+            JournalEntry::StorageChage { address, key, .. } => journaled.insert((*address, *key)),
+            _ => true,
+        });
+    }
+
+    /// Make space for one more entry of a frame. Full journal of at least
+    /// [JOURNAL_COMPACTION_MIN_LEN] entries is compacted, and grown if compaction freed less than
+    /// half of it, so next compaction runs only after as many new entries as were kept.
+    /// Returns true if journal was compacted.
+    fn reserve_journal_entry(entries: &mut Vec<JournalEntry>) -> bool {
+        if entries.len() < entries.capacity() || entries.len() < JOURNAL_COMPACTION_MIN_LEN {
+            return false;
+        }
+        Self::compact_journal(entries);
+        entries.reserve(entries.len());
+        true
+    }
+
     pub fn checkpoint(&mut self) -> JournalCheckpoint {
         let checkpoint = JournalCheckpoint {
             log_i: self.logs.len(),
@@ -411,11 +693,19 @@ impl JournaledState {
         };
         self.depth += 1;
         self.journal.push(Default::default());
+        self.frame_logs.push(checkpoint.log_i);
         checkpoint
     }
 
     pub fn checkpoint_commit(&mut self) {
         self.depth -= 1;
+        self.frame_logs.pop();
+    }
+
+    /// Number of logs emitted in current frame, including logs of its subcalls that
+    /// were not reverted.
+    pub fn frame_log_count(&self) -> usize {
+        self.logs.len() - self.frame_logs.last().copied().unwrap_or_default()
     }
 
     pub fn checkpoint_revert(&mut self, checkpoint: JournalCheckpoint) {
@@ -431,6 +721,7 @@ impl JournaledState {
             .for_each(|cs| Self::journal_revert(state, mem::take(cs), is_spurious_dragon_enabled));
 
         self.logs.truncate(checkpoint.log_i);
+        self.frame_logs.pop();
         self.journal.truncate(checkpoint.journal_i);
     }
 
@@ -441,7 +732,10 @@ impl JournaledState {
         target: H160,
         db: &mut DB,
     ) -> Result<SelfDestructResult, DB::Error> {
-        let (is_cold, target_exists) = self.load_account_exist(target, db)?;
+        let StateLoad {
+            data: target_exists,
+            is_cold,
+        } = self.load_account_exist(target, db)?;
         // transfer all the balance
         let acc = self.state.get_mut(&address).unwrap();
         let balance = mem::take(&mut acc.info.balance);
@@ -450,11 +744,16 @@ impl JournaledState {
         // In case that target and destroyed addresses are same, balance will be lost.
         // ref: https://github.com/ethereum/go-ethereum/blob/141cd425310b503c5678e674a8c3872cf46b7086/core/vm/instructions.go#L832-L833
         // https://github.com/ethereum/go-ethereum/blob/141cd425310b503c5678e674a8c3872cf46b7086/core/state/statedb.go#L449
-        if address != target {
+        let burned_balance = if address != target {
             let target_account = self.state.get_mut(&target).unwrap();
-            Self::touch_account(self.journal.last_mut().unwrap(), &target, target_account);
+            if !balance.is_zero() || self.touch_zero_value {
+                Self::touch_account(self.journal.last_mut().unwrap(), &target, target_account);
+            }
             target_account.info.balance += balance;
-        }
+            U256::zero()
+        } else {
+            balance
+        };
 
         self.journal
             .last_mut()
@@ -468,9 +767,12 @@ impl JournaledState {
 
         Ok(SelfDestructResult {
             had_value: !balance.is_zero(),
+            value: balance,
             is_cold,
             target_exists,
+            target_created: !target_exists && address != target && !balance.is_zero(),
             previously_destroyed,
+            burned_balance,
         })
     }
 
@@ -479,10 +781,12 @@ impl JournaledState {
         &mut self,
         address: H160,
         db: &mut DB,
-    ) -> Result<(&mut Account, bool), DB::Error> {
+    ) -> Result<StateLoad<&mut Account>, DB::Error> {
         Ok(match self.state.entry(address) {
-            Entry::Occupied(entry) => (entry.into_mut(), false),
+            Entry::Occupied(entry) => StateLoad::new(entry.into_mut(), false),
             Entry::Vacant(vac) => {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(target: "revm::db", ?address, "basic");
                 let account = if let Some(account) = db.basic(address)? {
                     account.into()
                 } else {
@@ -496,46 +800,91 @@ impl JournaledState {
                     .push(JournalEntry::AccountLoaded { address });
 
                 // precompiles are hot loaded so we need to take that into account
-                let is_cold = !is_precompile(address, self.num_of_precompiles);
+                let is_cold = !self.precompiles.contains(&address);
 
-                (vac.insert(account), is_cold)
+                StateLoad::new(vac.insert(account), is_cold)
             }
         })
     }
 
-    // first is is_cold second bool is exists.
+    /// Load account and return only its balance.
+    pub fn balance<DB: Database>(
+        &mut self,
+        address: H160,
+        db: &mut DB,
+    ) -> Result<StateLoad<U256>, DB::Error> {
+        Ok(self.load_account(address, db)?.map(|acc| acc.info.balance))
+    }
+
+    /// load account and return if it exists.
     pub fn load_account_exist<DB: Database>(
         &mut self,
         address: H160,
         db: &mut DB,
-    ) -> Result<(bool, bool), DB::Error> {
+    ) -> Result<StateLoad<bool>, DB::Error> {
         let is_before_spurious_dragon = self.is_before_spurious_dragon;
-        let (acc, is_cold) = self.load_code(address, db)?;
+        let load = self.load_code(address, db)?;
 
-        let exist = if is_before_spurious_dragon {
-            !acc.is_not_existing || acc.is_touched
-        } else {
-            !acc.is_empty()
-        };
-        Ok((is_cold, exist))
+        Ok(load.map(|acc| {
+            if is_before_spurious_dragon {
+                !acc.is_not_existing || acc.is_touched
+            } else {
+                !acc.is_empty()
+            }
+        }))
     }
 
     pub fn load_code<DB: Database>(
         &mut self,
         address: H160,
         db: &mut DB,
-    ) -> Result<(&mut Account, bool), DB::Error> {
-        let (acc, is_cold) = self.load_account(address, db)?;
+    ) -> Result<StateLoad<&mut Account>, DB::Error> {
+        let load = self.load_account(address, db)?;
+        let acc = load.data;
         if acc.info.code.is_none() {
             if acc.info.code_hash == KECCAK_EMPTY {
                 let empty = Bytecode::new();
                 acc.info.code = Some(empty);
             } else {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(target: "revm::db", code_hash = ?acc.info.code_hash, "code_by_hash");
                 let code = db.code_by_hash(acc.info.code_hash)?;
                 acc.info.code = Some(code);
             }
         }
-        Ok((acc, is_cold))
+        Ok(StateLoad::new(acc, load.is_cold))
+    }
+
+    /// Insert storage values that were already fetched from database, for example while warming
+    /// the access list or by parallel prefetcher, so that execution does not need to ask database
+    /// for them again.
+    ///
+    /// Account and slots become warm in regards to EIP-2929 same as if they were loaded, and
+    /// are removed on revert. Slots that are already loaded keep their values, as do slots of
+    /// account which storage was cleared.
+    pub fn insert_storage_prefetched<DB: Database>(
+        &mut self,
+        address: H160,
+        slots: impl IntoIterator<Item = (U256, U256)>,
+        db: &mut DB,
+    ) -> Result<(), DB::Error> {
+        self.load_account(address, db)?;
+        let account = self.state.get_mut(&address).unwrap();
+        if account.storage_cleared {
+            return Ok(());
+        }
+        let journal = self.journal.last_mut().unwrap();
+        for (key, value) in slots {
+            if let storage_map::Entry::Vacant(vac) = account.storage.entry(key) {
+                journal.push(JournalEntry::StorageChage {
+                    address,
+                    key,
+                    had_value: None,
+                });
+                vac.insert(StorageSlot::new(value));
+            }
+        }
+        Ok(())
     }
 
     // account is already present and loaded.
@@ -544,15 +893,17 @@ impl JournaledState {
         address: H160,
         key: U256,
         db: &mut DB,
-    ) -> Result<(U256, bool), DB::Error> {
+    ) -> Result<StateLoad<U256>, DB::Error> {
         let account = self.state.get_mut(&address).unwrap(); // asume acc is hot
         let load = match account.storage.entry(key) {
-            Entry::Occupied(occ) => (occ.get().present_value, false),
-            Entry::Vacant(vac) => {
+            storage_map::Entry::Occupied(occ) => StateLoad::new(occ.get().present_value, false),
+            storage_map::Entry::Vacant(vac) => {
                 // if storage was cleared, we dont need to ping db.
                 let value = if account.storage_cleared {
                     U256::zero()
                 } else {
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(target: "revm::db", ?address, %key, "storage");
                     db.storage(address, key)?
                 };
                 // add it to journal as cold loaded.
@@ -567,7 +918,7 @@ impl JournaledState {
 
                 vac.insert(StorageSlot::new(value));
 
-                (value, true)
+                StateLoad::new(value, true)
             }
         };
         Ok(load)
@@ -583,7 +934,10 @@ impl JournaledState {
         db: &mut DB,
     ) -> Result<(U256, U256, U256, bool), DB::Error> {
         // assume that acc exists and load the slot.
-        let (present, is_cold) = self.sload(address, key, db)?;
+        let StateLoad {
+            data: present,
+            is_cold,
+        } = self.sload(address, key, db)?;
         let acc = self.state.get_mut(&address).unwrap();
 
         // if there is no original value in dirty return present value, that is our original.
@@ -594,14 +948,15 @@ impl JournaledState {
             return Ok((slot.original_value, present, new, is_cold));
         }
 
-        self.journal
-            .last_mut()
-            .unwrap()
-            .push(JournalEntry::StorageChage {
-                address,
-                key,
-                had_value: Some(present),
-            });
+        let entries = self.journal.last_mut().unwrap();
+        // compact the frame instead of growing it, repeated stores to the same slots leave
+        // journal of constant size.
+        Self::reserve_journal_entry(entries);
+        entries.push(JournalEntry::StorageChage {
+            address,
+            key,
+            had_value: Some(present),
+        });
         // insert value into present state.
         slot.present_value = new;
         Ok((slot.original_value, present, new, is_cold))
@@ -611,65 +966,709 @@ impl JournaledState {
     pub fn log(&mut self, log: Log) {
         self.logs.push(log);
     }
-}
 
-fn is_precompile(address: H160, num_of_precompiles: usize) -> bool {
-    if !address[..18].iter().all(|i| *i == 0) {
-        return false;
+    pub fn is_precompile(&self, address: &H160) -> bool {
+        self.precompiles.contains(address)
     }
-    let num = u16::from_be_bytes([address[18], address[19]]);
-    num.wrapping_sub(1) < num_of_precompiles as u16
 }
 
+/// Length of journal of one frame from which it is compacted when it needs to grow.
+const JOURNAL_COMPACTION_MIN_LEN: usize = 1024;
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
     fn test_is_precompile() {
+        let journal = JournaledState::with_precompiles(HashSet::from([
+            H160::from_low_u64_be(1),
+            H160::from_low_u64_be(2),
+            H160::from_low_u64_be(3),
+            H160::from_low_u64_be(0x100),
+        ]));
+        let is_precompile = |address: [u8; 20]| journal.is_precompile(&H160(address));
         assert_eq!(
-            is_precompile(
-                H160([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
-                3
-            ),
+            is_precompile([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
             false,
             "Zero is not precompile"
         );
 
         assert_eq!(
-            is_precompile(
-                H160([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 9]),
-                3
-            ),
+            is_precompile([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 9]),
             false,
             "0x100..0 is not precompile"
         );
 
         assert_eq!(
-            is_precompile(
-                H160([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4]),
-                3
-            ),
+            is_precompile([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4]),
             false,
             "0x000..4 is not precompile"
         );
 
         assert_eq!(
-            is_precompile(
-                H160([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]),
-                3
-            ),
+            is_precompile([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]),
             true,
             "0x00..01 is precompile"
         );
 
         assert_eq!(
-            is_precompile(
-                H160([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3]),
-                3
-            ),
+            is_precompile([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3]),
             true,
             "0x000..3 is precompile"
         );
+
+        assert_eq!(
+            is_precompile([0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0]),
+            true,
+            "0x000..100 is precompile that doesn't follow the others"
+        );
+
+        // precompiles of `new` span addresses from 0x1.
+        let journal = JournaledState::new(3);
+        assert!(journal.is_precompile(&H160::from_low_u64_be(3)));
+        assert!(!journal.is_precompile(&H160::from_low_u64_be(4)));
+        assert!(!journal.is_precompile(&H160::zero()));
+    }
+
+    #[test]
+    fn test_state_load_gas() {
+        use crate::{SpecFlags, SpecId};
+        let berlin = &GasTable::new(SpecFlags::new(SpecId::BERLIN));
+        let istanbul = &GasTable::new(SpecFlags::new(SpecId::ISTANBUL));
+        let cold = StateLoad::new(U256::one(), true);
+        let warm = cold.map(|value| value + 1);
+        assert_eq!(warm.data, 2.into());
+        let warm = StateLoad::new(warm.data, false);
+
+        assert_eq!(cold.account_access_gas(berlin, 700), 2600);
+        assert_eq!(warm.account_access_gas(berlin, 700), 100);
+        assert_eq!(cold.account_access_gas(istanbul, 700), 700);
+
+        assert_eq!(cold.cold_account_additional_gas(berlin), 2500);
+        assert_eq!(warm.cold_account_additional_gas(berlin), 0);
+        assert_eq!(cold.cold_account_additional_gas(istanbul), 0);
+
+        assert_eq!(cold.sload_gas(berlin), 2100);
+        assert_eq!(warm.sload_gas(berlin), 100);
+        assert_eq!(cold.sload_gas(istanbul), 800);
+    }
+
+    #[test]
+    fn test_journaled_cheat_operations() {
+        use crate::db::InMemoryDB;
+        let address = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(address, AccountInfo::from_balance(10.into()));
+        let mut journal = JournaledState::new(0);
+        journal.load_account(address, &mut db).unwrap();
+
+        let checkpoint = journal.checkpoint();
+        journal.set_balance(address, 100.into(), &mut db).unwrap();
+        journal.set_nonce(address, 5, &mut db).unwrap();
+        journal.set_code(address, Bytecode::new_raw(vec![0x00].into()));
+        journal
+            .sstore(address, 1.into(), 2.into(), &mut db)
+            .unwrap();
+        assert_eq!(journal.account(address).info.balance, 100.into());
+        assert_eq!(journal.account(address).info.nonce, 5);
+        journal.checkpoint_revert(checkpoint);
+
+        let account = journal.account(address);
+        assert_eq!(account.info.balance, 10.into());
+        assert_eq!(account.info.nonce, 0);
+        assert_eq!(account.info.code_hash, KECCAK_EMPTY);
+        assert!(account.storage.is_empty());
+
+        // code that was not loaded is restored as not loaded, with its hash.
+        let contract = H160::from_low_u64_be(0x3000);
+        let code_hash = H256::repeat_byte(1);
+        db.insert_account_info(
+            contract,
+            AccountInfo {
+                code_hash,
+                code: None,
+                ..Default::default()
+            },
+        );
+        journal.load_account(contract, &mut db).unwrap();
+        let checkpoint = journal.checkpoint();
+        journal.set_code(contract, Bytecode::new_raw(vec![0x00].into()));
+        journal.checkpoint_revert(checkpoint);
+        let account = journal.account(contract);
+        assert_eq!(account.info.code_hash, code_hash);
+        assert!(account.info.code.is_none());
+
+        // account that is not yet loaded is loaded and change is kept on commit.
+        let other = H160::from_low_u64_be(0x2000);
+        journal.checkpoint();
+        journal.set_balance(other, 1.into(), &mut db).unwrap();
+        journal.checkpoint_commit();
+        let (state, _) = journal.finalize();
+        assert_eq!(state[&other].info.balance, 1.into());
+        assert!(state[&other].is_touched);
+    }
+
+    #[test]
+    fn test_balance_load() {
+        use crate::db::InMemoryDB;
+        let address = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(address, AccountInfo::from_balance(10.into()));
+        let mut journal = JournaledState::new(0);
+
+        assert_eq!(
+            journal.balance(address, &mut db).unwrap(),
+            StateLoad::new(10.into(), true)
+        );
+        assert_eq!(
+            journal.balance(address, &mut db).unwrap(),
+            StateLoad::new(10.into(), false)
+        );
+    }
+
+    #[test]
+    fn test_sorted_state() {
+        let mut state = State::new();
+        for i in [3u64, 1, 2, 5, 4] {
+            let mut account: Account = AccountInfo::from_balance(i.into()).into();
+            for key in [9u64, 7, 8] {
+                account
+                    .storage
+                    .insert(key.into(), StorageSlot::new(i.into()));
+            }
+            state.insert(H160::from_low_u64_be(i), account);
+        }
+        let accounts = sorted_accounts(&state);
+        let addresses: Vec<_> = accounts.iter().map(|(address, _)| **address).collect();
+        assert_eq!(
+            addresses,
+            (1..=5).map(H160::from_low_u64_be).collect::<Vec<_>>()
+        );
+        let keys: Vec<_> = accounts[0]
+            .1
+            .sorted_storage()
+            .into_iter()
+            .map(|(key, _)| *key)
+            .collect();
+        assert_eq!(keys, vec![7.into(), 8.into(), 9.into()]);
+    }
+
+    #[test]
+    fn test_merge_state() {
+        let address = H160::from_low_u64_be(1);
+        let tx_state = |slots: &[(u64, u64, u64)], storage_cleared: bool| {
+            let mut account: Account = AccountInfo::from_balance(1.into()).into();
+            account.is_touched = true;
+            account.storage_cleared = storage_cleared;
+            for (key, original, present) in slots {
+                account.storage.insert(
+                    (*key).into(),
+                    StorageSlot::new_changed((*original).into(), (*present).into()),
+                );
+            }
+            State::from([(address, account)])
+        };
+        let slots = |state: &State| {
+            state[&address]
+                .sorted_storage()
+                .into_iter()
+                .map(|(key, slot)| {
+                    (
+                        key.as_u64(),
+                        slot.original_value().as_u64(),
+                        slot.present_value().as_u64(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut block_state = State::new();
+        merge_state(&mut block_state, tx_state(&[(1, 1, 2), (2, 5, 5)], false));
+        merge_state(&mut block_state, tx_state(&[(1, 2, 3), (3, 0, 4)], false));
+        assert_eq!(slots(&block_state), vec![(1, 1, 3), (2, 5, 5), (3, 0, 4)]);
+
+        // storage cleared by creation removes values of previous transactions.
+        merge_state(&mut block_state, tx_state(&[(4, 0, 7)], true));
+        assert!(block_state[&address].storage_cleared);
+        assert_eq!(
+            slots(&block_state),
+            vec![(1, 1, 0), (2, 5, 0), (3, 0, 0), (4, 0, 7)]
+        );
+
+        reset_storage_originals(&mut block_state);
+        assert_eq!(
+            slots(&block_state),
+            vec![(1, 0, 0), (2, 0, 0), (3, 0, 0), (4, 7, 7)]
+        );
+
+        // selfdestruct clears storage, also slot written before it in the same transaction.
+        let mut destroyed = tx_state(&[(5, 0, 8)], false);
+        destroyed.get_mut(&address).unwrap().is_destroyed = true;
+        merge_state(&mut block_state, destroyed);
+        assert!(block_state[&address].is_destroyed);
+        assert_eq!(
+            slots(&block_state),
+            vec![(1, 0, 0), (2, 0, 0), (3, 0, 0), (4, 7, 0), (5, 0, 0)]
+        );
+        // recreated account has only new slots.
+        merge_state(&mut block_state, tx_state(&[(6, 0, 9)], true));
+        assert!(!block_state[&address].is_destroyed);
+        assert_eq!(
+            slots(&block_state),
+            vec![
+                (1, 0, 0),
+                (2, 0, 0),
+                (3, 0, 0),
+                (4, 7, 0),
+                (5, 0, 0),
+                (6, 0, 9)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_selfdestruct_to_self() {
+        use crate::{
+            db::InMemoryDB, opcode::*, Bytecode, Inspector, SelfDestructResult, TransactTo,
+        };
+
+        #[derive(Default)]
+        struct Burned(Vec<(H160, U256)>);
+        impl<DB: Database> Inspector<DB> for Burned {
+            fn selfdestruct(&mut self, address: H160, target: H160, result: &SelfDestructResult) {
+                assert_eq!(address, target);
+                self.0.push((address, result.burned_balance));
+            }
+        }
+
+        let destroyed = H160::from_low_u64_be(0x1000);
+        let reverting = H160::from_low_u64_be(0x2000);
+        let caller = H160::from_low_u64_be(0x3000);
+        let call = |address: u8, args_len: u8| {
+            [
+                &[PUSH1, 0, PUSH1, 0, PUSH1, args_len, PUSH1, 0, PUSH1, 0][..],
+                &[PUSH2, address, 0, GAS, CALL],
+            ]
+            .concat()
+        };
+        let codes = [
+            // selfdestruct to self only if there is calldata.
+            (
+                destroyed,
+                vec![
+                    CALLDATASIZE,
+                    PUSH1,
+                    5,
+                    JUMPI,
+                    STOP,
+                    JUMPDEST,
+                    ADDRESS,
+                    SELFDESTRUCT,
+                ],
+            ),
+            (
+                reverting,
+                [call(0x10, 1), vec![PUSH1, 0, DUP1, REVERT]].concat(),
+            ),
+            // touch `destroyed` so that it is part of the state diff.
+            (caller, [call(0x10, 0), call(0x20, 0), vec![STOP]].concat()),
+        ];
+        let run = |to: H160| {
+            let mut db = InMemoryDB::default();
+            for (address, code) in codes.clone() {
+                let balance = if address == destroyed { 10 } else { 0 };
+                db.insert_account_info(
+                    address,
+                    AccountInfo::new(balance.into(), 0, Bytecode::new_raw(code.into())),
+                );
+            }
+            let mut evm = crate::new();
+            evm.database(db);
+            evm.env.tx.transact_to = TransactTo::Call(to);
+            evm.env.tx.data = vec![1].into();
+            evm.env.tx.gas_limit = 100_000;
+            let mut burned = Burned::default();
+            let exit_reason = evm.inspect_commit(&mut burned).exit_reason;
+            let account = evm.db().unwrap().basic(destroyed).unwrap();
+            (exit_reason, account, burned.0)
+        };
+
+        // balance is burned and account is removed.
+        let (exit_reason, account, burned) = run(destroyed);
+        assert_eq!(exit_reason, Return::SelfDestruct);
+        assert_eq!(burned, vec![(destroyed, 10.into())]);
+        assert_eq!(account, None);
+
+        // revert of selfdestruct gives balance back.
+        let (exit_reason, account, burned) = run(caller);
+        assert_eq!(exit_reason, Return::Stop);
+        assert_eq!(burned, vec![(destroyed, 10.into())]);
+        assert_eq!(account.unwrap().balance, 10.into());
+    }
+
+    #[test]
+    fn test_insert_storage_prefetched() {
+        let address = H160::from_low_u64_be(0x1000);
+        let mut db = crate::db::InMemoryDB::default();
+        let mut journal = JournaledState::new(0);
+        journal.load_account(address, &mut db).unwrap();
+        journal
+            .sstore(address, 2.into(), 20.into(), &mut db)
+            .unwrap();
+
+        let checkpoint = journal.checkpoint();
+        journal
+            .insert_storage_prefetched(
+                address,
+                [(1.into(), 10.into()), (2.into(), 30.into())],
+                &mut db,
+            )
+            .unwrap();
+        // prefetched slot is warm and value is not fetched from database.
+        let load = journal.sload(address, 1.into(), &mut db).unwrap();
+        assert_eq!((load.data, load.is_cold), (10.into(), false));
+        // loaded slot keeps its value.
+        assert_eq!(
+            journal.sload(address, 2.into(), &mut db).unwrap().data,
+            20.into()
+        );
+
+        journal.checkpoint_revert(checkpoint);
+        let load = journal.sload(address, 1.into(), &mut db).unwrap();
+        assert_eq!((load.data, load.is_cold), (U256::zero(), true));
+    }
+
+    #[test]
+    fn test_journal_compaction() {
+        let address = H160::from_low_u64_be(0x1000);
+        let mut db = crate::db::InMemoryDB::default();
+        let mut journal = JournaledState::new(0);
+        journal.load_account(address, &mut db).unwrap();
+        journal
+            .sstore(address, 1.into(), 1.into(), &mut db)
+            .unwrap();
+
+        let checkpoint = journal.checkpoint();
+        for i in 2..10_000u64 {
+            for key in [1u64, 2] {
+                journal
+                    .sstore(address, key.into(), i.into(), &mut db)
+                    .unwrap();
+            }
+        }
+        // repeated stores don't grow journal of the frame.
+        assert!(journal.journal.last().unwrap().len() <= JOURNAL_COMPACTION_MIN_LEN);
+        assert_eq!(
+            journal.sload(address, 2.into(), &mut db).unwrap().data,
+            9_999.into()
+        );
+
+        journal.checkpoint_revert(checkpoint);
+        assert_eq!(
+            journal.sload(address, 1.into(), &mut db).unwrap().data,
+            1.into()
+        );
+        let load = journal.sload(address, 2.into(), &mut db).unwrap();
+        assert_eq!((load.data, load.is_cold), (U256::zero(), true));
+    }
+
+    #[test]
+    fn test_journal_compaction_is_amortized() {
+        let address = H160::from_low_u64_be(0x1000);
+        let entry = |key: u64| JournalEntry::StorageChage {
+            address,
+            key: key.into(),
+            had_value: None,
+        };
+        // cold loads of distinct slots that compaction can't remove, then stores to one slot.
+        let loaded = 3 * JOURNAL_COMPACTION_MIN_LEN as u64;
+        let mut entries = Vec::new();
+        let mut compactions = 0;
+        for i in 0..loaded + 100_000 {
+            if JournaledState::reserve_journal_entry(&mut entries) {
+                compactions += 1;
+            }
+            entries.push(entry(i.min(loaded)));
+        }
+        // every compaction is followed by at least as many pushes as entries it kept.
+        assert!(compactions <= 2 * 100_000 / loaded + 2, "{}", compactions);
+        assert!(entries.len() <= 2 * (loaded as usize + 1));
+    }
+
+    #[test]
+    fn test_logs_of_reverted_frames() {
+        use crate::{db::InMemoryDB, opcode::*, Bytecode, EVMData, Inspector, TransactTo};
+        use bytes::Bytes;
+        use primitive_types::H256;
+
+        /// Records emitter of the log and number of logs emitted before it in the same frame.
+        #[derive(Default)]
+        struct FrameLogs(Vec<(H160, usize)>);
+        impl<DB: Database> Inspector<DB> for FrameLogs {
+            fn log(
+                &mut self,
+                evm_data: &mut EVMData<'_, DB>,
+                address: &H160,
+                _topics: &[H256],
+                _data: &Bytes,
+            ) {
+                self.0
+                    .push((*address, evm_data.journaled_state.frame_log_count()));
+            }
+        }
+
+        let addr = H160::from_low_u64_be;
+        let log = vec![PUSH1, 0, DUP1, LOG0];
+        let call = |address: u8| {
+            vec![
+                PUSH1, 0, DUP1, DUP1, DUP1, DUP1, PUSH2, address, 0, GAS, CALL, POP,
+            ]
+        };
+        let codes = [
+            (
+                0x10,
+                [&log[..], &call(0x20), &call(0x30), &log, &[STOP]].concat(),
+            ),
+            (0x20, [&log[..], &call(0x30), &log, &[STOP]].concat()),
+            // logs of reverted frame are dropped.
+            (0x30, [&log[..], &[PUSH1, 0, DUP1, REVERT]].concat()),
+        ];
+        let mut db = InMemoryDB::default();
+        for (address, code) in codes {
+            db.insert_account_info(
+                addr(address << 8),
+                AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code.into())),
+            );
+        }
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(addr(0x1000));
+        evm.env.tx.gas_limit = 1_000_000;
+
+        let mut frame_logs = FrameLogs::default();
+        let (result, _) = evm.inspect(&mut frame_logs);
+        assert_eq!(result.exit_reason, Return::Stop);
+        let emitters: Vec<_> = result.logs.iter().map(|log| log.address).collect();
+        assert_eq!(
+            emitters,
+            vec![addr(0x1000), addr(0x2000), addr(0x2000), addr(0x1000)]
+        );
+        assert_eq!(
+            frame_logs.0,
+            vec![
+                (addr(0x1000), 0),
+                (addr(0x2000), 0),
+                (addr(0x3000), 0),
+                // log of reverted call is not counted.
+                (addr(0x2000), 1),
+                (addr(0x3000), 0),
+                // logs of committed subcall are counted.
+                (addr(0x1000), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_state_clear() {
+        use crate::{db::InMemoryDB, opcode::*, Bytecode, DatabaseCommit, SpecId, TransactTo};
+
+        let contract = H160::from_low_u64_be(0x1000);
+        let target = H160::from_low_u64_be(0x2000);
+        // zero value CALL to target.
+        let code = [
+            &[PUSH1, 0, DUP1, DUP1, DUP1, DUP1][..],
+            &[PUSH2, 0x20, 0, GAS, CALL, STOP],
+        ]
+        .concat();
+        let evm = |spec_id, target_info: Option<AccountInfo>| {
+            let mut db = InMemoryDB::default();
+            db.insert_account_info(
+                contract,
+                AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code.clone().into())),
+            );
+            if let Some(info) = target_info {
+                db.insert_account_info(target, info);
+            }
+            let mut evm = crate::new();
+            evm.database(db);
+            evm.env.cfg.spec_id = spec_id;
+            evm.env.tx.transact_to = TransactTo::Call(contract);
+            evm.env.tx.gas_limit = 100_000;
+            evm
+        };
+
+        // before Spurious Dragon touched account exists even if it is empty.
+        let mut legacy = evm(SpecId::TANGERINE, None);
+        let first = legacy.transact_commit();
+        assert_eq!(first.exit_reason, Return::Stop);
+        assert_eq!(
+            legacy.db().unwrap().basic(target).unwrap(),
+            Some(AccountInfo::default())
+        );
+        // existence is kept across commits, second call doesn't create new account.
+        let second = legacy.transact_commit();
+        assert_eq!(first.gas_used - second.gas_used, gas::NEWACCOUNT);
+        // BALANCE of not existing account doesn't create it.
+        let mut legacy = evm(SpecId::TANGERINE, None);
+        legacy.db().unwrap().insert_account_info(
+            contract,
+            AccountInfo::new(
+                U256::zero(),
+                0,
+                Bytecode::new_raw(vec![PUSH2, 0x20, 0, BALANCE, STOP].into()),
+            ),
+        );
+        legacy.transact_commit();
+        assert_eq!(legacy.db().unwrap().basic(target).unwrap(), None);
+
+        // since Spurious Dragon touched empty account is removed.
+        let mut evm_sd = evm(SpecId::SPURIOUS_DRAGON, Some(AccountInfo::default()));
+        let (result, state) = evm_sd.transact();
+        assert_eq!(result.exit_reason, Return::Stop);
+        assert!(state[&target].is_destroyed);
+        evm_sd.db().unwrap().commit(state);
+        assert_eq!(evm_sd.db().unwrap().basic(target).unwrap(), None);
+        // and not existing account is not created.
+        let mut evm_sd = evm(SpecId::SPURIOUS_DRAGON, None);
+        evm_sd.transact_commit();
+        assert_eq!(evm_sd.db().unwrap().basic(target).unwrap(), None);
+        // empty account that is not touched stays.
+        let mut evm_sd = evm(SpecId::SPURIOUS_DRAGON, Some(AccountInfo::default()));
+        evm_sd.env.tx.transact_to = TransactTo::Call(H160::from_low_u64_be(0x3000));
+        evm_sd.transact_commit();
+        assert_eq!(
+            evm_sd.db().unwrap().basic(target).unwrap(),
+            Some(AccountInfo::default())
+        );
+    }
+
+    #[test]
+    fn test_zero_value_touch() {
+        use crate::{db::InMemoryDB, opcode::*, Bytecode, SpecId, TransactTo, ZeroValueTouch};
+
+        let contract = H160::from_low_u64_be(0x1000);
+        let empty = H160::from_low_u64_be(0x2000);
+        let beneficiary = H160::from_low_u64_be(0x3000);
+        let coinbase = H160::from_low_u64_be(0x4000);
+        let ecrecover = H160::from_low_u64_be(1);
+        let ripemd = H160::from_low_u64_be(3);
+        // zero value CALL with `gas` to `to`.
+        let call = |to: H160, gas: u8| {
+            [
+                &[PUSH1, 0, DUP1, DUP1, DUP1, DUP1, PUSH20][..],
+                to.as_bytes(),
+                &[PUSH1, gas, CALL, POP],
+            ]
+            .concat()
+        };
+        let transact = |spec_id, touch, code: Vec<u8>| {
+            let mut db = InMemoryDB::default();
+            db.insert_account_info(
+                contract,
+                AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code.into())),
+            );
+            for address in [empty, beneficiary, coinbase, ecrecover, ripemd] {
+                db.insert_account_info(address, AccountInfo::default());
+            }
+            let mut evm = crate::new();
+            evm.database(db);
+            evm.env.cfg.spec_id = spec_id;
+            evm.env.cfg.zero_value_touch = touch;
+            evm.env.block.coinbase = coinbase;
+            evm.env.tx.transact_to = TransactTo::Call(contract);
+            evm.env.tx.gas_limit = 100_000;
+            evm.transact()
+        };
+
+        // zero value call touches and removes existing empty account.
+        let code = [call(empty, 0xff), vec![STOP]].concat();
+        let (result, state) =
+            transact(SpecId::SPURIOUS_DRAGON, ZeroValueTouch::Touch, code.clone());
+        assert_eq!(result.exit_reason, Return::Stop);
+        assert!(state[&empty].is_destroyed);
+        // zero gas price pays nothing to coinbase but touches it.
+        assert!(state[&coinbase].is_destroyed);
+        assert!(result.touched.contains(&empty) && result.touched.contains(&coinbase));
+        assert!(result.touched.windows(2).all(|pair| pair[0] < pair[1]));
+        // before Spurious Dragon touched empty account stays.
+        let (_, state) = transact(SpecId::TANGERINE, ZeroValueTouch::Touch, code.clone());
+        assert!(state[&empty].is_touched && !state[&empty].is_destroyed);
+        // without touch empty account is left as it is.
+        let (result, state) = transact(SpecId::SPURIOUS_DRAGON, ZeroValueTouch::Skip, code);
+        assert!(!state.contains_key(&empty) && !state.contains_key(&coinbase));
+        assert!(!result.touched.contains(&empty));
+
+        // SELFDESTRUCT without balance touches beneficiary.
+        let mut code = vec![PUSH20];
+        code.extend(beneficiary.as_bytes());
+        code.push(SELFDESTRUCT);
+        let (_, state) = transact(SpecId::SPURIOUS_DRAGON, ZeroValueTouch::Touch, code.clone());
+        assert!(state[&beneficiary].is_destroyed);
+        let (_, state) = transact(SpecId::SPURIOUS_DRAGON, ZeroValueTouch::Skip, code);
+        assert!(!state.contains_key(&beneficiary));
+
+        // touch of RIPEMD precompile is kept even if call to it runs out of gas, as it was on
+        // mainnet when touched empty accounts started to be removed.
+        let code = [call(ripemd, 1), vec![STOP]].concat();
+        let (result, state) =
+            transact(SpecId::SPURIOUS_DRAGON, ZeroValueTouch::Touch, code.clone());
+        assert_eq!(result.exit_reason, Return::Stop);
+        assert!(state[&ripemd].is_destroyed);
+        // touch of other precompile is reverted with the call that failed.
+        let code = [call(ecrecover, 1), vec![STOP]].concat();
+        let (_, state) = transact(SpecId::SPURIOUS_DRAGON, ZeroValueTouch::Touch, code);
+        assert!(!state.contains_key(&ecrecover));
+    }
+
+    #[test]
+    fn test_selfdestruct_sweep() {
+        use crate::{
+            db::InMemoryDB, opcode::*, Bytecode, Inspector, SelfDestructResult, TransactTo,
+        };
+
+        #[derive(Default)]
+        struct Sweeps(Vec<(H160, SelfDestructResult)>);
+        impl<DB: Database> Inspector<DB> for Sweeps {
+            fn selfdestruct(&mut self, _address: H160, target: H160, result: &SelfDestructResult) {
+                self.0.push((target, *result));
+            }
+        }
+
+        let contract = H160::from_low_u64_be(0x1000);
+        let existing = H160::from_low_u64_be(0x2000);
+        let missing = H160::from_low_u64_be(0x3000);
+        let sweep = |target: H160| {
+            let mut db = InMemoryDB::default();
+            let code = [&[PUSH20][..], target.as_bytes(), &[SELFDESTRUCT]].concat();
+            db.insert_account_info(
+                contract,
+                AccountInfo::new(U256::from(100), 1, Bytecode::new_raw(code.into())),
+            );
+            db.insert_account_info(existing, AccountInfo::from_balance(U256::one()));
+            let mut evm = crate::new();
+            evm.database(db);
+            evm.env.cfg.spec_id = crate::SpecId::LONDON;
+            evm.env.tx.transact_to = TransactTo::Call(contract);
+            evm.env.tx.gas_limit = 100_000;
+            let mut sweeps = Sweeps::default();
+            let (result, state) = evm.inspect(&mut sweeps);
+            assert_eq!(result.exit_reason, Return::SelfDestruct);
+            let before = if target == existing { 1 } else { 0 };
+            assert_eq!(state[&target].info.balance, U256::from(100 + before));
+            assert_eq!(sweeps.0.len(), 1);
+            assert_eq!(sweeps.0[0].0, target);
+            sweeps.0[0].1
+        };
+
+        let result = sweep(missing);
+        assert_eq!(result.value, U256::from(100));
+        assert!(result.had_value && result.target_created && result.is_cold);
+        assert!(!result.target_exists);
+        assert_eq!(result.burned_balance, U256::zero());
+
+        let result = sweep(existing);
+        assert_eq!(result.value, U256::from(100));
+        assert!(result.target_exists && !result.target_created && result.is_cold);
     }
 }
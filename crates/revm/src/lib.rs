@@ -1,35 +1,90 @@
 #![allow(dead_code)]
 //#![no_std]
 
+pub mod analysis;
+mod analysis_cache;
+mod block_hashes;
+mod block_reward;
+mod coverage;
+mod create_address_hook;
 pub mod db;
 mod evm;
 mod evm_impl;
-pub(crate) mod gas;
+mod fee_hook;
+pub mod fees;
+pub mod gas;
 mod inspector;
 mod instructions;
 mod interpreter;
 mod journaled_state;
+mod log_filter;
 mod models;
+mod multi_spec;
+mod precompile_registry;
+mod prepared_tx;
+mod repro;
 mod specification;
+mod storage_layout;
+pub mod storage_map;
+#[cfg(any(test, feature = "test_utils"))]
+pub mod test_utils;
+#[cfg(feature = "std")]
+mod trace_stream;
+mod tx_preprocess;
+mod tx_validator;
+mod units;
+mod witness;
 
 pub use evm_impl::{create2_address, create_address, EVMData, Host};
 
 pub type DummyStateDB = InMemoryDB;
 
+pub use analysis_cache::AnalysisCache;
+#[cfg(feature = "std")]
+pub use analysis_cache::SharedAnalysisCache;
+pub use block_hashes::{BlockHashes, DatabaseBlockHashes, HeaderChainError, RecentBlockHashes};
+pub use block_reward::{apply_block_rewards, block_reward, block_rewards, Uncle};
+pub use coverage::{coverage_id, CoverageInspector, COVERAGE_MAP_SIZE};
+pub use create_address_hook::CreateAddressHook;
 pub use db::{Database, DatabaseCommit, InMemoryDB};
-pub use evm::{evm_inner, new, EVM};
-pub use gas::Gas;
-pub use inspector::{GasInspector, Inspector, NoOpInspector};
+pub use evm::{evm_inner, new, EVMHooks, Evm, EVM};
+pub use fee_hook::TxFeeHook;
+pub use gas::{all_but_one_64th, call_gas, create_gas, initial_tx_gas, Gas};
+#[cfg(feature = "gas_accounting")]
+pub use gas::{GasAccounting, GasCategory};
+pub use inspector::{FrameUsage, GasInspector, Inspector, NoOpInspector};
 pub use instructions::{
-    opcode::{self, spec_opcode_gas, OpCode, OPCODE_JUMPMAP},
+    opcode::{self, custom_opcode_gas, spec_opcode_gas, OpCode, OpcodeSet, OPCODE_JUMPMAP},
     Return,
 };
 pub use interpreter::{
-    Bytecode, BytecodeLocked, BytecodeState, Contract, Interpreter, Memory, Stack,
+    Bytecode, BytecodeLocked, BytecodeState, Contract, Interpreter, Memory, ReturnData, Stack,
 };
-pub use journaled_state::{Account, JournalEntry, JournaledState};
+pub use journaled_state::{
+    merge_state, reset_storage_originals, sorted_accounts, Account, JournalCheckpoint,
+    JournalEntry, JournaledState, State, StateLoad, StorageSlot,
+};
+pub use log_filter::{Bloom, LogFilter};
 pub use models::*;
+pub use multi_spec::{SpecOutcome, SpecReport};
+pub use precompile_registry::PrecompileRegistry;
+pub use prepared_tx::PreparedTx;
+pub use repro::{ReproAccount, ReproBundle, ReproExpected, ReproMismatch, ReproRecorder};
 pub use specification::*;
+pub use storage_layout::{
+    DecodedSlot, StorageAccess, StorageKind, StorageLayout, StorageLayoutInspector, StorageVariable,
+};
+
+pub use storage_map::{StorageMap, INLINE_SLOTS};
+#[cfg(feature = "std")]
+pub use trace_stream::{
+    trace_channel, write_batches, Backpressure, DecodeError, TraceEvent, TraceStreamInspector,
+    DEFAULT_BATCH_SIZE,
+};
+pub use tx_preprocess::AccessListWarmSet;
+pub use tx_validator::{BlobTx, InvalidTransaction, TxValidator};
+pub use units::{Gwei, Wei, WEI_PER_GWEI};
+pub use witness::{Witness, WitnessCode, WitnessEntry, WitnessInspector};
 
 extern crate alloc;
 
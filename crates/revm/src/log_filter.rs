@@ -0,0 +1,183 @@
+use crate::{alloc::vec::Vec, Log};
+use primitive_types::{H160, H256};
+use sha3::{Digest, Keccak256};
+
+/// Ethereum 2048 bit logs bloom as defined in yellow paper.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Bloom(pub [u8; 256]);
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self([0; 256])
+    }
+}
+
+impl Bloom {
+    /// Create bloom from address and topics of logs.
+    pub fn from_logs<'a>(logs: impl IntoIterator<Item = &'a Log>) -> Self {
+        let mut bloom = Self::default();
+        for log in logs {
+            bloom.accrue_log(log);
+        }
+        bloom
+    }
+
+    /// Add address and topics of log to bloom.
+    pub fn accrue_log(&mut self, log: &Log) {
+        self.accrue(log.address.as_bytes());
+        for topic in log.topics.iter() {
+            self.accrue(topic.as_bytes());
+        }
+    }
+
+    /// Add input to bloom.
+    pub fn accrue(&mut self, input: &[u8]) {
+        for (index, bit) in Self::bits(input) {
+            self.0[index] |= bit;
+        }
+    }
+
+    /// Return false if input was definitely not added to bloom.
+    pub fn contains_input(&self, input: &[u8]) -> bool {
+        Self::bits(input)
+            .into_iter()
+            .all(|(index, bit)| self.0[index] & bit == bit)
+    }
+
+    /// Three bits that input sets as byte index and bit mask.
+    fn bits(input: &[u8]) -> [(usize, u8); 3] {
+        let hash = Keccak256::digest(input);
+        let mut bits = [(0, 0); 3];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            let position = ((hash[2 * i] as usize) << 8 | hash[2 * i + 1] as usize) & 0x7ff;
+            *bit = (255 - position / 8, 1 << (position % 8));
+        }
+        bits
+    }
+}
+
+/// Filter of logs by emitting address and topics.
+///
+/// Empty list of addresses or topics on some position matches anything. Otherwise log needs to
+/// match one of listed values.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LogFilter {
+    pub addresses: Vec<H160>,
+    pub topics: [Vec<H256>; 4],
+}
+
+impl LogFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add address that log can be emitted from.
+    pub fn address(mut self, address: H160) -> Self {
+        self.addresses.push(address);
+        self
+    }
+
+    /// Add topic that log can have on `index` position. Panics if index is more than 3.
+    pub fn topic(mut self, index: usize, topic: H256) -> Self {
+        self.topics[index].push(topic);
+        self
+    }
+
+    /// Check if log matches filter.
+    pub fn matches(&self, log: &Log) -> bool {
+        if !self.addresses.is_empty() && !self.addresses.contains(&log.address) {
+            return false;
+        }
+        self.topics
+            .iter()
+            .enumerate()
+            .filter(|(_, topics)| !topics.is_empty())
+            .all(|(i, topics)| {
+                log.topics
+                    .get(i)
+                    .map(|topic| topics.contains(topic))
+                    .unwrap_or_default()
+            })
+    }
+
+    /// Return false if none of logs accrued in bloom can match filter.
+    pub fn matches_bloom(&self, bloom: &Bloom) -> bool {
+        let any = |inputs: &mut dyn Iterator<Item = &[u8]>| {
+            let mut empty = true;
+            let mut found = false;
+            for input in inputs {
+                empty = false;
+                found |= bloom.contains_input(input);
+            }
+            empty || found
+        };
+        any(&mut self.addresses.iter().map(|address| address.as_bytes()))
+            && self
+                .topics
+                .iter()
+                .all(|topics| any(&mut topics.iter().map(|topic| topic.as_bytes())))
+    }
+
+    /// Return logs that match filter. Bloom of logs is checked first so that logs
+    /// are not iterated if none can match.
+    pub fn filter<'a>(&self, logs: &'a [Log]) -> Vec<&'a Log> {
+        if !self.matches_bloom(&Bloom::from_logs(logs)) {
+            return Vec::new();
+        }
+        logs.iter().filter(|log| self.matches(log)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::vec;
+    use bytes::Bytes;
+
+    fn log(address: u64, topics: &[u64]) -> Log {
+        Log {
+            address: H160::from_low_u64_be(address),
+            topics: topics.iter().map(|t| H256::from_low_u64_be(*t)).collect(),
+            data: Bytes::new(),
+        }
+    }
+
+    #[test]
+    fn test_bloom() {
+        let bloom = Bloom::from_logs(&[log(0, &[])]);
+        assert!(bloom.contains_input(H160::zero().as_bytes()));
+        assert!(!bloom.contains_input(H160::from_low_u64_be(1).as_bytes()));
+    }
+
+    #[test]
+    fn test_log_filter() {
+        let logs = vec![log(1, &[10, 20]), log(2, &[10]), log(1, &[30])];
+
+        assert_eq!(LogFilter::new().filter(&logs).len(), 3);
+        assert_eq!(
+            LogFilter::new()
+                .address(H160::from_low_u64_be(1))
+                .filter(&logs),
+            vec![&logs[0], &logs[2]]
+        );
+        assert_eq!(
+            LogFilter::new()
+                .topic(0, H256::from_low_u64_be(10))
+                .topic(1, H256::from_low_u64_be(20))
+                .filter(&logs),
+            vec![&logs[0]]
+        );
+        // alternatives on same position
+        assert_eq!(
+            LogFilter::new()
+                .address(H160::from_low_u64_be(1))
+                .topic(0, H256::from_low_u64_be(10))
+                .topic(0, H256::from_low_u64_be(30))
+                .filter(&logs),
+            vec![&logs[0], &logs[2]]
+        );
+        let missing = LogFilter::new().address(H160::from_low_u64_be(3));
+        assert!(!missing.matches_bloom(&Bloom::from_logs(&logs)));
+        assert!(missing.filter(&logs).is_empty());
+    }
+}
@@ -1,9 +1,17 @@
 use core::cmp::min;
 
-use crate::{alloc::vec::Vec, interpreter::bytecode::Bytecode, Return, SpecId};
+use crate::{
+    alloc::{string::String, sync::Arc, vec::Vec},
+    interpreter::bytecode::Bytecode,
+    precompiles::KzgSettings,
+    Gas, Return, SpecFlags, SpecId, Wei,
+};
 use bytes::Bytes;
 use primitive_types::{H160, H256, U256};
 
+/// EIP-170: Contract code size limit.
+pub const MAX_CODE_SIZE: usize = 0x6000;
+
 pub const KECCAK_EMPTY: H256 = H256([
     0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7, 0x03, 0xc0,
     0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
@@ -85,6 +93,8 @@ pub struct CallInputs {
     pub gas_limit: u64,
     /// The context of the call.
     pub context: CallContext,
+    /// Whether the call is executed in static context, either as STATICCALL or nested inside one.
+    pub is_static: bool,
 }
 
 #[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
@@ -118,6 +128,8 @@ pub enum TransactOut {
     None,
     #[cfg_attr(feature = "with-serde", serde(with = "serde_hex_bytes"))]
     Call(Bytes),
+    /// Output of init code and address of created contract. Address is set whenever it was
+    /// derived, also if deployment failed afterwards, see [ExecutionResult::created_address].
     Create(
         #[cfg_attr(feature = "with-serde", serde(with = "serde_hex_bytes"))] Bytes,
         Option<H160>,
@@ -212,19 +224,24 @@ pub struct TxEnv {
     pub value: U256,
     #[cfg_attr(feature = "with-serde", serde(with = "serde_hex_bytes"))]
     pub data: Bytes,
+    /// Chain id the transaction is signed for (EIP-155). If set it needs to match [Env::chain_id].
     pub chain_id: Option<u64>,
     pub nonce: Option<u64>,
     pub access_list: Vec<(H160, Vec<U256>)>,
+    /// If set it is used instead of `cfg.chain_id` for this transaction.
+    /// Useful to replay same transaction on multiple chains without changing config.
+    #[cfg_attr(feature = "with-serde", serde(default))]
+    pub chain_id_override: Option<U256>,
+    /// If set it is returned by ORIGIN opcode instead of `caller`.
+    /// Gas payment, nonce and value transfer are still done by `caller`.
+    #[cfg_attr(feature = "with-serde", serde(default))]
+    pub origin_override: Option<H160>,
 }
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CfgEnv {
     pub chain_id: U256,
     pub spec_id: SpecId,
-    /// If all precompiles have some balance we can skip initially fetching them from the database.
-    /// This is is not really needed on mainnet, and defaults to false, but in most cases it is
-    /// safe to be set to `true`, depending on the chain.
-    pub perf_all_precompiles_have_balance: bool,
     /// Bytecode that is created with CREATE/CREATE2 is by default analysed and jumptable is created.
     /// This is very benefitial for testing and speeds up execution of that bytecode when.
     /// It will have side effect if it is enabled in client that switches between forks.
@@ -233,6 +250,12 @@ pub struct CfgEnv {
     /// If some it will effects EIP-170: Contract code size limit. Usefull to increase this because of tests.
     /// By default it is 0x6000 (~25kb).
     pub limit_contract_code_size: Option<usize>,
+    /// If some, init code of create transaction or CREATE/CREATE2 that is larger fails with
+    /// [Return::CreateInitcodeSizeLimit]. No limit is applied by default.
+    pub limit_initcode_size: Option<usize>,
+    /// If some, frame that receives larger output from call or create fails with
+    /// [Return::ReturnDataLimit]. No limit is applied by default.
+    pub limit_return_data_size: Option<usize>,
     /// A hard memory limit in bytes beyond which [Memory] cannot be resized.
     ///
     /// In cases where the gas limit may be extraordinarily high, it is recommended to set this to
@@ -240,6 +263,27 @@ pub struct CfgEnv {
     /// EIP-1985.
     #[cfg(feature = "memory_limit")]
     pub memory_limit: u64,
+    /// Custom set of enabled hardforks. If set it is used instead of `spec_id`.
+    /// See [crate::CustomSpec].
+    pub custom_spec: Option<SpecFlags>,
+    /// How DELEGATECALL and CALLCODE of precompile are handled. Default is to execute
+    /// precompile as on mainnet.
+    pub precompile_delegation: PrecompileDelegation,
+    /// Whether receiver of zero value is touched. Default is to touch it as on mainnet.
+    pub zero_value_touch: ZeroValueTouch,
+    /// If some, output retained in [ExecutionResult] is truncated to this size. Unlike
+    /// `limit_return_data_size` it does not change execution. No limit is applied by default.
+    pub limit_result_output_size: Option<usize>,
+    /// If some, data of logs retained in [ExecutionResult] is truncated so that total size of
+    /// it is not larger than this. Topics are always retained. No limit is applied by default.
+    pub limit_result_log_data_size: Option<usize>,
+    /// Addresses that are warm (EIP-2929) from the start of every transaction, like system
+    /// contracts of L2s. Unlike access list they don't add to intrinsic gas.
+    pub warm_addresses: Vec<H160>,
+    /// Trusted setup of KZG commitments that is passed to precompiles in
+    /// [crate::precompiles::PrecompileContext]. It is not serialized. Default: None
+    #[cfg_attr(feature = "with-serde", serde(skip))]
+    pub kzg_settings: Option<Arc<dyn KzgSettings>>,
 }
 
 #[derive(Clone, Default, Debug, Eq, PartialEq)]
@@ -251,16 +295,65 @@ pub enum AnalysisKind {
     Analyse,
 }
 
+/// Handling of precompile that is called with DELEGATECALL or CALLCODE.
+///
+/// On mainnet precompile is executed same as with CALL, with apparent value of the call.
+/// Value of CALLCODE is transferred from caller to itself.
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PrecompileDelegation {
+    /// Execute precompile.
+    #[default]
+    Execute,
+    /// Call is executed as if precompile address has no code, it succeeds with empty output.
+    NoCode,
+    /// Call fails with [Return::PrecompileDelegation] and consumes all gas given to it.
+    Reject,
+}
+
+/// Whether accounts are touched by transfer of zero value: by zero value CALL, CALLCODE and
+/// transaction, SELFDESTRUCT of account without balance and payment of coinbase when gas
+/// price is zero.
+///
+/// Touch has effect only on empty account. Before Spurious Dragon touched account that doesn't
+/// exist is created as empty account, since Spurious Dragon touched empty account is deleted
+/// (EIP-161). Accounts touched by transaction are in [ExecutionResult::touched].
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ZeroValueTouch {
+    /// Touch accounts, as on mainnet.
+    #[default]
+    Touch,
+    /// Zero value doesn't touch accounts, it neither creates nor deletes empty accounts.
+    /// Not compatible with Ethereum.
+    Skip,
+}
+
+impl CfgEnv {
+    /// Maximum size of deployed contract code.
+    pub fn max_code_size(&self) -> usize {
+        self.limit_contract_code_size.unwrap_or(MAX_CODE_SIZE)
+    }
+}
+
 impl Default for CfgEnv {
     fn default() -> CfgEnv {
         CfgEnv {
             chain_id: 1.into(),
             spec_id: SpecId::LATEST,
-            perf_all_precompiles_have_balance: false,
             perf_analyse_created_bytecodes: Default::default(),
             limit_contract_code_size: None,
+            limit_initcode_size: None,
+            limit_return_data_size: None,
             #[cfg(feature = "memory_limit")]
             memory_limit: 2u64.pow(32) - 1,
+            custom_spec: None,
+            precompile_delegation: Default::default(),
+            zero_value_touch: Default::default(),
+            limit_result_output_size: None,
+            limit_result_log_data_size: None,
+            warm_addresses: Vec::new(),
+            kzg_settings: None,
         }
     }
 }
@@ -291,11 +384,41 @@ impl Default for TxEnv {
             chain_id: None,
             nonce: None,
             access_list: Vec::new(),
+            chain_id_override: None,
+            origin_override: None,
+        }
+    }
+}
+
+impl TxEnv {
+    /// Default transaction of the type native to `spec_id`: EIP-1559 transaction with zero
+    /// priority fee since London and legacy transaction before it.
+    pub fn default_for(spec_id: SpecId) -> TxEnv {
+        TxEnv {
+            gas_priority_fee: SpecId::enabled(spec_id, SpecId::LONDON).then(U256::zero),
+            ..Default::default()
         }
     }
 }
 
 impl Env {
+    /// Reset transaction to [TxEnv::default_for] spec of config, keeping config and block.
+    /// Call it before filling fields of next transaction so that nothing is left from
+    /// previous one, for example its access list.
+    pub fn clear_tx(&mut self) {
+        self.tx = TxEnv::default_for(self.cfg.spec_id);
+    }
+
+    /// Address returned by ORIGIN opcode.
+    pub fn origin(&self) -> H160 {
+        self.tx.origin_override.unwrap_or(self.tx.caller)
+    }
+
+    /// Chain id used by CHAINID opcode and transaction chain id check.
+    pub fn chain_id(&self) -> U256 {
+        self.tx.chain_id_override.unwrap_or(self.cfg.chain_id)
+    }
+
     pub fn effective_gas_price(&self) -> U256 {
         if self.tx.gas_priority_fee.is_none() {
             self.tx.gas_price
@@ -306,6 +429,44 @@ impl Env {
             )
         }
     }
+
+    pub fn basefee_wei(&self) -> Wei {
+        Wei(self.block.basefee)
+    }
+
+    pub fn set_basefee_wei(&mut self, basefee: impl Into<Wei>) {
+        self.block.basefee = basefee.into().0;
+    }
+
+    pub fn gas_price_wei(&self) -> Wei {
+        Wei(self.tx.gas_price)
+    }
+
+    pub fn set_gas_price_wei(&mut self, gas_price: impl Into<Wei>) {
+        self.tx.gas_price = gas_price.into().0;
+    }
+
+    pub fn gas_priority_fee_wei(&self) -> Option<Wei> {
+        self.tx.gas_priority_fee.map(Wei)
+    }
+
+    /// Set priority fee of EIP-1559 transaction, `None` makes transaction legacy.
+    pub fn set_gas_priority_fee_wei(&mut self, gas_priority_fee: Option<impl Into<Wei>>) {
+        self.tx.gas_priority_fee = gas_priority_fee.map(|fee| fee.into().0);
+    }
+
+    pub fn tx_value_wei(&self) -> Wei {
+        Wei(self.tx.value)
+    }
+
+    pub fn set_tx_value_wei(&mut self, value: impl Into<Wei>) {
+        self.tx.value = value.into().0;
+    }
+
+    /// Typed [Env::effective_gas_price].
+    pub fn effective_gas_price_wei(&self) -> Wei {
+        Wei(self.effective_gas_price())
+    }
 }
 
 /// Transfer from source to target, with given value.
@@ -329,13 +490,22 @@ pub struct Log {
     pub data: Bytes,
 }
 
-#[derive(Default)]
+#[derive(Clone, Copy, Debug, Default)]
 #[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SelfDestructResult {
     pub had_value: bool,
+    /// Balance of destroyed account that is swept to target, or burned if account is its own
+    /// beneficiary.
+    pub value: U256,
     pub target_exists: bool,
+    /// Target didn't exist and is created by receiving swept value.
+    pub target_created: bool,
+    /// Target was cold, so its access was charged as cold (EIP-2929).
     pub is_cold: bool,
     pub previously_destroyed: bool,
+    /// Balance that is burned because account is its own beneficiary. Balance is taken from
+    /// the account but not given to anyone, so it disappears from the state.
+    pub burned_balance: U256,
 }
 /// Serde functions to serde as [bytes::Bytes] hex string
 #[cfg(feature = "with-serde")]
@@ -415,6 +585,136 @@ pub struct ExecutionResult {
     pub gas_used: u64,
     pub gas_refunded: u64,
     pub logs: Vec<Log>,
+    /// Set if output or logs were truncated because of result limits of [CfgEnv].
+    pub truncated: Truncated,
+    /// Gas of transaction with gas limit, gas spent before refund and refund that was applied.
+    /// Zero if transaction was rejected.
+    pub gas: Gas,
+    /// Split of gas spent by transaction, zero if transaction was rejected.
+    pub gas_breakdown: GasBreakdown,
+    /// Amounts paid out when transaction is finalized, zero if transaction was rejected.
+    pub fees: FeeSettlement,
+    /// Contract deployed by successful create transaction.
+    pub deployment: Option<Deployment>,
+    /// Hashes of blocks read by BLOCKHASH, sorted by block number. Includes reads of reverted
+    /// frames, so these are the ancestor headers needed to replay the transaction.
+    pub block_hashes: Vec<(U256, H256)>,
+    /// Accounts touched by transaction, sorted. Empty touched accounts are marked destroyed in
+    /// state since Spurious Dragon, see [ZeroValueTouch].
+    pub touched: Vec<H160>,
+    /// Creates that failed with [Return::CreateCollision], in all frames including reverted
+    /// ones, in order they happened.
+    pub create_collisions: Vec<CreateCollision>,
+    /// Statistics of execution. `None` if transaction was rejected or if crate is built
+    /// without `execution_stats` feature.
+    pub stats: Option<ExecutionStats>,
+}
+
+/// Create that failed with [Return::CreateCollision] and account that was found at created
+/// address.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CreateCollision {
+    pub address: H160,
+    pub reason: CollisionReason,
+    /// Nonce of existing account.
+    pub nonce: u64,
+    /// Code hash of existing account.
+    pub code_hash: H256,
+    /// Address was warm before create, because it is in access list or it was accessed earlier
+    /// in transaction. Warm address doesn't collide by itself, it only tells how it was loaded.
+    pub was_warm: bool,
+}
+
+/// Why created address collides with existing account, first one that applies.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CollisionReason {
+    /// Account has code.
+    Code,
+    /// Account has non zero nonce.
+    Nonce,
+    /// Address is active precompile.
+    Precompile,
+}
+
+/// Split of gas that transaction spent before refund. Parts add up to [Gas::spend].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GasBreakdown {
+    /// Gas paid before execution, see [crate::initial_tx_gas].
+    pub intrinsic: u64,
+    /// Gas of executed instructions and precompiles in all frames.
+    pub execution: u64,
+    /// Gas paid for storing code of created contracts, in all frames.
+    pub code_deposit: u64,
+}
+
+/// Amounts of ether moved when transaction is finalized. Caller paid `gas_limit * gas_price`
+/// and fee of [crate::TxFeeHook] upfront, that payment is split into these parts.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FeeSettlement {
+    /// Unused gas and gas refund credited back to caller, at effective gas price.
+    pub caller_refund: U256,
+    /// Priority fee credited to coinbase, whole gas price before London.
+    pub coinbase_reward: U256,
+    /// EIP-1559 basefee of used gas and fee of [crate::TxFeeHook] that was not distributed.
+    pub burned: U256,
+    /// Fee of [crate::TxFeeHook] credited to accounts it returned.
+    pub distributed: U256,
+}
+
+impl FeeSettlement {
+    /// Fee that caller paid in the end: upfront payment without [FeeSettlement::caller_refund].
+    pub fn paid(&self) -> Wei {
+        Wei(self.coinbase_reward + self.burned + self.distributed)
+    }
+}
+
+/// Contract deployed by create transaction.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Deployment {
+    /// Runtime code returned by init code and stored under created address.
+    pub code: Bytes,
+    /// Gas paid for storing `code`. Code deposit of contracts created by init code is not
+    /// included, see [GasBreakdown::code_deposit] for all frames.
+    pub code_deposit_gas: u64,
+}
+
+/// Statistics of transaction execution that can be used to estimate its cost without tracing.
+/// Collected only with `execution_stats` feature, see [ExecutionResult::stats].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ExecutionStats {
+    /// Number of executed instructions in all frames.
+    pub instructions: u64,
+    /// Largest memory of a single frame, in bytes.
+    pub memory_high_water: usize,
+    /// Largest number of items on stack of a single frame.
+    pub max_stack_depth: usize,
+    /// Number of call and create frames including the first one and calls of precompiles.
+    pub calls: u64,
+}
+
+#[cfg(feature = "execution_stats")]
+impl ExecutionStats {
+    /// Add statistics of frame that finished executing.
+    pub(crate) fn add_frame(&mut self, interp: &crate::Interpreter) {
+        self.instructions += interp.stats.instructions;
+        self.memory_high_water = self.memory_high_water.max(interp.memory.len());
+        self.max_stack_depth = self.max_stack_depth.max(interp.max_stack_depth);
+    }
+}
+
+/// Original sizes of parts of [ExecutionResult] that were truncated.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Truncated {
+    /// Size of output before it was truncated.
+    pub output_size: Option<usize>,
+    /// Total size of log data before it was truncated.
+    pub log_data_size: Option<usize>,
+}
+
+impl Truncated {
+    pub fn is_truncated(&self) -> bool {
+        self.output_size.is_some() || self.log_data_size.is_some()
+    }
 }
 
 impl ExecutionResult {
@@ -425,6 +725,609 @@ impl ExecutionResult {
             gas_used: 0,
             gas_refunded: 0,
             logs: Vec::new(),
+            truncated: Truncated::default(),
+            gas: Gas::new(0),
+            gas_breakdown: GasBreakdown::default(),
+            fees: FeeSettlement::default(),
+            deployment: None,
+            block_hashes: Vec::new(),
+            touched: Vec::new(),
+            create_collisions: Vec::new(),
+            stats: None,
+        }
+    }
+
+    /// Truncate output and log data to limits, see [CfgEnv::limit_result_output_size] and
+    /// [CfgEnv::limit_result_log_data_size]. Original sizes are recorded in `truncated`.
+    pub fn truncate(&mut self, output_limit: Option<usize>, log_data_limit: Option<usize>) {
+        let output = match &mut self.out {
+            TransactOut::None => None,
+            TransactOut::Call(out) => Some(out),
+            TransactOut::Create(out, _) => Some(out),
+        };
+        if let (Some(output), Some(limit)) = (output, output_limit) {
+            if output.len() > limit {
+                self.truncated.output_size = Some(output.len());
+                output.truncate(limit);
+            }
+        }
+        if let Some(mut remaining) = log_data_limit {
+            let size: usize = self.logs.iter().map(|log| log.data.len()).sum();
+            if size > remaining {
+                for log in &mut self.logs {
+                    log.data.truncate(remaining);
+                    remaining -= log.data.len();
+                }
+                self.truncated.log_data_size = Some(size);
+            }
+        }
+    }
+
+    /// Output of call or create.
+    pub fn output(&self) -> Option<&Bytes> {
+        match &self.out {
+            TransactOut::None => None,
+            TransactOut::Call(out) => Some(out),
+            TransactOut::Create(out, _) => Some(out),
         }
     }
+
+    /// Whether call or create finished successfully.
+    pub fn is_success(&self) -> bool {
+        matches!(self.exit_reason, crate::return_ok!())
+    }
+
+    /// Address derived for create transaction. It is set even if deployment failed, for example
+    /// on collision or revert of init code, use [Self::is_success] to check if code was deployed.
+    /// `None` for calls and if transaction failed before address was derived.
+    pub fn created_address(&self) -> Option<H160> {
+        match self.out {
+            TransactOut::Create(_, address) => address,
+            _ => None,
+        }
+    }
+
+    /// Decode revert reason from output if transaction reverted.
+    pub fn revert_reason(&self) -> Option<RevertReason> {
+        if self.exit_reason != Return::Revert {
+            return None;
+        }
+        Some(RevertReason::decode(
+            self.output().cloned().unwrap_or_default(),
+        ))
+    }
+}
+
+/// Decoded revert data of standard Solidity errors.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RevertReason {
+    /// `Error(string)` emitted by `revert("reason")` and `require(cond, "reason")`.
+    Error(String),
+    /// `Panic(uint256)` emitted by failed assert, overflow, division by zero etc.
+    Panic(U256),
+    /// Custom error or any other data that can't be decoded, returned as is.
+    Custom(Bytes),
+}
+
+impl RevertReason {
+    /// Selector of `Error(string)`.
+    pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+    /// Selector of `Panic(uint256)`.
+    pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+    /// Decode revert data. Data that is not `Error(string)` or `Panic(uint256)` is returned as
+    /// [RevertReason::Custom].
+    pub fn decode(data: Bytes) -> Self {
+        if data.len() >= 4 {
+            let (selector, args) = data.split_at(4);
+            if selector == Self::ERROR_SELECTOR {
+                if let Some(reason) = Self::decode_string(args) {
+                    return Self::Error(reason);
+                }
+            } else if selector == Self::PANIC_SELECTOR && args.len() == 32 {
+                return Self::Panic(U256::from_big_endian(args));
+            }
+        }
+        Self::Custom(data)
+    }
+
+    /// Decode ABI encoded string that is single argument of function.
+    fn decode_string(args: &[u8]) -> Option<String> {
+        let word = |offset: usize| -> Option<usize> {
+            let word = args.get(offset..offset.checked_add(32)?)?;
+            // values larger than usize are invalid in any case.
+            if word[..24].iter().any(|b| *b != 0) {
+                return None;
+            }
+            Some(u64::from_be_bytes(word[24..].try_into().unwrap()) as usize)
+        };
+        let offset = word(0)?;
+        let len = word(offset)?;
+        let start = offset.checked_add(32)?;
+        let data = args.get(start..start.checked_add(len)?)?;
+        String::from_utf8(data.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::InMemoryDB, AccountInfo};
+
+    fn call_chain_id(tx_chain_id: Option<u64>, chain_id_override: Option<U256>) -> ExecutionResult {
+        let contract = H160::from_low_u64_be(0x1000);
+        // CHAINID PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+        let code = Bytes::from_static(&[0x46, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3]);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code)),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.chain_id = 1.into();
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.chain_id = tx_chain_id;
+        evm.env.tx.chain_id_override = chain_id_override;
+        evm.transact().0
+    }
+
+    #[test]
+    fn test_chain_id_override() {
+        let out = |result: ExecutionResult| match result.out {
+            TransactOut::Call(out) => U256::from_big_endian(&out),
+            _ => panic!("expected call output"),
+        };
+        assert_eq!(out(call_chain_id(None, None)), 1.into());
+        assert_eq!(out(call_chain_id(Some(1), None)), 1.into());
+        assert_eq!(out(call_chain_id(Some(10), Some(10.into()))), 10.into());
+        assert_eq!(
+            call_chain_id(Some(1), Some(10.into())).exit_reason,
+            Return::InvalidChainId
+        );
+    }
+
+    #[test]
+    fn test_origin_override() {
+        let caller = H160::from_low_u64_be(0x2000);
+        let origin = H160::from_low_u64_be(0x3000);
+        let contract = H160::from_low_u64_be(0x1000);
+        // ORIGIN PUSH1 0 MSTORE CALLER PUSH1 32 MSTORE PUSH1 64 PUSH1 0 RETURN
+        let code = Bytes::from_static(&[
+            0x32, 0x60, 0x00, 0x52, 0x33, 0x60, 0x20, 0x52, 0x60, 0x40, 0x60, 0x00, 0xf3,
+        ]);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code)),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = caller;
+        evm.env.tx.origin_override = Some(origin);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        let (result, state) = evm.transact();
+        match result.out {
+            TransactOut::Call(out) => {
+                assert_eq!(H160::from_slice(&out[12..32]), origin);
+                assert_eq!(H160::from_slice(&out[44..64]), caller);
+            }
+            _ => panic!("expected call output"),
+        }
+        // nonce is bumped on caller.
+        assert_eq!(state[&caller].info.nonce, 1);
+        assert!(!state.contains_key(&origin));
+    }
+
+    #[test]
+    fn test_revert_reason() {
+        let mut error = RevertReason::ERROR_SELECTOR.to_vec();
+        error.extend_from_slice(&H256::from_low_u64_be(0x20).0);
+        error.extend_from_slice(&H256::from_low_u64_be(4).0);
+        let mut reason = [0u8; 32];
+        reason[..4].copy_from_slice(b"fail");
+        error.extend_from_slice(&reason);
+        assert_eq!(
+            RevertReason::decode(error.clone().into()),
+            RevertReason::Error("fail".into())
+        );
+        // truncated string
+        error.truncate(4 + 64 + 2);
+        assert_eq!(
+            RevertReason::decode(error.clone().into()),
+            RevertReason::Custom(error.into())
+        );
+
+        let mut panic = RevertReason::PANIC_SELECTOR.to_vec();
+        panic.extend_from_slice(&H256::from_low_u64_be(0x11).0);
+        assert_eq!(
+            RevertReason::decode(panic.into()),
+            RevertReason::Panic(0x11.into())
+        );
+
+        let custom = Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef, 0x01]);
+        let mut result = ExecutionResult::new_with_reason(Return::Revert);
+        result.out = TransactOut::Call(custom.clone());
+        assert_eq!(result.revert_reason(), Some(RevertReason::Custom(custom)));
+        result.exit_reason = Return::Stop;
+        assert_eq!(result.revert_reason(), None);
+    }
+
+    #[test]
+    fn test_code_size_limits() {
+        let create = |cfg: CfgEnv, size: u16| {
+            // PUSH2 size PUSH1 0 RETURN
+            let [hi, lo] = size.to_be_bytes();
+            let mut evm = crate::new();
+            evm.database(InMemoryDB::default());
+            evm.env.cfg = cfg;
+            evm.env.tx.transact_to = TransactTo::create();
+            evm.env.tx.gas_limit = 10_000_000;
+            evm.env.tx.data = Bytes::from(vec![0x61, hi, lo, 0x60, 0x00, 0xf3]);
+            evm.transact().0.exit_reason
+        };
+        let size = MAX_CODE_SIZE as u16 + 1;
+        assert_eq!(create(CfgEnv::default(), size), Return::CreateContractLimit);
+        let cfg = CfgEnv {
+            limit_contract_code_size: Some(0x7000),
+            ..Default::default()
+        };
+        assert_eq!(create(cfg.clone(), size), Return::Continue);
+
+        let cfg = CfgEnv {
+            limit_initcode_size: Some(5),
+            ..cfg
+        };
+        assert_eq!(create(cfg, size), Return::CreateInitcodeSizeLimit);
+    }
+
+    #[test]
+    fn test_created_address() {
+        let caller = H160::from_low_u64_be(0x1000);
+        let address = crate::create_address(caller, 0);
+        let create = |db: InMemoryDB, init_code: &'static [u8]| {
+            let mut evm = crate::new();
+            evm.database(db);
+            evm.env.tx.caller = caller;
+            evm.env.tx.transact_to = TransactTo::create();
+            evm.env.tx.gas_limit = 100_000;
+            evm.env.tx.data = Bytes::from_static(init_code);
+            evm.transact().0
+        };
+        // PUSH1 0 PUSH1 0 RETURN
+        let result = create(InMemoryDB::default(), &[0x60, 0x00, 0x60, 0x00, 0xf3]);
+        assert!(result.is_success());
+        assert_eq!(result.created_address(), Some(address));
+
+        // PUSH1 0 PUSH1 0 REVERT
+        let result = create(InMemoryDB::default(), &[0x60, 0x00, 0x60, 0x00, 0xfd]);
+        assert_eq!(result.exit_reason, Return::Revert);
+        assert!(!result.is_success());
+        assert_eq!(result.created_address(), Some(address));
+
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(address, AccountInfo::new(U256::zero(), 1, Bytecode::new()));
+        let result = create(db, &[]);
+        assert_eq!(result.exit_reason, Return::CreateCollision);
+        assert_eq!(result.created_address(), Some(address));
+        assert_eq!(
+            result.create_collisions,
+            vec![CreateCollision {
+                address,
+                reason: CollisionReason::Nonce,
+                nonce: 1,
+                code_hash: KECCAK_EMPTY,
+                was_warm: false,
+            }]
+        );
+
+        // STOP
+        let code = Bytecode::new_raw(Bytes::from_static(&[0x00]));
+        let code_hash = code.hash();
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(address, AccountInfo::new(U256::zero(), 0, code));
+        let result = create(db, &[]);
+        let collision = &result.create_collisions[0];
+        assert_eq!(collision.reason, CollisionReason::Code);
+        assert_eq!((collision.nonce, collision.code_hash), (0, code_hash));
+
+        // address in access list is warm, it still collides only because of its nonce.
+        let mut evm = crate::new();
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(address, AccountInfo::new(U256::zero(), 1, Bytecode::new()));
+        evm.database(db);
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::create();
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.access_list = vec![(address, Vec::new())];
+        let collision = &evm.transact().0.create_collisions[0];
+        assert_eq!(collision.reason, CollisionReason::Nonce);
+        assert!(collision.was_warm);
+
+        // address is not derived if transaction is rejected.
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            caller,
+            AccountInfo::new(U256::zero(), u64::MAX, Bytecode::new()),
+        );
+        let result = create(db, &[]);
+        assert_eq!(result.exit_reason, Return::NonceOverflow);
+        assert_eq!(result.created_address(), None);
+    }
+
+    #[test]
+    fn test_gas_breakdown() {
+        let mut evm = crate::new();
+        evm.database(InMemoryDB::default());
+        evm.env.tx.transact_to = TransactTo::create();
+        evm.env.tx.gas_limit = 100_000;
+        // PUSH1 1 PUSH1 0 RETURN
+        evm.env.tx.data = Bytes::from_static(&[0x60, 0x01, 0x60, 0x00, 0xf3]);
+        let result = evm.transact().0;
+        assert_eq!(
+            result.gas_breakdown,
+            GasBreakdown {
+                intrinsic: 53_000 + 4 * 16 + 4,
+                execution: 3 + 3 + 3,
+                code_deposit: 200,
+            }
+        );
+        assert_eq!(result.gas.limit(), 100_000);
+        assert_eq!(result.gas.spend(), result.gas_used + result.gas_refunded);
+        assert_eq!(
+            result.deployment,
+            Some(Deployment {
+                code: Bytes::from_static(&[0]),
+                code_deposit_gas: 200,
+            })
+        );
+
+        // deployment has code deposit of the transaction frame only.
+        // PUSH5 (PUSH1 2 PUSH1 0 RETURN) PUSH1 0 MSTORE, CREATE of it, PUSH1 1 PUSH1 0 RETURN
+        evm.env.tx.data = Bytes::from_static(&[
+            0x64, 0x60, 0x02, 0x60, 0x00, 0xf3, 0x60, 0x00, 0x52, 0x60, 0x05, 0x60, 0x1b, 0x60,
+            0x00, 0xf0, 0x50, 0x60, 0x01, 0x60, 0x00, 0xf3,
+        ]);
+        let result = evm.transact().0;
+        assert_eq!(result.gas_breakdown.code_deposit, 600);
+        assert_eq!(result.deployment.unwrap().code_deposit_gas, 200);
+
+        // refund that was applied is part of final gas.
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(
+                U256::zero(),
+                0,
+                // PUSH1 0 PUSH1 0 SSTORE
+                Bytecode::new_raw(Bytes::from_static(&[0x60, 0x00, 0x60, 0x00, 0x55])),
+            ),
+        );
+        db.insert_account_storage(contract, U256::zero(), U256::one())
+            .unwrap();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.data = Bytes::new();
+        let result = evm.transact().0;
+        assert!(result.gas_refunded > 0);
+        assert_eq!(result.gas.refunded(), result.gas_refunded as i64);
+        assert_eq!(result.gas_breakdown.intrinsic, 21_000);
+        assert_eq!(result.gas_breakdown.code_deposit, 0);
+        assert_eq!(result.deployment, None);
+        assert_eq!(result.gas.spend(), 21_000 + result.gas_breakdown.execution);
+
+        let result = ExecutionResult::new_with_reason(Return::OutOfGas);
+        assert_eq!(result.gas.spend(), 0);
+        assert_eq!(result.gas_breakdown, GasBreakdown::default());
+    }
+
+    #[test]
+    fn test_fee_settlement() {
+        let caller = H160::from_low_u64_be(0x1000);
+        let coinbase = H160::from_low_u64_be(0x2000);
+        let transact = |spec_id| {
+            let mut db = InMemoryDB::default();
+            db.insert_account_info(caller, AccountInfo::from_balance(U256::from(1_000_000)));
+            let mut evm = crate::new();
+            evm.database(db);
+            evm.env.cfg.spec_id = spec_id;
+            evm.env.block.coinbase = coinbase;
+            evm.env.block.basefee = U256::from(10);
+            evm.env.tx.caller = caller;
+            evm.env.tx.transact_to = TransactTo::Call(H160::from_low_u64_be(0x3000));
+            evm.env.tx.gas_limit = 30_000;
+            evm.env.tx.gas_price = U256::from(15);
+            evm.env.tx.gas_priority_fee = Some(U256::from(2));
+            evm.transact()
+        };
+
+        let (result, state) = transact(SpecId::LONDON);
+        assert_eq!(result.gas_used, 21_000);
+        assert_eq!(
+            result.fees,
+            FeeSettlement {
+                caller_refund: U256::from(12 * 9_000),
+                coinbase_reward: U256::from(2 * 21_000),
+                burned: U256::from(10 * 21_000),
+                distributed: U256::zero(),
+            }
+        );
+        assert_eq!(state[&coinbase].info.balance, result.fees.coinbase_reward);
+        assert_eq!(
+            state[&caller].info.balance,
+            U256::from(1_000_000 - 12 * 21_000)
+        );
+
+        // whole gas price goes to coinbase before London.
+        let (result, _) = transact(SpecId::BERLIN);
+        assert_eq!(result.fees.coinbase_reward, U256::from(12 * 21_000));
+        assert_eq!(result.fees.burned, U256::zero());
+
+        let result = ExecutionResult::new_with_reason(Return::OutOfGas);
+        assert_eq!(result.fees, FeeSettlement::default());
+    }
+
+    #[test]
+    fn test_result_truncation() {
+        use crate::opcode::*;
+        // log 100 bytes twice and return 1000 bytes.
+        let code = [
+            &[PUSH1, 100, PUSH1, 0, LOG0, PUSH1, 100, PUSH1, 0, LOG0][..],
+            &[PUSH2, 0x03, 0xe8, PUSH1, 0, RETURN],
+        ]
+        .concat();
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 0, crate::Bytecode::new_raw(code.into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+
+        let result = evm.transact().0;
+        assert_eq!(result.output().unwrap().len(), 1000);
+        assert!(!result.truncated.is_truncated());
+
+        evm.env.cfg.limit_result_output_size = Some(64);
+        evm.env.cfg.limit_result_log_data_size = Some(150);
+        let result = evm.transact().0;
+        assert_eq!(result.exit_reason, Return::Return);
+        assert_eq!(result.output().unwrap().len(), 64);
+        let log_sizes: Vec<_> = result.logs.iter().map(|log| log.data.len()).collect();
+        assert_eq!(log_sizes, vec![100, 50]);
+        assert_eq!(
+            result.truncated,
+            Truncated {
+                output_size: Some(1000),
+                log_data_size: Some(200),
+            }
+        );
+    }
+
+    #[test]
+    fn test_warm_addresses() {
+        use crate::opcode::*;
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(
+                U256::zero(),
+                0,
+                crate::Bytecode::new_raw(vec![PUSH2, 0x20, 0, BALANCE, POP, STOP].into()),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+
+        let cold = evm.transact().0.gas_used;
+        evm.env.cfg.warm_addresses = vec![H160::from_low_u64_be(0x2000)];
+        let (result, state) = evm.transact();
+        assert_eq!(cold - result.gas_used, 2600 - 100);
+        // warm account is not touched.
+        assert!(!state.contains_key(&H160::from_low_u64_be(0x2000)));
+    }
+
+    #[test]
+    fn test_block_hashes() {
+        use crate::{db::Database, opcode::*};
+        let contract = H160::from_low_u64_be(0x1000);
+        // BLOCKHASH of blocks 5, 4, 5 again and of future block 20, then REVERT.
+        let mut code = Vec::new();
+        for number in [5, 4, 5, 20] {
+            code.extend([PUSH1, number, BLOCKHASH, POP]);
+        }
+        code.extend([PUSH1, 0, PUSH1, 0, REVERT]);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 0, crate::Bytecode::new_raw(code.into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.block.number = 10.into();
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+
+        let result = evm.transact().0;
+        assert_eq!(result.exit_reason, Return::Revert);
+        // reads are kept although frame reverted, blocks out of range are not read.
+        let mut db = InMemoryDB::default();
+        let expected: Vec<_> = [4, 5]
+            .into_iter()
+            .map(|number| (U256::from(number), db.block_hash(number.into()).unwrap()))
+            .collect();
+        assert_eq!(result.block_hashes, expected);
+    }
+
+    #[test]
+    fn test_reuse_env() {
+        assert_eq!(TxEnv::default_for(SpecId::BERLIN).gas_priority_fee, None);
+        assert_eq!(
+            TxEnv::default_for(SpecId::LONDON).gas_priority_fee,
+            Some(U256::zero())
+        );
+
+        let mut evm = crate::new();
+        evm.database(InMemoryDB::default());
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.access_list = vec![(H160::from_low_u64_be(0x2000), Vec::new())];
+        assert_eq!(evm.transact().0.gas_used, 21_000 + 2_400);
+
+        // access list of previous transaction is not used.
+        let tx = TxEnv {
+            gas_limit: 100_000,
+            ..TxEnv::default_for(evm.env.cfg.spec_id)
+        };
+        assert_eq!(evm.transact_with(tx).0.gas_used, 21_000);
+
+        evm.env.tx.access_list = vec![(H160::from_low_u64_be(0x2000), Vec::new())];
+        evm.env.clear_tx();
+        assert!(evm.env.tx.access_list.is_empty());
+        assert_eq!(evm.env.tx.gas_priority_fee, Some(U256::zero()));
+    }
+
+    #[test]
+    #[cfg(feature = "execution_stats")]
+    fn test_execution_stats() {
+        use crate::{opcode::*, Bytecode};
+        let contract = H160::from_low_u64_be(0x1000);
+        let code = [
+            &[PUSH1, 1, PUSH1, 2, ADD, PUSH1, 0, MSTORE][..],
+            // call identity precompile.
+            &[PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 0],
+            &[PUSH1, 4, GAS, CALL, STOP],
+        ]
+        .concat();
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code.into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        let (result, _) = evm.transact();
+        assert_eq!(result.exit_reason, Return::Stop);
+        assert_eq!(
+            result.stats,
+            Some(ExecutionStats {
+                instructions: 14,
+                memory_high_water: 32,
+                max_stack_depth: 7,
+                calls: 2,
+            })
+        );
+    }
 }
@@ -0,0 +1,120 @@
+use crate::{alloc::vec::Vec, ExecutionResult, Return, SpecId};
+use core::fmt;
+
+/// Result of transaction executed under one spec.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SpecOutcome {
+    pub spec_id: SpecId,
+    pub exit_reason: Return,
+    pub gas_used: u64,
+    pub gas_refunded: u64,
+}
+
+impl SpecOutcome {
+    pub fn new(spec_id: SpecId, result: &ExecutionResult) -> Self {
+        Self {
+            spec_id,
+            exit_reason: result.exit_reason,
+            gas_used: result.gas_used,
+            gas_refunded: result.gas_refunded,
+        }
+    }
+
+    /// Same exit reason and gas as other outcome.
+    pub fn same_behavior(&self, other: &SpecOutcome) -> bool {
+        self.exit_reason == other.exit_reason
+            && self.gas_used == other.gas_used
+            && self.gas_refunded == other.gas_refunded
+    }
+}
+
+/// Results of the same transaction executed under every spec, created by
+/// [crate::EVM::transact_all_specs]. Displayed as table with one row per spec where rows
+/// that differ from previous spec are marked with `*`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SpecReport {
+    /// Outcomes in order of hardfork activation.
+    pub outcomes: Vec<SpecOutcome>,
+}
+
+impl SpecReport {
+    pub fn get(&self, spec_id: SpecId) -> Option<&SpecOutcome> {
+        self.outcomes
+            .iter()
+            .find(|outcome| outcome.spec_id == spec_id)
+    }
+
+    /// Specs that changed behavior of transaction compared to previous spec.
+    pub fn changes(&self) -> impl Iterator<Item = &SpecOutcome> {
+        self.outcomes
+            .windows(2)
+            .filter(|pair| !pair[0].same_behavior(&pair[1]))
+            .map(|pair| &pair[1])
+    }
+}
+
+impl fmt::Display for SpecReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "  {:<18} {:<32} {:>12} {:>12}",
+            "spec", "result", "gas used", "gas refunded"
+        )?;
+        let mut previous: Option<&SpecOutcome> = None;
+        for outcome in &self.outcomes {
+            let changed = matches!(previous, Some(previous) if !previous.same_behavior(outcome));
+            writeln!(
+                f,
+                "{} {:<18} {:<32} {:>12} {:>12}",
+                if changed { '*' } else { ' ' },
+                // pad strings only, Debug of enums ignores width.
+                alloc::format!("{:?}", outcome.spec_id),
+                alloc::format!("{:?}", outcome.exit_reason),
+                outcome.gas_used,
+                outcome.gas_refunded
+            )?;
+            previous = Some(outcome);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{db::InMemoryDB, opcode::*, AccountInfo, Bytecode, Return, SpecId, TransactTo};
+    use primitive_types::{H160, U256};
+
+    #[test]
+    fn test_transact_all_specs() {
+        // SHL is introduced in Constantinople.
+        let code = [PUSH1, 1, PUSH1, 1, SHL, POP, STOP];
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code.to_vec().into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+
+        let report = evm.transact_all_specs();
+        assert_eq!(report.outcomes.len(), SpecId::ALL.len());
+        for outcome in &report.outcomes {
+            let expected = if SpecId::enabled(outcome.spec_id, SpecId::CONSTANTINOPLE) {
+                Return::Stop
+            } else {
+                Return::NotActivated
+            };
+            assert_eq!(outcome.exit_reason, expected, "{:?}", outcome.spec_id);
+        }
+        assert_eq!(report.get(SpecId::LONDON).unwrap().gas_used, 21_000 + 11);
+
+        let changes: Vec<_> = report.changes().map(|outcome| outcome.spec_id).collect();
+        assert_eq!(changes, vec![SpecId::CONSTANTINOPLE]);
+        let table = alloc::format!("{}", report);
+        assert_eq!(table.lines().count(), SpecId::ALL.len() + 1);
+        assert!(table.contains("* CONSTANTINOPLE"));
+    }
+}
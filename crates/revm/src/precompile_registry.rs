@@ -0,0 +1,162 @@
+use crate::{precompiles::Precompile, Env, SpecFlags};
+use alloc::{collections::BTreeMap, vec::Vec};
+use primitive_types::H160;
+use revm_precompiles::Precompiles;
+
+/// Precompiles that chains add on top of precompiles of the spec, keyed by chain id.
+///
+/// Simulators of multiple chains register behavior of every chain once, like system
+/// contracts of rollups that are implemented as precompiles, and select it for the EVM with
+/// [crate::EVM::select_precompiles]. Precompile registered for a chain replaces precompile of
+/// the spec at the same address.
+#[derive(Clone, Debug, Default)]
+pub struct PrecompileRegistry {
+    chains: BTreeMap<u64, Vec<(H160, Precompile)>>,
+}
+
+impl PrecompileRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add precompile at `address` for chain `chain_id`, replacing one already registered
+    /// there.
+    pub fn insert(&mut self, chain_id: u64, address: H160, precompile: Precompile) {
+        let chain = self.chains.entry(chain_id).or_default();
+        chain.retain(|(registered, _)| *registered != address);
+        chain.push((address, precompile));
+    }
+
+    /// Builder variant of [PrecompileRegistry::insert].
+    pub fn with(mut self, chain_id: u64, address: H160, precompile: Precompile) -> Self {
+        self.insert(chain_id, address, precompile);
+        self
+    }
+
+    /// Precompiles registered for chain `chain_id`, in order of registration.
+    pub fn get(&self, chain_id: u64) -> Option<&[(H160, Precompile)]> {
+        self.chains.get(&chain_id).map(Vec::as_slice)
+    }
+
+    pub fn contains(&self, chain_id: u64) -> bool {
+        self.chains.contains_key(&chain_id)
+    }
+
+    /// Chain ids that have precompiles registered, in ascending order.
+    pub fn chain_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.chains.keys().copied()
+    }
+
+    /// Precompiles of `spec` extended with ones registered for `chain_id`. `None` if nothing
+    /// is registered for the chain, so precompiles of the spec can be used without a copy.
+    pub fn precompiles(
+        &self,
+        chain_id: u64,
+        spec: revm_precompiles::SpecId,
+    ) -> Option<Precompiles> {
+        let chain = self.chains.get(&chain_id)?;
+        let mut precompiles = Precompiles::new(spec).clone();
+        for (address, precompile) in chain {
+            precompiles.insert(*address, precompile.clone());
+        }
+        Some(precompiles)
+    }
+
+    /// Precompiles for chain and spec configured in `env`, see [PrecompileRegistry::precompiles].
+    pub fn precompiles_for_env(&self, env: &Env) -> Option<Precompiles> {
+        if env.cfg.chain_id.bits() > 64 {
+            return None;
+        }
+        let flags = env
+            .cfg
+            .custom_spec
+            .unwrap_or_else(|| SpecFlags::new(env.cfg.spec_id));
+        self.precompiles(env.cfg.chain_id.as_u64(), flags.to_precompile_id())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::InMemoryDB,
+        opcode::{BALANCE, PUSH1, STOP},
+        precompiles::{PrecompileOutput, PrecompileResult},
+        AccountInfo, Bytecode, Return, SpecId, TransactOut, TransactTo,
+    };
+    use alloc::vec;
+    use primitive_types::U256;
+
+    fn system_precompile(_input: &[u8], _gas_limit: u64) -> PrecompileResult {
+        Ok(PrecompileOutput::without_logs(10, vec![42]))
+    }
+
+    #[test]
+    fn test_precompile_registry() {
+        let system = H160::from_low_u64_be(0x64);
+        let ecrecover = H160::from_low_u64_be(1);
+        let registry = PrecompileRegistry::new()
+            .with(42161, system, Precompile::Standard(system_precompile))
+            .with(10, ecrecover, Precompile::Standard(system_precompile));
+        assert_eq!(registry.chain_ids().collect::<Vec<_>>(), vec![10, 42161]);
+        assert!(registry
+            .precompiles(1, revm_precompiles::SpecId::BERLIN)
+            .is_none());
+        let precompiles = registry
+            .precompiles(42161, revm_precompiles::SpecId::BERLIN)
+            .unwrap();
+        assert!(precompiles.contains(&system) && precompiles.contains(&ecrecover));
+        assert_eq!(precompiles.len(), Precompiles::berlin().len() + 1);
+
+        let mut evm = crate::new::<InMemoryDB>();
+        evm.database(InMemoryDB::default());
+        evm.env.cfg.spec_id = SpecId::LONDON;
+        evm.env.tx.transact_to = TransactTo::Call(system);
+        evm.env.tx.gas_limit = 100_000;
+        let mut run = |chain_id: u64| {
+            evm.env.cfg.chain_id = chain_id.into();
+            evm.select_precompiles(&registry);
+            let (result, _) = evm.transact();
+            match result.out {
+                TransactOut::Call(out) => (result.exit_reason, out.to_vec()),
+                _ => panic!("expected call output"),
+            }
+        };
+        assert_eq!(run(42161), (Return::Continue, vec![42]));
+        // on other chain the address is an empty account.
+        assert_eq!(run(1), (Return::Stop, vec![]));
+    }
+
+    #[test]
+    fn test_registered_precompile_warm() {
+        let system = H160::from_low_u64_be(0x64);
+        let registry =
+            PrecompileRegistry::new().with(42161, system, Precompile::Standard(system_precompile));
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(
+                U256::zero(),
+                0,
+                Bytecode::new_raw(vec![PUSH1, 0x64, BALANCE, STOP].into()),
+            ),
+        );
+
+        let mut evm = crate::new::<InMemoryDB>();
+        evm.database(db);
+        evm.env.cfg.spec_id = SpecId::LONDON;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        let mut balance_gas = |chain_id: u64| {
+            evm.env.cfg.chain_id = chain_id.into();
+            evm.select_precompiles(&registry);
+            let (result, _) = evm.transact();
+            assert_eq!(result.exit_reason, Return::Stop);
+            result.gas_used - 21_000 - 3
+        };
+        // EIP-2929: registered precompile is warm although it doesn't follow standard ones.
+        assert_eq!(balance_gas(42161), 100);
+        assert_eq!(balance_gas(1), 2600);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::{
+    analysis::calldata_address_hints,
+    db::{CacheDB, DatabaseRef},
+    AccessListWarmSet, CreateAddressHook, CreateScheme, Env, InvalidTransaction, SpecFlags,
+    TransactTo, TxValidator,
+};
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+use primitive_types::{H160, H256};
+use sha3::{Digest, Keccak256};
+
+/// Transaction that is validated and prepared for execution by [crate::EVM::prepare], so that
+/// it can be scheduled and executed later or on another thread with
+/// [crate::EVM::transact_prepared].
+///
+/// Preparation needs no state. It does stateless checks of [TxValidator], calculates intrinsic
+/// gas and derives accounts and slots that execution is going to read, so that they can be
+/// fetched with [PreparedTx::prefetch] before execution. Checks against state of the sender are
+/// done when transaction is executed.
+///
+/// Execution trusts intrinsic gas and warm set of preparation, so prepared transaction can only
+/// be read and not changed.
+#[derive(Clone, Debug)]
+pub struct PreparedTx {
+    env: Env,
+    intrinsic_gas: u64,
+    accounts: Vec<H160>,
+    warm_set: AccessListWarmSet,
+}
+
+impl PreparedTx {
+    /// Validate transaction of `env`. `create_address_hook` is used to derive address of
+    /// created contract, as [crate::EVM::create_address_hook].
+    pub fn new(
+        env: Env,
+        create_address_hook: Option<&dyn CreateAddressHook>,
+    ) -> Result<Self, InvalidTransaction> {
+        let flags = env
+            .cfg
+            .custom_spec
+            .unwrap_or_else(|| SpecFlags::new(env.cfg.spec_id));
+        let validator = TxValidator::with_flags(flags);
+        let intrinsic_gas = validator.validate_stateless(&env, None)?;
+        let warm_set = validator.access_list_warm_set(&env);
+
+        let tx = &env.tx;
+        let mut accounts = vec![tx.caller];
+        match tx.transact_to {
+            TransactTo::Call(address) => accounts.push(address),
+            // CREATE address is known only if nonce is part of transaction.
+            TransactTo::Create(scheme) => {
+                let nonce = tx.nonce.unwrap_or_default();
+                let code_hash = H256::from_slice(&Keccak256::digest(&tx.data));
+                let created = match (create_address_hook, scheme) {
+                    (Some(hook), _) if tx.nonce.is_some() => {
+                        Some(hook.create_address(tx.caller, nonce, scheme, code_hash))
+                    }
+                    (Some(_), _) => None,
+                    (None, CreateScheme::Create) => tx
+                        .nonce
+                        .map(|nonce| crate::create_address(tx.caller, nonce)),
+                    (None, CreateScheme::Create2 { salt }) => {
+                        Some(crate::create2_address(tx.caller, code_hash, salt))
+                    }
+                };
+                accounts.extend(created);
+            }
+        }
+        accounts.push(env.block.coinbase);
+        accounts.extend(&env.cfg.warm_addresses);
+        accounts.extend(&warm_set.addresses);
+        if let TransactTo::Call(_) = tx.transact_to {
+            accounts.extend(calldata_address_hints(&tx.data));
+        }
+        let mut seen = BTreeSet::new();
+        accounts.retain(|address| seen.insert(*address));
+
+        Ok(Self {
+            env,
+            intrinsic_gas,
+            accounts,
+            warm_set,
+        })
+    }
+
+    /// Environment of transaction.
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    /// Intrinsic gas of transaction, see [crate::initial_tx_gas].
+    pub fn intrinsic_gas(&self) -> u64 {
+        self.intrinsic_gas
+    }
+
+    /// Accounts that are read at the start of transaction: caller, target or created address,
+    /// coinbase, [crate::CfgEnv::warm_addresses] and accounts of access list, and addresses
+    /// hinted by call data. Without duplicates.
+    pub fn accounts(&self) -> &[H160] {
+        &self.accounts
+    }
+
+    /// Accounts and slots that access list makes warm, used by execution.
+    pub fn warm_set(&self) -> &AccessListWarmSet {
+        &self.warm_set
+    }
+
+    /// Environment, intrinsic gas and warm set that [crate::EVM::transact_prepared] executes.
+    pub(crate) fn into_parts(self) -> (Env, u64, AccessListWarmSet) {
+        (self.env, self.intrinsic_gas, self.warm_set)
+    }
+
+    /// Load [PreparedTx::accounts] and slots of [PreparedTx::warm_set] into `db` in batches, see
+    /// [CacheDB::prefetch].
+    pub fn prefetch<ExtDB: DatabaseRef>(
+        &self,
+        db: &mut CacheDB<ExtDB>,
+    ) -> Result<(), ExtDB::Error> {
+        let slots: Vec<_> = self.warm_set.slots.iter().copied().collect();
+        db.prefetch(&self.accounts, &slots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::EmptyDB,
+        opcode::{PUSH1, SLOAD, STOP},
+        AccountInfo, Bytecode, InMemoryDB, Return, SpecId,
+    };
+    use bytes::Bytes;
+    use primitive_types::U256;
+
+    #[test]
+    fn test_prepared_tx() {
+        let caller = H160::from_low_u64_be(0x1000);
+        let target = H160::from_low_u64_be(0x2000);
+        let listed = H160::from_low_u64_be(0x3000);
+        let hinted = H160::repeat_byte(0xaa);
+
+        let mut evm = crate::new::<InMemoryDB>();
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(target);
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.access_list = vec![(listed, vec![U256::one()]), (target, Vec::new())];
+        let mut data = vec![0; 4];
+        data.extend(H256::from(hinted).as_bytes());
+        evm.env.tx.data = Bytes::from(data);
+        let prepared = evm.prepare().unwrap();
+        assert_eq!(
+            prepared.accounts(),
+            [caller, target, H160::zero(), listed, hinted]
+        );
+        assert_eq!(
+            prepared.warm_set().slots.iter().collect::<Vec<_>>(),
+            [&(listed, U256::one())]
+        );
+        assert_eq!(
+            prepared.intrinsic_gas(),
+            21_000 + 2 * 2400 + 1900 + 16 * 4 + 20 * 16
+        );
+
+        // prepared on this thread, prefetched and executed on another one.
+        let mut db = InMemoryDB::new(EmptyDB::default());
+        db.insert_account_info(caller, AccountInfo::from_balance(U256::from(10)));
+        let result = std::thread::spawn(move || {
+            let mut evm = crate::new();
+            prepared.prefetch(&mut db).unwrap();
+            assert!(db.accounts.contains_key(&hinted));
+            evm.database(db);
+            evm.transact_prepared(prepared).0
+        })
+        .join()
+        .unwrap();
+        assert_eq!(result.exit_reason, Return::Stop);
+        assert_eq!(result.gas_used, 21_000 + 2 * 2400 + 1900 + 16 * 4 + 20 * 16);
+
+        // invalid transaction is rejected before it is scheduled.
+        evm.env.tx.gas_limit = 21_000;
+        assert!(matches!(
+            evm.prepare(),
+            Err(InvalidTransaction::GasLimitLessThanIntrinsic { .. })
+        ));
+
+        // address created by CREATE is known when nonce is set.
+        evm.env.cfg.spec_id = SpecId::LONDON;
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.transact_to = TransactTo::create();
+        evm.env.tx.nonce = Some(3);
+        let prepared = evm.prepare().unwrap();
+        assert_eq!(prepared.accounts()[1], crate::create_address(caller, 3));
+    }
+
+    #[test]
+    fn test_transact_prepared_uses_preparation() {
+        let target = H160::from_low_u64_be(0x2000);
+        let mut db = InMemoryDB::new(EmptyDB::default());
+        db.insert_account_info(
+            target,
+            AccountInfo::new(
+                U256::zero(),
+                0,
+                Bytecode::new_raw(Bytes::from_static(&[PUSH1, 0x01, SLOAD, STOP])),
+            ),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(target);
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.tx.access_list = vec![(target, vec![U256::one()])];
+        let prepared = evm.prepare().unwrap();
+        let intrinsic_gas = prepared.intrinsic_gas();
+
+        let (result, _) = evm.transact_prepared(prepared.clone());
+        assert_eq!(result.gas_used, intrinsic_gas + 3 + 100);
+
+        // execution doesn't compute intrinsic gas and warm set again.
+        let mut changed = prepared;
+        changed.intrinsic_gas += 1000;
+        changed.warm_set.slots.clear();
+        let (result, _) = evm.transact_prepared(changed);
+        assert_eq!(result.gas_used, intrinsic_gas + 1000 + 3 + 2100);
+    }
+}
@@ -0,0 +1,378 @@
+//! Self contained reproduction of a transaction, for bug reports that can be replayed with one
+//! command (`revme repro bundle.json`).
+//!
+//! [ReproBundle] holds environment, state that transaction reads and optionally result that is
+//! expected. Transaction is executed on [ReproRecorder] that records every read of the database,
+//! bundle is captured from it with [ReproBundle::capture] and replayed with
+//! [crate::EVM::from_repro_bundle] on [InMemoryDB] that holds only that state.
+
+#[cfg(feature = "with-serde")]
+use crate::models::{serde_hex_bytes, serde_hex_bytes_opt};
+use crate::{
+    AccountInfo, Bytecode, Database, Env, ExecutionResult, InMemoryDB, Return, EVM, KECCAK_EMPTY,
+};
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use bytes::Bytes;
+use core::fmt;
+use primitive_types::{H160, H256, U256};
+
+/// Transaction with state it needs to be executed and its expected result.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "with-serde", serde(rename_all = "camelCase"))]
+pub struct ReproBundle {
+    pub env: Env,
+    /// Accounts and slots that transaction reads, as they were before it.
+    pub pre_state: BTreeMap<H160, ReproAccount>,
+    /// Hashes of blocks read by BLOCKHASH.
+    #[cfg_attr(feature = "with-serde", serde(default))]
+    pub block_hashes: BTreeMap<U256, H256>,
+    /// Result that replay is checked against, see [ReproBundle::check].
+    #[cfg_attr(feature = "with-serde", serde(default))]
+    pub expected: Option<ReproExpected>,
+}
+
+/// Account of [ReproBundle::pre_state]. Only slots that are read are included.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReproAccount {
+    pub balance: U256,
+    pub nonce: u64,
+    #[cfg_attr(feature = "with-serde", serde(default, with = "serde_hex_bytes"))]
+    pub code: Bytes,
+    #[cfg_attr(feature = "with-serde", serde(default))]
+    pub storage: BTreeMap<U256, U256>,
+}
+
+/// Expected result of [ReproBundle]. Fields that are `None` are not checked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "with-serde", serde(rename_all = "camelCase"))]
+pub struct ReproExpected {
+    pub exit_reason: Return,
+    #[cfg_attr(feature = "with-serde", serde(default))]
+    pub gas_used: Option<u64>,
+    #[cfg_attr(feature = "with-serde", serde(default, with = "serde_hex_bytes_opt"))]
+    pub output: Option<Bytes>,
+}
+
+/// Difference between replayed and expected result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReproMismatch {
+    pub field: &'static str,
+    pub expected: String,
+    pub got: String,
+}
+
+impl fmt::Display for ReproMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, got {}",
+            self.field, self.expected, self.got
+        )
+    }
+}
+
+/// Wrapper of [Database] that records accounts and slots as they are first read, so that
+/// pre-state of [ReproBundle] has everything transaction read: also accounts that are only read
+/// by BALANCE or EXTCODE* and reads of reverted frames, that are not in state of the result.
+///
+/// Code of accounts is read together with them, so it is recorded even if transaction only
+/// reads its hash or size.
+#[derive(Debug, Default)]
+pub struct ReproRecorder<DB> {
+    db: DB,
+    /// Accounts as they were read, `None` if account doesn't exist.
+    pub accounts: BTreeMap<H160, Option<AccountInfo>>,
+    pub storage: BTreeMap<(H160, U256), U256>,
+    /// Hashes of blocks read by BLOCKHASH, by block number.
+    pub block_hashes: BTreeMap<U256, H256>,
+}
+
+impl<DB> ReproRecorder<DB> {
+    pub fn new(db: DB) -> Self {
+        Self {
+            db,
+            accounts: BTreeMap::new(),
+            storage: BTreeMap::new(),
+            block_hashes: BTreeMap::new(),
+        }
+    }
+
+    pub fn db(&self) -> &DB {
+        &self.db
+    }
+
+    pub fn into_inner(self) -> DB {
+        self.db
+    }
+}
+
+impl<DB: Database> Database for ReproRecorder<DB> {
+    type Error = DB::Error;
+
+    fn basic(&mut self, address: H160) -> Result<Option<AccountInfo>, Self::Error> {
+        let mut info = self.db.basic(address)?;
+        if let Some(info) = &mut info {
+            if info.code.is_none() {
+                info.code = Some(if info.code_hash == KECCAK_EMPTY {
+                    Bytecode::new()
+                } else {
+                    self.db.code_by_hash(info.code_hash)?
+                });
+            }
+        }
+        self.accounts.entry(address).or_insert_with(|| info.clone());
+        Ok(info)
+    }
+
+    fn code_by_hash(&mut self, code_hash: H256) -> Result<Bytecode, Self::Error> {
+        self.db.code_by_hash(code_hash)
+    }
+
+    fn storage(&mut self, address: H160, index: U256) -> Result<U256, Self::Error> {
+        let value = self.db.storage(address, index)?;
+        self.storage.entry((address, index)).or_insert(value);
+        Ok(value)
+    }
+
+    fn block_hash(&mut self, number: U256) -> Result<H256, Self::Error> {
+        let hash = self.db.block_hash(number)?;
+        self.block_hashes.entry(number).or_insert(hash);
+        Ok(hash)
+    }
+}
+
+impl ReproBundle {
+    /// Bundle of transaction of `env` that was executed on `recorder` and returned `result`.
+    /// Pre-state are accounts, slots and block hashes that `recorder` recorded, so it needs to
+    /// be new or cleared before the transaction. Result is recorded as expected.
+    pub fn capture<DB>(env: &Env, recorder: &ReproRecorder<DB>, result: &ExecutionResult) -> Self {
+        let mut pre_state = BTreeMap::new();
+        for (address, info) in &recorder.accounts {
+            let Some(info) = info else {
+                continue;
+            };
+            let code = info.code.clone().unwrap_or_default();
+            pre_state.insert(
+                *address,
+                ReproAccount {
+                    balance: info.balance,
+                    nonce: info.nonce,
+                    code: code.range(0, code.len()),
+                    storage: BTreeMap::new(),
+                },
+            );
+        }
+        for ((address, slot), value) in &recorder.storage {
+            if let Some(account) = pre_state.get_mut(address) {
+                account.storage.insert(*slot, *value);
+            }
+        }
+        Self {
+            env: env.clone(),
+            pre_state,
+            block_hashes: recorder.block_hashes.clone(),
+            expected: Some(ReproExpected {
+                exit_reason: result.exit_reason,
+                gas_used: Some(result.gas_used),
+                output: result.output().cloned(),
+            }),
+        }
+    }
+
+    /// Database with pre-state and block hashes of the bundle.
+    pub fn database(&self) -> InMemoryDB {
+        let mut db = InMemoryDB::default();
+        for (address, account) in &self.pre_state {
+            let code = Bytecode::new_raw(account.code.clone());
+            db.insert_account_info(
+                *address,
+                AccountInfo::new(account.balance, account.nonce, code),
+            );
+            let cached = db.accounts.get_mut(address).unwrap();
+            cached.storage.extend(&account.storage);
+        }
+        db.block_hashes.extend(&self.block_hashes);
+        db
+    }
+
+    /// Differences of `result` from [ReproBundle::expected], empty if it matches or nothing is
+    /// expected.
+    pub fn check(&self, result: &ExecutionResult) -> Vec<ReproMismatch> {
+        let mut mismatches = Vec::new();
+        let Some(expected) = &self.expected else {
+            return mismatches;
+        };
+        if result.exit_reason != expected.exit_reason {
+            mismatches.push(ReproMismatch {
+                field: "exit_reason",
+                expected: format!("{:?}", expected.exit_reason),
+                got: format!("{:?}", result.exit_reason),
+            });
+        }
+        if let Some(gas_used) = expected.gas_used.filter(|gas| *gas != result.gas_used) {
+            mismatches.push(ReproMismatch {
+                field: "gas_used",
+                expected: format!("{gas_used}"),
+                got: format!("{}", result.gas_used),
+            });
+        }
+        if let Some(output) = &expected.output {
+            let got = result.output().cloned().unwrap_or_default();
+            if *output != got {
+                mismatches.push(ReproMismatch {
+                    field: "output",
+                    expected: format!("0x{}", hex_string(output)),
+                    got: format!("0x{}", hex_string(&got)),
+                });
+            }
+        }
+        mismatches
+    }
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+impl EVM<InMemoryDB> {
+    /// EVM with environment of `bundle` and database with its pre-state, ready to replay the
+    /// transaction with [EVM::transact].
+    pub fn from_repro_bundle(bundle: &ReproBundle) -> Self {
+        let mut evm = Self::new();
+        evm.env = bundle.env.clone();
+        evm.database(bundle.database());
+        evm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{opcode::*, TransactTo};
+    use alloc::vec;
+
+    #[test]
+    fn test_repro_bundle() {
+        let contract = H160::from_low_u64_be(0x1000);
+        let caller = H160::from_low_u64_be(0x2000);
+        let read_only = H160::repeat_byte(8);
+        // read hash of block 9, add slot 1, slot 0 and balance of `read_only`, store the sum
+        // and return it.
+        let mut code = vec![
+            PUSH1, 9, BLOCKHASH, POP, PUSH1, 1, SLOAD, PUSH1, 0, SLOAD, ADD, PUSH20,
+        ];
+        code.extend(read_only.as_bytes());
+        code.extend([
+            BALANCE, ADD, DUP1, PUSH1, 0, SSTORE, PUSH1, 0, MSTORE, PUSH1, 32, PUSH1, 0, RETURN,
+        ]);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 1, Bytecode::new_raw(code.clone().into())),
+        );
+        db.insert_account_info(caller, AccountInfo::from_balance(U256::from(10)));
+        db.insert_account_storage(contract, U256::zero(), U256::from(5))
+            .unwrap();
+        db.insert_account_storage(contract, U256::one(), U256::from(7))
+            .unwrap();
+        db.insert_account_info(read_only, AccountInfo::from_balance(3.into()));
+        // unrelated account is not captured.
+        db.insert_account_info(H160::repeat_byte(9), AccountInfo::from_balance(1.into()));
+        db.block_hashes.insert(9.into(), H256::repeat_byte(9));
+
+        let mut evm = crate::new();
+        evm.database(ReproRecorder::new(db));
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.block.number = 10.into();
+        let (result, state) = evm.transact();
+        // account that is only read is not in state of the result.
+        assert!(!state.contains_key(&read_only));
+        let bundle = ReproBundle::capture(&evm.env, evm.db.as_ref().unwrap(), &result);
+        assert_eq!(
+            bundle.pre_state.keys().copied().collect::<Vec<_>>(),
+            vec![contract, caller, read_only]
+        );
+        assert_eq!(
+            bundle.block_hashes,
+            BTreeMap::from([(9.into(), H256::repeat_byte(9))])
+        );
+        let account = &bundle.pre_state[&contract];
+        assert_eq!(account.code, Bytes::from(code));
+        assert_eq!(
+            account.storage,
+            BTreeMap::from([(U256::zero(), U256::from(5)), (U256::one(), U256::from(7))])
+        );
+
+        // replay gives the same result.
+        let mut replay = EVM::from_repro_bundle(&bundle);
+        let (replayed, _) = replay.transact();
+        assert!(bundle.check(&replayed).is_empty());
+        assert_eq!(
+            U256::from_big_endian(replayed.output().unwrap()),
+            U256::from(15)
+        );
+
+        // changed pre-state is reported.
+        let mut changed = bundle.clone();
+        changed
+            .pre_state
+            .get_mut(&contract)
+            .unwrap()
+            .storage
+            .insert(U256::zero(), U256::zero());
+        let (replayed, _) = EVM::from_repro_bundle(&changed).transact();
+        let mismatches = bundle.check(&replayed);
+        assert_eq!(
+            mismatches.iter().map(|m| m.field).collect::<Vec<_>>(),
+            vec!["gas_used", "output"]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "with-serde")]
+    fn test_repro_bundle_serde() {
+        let contract = H160::from_low_u64_be(0x1000);
+        // add hash of block 9 and slot 0 and return it.
+        let code = vec![
+            PUSH1, 9, BLOCKHASH, PUSH1, 0, SLOAD, ADD, PUSH1, 0, MSTORE, PUSH1, 32, PUSH1, 0,
+            RETURN,
+        ];
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 1, Bytecode::new_raw(code.into())),
+        );
+        db.insert_account_storage(contract, U256::zero(), U256::from(5))
+            .unwrap();
+        db.block_hashes.insert(9.into(), H256::repeat_byte(9));
+
+        let mut evm = crate::new();
+        evm.database(ReproRecorder::new(db));
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        evm.env.block.number = 10.into();
+        let (result, _) = evm.transact();
+        let bundle = ReproBundle::capture(&evm.env, evm.db.as_ref().unwrap(), &result);
+
+        let json = serde_json::to_string(&bundle).unwrap();
+        let decoded: ReproBundle = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+        assert_eq!(decoded.pre_state, bundle.pre_state);
+        assert_eq!(decoded.block_hashes, bundle.block_hashes);
+        assert_eq!(decoded.expected, bundle.expected);
+
+        let (replayed, _) = EVM::from_repro_bundle(&decoded).transact();
+        assert!(decoded.check(&replayed).is_empty());
+        let mut expected = [0u8; 32];
+        U256::from_big_endian(H256::repeat_byte(9).as_bytes())
+            .overflowing_add(U256::from(5))
+            .0
+            .to_big_endian(&mut expected);
+        assert_eq!(replayed.output().unwrap().as_ref(), expected);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::gas::GasTable;
 use core::convert::TryFrom;
 use num_enum::TryFromPrimitive;
 use revm_precompiles::SpecId as PrecompileId;
@@ -25,10 +26,33 @@ pub enum SpecId {
     ARROW_GLACIER = 13,   // Arrow Glacier	        13773000
     GRAY_GLACIER = 14,    // Gray Glacier	        15050000
     MERGE = 15,           // Paris/Merge	        TBD (Depends on difficulty)
-    LATEST = 16,
+    CANCUN = 16,          // Cancun	                TBD
+    LATEST = 17,
 }
 
 impl SpecId {
+    /// All hardforks in order of activation.
+    pub const ALL: [SpecId; 18] = [
+        FRONTIER,
+        FRONTIER_THAWING,
+        HOMESTEAD,
+        DAO_FORK,
+        TANGERINE,
+        SPURIOUS_DRAGON,
+        BYZANTIUM,
+        CONSTANTINOPLE,
+        PETERSBURG,
+        ISTANBUL,
+        MUIR_GLACIER,
+        BERLIN,
+        LONDON,
+        ARROW_GLACIER,
+        GRAY_GLACIER,
+        MERGE,
+        CANCUN,
+        LATEST,
+    ];
+
     pub const fn to_precompile_id(self) -> PrecompileId {
         match self {
             FRONTIER | FRONTIER_THAWING | HOMESTEAD | DAO_FORK | TANGERINE | SPURIOUS_DRAGON => {
@@ -36,7 +60,8 @@ impl SpecId {
             }
             BYZANTIUM | CONSTANTINOPLE | PETERSBURG => PrecompileId::BYZANTIUM,
             ISTANBUL | MUIR_GLACIER => PrecompileId::ISTANBUL,
-            BERLIN | LONDON | ARROW_GLACIER | GRAY_GLACIER | MERGE | LATEST => PrecompileId::BERLIN,
+            BERLIN | LONDON | ARROW_GLACIER | GRAY_GLACIER | MERGE => PrecompileId::BERLIN,
+            CANCUN | LATEST => PrecompileId::CANCUN,
         }
     }
 
@@ -62,6 +87,7 @@ impl From<&str> for SpecId {
             "Berlin" => SpecId::BERLIN,
             "London" => SpecId::LONDON,
             "Merge" => SpecId::MERGE,
+            "Cancun" => SpecId::CANCUN,
             _ => SpecId::LATEST,
         }
     }
@@ -72,54 +98,231 @@ impl SpecId {
     pub const fn enabled(our: SpecId, other: SpecId) -> bool {
         our as u8 >= other as u8
     }
+
+    /// Whether changes of this hardfork are active in `other` hardfork. Use [SpecFlags] for
+    /// chains that don't follow order of Ethereum hardforks.
+    #[inline]
+    pub const fn is_enabled_in(self, other: SpecId) -> bool {
+        Self::enabled(other, self)
+    }
+}
+
+impl From<SpecId> for SpecFlags {
+    fn from(spec_id: SpecId) -> Self {
+        Self::new(spec_id)
+    }
+}
+
+/// Helpers for EIPs that are checked outside of interpreter, on [SpecId] and on [SpecFlags].
+/// Every helper checks hardfork that interpreter uses to enable the EIP.
+macro_rules! eip_helpers {
+    ($($(#[$doc:meta])* $name:ident => $spec_id:ident,)*) => {
+        impl SpecId {
+            $(
+                $(#[$doc])*
+                #[inline]
+                pub const fn $name(self) -> bool {
+                    Self::enabled(self, $spec_id)
+                }
+            )*
+        }
+
+        impl SpecFlags {
+            $(
+                $(#[$doc])*
+                #[inline]
+                pub const fn $name(self) -> bool {
+                    self.enabled($spec_id)
+                }
+            )*
+        }
+    };
+}
+
+eip_helpers! {
+    /// EIP-7: DELEGATECALL.
+    supports_delegatecall => HOMESTEAD,
+    /// EIP-150: all but one 64th of gas is forwarded to calls.
+    supports_eip150 => TANGERINE,
+    /// EIP-155: replay protected transactions with chain id.
+    supports_eip155 => SPURIOUS_DRAGON,
+    /// EIP-161: state trie clearing of empty accounts.
+    supports_state_clearing => SPURIOUS_DRAGON,
+    /// EIP-170: contract code size limit.
+    supports_code_size_limit => SPURIOUS_DRAGON,
+    /// EIP-140: REVERT.
+    supports_revert => BYZANTIUM,
+    /// EIP-211: RETURNDATASIZE and RETURNDATACOPY.
+    supports_return_data => BYZANTIUM,
+    /// EIP-214: STATICCALL.
+    supports_static_call => BYZANTIUM,
+    /// EIP-145: SHL, SHR and SAR.
+    supports_bitwise_shifts => CONSTANTINOPLE,
+    /// EIP-1052: EXTCODEHASH.
+    supports_extcodehash => CONSTANTINOPLE,
+    /// EIP-1014: CREATE2.
+    supports_create2 => CONSTANTINOPLE,
+    /// EIP-1344: CHAINID.
+    supports_chain_id => ISTANBUL,
+    /// EIP-1884: SELFBALANCE.
+    supports_selfbalance => ISTANBUL,
+    /// EIP-2200: net gas metering of SSTORE.
+    supports_net_gas_metering => ISTANBUL,
+    /// EIP-2929: gas of cold and warm state access.
+    supports_eip2929 => BERLIN,
+    /// EIP-2930: access lists.
+    supports_access_lists => BERLIN,
+    /// EIP-1559: basefee and priority fee.
+    supports_eip1559 => LONDON,
+    /// EIP-3198: BASEFEE.
+    supports_basefee => LONDON,
+    /// EIP-3529: reduced refunds.
+    supports_reduced_refunds => LONDON,
+    /// EIP-3541: new code starting with 0xEF is rejected.
+    supports_eip3541 => LONDON,
+    /// No block reward after the merge.
+    supports_proof_of_stake => MERGE,
 }
 
-pub(crate) trait NotStaticSpec {}
+/// Set of enabled hardforks. Used by [CustomSpec] for chains that enable nonstandard mix of EIPs,
+/// for example Berlin gas rules without London basefee.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpecFlags {
+    /// Bit for every enabled SpecId.
+    enabled: u32,
+}
+
+impl SpecFlags {
+    /// Flags with all hardforks up to and including `spec_id` enabled, same as standard spec.
+    pub const fn new(spec_id: SpecId) -> Self {
+        Self {
+            enabled: (2 << spec_id as u8) - 1,
+        }
+    }
+
+    /// Enable EIPs introduced in `spec_id` hardfork.
+    pub const fn enable(self, spec_id: SpecId) -> Self {
+        Self {
+            enabled: self.enabled | (1 << spec_id as u8),
+        }
+    }
+
+    /// Disable EIPs introduced in `spec_id` hardfork.
+    pub const fn disable(self, spec_id: SpecId) -> Self {
+        Self {
+            enabled: self.enabled & !(1 << spec_id as u8),
+        }
+    }
+
+    #[inline]
+    pub const fn enabled(self, spec_id: SpecId) -> bool {
+        self.enabled & (1 << spec_id as u8) != 0
+    }
+
+    /// Precompiles of latest enabled hardfork that changed them.
+    pub const fn to_precompile_id(self) -> PrecompileId {
+        if self.enabled(CANCUN) {
+            PrecompileId::CANCUN
+        } else if self.enabled(BERLIN) {
+            PrecompileId::BERLIN
+        } else if self.enabled(ISTANBUL) {
+            PrecompileId::ISTANBUL
+        } else if self.enabled(BYZANTIUM) {
+            PrecompileId::BYZANTIUM
+        } else {
+            PrecompileId::HOMESTEAD
+        }
+    }
+}
 
 pub trait Spec: Sized {
-    /// little bit of magic. We can have child version of Spec that contains static flag enabled
-    type STATIC: Spec;
+    /// Hardfork of standard spec. None for [CustomSpec], whose hardforks are known only at
+    /// runtime, see [SpecRules].
+    const SPEC_ID: Option<SpecId>;
+
+    /// Gas prices of standard spec, evaluated at compile time. None for [CustomSpec], use
+    /// [SpecRules::gas_table] to get prices of any spec.
+    const GAS_TABLE: Option<GasTable> = match Self::SPEC_ID {
+        Some(spec_id) => Some(GasTable::new(SpecFlags::new(spec_id))),
+        None => None,
+    };
+
+    const ASSUME_PRECOMPILE_HAS_BALANCE: bool;
+}
 
+/// Hardforks and gas prices that EVM executes with. Standard specs know them at compile time,
+/// [CustomSpec] takes them from [crate::CfgEnv::custom_spec] when EVM is created.
+///
+/// Checks are done with spec the rules were made for, checks of standard specs don't read the
+/// rules and are resolved at compile time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SpecRules {
+    flags: SpecFlags,
+    gas_table: GasTable,
+}
+
+impl SpecRules {
+    /// Rules of hardforks enabled in `flags`.
+    pub const fn new(flags: SpecFlags) -> Self {
+        Self {
+            flags,
+            gas_table: GasTable::new(flags),
+        }
+    }
+
+    /// Rules of `SPEC`. `custom` flags are used only by [CustomSpec], without them it has no
+    /// hardfork enabled.
+    pub fn of<SPEC: Spec>(custom: Option<SpecFlags>) -> Self {
+        match SPEC::SPEC_ID {
+            Some(spec_id) => Self::new(SpecFlags::new(spec_id)),
+            None => Self::new(custom.unwrap_or(SpecFlags { enabled: 0 })),
+        }
+    }
+
+    /// Enabled hardforks.
+    pub fn flags(&self) -> SpecFlags {
+        self.flags
+    }
+
+    /// Whether changes of `spec_id` hardfork are enabled in `SPEC`.
     #[inline(always)]
-    fn enabled(spec_id: SpecId) -> bool {
-        Self::SPEC_ID as u8 >= spec_id as u8
+    pub fn enabled<SPEC: Spec>(&self, spec_id: SpecId) -> bool {
+        match SPEC::SPEC_ID {
+            Some(our) => SpecId::enabled(our, spec_id),
+            None => self.flags.enabled(spec_id),
+        }
     }
-    const SPEC_ID: SpecId;
-    /// static flag used in STATIC type;
-    const IS_STATIC_CALL: bool;
 
-    const ASSUME_PRECOMPILE_HAS_BALANCE: bool;
+    /// Gas prices of `SPEC`.
+    #[inline(always)]
+    pub fn gas_table<SPEC: Spec>(&self) -> &GasTable {
+        match &SPEC::GAS_TABLE {
+            Some(table) => table,
+            None => &self.gas_table,
+        }
+    }
 }
 
 pub(crate) mod spec_impl {
-    use super::{NotStaticSpec, Spec};
+    use super::Spec;
 
     macro_rules! spec {
         ($spec_id:tt) => {
             #[allow(non_snake_case)]
             pub mod $spec_id {
-                use super::{NotStaticSpec, Spec};
+                use super::Spec;
                 use crate::SpecId;
 
-                pub struct SpecInner<
-                    const STATIC_CALL: bool,
-                    const ASSUME_PRECOMPILE_HAS_BALANCE: bool,
-                >;
+                pub struct SpecInner<const ASSUME_PRECOMPILE_HAS_BALANCE: bool>;
 
-                pub type SpecImpl = SpecInner<false, true>;
-                pub type SpecStaticImpl = SpecInner<true, true>;
+                pub type SpecImpl = SpecInner<true>;
 
-                impl NotStaticSpec for SpecImpl {}
-
-                impl<const IS_STATIC_CALL: bool, const ASSUME_PRECOMPILE_HAS_BALANCE: bool> Spec
-                    for SpecInner<IS_STATIC_CALL, ASSUME_PRECOMPILE_HAS_BALANCE>
+                impl<const ASSUME_PRECOMPILE_HAS_BALANCE: bool> Spec
+                    for SpecInner<ASSUME_PRECOMPILE_HAS_BALANCE>
                 {
-                    type STATIC = SpecInner<true, ASSUME_PRECOMPILE_HAS_BALANCE>;
-
                     //specification id
-                    const SPEC_ID: SpecId = SpecId::$spec_id;
-
-                    const IS_STATIC_CALL: bool = IS_STATIC_CALL;
+                    const SPEC_ID: Option<SpecId> = Some(SpecId::$spec_id);
 
                     const ASSUME_PRECOMPILE_HAS_BALANCE: bool = ASSUME_PRECOMPILE_HAS_BALANCE;
                 }
@@ -143,11 +346,13 @@ pub(crate) mod spec_impl {
     // ARROW_GLACIER no EVM spec change
     // GRAT_GLACIER no EVM spec change
     spec!(MERGE);
+    spec!(CANCUN);
     spec!(LATEST);
 }
 
 pub use spec_impl::BERLIN::SpecImpl as BerlinSpec;
 pub use spec_impl::BYZANTIUM::SpecImpl as ByzantiumSpec;
+pub use spec_impl::CANCUN::SpecImpl as CancunSpec;
 pub use spec_impl::FRONTIER::SpecImpl as FrontierSpec;
 pub use spec_impl::HOMESTEAD::SpecImpl as HomesteadSpec;
 pub use spec_impl::ISTANBUL::SpecImpl as IstanbulSpec;
@@ -157,3 +362,117 @@ pub use spec_impl::MERGE::SpecImpl as MergeSpec;
 pub use spec_impl::PETERSBURG::SpecImpl as PetersburgSpec;
 pub use spec_impl::SPURIOUS_DRAGON::SpecImpl as SpuriousDragonSpec;
 pub use spec_impl::TANGERINE::SpecImpl as TangerineSpec;
+
+/// Spec with enabled hardforks defined at runtime by [crate::CfgEnv::custom_spec]. Checks of
+/// hardforks and gas prices are done on [SpecRules] built from the flags.
+pub struct CustomSpec;
+
+impl Spec for CustomSpec {
+    const SPEC_ID: Option<SpecId> = None;
+
+    const ASSUME_PRECOMPILE_HAS_BALANCE: bool = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::InMemoryDB, opcode, AccountInfo, Bytecode, Return, TransactTo};
+    use primitive_types::H160;
+
+    #[test]
+    fn test_spec_flags() {
+        let flags = SpecFlags::new(BERLIN);
+        assert!(flags.enabled(FRONTIER));
+        assert!(flags.enabled(BERLIN));
+        assert!(!flags.enabled(LONDON));
+
+        let flags = SpecFlags::new(LONDON).disable(BERLIN);
+        assert!(flags.enabled(LONDON));
+        assert!(!flags.enabled(BERLIN));
+        assert_eq!(flags.to_precompile_id(), PrecompileId::ISTANBUL);
+        assert_eq!(flags.enable(BERLIN), SpecFlags::new(LONDON));
+
+        let flags = SpecFlags::new(CANCUN);
+        assert!(flags.enabled(MERGE) && !flags.enabled(LATEST));
+        assert_eq!(flags.to_precompile_id(), CANCUN.to_precompile_id());
+        assert_eq!(SpecId::from("Cancun"), CANCUN);
+        assert_eq!(SpecId::try_from_u8(CANCUN as u8), Some(CANCUN));
+    }
+
+    #[test]
+    fn test_spec_rules() {
+        let flags = SpecFlags::new(LONDON).disable(BERLIN);
+        let rules = SpecRules::of::<CustomSpec>(Some(flags));
+        assert_eq!(CustomSpec::SPEC_ID, None);
+        assert!(rules.enabled::<CustomSpec>(LONDON) && !rules.enabled::<CustomSpec>(BERLIN));
+        // standard spec doesn't read the rules.
+        assert!(rules.enabled::<LondonSpec>(BERLIN));
+        // custom spec without flags has no hardfork enabled.
+        let rules = SpecRules::of::<CustomSpec>(None);
+        assert!(!rules.enabled::<CustomSpec>(FRONTIER));
+        assert_eq!(
+            SpecRules::of::<BerlinSpec>(Some(flags)),
+            SpecRules::new(SpecFlags::new(BERLIN))
+        );
+    }
+
+    #[test]
+    fn test_eip_helpers() {
+        assert!(BERLIN.is_enabled_in(LONDON));
+        assert!(LONDON.is_enabled_in(LONDON));
+        assert!(!LONDON.is_enabled_in(BERLIN));
+
+        assert!(LONDON.supports_eip1559() && LONDON.supports_access_lists());
+        assert!(!BERLIN.supports_eip1559() && BERLIN.supports_access_lists());
+        assert!(!FRONTIER.supports_delegatecall() && HOMESTEAD.supports_delegatecall());
+        assert!(!BYZANTIUM.supports_create2() && PETERSBURG.supports_create2());
+        // EIP-1014 shipped in CONSTANTINOPLE, PETERSBURG only removed EIP-1283.
+        assert!(CONSTANTINOPLE.supports_create2() && CONSTANTINOPLE.supports_extcodehash());
+
+        // same answer for flags of standard hardfork, and for nonstandard mix of EIPs.
+        for spec_id in SpecId::ALL {
+            let flags = SpecFlags::from(spec_id);
+            assert_eq!(flags.supports_eip1559(), spec_id.supports_eip1559());
+            assert_eq!(flags.supports_chain_id(), spec_id.supports_chain_id());
+        }
+        let flags = SpecFlags::new(LONDON).disable(BERLIN);
+        assert!(flags.supports_eip1559() && !flags.supports_access_lists());
+    }
+
+    /// Execute BASEFEE and SLOAD with given config, return exit reason and gas used.
+    fn gas_used(spec_id: SpecId, custom_spec: Option<SpecFlags>) -> (Return, u64) {
+        let code = vec![
+            opcode::BASEFEE,
+            opcode::POP,
+            opcode::PUSH1,
+            0,
+            opcode::SLOAD,
+            opcode::STOP,
+        ];
+        let contract = H160::from_low_u64_be(0x1000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(0.into(), 1, Bytecode::new_raw(code.into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.cfg.spec_id = spec_id;
+        evm.env.cfg.custom_spec = custom_spec;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+        let (result, _) = evm.transact();
+        (result.exit_reason, result.gas_used)
+    }
+
+    #[test]
+    fn test_custom_spec() {
+        // cold SLOAD of Berlin
+        assert_eq!(gas_used(LONDON, None), (Return::Stop, 21000 + 2107));
+        // BASEFEE is not activated before London.
+        assert_eq!(gas_used(ISTANBUL, None).0, Return::NotActivated);
+        // London without Berlin has BASEFEE and Istanbul SLOAD.
+        let flags = SpecFlags::new(LONDON).disable(BERLIN);
+        assert_eq!(gas_used(LONDON, Some(flags)), (Return::Stop, 21000 + 807));
+    }
+}
@@ -0,0 +1,353 @@
+//! Decoding of storage slots into Solidity variables.
+//!
+//! Contract storage layout (as found in `storageLayout` output of solc) can be registered in
+//! [StorageLayoutInspector]. Inspector records keccak preimages computed by SHA3 opcode and uses
+//! them to resolve slots of mappings and dynamic arrays accessed by SLOAD and SSTORE back to the
+//! variable and keys used to access it.
+use crate::{
+    alloc::{boxed::Box, string::String, vec::Vec},
+    evm_impl::EVMData,
+    opcode, Database, Inspector, Interpreter, OpcodeSet, Return,
+};
+use hashbrown::HashMap as Map;
+use primitive_types::{H160, U256};
+
+/// Type of storage variable. Only types that change how slot is computed are distinguished.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StorageKind {
+    /// Value type, struct or static array that is stored inline.
+    Value,
+    /// Mapping, value is stored at `keccak(key . slot)`.
+    Mapping(Box<StorageKind>),
+    /// Dynamic array, element `i` is stored at `keccak(slot) + i`.
+    DynamicArray(Box<StorageKind>),
+}
+
+/// Storage variable declared by contract.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StorageVariable {
+    /// Name of variable.
+    pub label: String,
+    /// Slot where variable starts.
+    pub slot: U256,
+    /// Byte offset inside of slot for packed variables.
+    pub offset: u8,
+    pub kind: StorageKind,
+}
+
+/// Storage variables of one contract.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StorageLayout {
+    pub variables: Vec<StorageVariable>,
+}
+
+impl StorageLayout {
+    pub fn new(variables: Vec<StorageVariable>) -> Self {
+        Self { variables }
+    }
+
+    /// Variables that start at `slot`. There can be more than one if they are packed.
+    pub fn variables_at(&self, slot: U256) -> impl Iterator<Item = &StorageVariable> {
+        self.variables.iter().filter(move |var| var.slot == slot)
+    }
+}
+
+/// Slot resolved to variable and keys (mapping keys or array indices) used to reach it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecodedSlot {
+    /// Name of variable.
+    pub variable: String,
+    /// Keys from outermost to innermost.
+    pub path: Vec<U256>,
+}
+
+/// SLOAD or SSTORE executed on contract.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StorageAccess {
+    pub address: H160,
+    pub slot: U256,
+    /// Loaded value for SLOAD, stored value for SSTORE.
+    pub value: U256,
+    pub is_write: bool,
+    /// Decoded slot if layout of contract is registered and slot could be resolved.
+    pub decoded: Option<DecodedSlot>,
+}
+
+/// How hashed slot was computed.
+#[derive(Clone, Copy, Debug)]
+enum Preimage {
+    /// `keccak(key . base)`
+    Mapping { key: U256, base: U256 },
+    /// `keccak(base)`
+    Array { base: U256 },
+}
+
+/// Inspector that records storage accesses and decodes them with registered storage layouts.
+#[derive(Clone, Debug, Default)]
+pub struct StorageLayoutInspector {
+    layouts: Map<H160, StorageLayout>,
+    preimages: Map<U256, Preimage>,
+    /// Memory range of SHA3 or slot of SLOAD that is waiting for result in `step_end`.
+    pending: Option<Pending>,
+    accesses: Vec<StorageAccess>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Pending {
+    Sha3 { offset: usize, len: usize },
+    Sload { address: H160, slot: U256 },
+}
+
+impl StorageLayoutInspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register storage layout of contract.
+    pub fn register(&mut self, address: H160, layout: StorageLayout) {
+        self.layouts.insert(address, layout);
+    }
+
+    /// Storage accesses recorded so far.
+    pub fn accesses(&self) -> &[StorageAccess] {
+        &self.accesses
+    }
+
+    /// Take recorded storage accesses.
+    pub fn take_accesses(&mut self) -> Vec<StorageAccess> {
+        core::mem::take(&mut self.accesses)
+    }
+
+    /// Resolve slot of contract into variable and keys.
+    pub fn resolve(&self, address: H160, slot: U256) -> Option<DecodedSlot> {
+        let layout = self.layouts.get(&address)?;
+        let mut path = Vec::new();
+        // kinds expected on the way from root variable to the slot, innermost first.
+        let mut steps = Vec::new();
+        let mut current = slot;
+        loop {
+            if let Some(var) = layout
+                .variables_at(current)
+                .find(|var| Self::matches_steps(&var.kind, &steps))
+            {
+                path.reverse();
+                return Some(DecodedSlot {
+                    variable: var.label.clone(),
+                    path,
+                });
+            }
+            let (key, base, is_mapping) = self.unhash(current)?;
+            path.push(key);
+            steps.push(is_mapping);
+            current = base;
+        }
+    }
+
+    /// Find preimage of slot. Array elements are found by searching for closest array start
+    /// that is below the slot.
+    fn unhash(&self, slot: U256) -> Option<(U256, U256, bool)> {
+        match self.preimages.get(&slot) {
+            Some(Preimage::Mapping { key, base }) => return Some((*key, *base, true)),
+            Some(Preimage::Array { base }) => return Some((U256::zero(), *base, false)),
+            None => (),
+        }
+        self.preimages
+            .iter()
+            .filter_map(|(hash, preimage)| match preimage {
+                Preimage::Array { base } if *hash < slot => Some((slot - *hash, *base)),
+                _ => None,
+            })
+            // array index is bounded so that unrelated slots are not taken as array elements.
+            .filter(|(index, _)| *index <= U256::from(u64::MAX))
+            .min_by_key(|(index, _)| *index)
+            .map(|(index, base)| (index, base, false))
+    }
+
+    /// Check that variable kind contains nesting described by steps (innermost first).
+    fn matches_steps(kind: &StorageKind, steps: &[bool]) -> bool {
+        let mut kind = kind;
+        for is_mapping in steps.iter().rev() {
+            kind = match (kind, is_mapping) {
+                (StorageKind::Mapping(inner), true) => inner,
+                (StorageKind::DynamicArray(inner), false) => inner,
+                _ => return false,
+            };
+        }
+        true
+    }
+
+    fn record(&mut self, address: H160, slot: U256, value: U256, is_write: bool) {
+        let decoded = self.resolve(address, slot);
+        self.accesses.push(StorageAccess {
+            address,
+            slot,
+            value,
+            is_write,
+            decoded,
+        });
+    }
+}
+
+impl<DB: Database> Inspector<DB> for StorageLayoutInspector {
+    fn step_opcodes(&self) -> OpcodeSet {
+        OpcodeSet::new(&[opcode::SHA3, opcode::SLOAD, opcode::SSTORE])
+    }
+
+    fn step(
+        &mut self,
+        interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+        _is_static: bool,
+    ) -> Return {
+        let stack = interp.stack();
+        self.pending = match interp.current_opcode() {
+            opcode::SHA3 => match stack.peek_n::<2>() {
+                // only preimages of mapping and array slots are interesting.
+                Ok([offset, len]) if len == 32.into() || len == 64.into() => {
+                    if offset > U256::from(usize::MAX) {
+                        None
+                    } else {
+                        Some(Pending::Sha3 {
+                            offset: offset.as_usize(),
+                            len: len.as_usize(),
+                        })
+                    }
+                }
+                _ => None,
+            },
+            opcode::SLOAD => stack.peek(0).ok().map(|slot| Pending::Sload {
+                address: interp.contract.address,
+                slot,
+            }),
+            opcode::SSTORE => {
+                if let Ok([slot, value]) = stack.peek_n::<2>() {
+                    self.record(interp.contract.address, slot, value, true);
+                }
+                None
+            }
+            _ => None,
+        };
+        Return::Continue
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+        _is_static: bool,
+        eval: Return,
+    ) -> Return {
+        let pending = self.pending.take();
+        if eval != Return::Continue {
+            return Return::Continue;
+        }
+        let top = match interp.stack().peek(0) {
+            Ok(top) => top,
+            Err(_) => return Return::Continue,
+        };
+        match pending {
+            Some(Pending::Sha3 { offset, len }) => {
+                // memory is expanded by SHA3 so range is valid.
+                let input = interp.memory.get_slice(offset, len);
+                let preimage = if len == 64 {
+                    Preimage::Mapping {
+                        key: U256::from_big_endian(&input[..32]),
+                        base: U256::from_big_endian(&input[32..]),
+                    }
+                } else {
+                    Preimage::Array {
+                        base: U256::from_big_endian(input),
+                    }
+                };
+                self.preimages.insert(top, preimage);
+            }
+            Some(Pending::Sload { address, slot }) => self.record(address, slot, top, false),
+            None => (),
+        }
+        Return::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::InMemoryDB, AccountInfo, Bytecode, TransactTo};
+    use bytes::Bytes;
+
+    fn variable(label: &str, slot: u64, kind: StorageKind) -> StorageVariable {
+        StorageVariable {
+            label: label.into(),
+            slot: slot.into(),
+            offset: 0,
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_storage_layout_inspector() {
+        let contract = H160::from_low_u64_be(0x1000);
+        let code = Bytes::from(
+            [
+                // owner = 1 (slot 0)
+                &[0x60, 0x01, 0x60, 0x00, 0x55][..],
+                // balances[0xaa] = 2 (slot 1): keccak(0xaa . 1)
+                &[0x60, 0xaa, 0x60, 0x00, 0x52, 0x60, 0x01, 0x60, 0x20, 0x52],
+                &[0x60, 0x40, 0x60, 0x00, 0x20, 0x60, 0x02, 0x90, 0x55],
+                // items[3] (slot 2): keccak(2) + 3
+                &[0x60, 0x02, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0x20],
+                &[0x60, 0x03, 0x01, 0x54, 0x00],
+            ]
+            .concat(),
+        );
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code)),
+        );
+
+        let mut inspector = StorageLayoutInspector::new();
+        inspector.register(
+            contract,
+            StorageLayout::new(vec![
+                variable("owner", 0, StorageKind::Value),
+                variable(
+                    "balances",
+                    1,
+                    StorageKind::Mapping(Box::new(StorageKind::Value)),
+                ),
+                variable(
+                    "items",
+                    2,
+                    StorageKind::DynamicArray(Box::new(StorageKind::Value)),
+                ),
+            ]),
+        );
+
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 1_000_000;
+        let (result, _) = evm.inspect(&mut inspector);
+        assert_eq!(result.exit_reason, Return::Stop);
+
+        let decoded: Vec<_> = inspector
+            .accesses()
+            .iter()
+            .map(|access| {
+                let decoded = access.decoded.clone().unwrap();
+                (decoded.variable, decoded.path, access.is_write)
+            })
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                ("owner".into(), vec![], true),
+                ("balances".into(), vec![0xaa.into()], true),
+                ("items".into(), vec![3.into()], false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,429 @@
+use crate::StorageSlot;
+use core::{fmt, ops::Index};
+use hashbrown::{hash_map, HashMap as Map};
+use primitive_types::U256;
+use smallvec::SmallVec;
+
+/// Number of slots that [StorageMap] keeps inline before it spills to hash map.
+pub const INLINE_SLOTS: usize = 1;
+
+/// Storage of [crate::Account]. Most touched accounts access at most one slot, it is kept
+/// inline, so loading an account and its first slot doesn't allocate. Inline capacity is kept
+/// small as every account pays for it, including accounts without storage.
+/// Map spills to hash map when it gets more than [INLINE_SLOTS] slots and stays spilled until
+/// it is cleared.
+///
+/// Iteration order is unspecified, as it is for hash map. Use [crate::Account::sorted_storage]
+/// for deterministic order.
+#[derive(Clone)]
+pub struct StorageMap {
+    repr: Repr,
+}
+
+// inline variant is the larger one on purpose, it is used by most accounts.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+enum Repr {
+    Inline(SmallVec<[(U256, StorageSlot); INLINE_SLOTS]>),
+    Spilled(Map<U256, StorageSlot>),
+}
+
+impl Default for StorageMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StorageMap {
+    pub fn new() -> Self {
+        Self {
+            repr: Repr::Inline(SmallVec::new()),
+        }
+    }
+
+    /// Returns true if slots are moved to hash map.
+    pub fn is_spilled(&self) -> bool {
+        matches!(self.repr, Repr::Spilled(_))
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            Repr::Inline(slots) => slots.len(),
+            Repr::Spilled(map) => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, key: &U256) -> Option<&StorageSlot> {
+        match &self.repr {
+            Repr::Inline(slots) => slots.iter().find(|(k, _)| k == key).map(|(_, slot)| slot),
+            Repr::Spilled(map) => map.get(key),
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &U256) -> Option<&mut StorageSlot> {
+        match &mut self.repr {
+            Repr::Inline(slots) => slots
+                .iter_mut()
+                .find(|(k, _)| k == key)
+                .map(|(_, slot)| slot),
+            Repr::Spilled(map) => map.get_mut(key),
+        }
+    }
+
+    pub fn contains_key(&self, key: &U256) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Insert slot and return previous one.
+    pub fn insert(&mut self, key: U256, slot: StorageSlot) -> Option<StorageSlot> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(core::mem::replace(entry.get_mut(), slot)),
+            Entry::Vacant(entry) => {
+                entry.insert(slot);
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &U256) -> Option<StorageSlot> {
+        match &mut self.repr {
+            Repr::Inline(slots) => {
+                let index = slots.iter().position(|(k, _)| k == key)?;
+                Some(slots.swap_remove(index).1)
+            }
+            Repr::Spilled(map) => map.remove(key),
+        }
+    }
+
+    /// Remove all slots, map is inline again.
+    pub fn clear(&mut self) {
+        self.repr = Repr::Inline(SmallVec::new());
+    }
+
+    pub fn entry(&mut self, key: U256) -> Entry<'_> {
+        if let Repr::Inline(slots) = &self.repr {
+            if slots.len() == INLINE_SLOTS && !slots.iter().any(|(k, _)| *k == key) {
+                self.spill();
+            }
+        }
+        match &mut self.repr {
+            Repr::Inline(slots) => match slots.iter().position(|(k, _)| *k == key) {
+                Some(index) => Entry::Occupied(OccupiedEntry {
+                    slot: &mut slots[index].1,
+                }),
+                None => Entry::Vacant(VacantEntry {
+                    inner: VacantInner::Inline(slots, key),
+                }),
+            },
+            Repr::Spilled(map) => match map.entry(key) {
+                hash_map::Entry::Occupied(entry) => Entry::Occupied(OccupiedEntry {
+                    slot: entry.into_mut(),
+                }),
+                hash_map::Entry::Vacant(entry) => Entry::Vacant(VacantEntry {
+                    inner: VacantInner::Spilled(entry),
+                }),
+            },
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        match &self.repr {
+            Repr::Inline(slots) => Iter::Inline(slots.iter()),
+            Repr::Spilled(map) => Iter::Spilled(map.iter()),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&U256, &mut StorageSlot)> {
+        let (inline, spilled) = match &mut self.repr {
+            Repr::Inline(slots) => (Some(slots.iter_mut().map(|(k, v)| (&*k, v))), None),
+            Repr::Spilled(map) => (None, Some(map.iter_mut())),
+        };
+        inline
+            .into_iter()
+            .flatten()
+            .chain(spilled.into_iter().flatten())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &U256> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &StorageSlot> {
+        self.iter().map(|(_, slot)| slot)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut StorageSlot> {
+        self.iter_mut().map(|(_, slot)| slot)
+    }
+
+    fn spill(&mut self) {
+        if let Repr::Inline(slots) = &mut self.repr {
+            let mut map = Map::with_capacity(slots.len() * 2);
+            map.extend(slots.drain(..));
+            self.repr = Repr::Spilled(map);
+        }
+    }
+}
+
+/// Entry of [StorageMap::entry], same as entry of hash map.
+pub enum Entry<'a> {
+    Occupied(OccupiedEntry<'a>),
+    Vacant(VacantEntry<'a>),
+}
+
+pub struct OccupiedEntry<'a> {
+    slot: &'a mut StorageSlot,
+}
+
+impl<'a> OccupiedEntry<'a> {
+    pub fn get(&self) -> &StorageSlot {
+        self.slot
+    }
+
+    pub fn get_mut(&mut self) -> &mut StorageSlot {
+        self.slot
+    }
+
+    pub fn into_mut(self) -> &'a mut StorageSlot {
+        self.slot
+    }
+}
+
+pub struct VacantEntry<'a> {
+    inner: VacantInner<'a>,
+}
+
+enum VacantInner<'a> {
+    /// Inline slots that have space for one more.
+    Inline(&'a mut SmallVec<[(U256, StorageSlot); INLINE_SLOTS]>, U256),
+    Spilled(hash_map::VacantEntry<'a, U256, StorageSlot, hash_map::DefaultHashBuilder>),
+}
+
+impl<'a> VacantEntry<'a> {
+    pub fn key(&self) -> &U256 {
+        match &self.inner {
+            VacantInner::Inline(_, key) => key,
+            VacantInner::Spilled(entry) => entry.key(),
+        }
+    }
+
+    pub fn insert(self, slot: StorageSlot) -> &'a mut StorageSlot {
+        match self.inner {
+            VacantInner::Inline(slots, key) => {
+                slots.push((key, slot));
+                &mut slots.last_mut().unwrap().1
+            }
+            VacantInner::Spilled(entry) => entry.insert(slot),
+        }
+    }
+}
+
+/// Iterator of [StorageMap::iter].
+pub enum Iter<'a> {
+    Inline(core::slice::Iter<'a, (U256, StorageSlot)>),
+    Spilled(hash_map::Iter<'a, U256, StorageSlot>),
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a U256, &'a StorageSlot);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Iter::Inline(iter) => iter.next().map(|(key, slot)| (key, slot)),
+            Iter::Spilled(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Iter::Inline(iter) => iter.size_hint(),
+            Iter::Spilled(iter) => iter.size_hint(),
+        }
+    }
+}
+
+/// Owning iterator of [StorageMap].
+#[allow(clippy::large_enum_variant)]
+pub enum IntoIter {
+    Inline(smallvec::IntoIter<[(U256, StorageSlot); INLINE_SLOTS]>),
+    Spilled(hash_map::IntoIter<U256, StorageSlot>),
+}
+
+impl Iterator for IntoIter {
+    type Item = (U256, StorageSlot);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            IntoIter::Inline(iter) => iter.next(),
+            IntoIter::Spilled(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            IntoIter::Inline(iter) => iter.size_hint(),
+            IntoIter::Spilled(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl IntoIterator for StorageMap {
+    type Item = (U256, StorageSlot);
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        match self.repr {
+            Repr::Inline(slots) => IntoIter::Inline(slots.into_iter()),
+            Repr::Spilled(map) => IntoIter::Spilled(map.into_iter()),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a StorageMap {
+    type Item = (&'a U256, &'a StorageSlot);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl Extend<(U256, StorageSlot)> for StorageMap {
+    fn extend<T: IntoIterator<Item = (U256, StorageSlot)>>(&mut self, iter: T) {
+        for (key, slot) in iter {
+            self.insert(key, slot);
+        }
+    }
+}
+
+impl FromIterator<(U256, StorageSlot)> for StorageMap {
+    fn from_iter<T: IntoIterator<Item = (U256, StorageSlot)>>(iter: T) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<const N: usize> From<[(U256, StorageSlot); N]> for StorageMap {
+    fn from(slots: [(U256, StorageSlot); N]) -> Self {
+        slots.into_iter().collect()
+    }
+}
+
+impl Index<&U256> for StorageMap {
+    type Output = StorageSlot;
+
+    fn index(&self, key: &U256) -> &StorageSlot {
+        self.get(key).expect("slot is not present in storage")
+    }
+}
+
+/// Maps are equal if they have same slots, regardless of their order or representation.
+impl PartialEq for StorageMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, slot)| other.get(key).is_some_and(|other| other == slot))
+    }
+}
+
+impl Eq for StorageMap {}
+
+impl fmt::Debug for StorageMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(feature = "with-serde")]
+impl serde::Serialize for StorageMap {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+#[cfg(feature = "with-serde")]
+impl<'de> serde::Deserialize<'de> for StorageMap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Map::<U256, StorageSlot>::deserialize(deserializer).map(|map| map.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn slot(value: u64) -> StorageSlot {
+        StorageSlot::new(value.into())
+    }
+
+    #[test]
+    fn test_storage_map() {
+        let mut map = StorageMap::new();
+        for key in 0..INLINE_SLOTS as u64 {
+            assert_eq!(map.insert(key.into(), slot(key)), None);
+        }
+        assert!(!map.is_spilled());
+        // existing key doesn't spill.
+        assert_eq!(map.insert(0.into(), slot(10)), Some(slot(0)));
+        assert!(!map.is_spilled());
+        let inline = map.clone();
+
+        map.insert(100.into(), slot(100));
+        assert!(map.is_spilled());
+        assert_eq!(map.len(), INLINE_SLOTS + 1);
+        assert_eq!(map[&0.into()], slot(10));
+        assert_eq!(map.get(&100.into()), Some(&slot(100)));
+        assert_eq!(map.remove(&100.into()), Some(slot(100)));
+        // equal regardless of representation.
+        assert_eq!(map, inline);
+
+        let mut keys: Vec<_> = inline.keys().map(|key| key.as_u64()).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, (0..INLINE_SLOTS as u64).collect::<Vec<_>>());
+
+        map.clear();
+        assert!(map.is_empty() && !map.is_spilled());
+    }
+
+    #[test]
+    fn test_storage_map_entry() {
+        let mut map = StorageMap::new();
+        for key in 0..INLINE_SLOTS as u64 * 2 {
+            match map.entry(key.into()) {
+                Entry::Vacant(entry) => {
+                    assert_eq!(entry.key(), &U256::from(key));
+                    entry.insert(slot(key));
+                }
+                Entry::Occupied(_) => panic!("slot {} is not inserted", key),
+            }
+            match map.entry(key.into()) {
+                Entry::Occupied(mut entry) => {
+                    *entry.get_mut() = StorageSlot::new_changed(key.into(), (key + 1).into())
+                }
+                Entry::Vacant(_) => panic!("slot {} is inserted", key),
+            }
+        }
+        assert!(map.is_spilled());
+        for (key, slot) in map.iter() {
+            assert_eq!(slot.present_value(), key + 1);
+            assert_eq!(slot.original_value(), *key);
+        }
+        let collected: StorageMap = map.clone().into_iter().collect();
+        assert_eq!(collected, map);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_account_size() {
+        // inline slot costs account 48 bytes over hash map.
+        assert_eq!(core::mem::size_of::<StorageMap>(), 112);
+        assert_eq!(core::mem::size_of::<crate::Account>(), 288);
+    }
+}
@@ -0,0 +1,324 @@
+//! Deterministic pseudo random accounts, storage and bytecode for property tests and fuzzing
+//! harnesses.
+//!
+//! Same seed always generates same state. Generated code is valid: jumps only target
+//! `JUMPDEST`s, loops run bounded number of times and stack never underflows, so execution
+//! explores state changes, calls, logs and reverts instead of failing on first instruction.
+//!
+//! [code_evm] and [transact_code] set up call of a single contract, which most of tests do.
+use crate::{
+    opcode::*, AccountInfo, Bytecode, ExecutionResult, InMemoryDB, SpecId, State, TransactTo, EVM,
+};
+use alloc::vec::Vec;
+use primitive_types::{H160, U256};
+
+/// Slots that generated storage and generated SLOAD/SSTORE use, small so that they overlap.
+pub const GENERATED_SLOTS: u64 = 16;
+
+/// Largest number of iterations of generated loop.
+pub const MAX_LOOP_ITERATIONS: u8 = 8;
+
+/// Address of contract that [code_evm] deploys and calls, 0x1000.
+pub const CONTRACT: H160 = H160([
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10, 0,
+]);
+
+/// EVM with `code` deployed at [CONTRACT] and transaction from `caller` that calls it under
+/// `spec_id` with gas limit of 100 000. Tests change state or environment before executing it.
+pub fn code_evm(code: &[u8], caller: H160, spec_id: SpecId) -> EVM<InMemoryDB> {
+    let mut db = InMemoryDB::default();
+    db.insert_account_info(
+        CONTRACT,
+        AccountInfo::new(U256::zero(), 1, Bytecode::new_raw(code.to_vec().into())),
+    );
+    let mut evm = crate::new();
+    evm.database(db);
+    evm.env.cfg.spec_id = spec_id;
+    evm.env.tx.caller = caller;
+    evm.env.tx.transact_to = TransactTo::Call(CONTRACT);
+    evm.env.tx.gas_limit = 100_000;
+    evm
+}
+
+/// Execute call of `code` from `caller` under `spec_id`, see [code_evm].
+pub fn transact_code(code: &[u8], caller: H160, spec_id: SpecId) -> (ExecutionResult, State) {
+    code_evm(code, caller, spec_id).transact()
+}
+
+/// Generator of test state from seed.
+#[derive(Clone, Debug)]
+pub struct StateGenerator {
+    rng: u64,
+    /// Generated addresses, used as targets of generated CALL and BALANCE.
+    addresses: Vec<H160>,
+}
+
+impl StateGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            // xorshift state can't be zero.
+            rng: (seed ^ 0x9e37_79b9_7f4a_7c15) | 1,
+            addresses: Vec::new(),
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    /// Number in `0..bound`, `bound` is not zero.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// Word that is zero, small, near the maximum or random, in equal parts.
+    pub fn u256(&mut self) -> U256 {
+        match self.below(4) {
+            0 => U256::zero(),
+            1 => U256::from(self.below(256)),
+            2 => U256::MAX - self.below(256),
+            _ => U256([
+                self.next_u64(),
+                self.next_u64(),
+                self.next_u64(),
+                self.next_u64(),
+            ]),
+        }
+    }
+
+    /// New address that is remembered as target of generated code.
+    pub fn address(&mut self) -> H160 {
+        let address = H160::from_low_u64_be(self.next_u64() | 0x1_0000);
+        self.addresses.push(address);
+        address
+    }
+
+    /// Addresses generated so far.
+    pub fn addresses(&self) -> &[H160] {
+        &self.addresses
+    }
+
+    /// Up to `max_slots` slots from `0..GENERATED_SLOTS` with non zero values.
+    pub fn storage(&mut self, max_slots: usize) -> Vec<(U256, U256)> {
+        let slots = self.below(max_slots as u64 + 1);
+        (0..slots)
+            .map(|_| {
+                let slot = U256::from(self.below(GENERATED_SLOTS));
+                (slot, self.u256().max(U256::one()))
+            })
+            .collect()
+    }
+
+    /// Code of `snippets` stack neutral pieces, that can be a bounded loop of other pieces, and
+    /// `terminal` opcode or random one of STOP, RETURN, REVERT and INVALID.
+    pub fn code(&mut self, snippets: usize, terminal: Option<u8>) -> Vec<u8> {
+        let mut code = Vec::new();
+        for _ in 0..snippets {
+            if self.below(8) == 0 {
+                self.bounded_loop(&mut code);
+            } else {
+                self.snippet(&mut code);
+            }
+        }
+        let terminal =
+            terminal.unwrap_or_else(|| [STOP, RETURN, REVERT, INVALID][self.below(4) as usize]);
+        if matches!(terminal, RETURN | REVERT) {
+            // return first word of memory.
+            code.extend([PUSH1, 32, PUSH1, 0]);
+        }
+        code.push(terminal);
+        code
+    }
+
+    /// Account with balance, nonce and generated code.
+    pub fn account(&mut self) -> AccountInfo {
+        let balance = U256::from(self.below(1_000_000));
+        let nonce = self.below(4);
+        let code = if self.below(4) == 0 {
+            Vec::new()
+        } else {
+            let snippets = self.below(16) as usize + 1;
+            self.code(snippets, None)
+        };
+        AccountInfo::new(balance, nonce, Bytecode::new_raw(code.into()))
+    }
+
+    /// Database with `accounts` generated accounts and their storage. Addresses are generated
+    /// first so that code of every account can call any other.
+    pub fn state(&mut self, accounts: usize) -> InMemoryDB {
+        let addresses: Vec<_> = (0..accounts).map(|_| self.address()).collect();
+        let mut db = InMemoryDB::default();
+        for address in addresses {
+            let info = self.account();
+            db.insert_account_info(address, info);
+            for (slot, value) in self.storage(4) {
+                db.insert_account_storage(address, slot, value)
+                    .expect("in memory database doesn't fail");
+            }
+        }
+        db
+    }
+
+    fn push_address(&mut self, code: &mut Vec<u8>) {
+        let address = match self.addresses.len() as u64 {
+            0 => H160::from_low_u64_be(self.below(10) + 1),
+            len => {
+                let index = self.below(len) as usize;
+                self.addresses[index]
+            }
+        };
+        code.push(PUSH20);
+        code.extend(address.as_bytes());
+    }
+
+    /// Piece of code that leaves stack as it was.
+    fn snippet(&mut self, code: &mut Vec<u8>) {
+        let slot = self.below(GENERATED_SLOTS) as u8;
+        match self.below(8) {
+            0 => {
+                let value = self.u256();
+                push_u256(code, value);
+                code.extend([PUSH1, slot, SSTORE]);
+            }
+            1 => code.extend([PUSH1, slot, SLOAD, POP]),
+            2 => {
+                let op = [ADD, MUL, SUB, DIV, SDIV, MOD, EXP, LT, SLT, SHL, SAR, BYTE];
+                let op = op[self.below(op.len() as u64) as usize];
+                let (a, b) = (self.u256(), self.u256());
+                push_u256(code, a);
+                push_u256(code, b);
+                code.extend([op, PUSH1, 0, MSTORE]);
+            }
+            3 => code.extend([PUSH1, 32, PUSH1, 0, LOG0]),
+            4 => {
+                self.push_address(code);
+                code.extend([BALANCE, POP]);
+            }
+            5 => {
+                // forward jump over a piece that is skipped if condition is set.
+                let condition = self.below(2) as u8;
+                code.extend([PUSH1, condition, PUSH2, 0, 0, JUMPI]);
+                let target = code.len() - 3;
+                code.extend([PUSH1, slot, SLOAD, POP]);
+                let destination = code.len() as u16;
+                code[target..target + 2].copy_from_slice(&destination.to_be_bytes());
+                code.push(JUMPDEST);
+            }
+            _ => {
+                // CALL with little gas and value of zero or one wei, its success is dropped.
+                code.extend([PUSH1, 0, PUSH1, 0, PUSH1, 0, PUSH1, 0]);
+                code.extend([PUSH1, self.below(2) as u8]);
+                self.push_address(code);
+                let gas = self.below(0x4000) as u16 + 0x1000;
+                code.push(PUSH2);
+                code.extend(gas.to_be_bytes());
+                code.extend([CALL, POP]);
+            }
+        }
+    }
+
+    /// Loop that runs its body `1..=MAX_LOOP_ITERATIONS` times. Counter stays on stack while
+    /// body runs and body is stack neutral.
+    fn bounded_loop(&mut self, code: &mut Vec<u8>) {
+        let iterations = self.below(MAX_LOOP_ITERATIONS as u64) as u8 + 1;
+        code.extend([PUSH1, iterations]);
+        let start = code.len() as u16;
+        code.push(JUMPDEST);
+        for _ in 0..self.below(3) + 1 {
+            self.snippet(code);
+        }
+        // counter - 1, jump to start while it is not zero.
+        code.extend([PUSH1, 1, SWAP1, SUB, DUP1, PUSH2]);
+        code.extend(start.to_be_bytes());
+        code.extend([JUMPI, POP]);
+    }
+}
+
+fn push_u256(code: &mut Vec<u8>, value: U256) {
+    let mut bytes = [0; 32];
+    value.to_big_endian(&mut bytes);
+    code.push(PUSH32);
+    code.extend(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::DatabaseRef, Return, TransactTo};
+
+    #[test]
+    fn test_deterministic() {
+        let state = |seed| {
+            let mut generator = StateGenerator::new(seed);
+            let db = generator.state(4);
+            let mut accounts: Vec<_> = db
+                .accounts
+                .iter()
+                .map(|(address, account)| (*address, account.info.clone(), account.storage.clone()))
+                .collect();
+            accounts.sort_by_key(|(address, ..)| *address);
+            accounts
+        };
+        assert_eq!(state(1), state(1));
+        assert_ne!(state(1), state(2));
+    }
+
+    #[test]
+    fn test_generated_code_is_valid() {
+        let mut generator = StateGenerator::new(7);
+        for _ in 0..100 {
+            let code = generator.code(32, Some(STOP));
+            let mut evm = code_evm(&code, H160::zero(), SpecId::LATEST);
+            evm.env.tx.gas_limit = 10_000_000;
+            // no invalid jump, stack underflow or running out of gas in unbounded loop.
+            assert_eq!(evm.transact().0.exit_reason, Return::Stop);
+        }
+    }
+
+    /// Property: transaction that reverts changes no account other than the caller.
+    #[test]
+    fn test_revert_restores_state() {
+        for seed in 0..64 {
+            let mut generator = StateGenerator::new(seed);
+            let db = generator.state(6);
+            let contract = generator.address();
+            let mut code = generator.code(24, Some(REVERT));
+            // contract stores to its own storage before it reverts. Store is inserted before
+            // return data and REVERT so that jump destinations don't move.
+            let at = code.len() - 5;
+            code.splice(at..at, [PUSH1, 1, PUSH1, 0, SSTORE]);
+            let mut db = db;
+            db.insert_account_info(
+                contract,
+                AccountInfo::new(U256::from(1_000), 0, Bytecode::new_raw(code.into())),
+            );
+
+            let mut evm = crate::new();
+            evm.database(db.clone());
+            evm.env.tx.transact_to = TransactTo::Call(contract);
+            evm.env.tx.gas_limit = 10_000_000;
+            let (result, state) = evm.transact();
+            assert_eq!(result.exit_reason, Return::Revert, "seed {}", seed);
+
+            for (address, account) in state {
+                if address == evm.env.tx.caller {
+                    continue;
+                }
+                let info = db.basic(address).unwrap().unwrap_or_default();
+                assert_eq!(account.info.balance, info.balance, "seed {}", seed);
+                assert_eq!(account.info.nonce, info.nonce, "seed {}", seed);
+                for (slot, value) in account.storage {
+                    assert_eq!(
+                        value.present_value(),
+                        db.storage(address, slot).unwrap(),
+                        "seed {}",
+                        seed
+                    );
+                }
+            }
+        }
+    }
+}
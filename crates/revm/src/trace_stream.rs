@@ -0,0 +1,619 @@
+use crate::{
+    evm_impl::EVMData, CallInputs, CallScheme, CreateInputs, CreateScheme, Database, Gas,
+    Inspector, Interpreter, OpcodeSet, Return,
+};
+use bytes::Bytes;
+use core::convert::TryFrom;
+use primitive_types::{H160, U256};
+use std::{
+    io::{self, Write},
+    sync::mpsc::{self, Receiver, SyncSender, TrySendError},
+    vec::Vec,
+};
+
+/// Events are sent in batches of at least this many bytes, see [TraceStreamInspector::batch_size].
+pub const DEFAULT_BATCH_SIZE: usize = 64 * 1024;
+
+/// Step or call event of [TraceStreamInspector].
+///
+/// Every event is encoded as frame of tag byte, payload length byte and payload. Numbers are
+/// little endian, addresses are 20 bytes and values 32 bytes big endian. Decoders skip frames
+/// with unknown tags, so new events can be added without breaking consumers.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TraceEvent {
+    /// Opcode at `pc` is about to be executed.
+    Step {
+        depth: u16,
+        pc: u32,
+        opcode: u8,
+        gas_remaining: u64,
+        stack_len: u16,
+    },
+    Call {
+        depth: u16,
+        scheme: CallScheme,
+        caller: H160,
+        /// Address whose storage is used.
+        address: H160,
+        /// Address whose code is executed.
+        code_address: H160,
+        value: U256,
+        gas_limit: u64,
+        input_len: u32,
+    },
+    CallEnd {
+        depth: u16,
+        result: Return,
+        gas_remaining: u64,
+        output_len: u32,
+    },
+    Create {
+        depth: u16,
+        /// CREATE2 if set, CREATE otherwise. Salt is not part of the event.
+        is_create2: bool,
+        caller: H160,
+        value: U256,
+        gas_limit: u64,
+        init_code_len: u32,
+    },
+    CreateEnd {
+        depth: u16,
+        result: Return,
+        address: Option<H160>,
+        gas_remaining: u64,
+        output_len: u32,
+    },
+}
+
+const TAG_STEP: u8 = 0;
+const TAG_CALL: u8 = 1;
+const TAG_CALL_END: u8 = 2;
+const TAG_CREATE: u8 = 3;
+const TAG_CREATE_END: u8 = 4;
+
+impl TraceEvent {
+    /// Append frame of the event to `out`.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        let start = out.len();
+        out.extend([0, 0]);
+        match self {
+            TraceEvent::Step {
+                depth,
+                pc,
+                opcode,
+                gas_remaining,
+                stack_len,
+            } => {
+                out[start] = TAG_STEP;
+                out.extend(depth.to_le_bytes());
+                out.extend(pc.to_le_bytes());
+                out.push(*opcode);
+                out.extend(gas_remaining.to_le_bytes());
+                out.extend(stack_len.to_le_bytes());
+            }
+            TraceEvent::Call {
+                depth,
+                scheme,
+                caller,
+                address,
+                code_address,
+                value,
+                gas_limit,
+                input_len,
+            } => {
+                out[start] = TAG_CALL;
+                out.extend(depth.to_le_bytes());
+                out.push(*scheme as u8);
+                out.extend(caller.as_bytes());
+                out.extend(address.as_bytes());
+                out.extend(code_address.as_bytes());
+                out.extend(<[u8; 32]>::from(*value));
+                out.extend(gas_limit.to_le_bytes());
+                out.extend(input_len.to_le_bytes());
+            }
+            TraceEvent::CallEnd {
+                depth,
+                result,
+                gas_remaining,
+                output_len,
+            } => {
+                out[start] = TAG_CALL_END;
+                out.extend(depth.to_le_bytes());
+                out.push(*result as u8);
+                out.extend(gas_remaining.to_le_bytes());
+                out.extend(output_len.to_le_bytes());
+            }
+            TraceEvent::Create {
+                depth,
+                is_create2,
+                caller,
+                value,
+                gas_limit,
+                init_code_len,
+            } => {
+                out[start] = TAG_CREATE;
+                out.extend(depth.to_le_bytes());
+                out.push(*is_create2 as u8);
+                out.extend(caller.as_bytes());
+                out.extend(<[u8; 32]>::from(*value));
+                out.extend(gas_limit.to_le_bytes());
+                out.extend(init_code_len.to_le_bytes());
+            }
+            TraceEvent::CreateEnd {
+                depth,
+                result,
+                address,
+                gas_remaining,
+                output_len,
+            } => {
+                out[start] = TAG_CREATE_END;
+                out.extend(depth.to_le_bytes());
+                out.push(*result as u8);
+                out.extend(address.unwrap_or_default().as_bytes());
+                out.push(address.is_some() as u8);
+                out.extend(gas_remaining.to_le_bytes());
+                out.extend(output_len.to_le_bytes());
+            }
+        }
+        out[start + 1] = (out.len() - start - 2) as u8;
+    }
+
+    /// Decode first frame of `bytes`. Returns the event, or `None` if its tag is unknown, and
+    /// length of the frame. Error is returned if frame is truncated or malformed.
+    pub fn decode(bytes: &[u8]) -> Result<(Option<TraceEvent>, usize), DecodeError> {
+        let (tag, len) = match bytes {
+            [tag, len, ..] => (*tag, *len as usize),
+            _ => return Err(DecodeError),
+        };
+        let payload = bytes.get(2..2 + len).ok_or(DecodeError)?;
+        let mut reader = Reader(payload);
+        let event = match tag {
+            TAG_STEP => Some(TraceEvent::Step {
+                depth: reader.u16()?,
+                pc: reader.u32()?,
+                opcode: reader.u8()?,
+                gas_remaining: reader.u64()?,
+                stack_len: reader.u16()?,
+            }),
+            TAG_CALL => Some(TraceEvent::Call {
+                depth: reader.u16()?,
+                scheme: match reader.u8()? {
+                    0 => CallScheme::Call,
+                    1 => CallScheme::CallCode,
+                    2 => CallScheme::DelegateCall,
+                    3 => CallScheme::StaticCall,
+                    _ => return Err(DecodeError),
+                },
+                caller: reader.address()?,
+                address: reader.address()?,
+                code_address: reader.address()?,
+                value: reader.u256()?,
+                gas_limit: reader.u64()?,
+                input_len: reader.u32()?,
+            }),
+            TAG_CALL_END => Some(TraceEvent::CallEnd {
+                depth: reader.u16()?,
+                result: reader.result()?,
+                gas_remaining: reader.u64()?,
+                output_len: reader.u32()?,
+            }),
+            TAG_CREATE => Some(TraceEvent::Create {
+                depth: reader.u16()?,
+                is_create2: reader.u8()? != 0,
+                caller: reader.address()?,
+                value: reader.u256()?,
+                gas_limit: reader.u64()?,
+                init_code_len: reader.u32()?,
+            }),
+            TAG_CREATE_END => Some(TraceEvent::CreateEnd {
+                depth: reader.u16()?,
+                result: reader.result()?,
+                address: {
+                    let address = reader.address()?;
+                    (reader.u8()? != 0).then_some(address)
+                },
+                gas_remaining: reader.u64()?,
+                output_len: reader.u32()?,
+            }),
+            _ => None,
+        };
+        Ok((event, 2 + len))
+    }
+
+    /// Decode all frames of a batch, events with unknown tags are skipped.
+    pub fn decode_all(mut bytes: &[u8]) -> Result<Vec<TraceEvent>, DecodeError> {
+        let mut events = Vec::new();
+        while !bytes.is_empty() {
+            let (event, len) = Self::decode(bytes)?;
+            events.extend(event);
+            bytes = &bytes[len..];
+        }
+        Ok(events)
+    }
+}
+
+/// Frame of [TraceEvent] is truncated or has invalid field.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DecodeError;
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let bytes = self.0.get(..N).ok_or(DecodeError)?;
+        self.0 = &self.0[N..];
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    fn u256(&mut self) -> Result<U256, DecodeError> {
+        self.take::<32>().map(|bytes| U256::from_big_endian(&bytes))
+    }
+
+    fn address(&mut self) -> Result<H160, DecodeError> {
+        self.take().map(H160)
+    }
+
+    fn result(&mut self) -> Result<Return, DecodeError> {
+        Return::try_from(self.u8()?).map_err(|_| DecodeError)
+    }
+}
+
+/// What [TraceStreamInspector] does when queue of the channel is full.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Backpressure {
+    /// Wait until consumer takes a batch. Trace is complete but slow consumer slows execution.
+    Block,
+    /// Drop the batch and count it in [TraceStreamInspector::dropped_batches]. Execution is
+    /// never blocked.
+    Drop,
+}
+
+/// Inspector that streams [TraceEvent]s to out-of-process consumers, like GUIs and analyzers.
+///
+/// Events are encoded into batches of frames and sent over bounded channel, so interpreter
+/// thread only encodes events and is not blocked beyond the queue. Consumer can decode batches
+/// with [TraceEvent::decode_all] or forward them to a socket with [write_batches]. Last batch is
+/// sent at the end of transaction. Tracing stops if receiver is dropped.
+///
+/// ```ignore
+/// let (mut inspector, receiver) = trace_channel(16, Backpressure::Block);
+/// let stream = TcpStream::connect("127.0.0.1:9000")?;
+/// std::thread::spawn(move || write_batches(receiver, stream));
+/// evm.inspect(&mut inspector);
+/// ```
+#[derive(Debug)]
+pub struct TraceStreamInspector {
+    sender: Option<SyncSender<Vec<u8>>>,
+    backpressure: Backpressure,
+    batch: Vec<u8>,
+    batch_size: usize,
+    steps: bool,
+    dropped_batches: u64,
+}
+
+/// Inspector and receiver of its batches, channel queues up to `bound` batches.
+pub fn trace_channel(
+    bound: usize,
+    backpressure: Backpressure,
+) -> (TraceStreamInspector, Receiver<Vec<u8>>) {
+    let (sender, receiver) = mpsc::sync_channel(bound);
+    (TraceStreamInspector::new(sender, backpressure), receiver)
+}
+
+/// Write every received batch to `writer` until all senders are dropped.
+pub fn write_batches(receiver: Receiver<Vec<u8>>, mut writer: impl Write) -> io::Result<()> {
+    for batch in receiver {
+        writer.write_all(&batch)?;
+    }
+    writer.flush()
+}
+
+impl TraceStreamInspector {
+    pub fn new(sender: SyncSender<Vec<u8>>, backpressure: Backpressure) -> Self {
+        Self {
+            sender: Some(sender),
+            backpressure,
+            batch: Vec::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            steps: true,
+            dropped_batches: 0,
+        }
+    }
+
+    /// Send batch when it has at least `batch_size` bytes, one sends every event separately.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Stream only call and create events. Steps are not inspected at all then.
+    pub fn without_steps(mut self) -> Self {
+        self.steps = false;
+        self
+    }
+
+    /// Number of batches dropped because the queue was full, see [Backpressure::Drop].
+    pub fn dropped_batches(&self) -> u64 {
+        self.dropped_batches
+    }
+
+    /// Returns false if receiver was dropped and events are not streamed anymore.
+    pub fn is_connected(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Send events that are not yet sent.
+    pub fn flush(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+        let Some(sender) = self.sender.as_ref() else {
+            self.batch.clear();
+            return;
+        };
+        let batch = core::mem::replace(&mut self.batch, Vec::with_capacity(self.batch_size));
+        let disconnected = match self.backpressure {
+            Backpressure::Block => sender.send(batch).is_err(),
+            Backpressure::Drop => match sender.try_send(batch) {
+                Ok(()) => false,
+                Err(TrySendError::Full(_)) => {
+                    self.dropped_batches += 1;
+                    false
+                }
+                Err(TrySendError::Disconnected(_)) => true,
+            },
+        };
+        if disconnected {
+            self.sender = None;
+        }
+    }
+
+    fn push(&mut self, event: TraceEvent) {
+        if self.sender.is_none() {
+            return;
+        }
+        event.encode(&mut self.batch);
+        if self.batch.len() >= self.batch_size {
+            self.flush();
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for TraceStreamInspector {
+    fn step(
+        &mut self,
+        interp: &mut Interpreter,
+        data: &mut EVMData<'_, DB>,
+        _is_static: bool,
+    ) -> Return {
+        self.push(TraceEvent::Step {
+            depth: data.journaled_state.depth() as u16,
+            pc: interp.program_counter() as u32,
+            opcode: interp.current_opcode(),
+            gas_remaining: interp.gas().remaining(),
+            stack_len: interp.stack().len() as u16,
+        });
+        Return::Continue
+    }
+
+    fn step_opcodes(&self) -> OpcodeSet {
+        if self.steps {
+            OpcodeSet::ALL
+        } else {
+            OpcodeSet::NONE
+        }
+    }
+
+    fn call(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+        _is_static: bool,
+    ) -> (Return, Gas, Bytes) {
+        self.push(TraceEvent::Call {
+            depth: data.journaled_state.depth() as u16,
+            scheme: inputs.context.scheme,
+            caller: inputs.context.caller,
+            address: inputs.context.address,
+            code_address: inputs.contract,
+            value: inputs.context.apparent_value,
+            gas_limit: inputs.gas_limit,
+            input_len: inputs.input.len() as u32,
+        });
+        (Return::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        _inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: Return,
+        out: Bytes,
+        _is_static: bool,
+    ) -> (Return, Gas, Bytes) {
+        self.push(TraceEvent::CallEnd {
+            depth: data.journaled_state.depth() as u16,
+            result: ret,
+            gas_remaining: remaining_gas.remaining(),
+            output_len: out.len() as u32,
+        });
+        (ret, remaining_gas, out)
+    }
+
+    fn create(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CreateInputs,
+    ) -> (Return, Option<H160>, Gas, Bytes) {
+        self.push(TraceEvent::Create {
+            depth: data.journaled_state.depth() as u16,
+            is_create2: matches!(inputs.scheme, CreateScheme::Create2 { .. }),
+            caller: inputs.caller,
+            value: inputs.value,
+            gas_limit: inputs.gas_limit,
+            init_code_len: inputs.init_code.len() as u32,
+        });
+        (Return::Continue, None, Gas::new(0), Bytes::default())
+    }
+
+    fn create_end(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        _inputs: &CreateInputs,
+        ret: Return,
+        address: Option<H160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (Return, Option<H160>, Gas, Bytes) {
+        self.push(TraceEvent::CreateEnd {
+            depth: data.journaled_state.depth() as u16,
+            result: ret,
+            address,
+            gas_remaining: remaining_gas.remaining(),
+            output_len: out.len() as u32,
+        });
+        (ret, address, remaining_gas, out)
+    }
+
+    fn transact_end(&mut self, _result: &crate::ExecutionResult, _state: &crate::State) {
+        self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::InMemoryDB, opcode::*, AccountInfo, Bytecode, TransactTo};
+
+    #[test]
+    fn test_trace_event_encoding() {
+        let events = vec![
+            TraceEvent::Step {
+                depth: 1,
+                pc: 7,
+                opcode: ADD,
+                gas_remaining: 100,
+                stack_len: 2,
+            },
+            TraceEvent::Call {
+                depth: 2,
+                scheme: CallScheme::DelegateCall,
+                caller: H160::repeat_byte(1),
+                address: H160::repeat_byte(2),
+                code_address: H160::repeat_byte(3),
+                value: U256::MAX,
+                gas_limit: 5,
+                input_len: 4,
+            },
+            TraceEvent::CreateEnd {
+                depth: 0,
+                result: Return::CreateCollision,
+                address: None,
+                gas_remaining: 0,
+                output_len: 0,
+            },
+        ];
+        let mut bytes = Vec::new();
+        for event in &events {
+            event.encode(&mut bytes);
+        }
+        // unknown frame is skipped.
+        bytes.extend([0xff, 2, 0, 0]);
+        assert_eq!(TraceEvent::decode_all(&bytes), Ok(events));
+        assert_eq!(
+            TraceEvent::decode_all(&bytes[..bytes.len() - 1]),
+            Err(DecodeError)
+        );
+    }
+
+    #[test]
+    fn test_trace_stream() {
+        let contract = H160::from_low_u64_be(0x1000);
+        let callee = H160::from_low_u64_be(0x2000);
+        // call callee with one byte of input.
+        let code = [
+            &[PUSH1, 0, DUP1, PUSH1, 1, PUSH1, 0, PUSH1, 0][..],
+            &[PUSH2, 0x20, 0, GAS, CALL, STOP],
+        ]
+        .concat();
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(code.into())),
+        );
+        db.insert_account_info(
+            callee,
+            AccountInfo::new(U256::zero(), 0, Bytecode::new_raw(vec![STOP].into())),
+        );
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+
+        let (inspector, receiver) = trace_channel(1024, Backpressure::Block);
+        let mut inspector = inspector.batch_size(1);
+        evm.inspect(&mut inspector);
+        let events: Vec<_> = receiver
+            .try_iter()
+            .flat_map(|batch| TraceEvent::decode_all(&batch).unwrap())
+            .collect();
+        let steps = events
+            .iter()
+            .filter(|event| matches!(event, TraceEvent::Step { .. }))
+            .count();
+        // 9 opcodes of contract and STOP of callee.
+        assert_eq!(steps, 10);
+        let calls: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                TraceEvent::Call {
+                    depth,
+                    code_address,
+                    gas_limit,
+                    input_len,
+                    ..
+                } => Some((*depth, *code_address, *gas_limit, *input_len)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(calls.len(), 2);
+        let (depth, code_address, gas_limit, input_len) = calls[1];
+        assert_eq!((code_address, input_len), (callee, 1));
+        // STOP of callee costs nothing.
+        assert!(events.contains(&TraceEvent::CallEnd {
+            depth,
+            result: Return::Stop,
+            gas_remaining: gas_limit,
+            output_len: 0,
+        }));
+
+        // without steps only calls are sent, and with full queue batches are dropped.
+        let (inspector, receiver) = trace_channel(1, Backpressure::Drop);
+        let mut inspector = inspector.batch_size(1).without_steps();
+        evm.inspect(&mut inspector);
+        assert_eq!(receiver.try_iter().count(), 1);
+        assert_eq!(inspector.dropped_batches(), 3);
+
+        // dropped receiver stops tracing.
+        drop(receiver);
+        evm.inspect(&mut inspector);
+        assert!(!inspector.is_connected());
+    }
+}
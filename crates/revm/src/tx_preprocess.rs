@@ -0,0 +1,329 @@
+//! Stages of transaction pre-processing that [crate::EVM::transact] runs before call or create
+//! of transaction is executed.
+//!
+//! Every stage is a separate function so that new kind of transaction adds a stage instead of
+//! growing one function. Stages run in order: [validate_env], warming of [warm_addresses] and
+//! [warm_access_list] (or [warm_access_list_set] of prepared transaction), and [intrinsic_gas].
+use crate::{
+    db::Database,
+    gas::{access_list_gas, initial_tx_gas, GasTable},
+    journaled_state::JournaledState,
+    Env, Return, SpecFlags,
+    SpecId::*,
+    TransactTo,
+};
+use alloc::{collections::BTreeSet, vec::Vec};
+use primitive_types::{H160, U256};
+
+/// EIP-2930: Accounts and storage slots that access list makes warm at the start of transaction,
+/// with intrinsic gas of the list. Used by [crate::EVM::transact] and by
+/// [crate::TxValidator::access_list_warm_set] for validation outside of execution.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AccessListWarmSet {
+    pub addresses: BTreeSet<H160>,
+    /// Slots with address of their account, that is also in `addresses`.
+    pub slots: BTreeSet<(H160, U256)>,
+    /// Intrinsic gas of access list, see [crate::gas::access_list_gas].
+    pub gas: u64,
+}
+
+impl AccessListWarmSet {
+    /// Warm sets of `access_list`. Duplicate addresses and slots are warmed once but charged for
+    /// every entry.
+    pub fn new(access_list: &[(H160, Vec<U256>)], table: &GasTable) -> Self {
+        let mut warm_set = Self {
+            gas: access_list_gas(access_list, table),
+            ..Default::default()
+        };
+        for (address, slots) in access_list {
+            warm_set.addresses.insert(*address);
+            warm_set
+                .slots
+                .extend(slots.iter().map(|slot| (*address, *slot)));
+        }
+        warm_set
+    }
+}
+
+/// Checks of transaction against block and config environment that don't need state.
+pub(crate) fn validate_env(flags: SpecFlags, env: &Env) -> Result<(), Return> {
+    if flags.enabled(LONDON) {
+        if let Some(priority_fee) = env.tx.gas_priority_fee {
+            if priority_fee > env.tx.gas_price {
+                // or gas_max_fee for eip1559
+                return Err(Return::GasMaxFeeGreaterThanPriorityFee);
+            }
+        }
+        // check minimal cost against basefee
+        if env.effective_gas_price() < env.block.basefee {
+            return Err(Return::GasPriceLessThenBasefee);
+        }
+    }
+    // EIP-155: Simple replay attack protection
+    if let Some(chain_id) = env.tx.chain_id {
+        if flags.enabled(SPURIOUS_DRAGON) && U256::from(chain_id) != env.chain_id() {
+            return Err(Return::InvalidChainId);
+        }
+    }
+    // unusual to be found here, but check if gas_limit is more then block_gas_limit
+    if U256::from(env.tx.gas_limit) > env.block.gas_limit {
+        return Err(Return::CallerGasLimitMoreThenBlock);
+    }
+    Ok(())
+}
+
+/// Load accounts that are warm at the start of every transaction, see
+/// [crate::CfgEnv::warm_addresses].
+pub(crate) fn warm_addresses<DB: Database>(
+    journaled_state: &mut JournaledState,
+    db: &mut DB,
+    addresses: &[H160],
+) -> Result<(), DB::Error> {
+    for address in addresses {
+        journaled_state.load_account(*address, db)?;
+    }
+    Ok(())
+}
+
+/// EIP-2930: Load accounts and storage slots of access list so that their first access is warm.
+/// Entries are loaded in order they are listed, duplicates are found loaded and are not read
+/// again. Can be called for every access list when transaction has more than one.
+pub(crate) fn warm_access_list<DB: Database>(
+    journaled_state: &mut JournaledState,
+    db: &mut DB,
+    access_list: &[(H160, Vec<U256>)],
+) -> Result<(), DB::Error> {
+    for (address, slots) in access_list {
+        journaled_state.load_account(*address, db)?;
+        for slot in slots {
+            journaled_state.sload(*address, *slot, db)?;
+        }
+    }
+    Ok(())
+}
+
+/// [warm_access_list] of access list that was already deduplicated to [AccessListWarmSet].
+pub(crate) fn warm_access_list_set<DB: Database>(
+    journaled_state: &mut JournaledState,
+    db: &mut DB,
+    warm_set: &AccessListWarmSet,
+) -> Result<(), DB::Error> {
+    for address in &warm_set.addresses {
+        journaled_state.load_account(*address, db)?;
+    }
+    for (address, slot) in &warm_set.slots {
+        journaled_state.sload(*address, *slot, db)?;
+    }
+    Ok(())
+}
+
+/// Gas that transaction pays before execution. Zero if gas is not measured.
+pub(crate) fn intrinsic_gas(table: &GasTable, env: &Env) -> u64 {
+    if !crate::USE_GAS {
+        return 0;
+    }
+    let is_create = matches!(env.tx.transact_to, TransactTo::Create(_));
+    initial_tx_gas(table, &env.tx.data, is_create, &env.tx.access_list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AccountInfo, CreateScheme, InMemoryDB};
+    use alloc::vec;
+    use primitive_types::H256;
+
+    #[test]
+    fn test_validate_env() {
+        let mut env = Env::default();
+        env.block.gas_limit = U256::from(30_000_000);
+        env.block.basefee = U256::from(10);
+        env.tx.gas_price = U256::from(20);
+        env.tx.gas_limit = 21_000;
+        assert_eq!(validate_env(LONDON.into(), &env), Ok(()));
+
+        env.tx.gas_priority_fee = Some(U256::from(21));
+        assert_eq!(
+            validate_env(LONDON.into(), &env),
+            Err(Return::GasMaxFeeGreaterThanPriorityFee)
+        );
+        env.tx.gas_priority_fee = None;
+
+        env.tx.gas_price = U256::from(5);
+        assert_eq!(
+            validate_env(LONDON.into(), &env),
+            Err(Return::GasPriceLessThenBasefee)
+        );
+        // basefee is not checked before London.
+        assert_eq!(validate_env(BERLIN.into(), &env), Ok(()));
+
+        env.tx.chain_id = Some(5);
+        assert_eq!(
+            validate_env(BERLIN.into(), &env),
+            Err(Return::InvalidChainId)
+        );
+        env.tx.chain_id = None;
+
+        env.tx.gas_limit = 30_000_001;
+        assert_eq!(
+            validate_env(SPURIOUS_DRAGON.into(), &env),
+            Err(Return::CallerGasLimitMoreThenBlock)
+        );
+    }
+
+    #[test]
+    fn test_warm_access_list() {
+        let first = H160::from_low_u64_be(0x1000);
+        let second = H160::from_low_u64_be(0x2000);
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(first, AccountInfo::from_balance(U256::one()));
+        db.insert_account_storage(first, U256::one(), U256::from(5))
+            .unwrap();
+
+        let mut journaled_state = JournaledState::new(0);
+        warm_addresses(&mut journaled_state, &mut db, &[second]).unwrap();
+        // lists are warmed one after another and can overlap.
+        warm_access_list(&mut journaled_state, &mut db, &[(first, vec![U256::one()])]).unwrap();
+        let table = GasTable::new(BERLIN.into());
+        let warm_set = AccessListWarmSet::new(&[(first, vec![U256::one(), U256::from(2)])], &table);
+        warm_access_list_set(&mut journaled_state, &mut db, &warm_set).unwrap();
+
+        for address in [first, second] {
+            assert!(
+                !journaled_state
+                    .load_account(address, &mut db)
+                    .unwrap()
+                    .is_cold
+            );
+        }
+        for (slot, value) in [(1, 5), (2, 0)] {
+            let load = journaled_state
+                .sload(first, U256::from(slot), &mut db)
+                .unwrap();
+            assert_eq!(load, crate::StateLoad::new(U256::from(value), false));
+        }
+    }
+
+    #[test]
+    fn test_access_list_warm_set() {
+        let token = H160::from_low_u64_be(0x1000);
+        let pair = H160::from_low_u64_be(0x2000);
+        // token and its slot 3 are listed twice.
+        let access_list = vec![
+            (token, vec![U256::from(3), U256::from(4)]),
+            (pair, vec![U256::from(8)]),
+            (token, vec![U256::from(3)]),
+        ];
+        let warm_set = AccessListWarmSet::new(&access_list, &GasTable::new(BERLIN.into()));
+        assert_eq!(warm_set.addresses, [token, pair].into_iter().collect());
+        assert_eq!(
+            warm_set.slots,
+            [(token, 3), (token, 4), (pair, 8)]
+                .into_iter()
+                .map(|(address, slot)| (address, U256::from(slot)))
+                .collect()
+        );
+        // EIP-2930 prices of 2400 per address and 1900 per slot, duplicates are charged.
+        assert_eq!(warm_set.gas, 3 * 2_400 + 4 * 1_900);
+
+        let mut env = Env::default();
+        env.tx.access_list = access_list.clone();
+        assert_eq!(
+            intrinsic_gas(&GasTable::new(BERLIN.into()), &env),
+            21_000 + warm_set.gas
+        );
+        // access list is free and warms nothing before Berlin, sets are still returned.
+        let istanbul = AccessListWarmSet::new(&access_list, &GasTable::new(ISTANBUL.into()));
+        assert_eq!(istanbul.gas, 0);
+        assert_eq!(istanbul.slots, warm_set.slots);
+    }
+
+    /// `transfer(address,uint256)` of ERC-20 with selector `0xa9059cbb`.
+    fn erc20_transfer(to: H160, amount: U256) -> bytes::Bytes {
+        let mut data = vec![0xa9, 0x05, 0x9c, 0xbb];
+        data.extend(H256::from(to).as_bytes());
+        let mut word = [0; 32];
+        amount.to_big_endian(&mut word);
+        data.extend(word);
+        data.into()
+    }
+
+    /// Access lists of mainnet token transfers: balance slots are `keccak(holder . slot)` of
+    /// balance mapping, slot 3 of WETH9 and slot 9 of USDC that is called through its proxy.
+    #[test]
+    fn test_mainnet_access_lists() {
+        let address = |s: &str| s.parse::<H160>().unwrap();
+        let slot = |s: &str| U256::from_big_endian(s.parse::<H256>().unwrap().as_bytes());
+        let weth = address("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2");
+        let usdc = address("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+        let usdc_implementation = address("43506849d7c04f9138d1a2050bbf3a0c054402dd");
+        let sender = address("28c6c06298d514db089934071355e5743bf21d60");
+        let recipient = address("d8da6bf26964af9d7eed9e03e53415d37aa96045");
+
+        let mut env = Env::default();
+        env.tx.caller = sender;
+        let table = GasTable::new(BERLIN.into());
+
+        // WETH transfer of 1 ether.
+        env.tx.transact_to = TransactTo::Call(weth);
+        env.tx.data = erc20_transfer(recipient, U256::exp10(18));
+        env.tx.access_list = vec![(
+            weth,
+            vec![
+                slot("dca77c2adfd7db987f63f9968b5a29d8cf2d5bee6727fb1e132443d4c5e6a94e"),
+                slot("3a988d762a24303c37d08f1543db6143453b579691d5c20fed39629ff1334cca"),
+            ],
+        )];
+        assert_eq!(intrinsic_gas(&table, &env), 27_832);
+
+        // USDC transfer of 1000 USDC, proxy reads implementation from ZeppelinOS proxy slot.
+        env.tx.transact_to = TransactTo::Call(usdc);
+        env.tx.data = erc20_transfer(recipient, U256::from(1_000_000_000u64));
+        env.tx.access_list = vec![
+            (
+                usdc,
+                vec![
+                    slot("7050c9e0f4ca769c69bd3a8ef740bc37934f8e2c036e5a723fd8ee048ed3f8c3"),
+                    slot("07081a045c3dbf2e63b62a407ef205e7586e2629d2e2b95ff093308ca0ff3727"),
+                    slot("bf4954ae1137d99a74d9587692d0c99fcc87859496c91311c267c25a44a35f95"),
+                ],
+            ),
+            (usdc_implementation, vec![]),
+        ];
+        assert_eq!(intrinsic_gas(&table, &env), 32_096);
+
+        // warming directly from access list and from warm set loads the same state.
+        let warm_set = AccessListWarmSet::new(&env.tx.access_list, &table);
+        assert_eq!(warm_set.gas, 2 * 2_400 + 3 * 1_900);
+        let mut direct = JournaledState::new(0);
+        let mut from_set = JournaledState::new(0);
+        let mut db = InMemoryDB::default();
+        warm_access_list(&mut direct, &mut db, &env.tx.access_list).unwrap();
+        warm_access_list_set(&mut from_set, &mut db, &warm_set).unwrap();
+        for journaled_state in [&mut direct, &mut from_set] {
+            for address in [usdc, usdc_implementation] {
+                let load = journaled_state.load_account(address, &mut db).unwrap();
+                assert!(!load.is_cold);
+            }
+            for (_, slot) in &warm_set.slots {
+                let load = journaled_state.sload(usdc, *slot, &mut db).unwrap();
+                assert!(!load.is_cold);
+            }
+        }
+    }
+
+    #[test]
+    fn test_intrinsic_gas() {
+        let mut env = Env::default();
+        env.tx.access_list = vec![(H160::zero(), vec![U256::zero()])];
+        assert_eq!(
+            intrinsic_gas(&GasTable::new(BERLIN.into()), &env),
+            21_000 + 2_400 + 1_900
+        );
+        env.tx.transact_to = TransactTo::Create(CreateScheme::Create);
+        assert_eq!(
+            intrinsic_gas(&GasTable::new(BERLIN.into()), &env),
+            53_000 + 2_400 + 1_900
+        );
+    }
+}
@@ -0,0 +1,441 @@
+//! Validation of transactions before they are accepted into transaction pool.
+//!
+//! [TxValidator] runs the same checks that are done at the start of [crate::EVM::transact] but
+//! without executing transaction, and reports reason of rejection in [InvalidTransaction].
+use crate::{
+    fees::{calc_blob_gasprice, GAS_PER_BLOB, MAX_BLOB_GAS_PER_BLOCK},
+    gas::{initial_tx_gas, GasTable},
+    AccessListWarmSet, AccountInfo, Env, SpecFlags, SpecId, TransactTo, Wei, KECCAK_EMPTY,
+};
+use primitive_types::U256;
+
+/// Reason why transaction can't be included in block.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InvalidTransaction {
+    /// EIP-1559: Priority fee is greater than max fee.
+    PriorityFeeGreaterThanMaxFee,
+    /// EIP-1559: Max fee is less than basefee of block.
+    GasPriceLessThanBasefee { gas_price: U256, basefee: U256 },
+    /// EIP-155: Transaction is signed for different chain.
+    InvalidChainId { tx: u64, chain_id: U256 },
+    /// Gas limit is more than gas limit of block.
+    GasLimitMoreThanBlock {
+        gas_limit: u64,
+        block_gas_limit: U256,
+    },
+    /// Gas limit does not cover intrinsic gas.
+    GasLimitLessThanIntrinsic { gas_limit: u64, intrinsic_gas: u64 },
+    /// Transaction data is larger than [TxValidator::max_tx_data_size].
+    TxDataSizeLimit { size: usize, limit: usize },
+    /// Init code is larger than [crate::CfgEnv::limit_initcode_size].
+    CreateInitcodeSizeLimit { size: usize, limit: usize },
+    /// EIP-3607: Sender has deployed code.
+    RejectCallerWithCode,
+    /// Nonce of transaction is lower than nonce of sender.
+    NonceTooLow { tx: u64, state: u64 },
+    /// Nonce of transaction is higher than nonce of sender.
+    NonceTooHigh { tx: u64, state: u64 },
+    /// Nonce of sender can't be increased.
+    NonceOverflow,
+    /// Calculation of maximal transaction cost overflowed.
+    OverflowPayment,
+    /// Sender can't pay maximal cost of transaction.
+    LackOfFundForMaxFee { cost: U256, balance: U256 },
+    /// EIP-4844: Blob transaction without blobs.
+    EmptyBlobs,
+    /// EIP-4844: More blobs than can fit in block.
+    TooManyBlobs { count: u64, limit: u64 },
+    /// EIP-4844: Blob transaction can't create contract.
+    BlobCreateTransaction,
+    /// EIP-4844: Max fee per blob gas is less than blob gas price of block.
+    BlobMaxFeeLessThanBlobGasPrice { blob_gasprice: u128, max_fee: u128 },
+}
+
+/// EIP-4844 fields of blob transaction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlobTx {
+    /// Number of blob versioned hashes.
+    pub count: u64,
+    pub max_fee_per_blob_gas: u128,
+}
+
+impl BlobTx {
+    /// Blob gas used by transaction.
+    pub fn blob_gas(&self) -> u64 {
+        self.count.saturating_mul(GAS_PER_BLOB)
+    }
+
+    /// Typed [BlobTx::max_fee_per_blob_gas].
+    pub fn max_fee_per_blob_gas_wei(&self) -> Wei {
+        self.max_fee_per_blob_gas.into()
+    }
+}
+
+/// Transaction checks done by transaction pool.
+///
+/// Checks are split between stateless ones, that need only [Env], and stateful ones that
+/// additionally need account of the sender. Unlike execution, balance is checked against max fee
+/// and not against effective gas price so that transaction stays valid if basefee rises.
+#[derive(Clone, Debug)]
+pub struct TxValidator {
+    flags: SpecFlags,
+    /// Blob gas price of the block transaction is validated for.
+    pub blob_gasprice: u128,
+    /// Limit of transaction data size. There is no limit by default.
+    pub max_tx_data_size: Option<usize>,
+}
+
+impl TxValidator {
+    pub fn new(spec_id: SpecId) -> Self {
+        Self::with_flags(SpecFlags::new(spec_id))
+    }
+
+    /// Validator for chain with custom set of hardforks. See [crate::CustomSpec].
+    pub fn with_flags(flags: SpecFlags) -> Self {
+        Self {
+            flags,
+            blob_gasprice: calc_blob_gasprice(0),
+            max_tx_data_size: None,
+        }
+    }
+
+    /// Set blob gas price from excess blob gas of the block.
+    pub fn excess_blob_gas(mut self, excess_blob_gas: u64) -> Self {
+        self.blob_gasprice = calc_blob_gasprice(excess_blob_gas);
+        self
+    }
+
+    /// Typed [TxValidator::blob_gasprice].
+    pub fn blob_gasprice_wei(&self) -> Wei {
+        self.blob_gasprice.into()
+    }
+
+    pub fn max_tx_data_size(mut self, limit: usize) -> Self {
+        self.max_tx_data_size = Some(limit);
+        self
+    }
+
+    /// Intrinsic gas of transaction.
+    pub fn intrinsic_gas(&self, env: &Env) -> u64 {
+        let is_create = matches!(env.tx.transact_to, TransactTo::Create(_));
+        initial_tx_gas(
+            &GasTable::new(self.flags),
+            &env.tx.data,
+            is_create,
+            &env.tx.access_list,
+        )
+    }
+
+    /// Accounts and storage slots that access list of transaction makes warm and its intrinsic
+    /// gas, as they are used by execution.
+    pub fn access_list_warm_set(&self, env: &Env) -> AccessListWarmSet {
+        AccessListWarmSet::new(&env.tx.access_list, &GasTable::new(self.flags))
+    }
+
+    /// Run all checks. Returns intrinsic gas of transaction.
+    pub fn validate(
+        &self,
+        env: &Env,
+        caller: &AccountInfo,
+        blob: Option<&BlobTx>,
+    ) -> Result<u64, InvalidTransaction> {
+        let intrinsic_gas = self.validate_stateless(env, blob)?;
+        self.validate_stateful(env, caller, blob)?;
+        Ok(intrinsic_gas)
+    }
+
+    /// Checks that don't depend on state: fees, chain id, sizes and intrinsic gas.
+    /// Returns intrinsic gas of transaction.
+    pub fn validate_stateless(
+        &self,
+        env: &Env,
+        blob: Option<&BlobTx>,
+    ) -> Result<u64, InvalidTransaction> {
+        let tx = &env.tx;
+        if self.flags.supports_eip1559() {
+            if matches!(tx.gas_priority_fee, Some(priority_fee) if priority_fee > tx.gas_price) {
+                return Err(InvalidTransaction::PriorityFeeGreaterThanMaxFee);
+            }
+            if tx.gas_price < env.block.basefee {
+                return Err(InvalidTransaction::GasPriceLessThanBasefee {
+                    gas_price: tx.gas_price,
+                    basefee: env.block.basefee,
+                });
+            }
+        }
+        // EIP-155: Simple replay attack protection
+        if let Some(chain_id) = tx.chain_id {
+            if self.flags.supports_eip155() && U256::from(chain_id) != env.chain_id() {
+                return Err(InvalidTransaction::InvalidChainId {
+                    tx: chain_id,
+                    chain_id: env.chain_id(),
+                });
+            }
+        }
+        if U256::from(tx.gas_limit) > env.block.gas_limit {
+            return Err(InvalidTransaction::GasLimitMoreThanBlock {
+                gas_limit: tx.gas_limit,
+                block_gas_limit: env.block.gas_limit,
+            });
+        }
+
+        let size = tx.data.len();
+        if let Some(limit) = self.max_tx_data_size {
+            if size > limit {
+                return Err(InvalidTransaction::TxDataSizeLimit { size, limit });
+            }
+        }
+        let is_create = matches!(tx.transact_to, TransactTo::Create(_));
+        if let (true, Some(limit)) = (is_create, env.cfg.limit_initcode_size) {
+            if size > limit {
+                return Err(InvalidTransaction::CreateInitcodeSizeLimit { size, limit });
+            }
+        }
+
+        if let Some(blob) = blob {
+            if blob.count == 0 {
+                return Err(InvalidTransaction::EmptyBlobs);
+            }
+            let limit = MAX_BLOB_GAS_PER_BLOCK / GAS_PER_BLOB;
+            if blob.count > limit {
+                return Err(InvalidTransaction::TooManyBlobs {
+                    count: blob.count,
+                    limit,
+                });
+            }
+            if is_create {
+                return Err(InvalidTransaction::BlobCreateTransaction);
+            }
+            if blob.max_fee_per_blob_gas < self.blob_gasprice {
+                return Err(InvalidTransaction::BlobMaxFeeLessThanBlobGasPrice {
+                    blob_gasprice: self.blob_gasprice,
+                    max_fee: blob.max_fee_per_blob_gas,
+                });
+            }
+        }
+
+        let intrinsic_gas = self.intrinsic_gas(env);
+        if intrinsic_gas > tx.gas_limit {
+            return Err(InvalidTransaction::GasLimitLessThanIntrinsic {
+                gas_limit: tx.gas_limit,
+                intrinsic_gas,
+            });
+        }
+        Ok(intrinsic_gas)
+    }
+
+    /// Checks against account of the sender: code, nonce and balance.
+    pub fn validate_stateful(
+        &self,
+        env: &Env,
+        caller: &AccountInfo,
+        blob: Option<&BlobTx>,
+    ) -> Result<(), InvalidTransaction> {
+        let tx = &env.tx;
+        // EIP-3607: Reject transactions from senders with deployed code
+        if caller.code_hash != KECCAK_EMPTY {
+            return Err(InvalidTransaction::RejectCallerWithCode);
+        }
+
+        if let Some(nonce) = tx.nonce {
+            let state = caller.nonce;
+            if nonce < state {
+                return Err(InvalidTransaction::NonceTooLow { tx: nonce, state });
+            }
+            if nonce > state {
+                return Err(InvalidTransaction::NonceTooHigh { tx: nonce, state });
+            }
+        }
+        if caller.nonce == u64::MAX {
+            return Err(InvalidTransaction::NonceOverflow);
+        }
+
+        // gas_limit * max_fee + value + blob_gas * max_fee_per_blob_gas
+        let blob_fee = match blob {
+            Some(blob) => U256::from(blob.blob_gas())
+                .checked_mul(U256::from(blob.max_fee_per_blob_gas))
+                .ok_or(InvalidTransaction::OverflowPayment)?,
+            None => U256::zero(),
+        };
+        let cost = U256::from(tx.gas_limit)
+            .checked_mul(tx.gas_price)
+            .and_then(|cost| cost.checked_add(tx.value))
+            .and_then(|cost| cost.checked_add(blob_fee))
+            .ok_or(InvalidTransaction::OverflowPayment)?;
+        if cost > caller.balance {
+            return Err(InvalidTransaction::LackOfFundForMaxFee {
+                cost,
+                balance: caller.balance,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use primitive_types::H160;
+
+    fn env() -> Env {
+        let mut env = Env::default();
+        env.block.gas_limit = U256::from(30_000_000);
+        env.block.basefee = U256::from(10);
+        env.tx.transact_to = TransactTo::Call(H160::from_low_u64_be(1));
+        env.tx.gas_limit = 21_000;
+        env.tx.gas_price = U256::from(20);
+        env.tx.nonce = Some(3);
+        env
+    }
+
+    fn caller(balance: u64) -> AccountInfo {
+        AccountInfo {
+            balance: U256::from(balance),
+            nonce: 3,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_stateless_checks() {
+        let validator = TxValidator::new(SpecId::LONDON);
+        assert_eq!(validator.validate_stateless(&env(), None), Ok(21_000));
+
+        let mut tx = env();
+        tx.tx.gas_price = U256::from(5);
+        assert_eq!(
+            validator.validate_stateless(&tx, None),
+            Err(InvalidTransaction::GasPriceLessThanBasefee {
+                gas_price: U256::from(5),
+                basefee: U256::from(10),
+            })
+        );
+        // basefee is not checked before London.
+        assert!(TxValidator::new(SpecId::BERLIN)
+            .validate_stateless(&tx, None)
+            .is_ok());
+
+        let mut tx = env();
+        tx.tx.gas_priority_fee = Some(U256::from(21));
+        assert_eq!(
+            validator.validate_stateless(&tx, None),
+            Err(InvalidTransaction::PriorityFeeGreaterThanMaxFee)
+        );
+
+        let mut tx = env();
+        tx.tx.data = Bytes::from_static(&[0, 1]);
+        assert_eq!(
+            validator.validate_stateless(&tx, None),
+            Err(InvalidTransaction::GasLimitLessThanIntrinsic {
+                gas_limit: 21_000,
+                intrinsic_gas: 21_000 + 4 + 16,
+            })
+        );
+        assert_eq!(
+            validator
+                .clone()
+                .max_tx_data_size(1)
+                .validate_stateless(&tx, None),
+            Err(InvalidTransaction::TxDataSizeLimit { size: 2, limit: 1 })
+        );
+
+        let mut tx = env();
+        tx.tx.chain_id = Some(5);
+        assert_eq!(
+            validator.validate_stateless(&tx, None),
+            Err(InvalidTransaction::InvalidChainId {
+                tx: 5,
+                chain_id: U256::one(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_access_list_warm_set() {
+        let mut tx = env();
+        tx.tx.access_list = vec![(H160::from_low_u64_be(0x1000), vec![U256::one()])];
+        for spec_id in [SpecId::ISTANBUL, SpecId::LONDON] {
+            let validator = TxValidator::new(spec_id);
+            let warm_set = validator.access_list_warm_set(&tx);
+            assert_eq!(validator.intrinsic_gas(&tx), 21_000 + warm_set.gas);
+            assert_eq!(warm_set.slots.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_blob_checks() {
+        let validator = TxValidator::new(SpecId::LATEST);
+        let blob = |count, max_fee_per_blob_gas| BlobTx {
+            count,
+            max_fee_per_blob_gas,
+        };
+        assert!(validator
+            .validate_stateless(&env(), Some(&blob(1, 1)))
+            .is_ok());
+        assert_eq!(
+            validator.validate_stateless(&env(), Some(&blob(0, 1))),
+            Err(InvalidTransaction::EmptyBlobs)
+        );
+        assert_eq!(
+            validator.validate_stateless(&env(), Some(&blob(7, 1))),
+            Err(InvalidTransaction::TooManyBlobs { count: 7, limit: 6 })
+        );
+        let validator = validator.excess_blob_gas(crate::fees::BLOB_GASPRICE_UPDATE_FRACTION);
+        assert_eq!(
+            validator.validate_stateless(&env(), Some(&blob(1, 1))),
+            Err(InvalidTransaction::BlobMaxFeeLessThanBlobGasPrice {
+                blob_gasprice: 2,
+                max_fee: 1,
+            })
+        );
+
+        // blob fee is part of maximal cost.
+        let cost = 21_000 * 20 + GAS_PER_BLOB * 2;
+        assert!(validator
+            .validate_stateful(&env(), &caller(cost), Some(&blob(1, 2)))
+            .is_ok());
+        assert_eq!(
+            validator.validate_stateful(&env(), &caller(cost - 1), Some(&blob(1, 2))),
+            Err(InvalidTransaction::LackOfFundForMaxFee {
+                cost: U256::from(cost),
+                balance: U256::from(cost - 1),
+            })
+        );
+    }
+
+    #[test]
+    fn test_stateful_checks() {
+        let validator = TxValidator::new(SpecId::LONDON);
+        let mut tx = env();
+        tx.tx.value = U256::from(1);
+        // balance covers max fee and not only effective gas price.
+        let cost = 21_000 * 20 + 1;
+        assert_eq!(validator.validate(&tx, &caller(cost), None), Ok(21_000));
+        assert_eq!(
+            validator.validate(&tx, &caller(cost - 1), None),
+            Err(InvalidTransaction::LackOfFundForMaxFee {
+                cost: U256::from(cost),
+                balance: U256::from(cost - 1),
+            })
+        );
+
+        tx.tx.nonce = Some(2);
+        assert_eq!(
+            validator.validate(&tx, &caller(cost), None),
+            Err(InvalidTransaction::NonceTooLow { tx: 2, state: 3 })
+        );
+        tx.tx.nonce = Some(4);
+        assert_eq!(
+            validator.validate(&tx, &caller(cost), None),
+            Err(InvalidTransaction::NonceTooHigh { tx: 4, state: 3 })
+        );
+
+        let mut contract = caller(cost);
+        contract.code_hash = primitive_types::H256::repeat_byte(1);
+        assert_eq!(
+            validator.validate_stateful(&env(), &contract, None),
+            Err(InvalidTransaction::RejectCallerWithCode)
+        );
+    }
+}
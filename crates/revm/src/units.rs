@@ -0,0 +1,172 @@
+//! Typed amounts of ether, to not mix up units where amounts cross API boundaries.
+//!
+//! [crate::Env] and results keep amounts as wei in [U256], typed accessors and setters convert
+//! from [Wei] and [Gwei] so code can migrate field by field, for example
+//! `env.set_gas_price_wei(Gwei(30))` can't be confused with 30 wei.
+//!
+//! Typed accessors cover transaction value, gas prices, basefee and blob fees. Withdrawals are
+//! not processed by this EVM, so there is no typed withdrawal amount; callers that apply them
+//! can convert their gwei amounts with [Gwei::to_wei].
+use core::{fmt, ops::Add};
+use primitive_types::U256;
+
+/// Number of wei in one gwei.
+pub const WEI_PER_GWEI: u64 = 1_000_000_000;
+
+/// Amount of ether in wei, unit of balances, values and gas prices of the EVM.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "with-serde", serde(transparent))]
+pub struct Wei(pub U256);
+
+/// Amount of ether in gwei, unit of withdrawals and of gas prices shown to users.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "with-serde", serde(transparent))]
+pub struct Gwei(pub u64);
+
+impl Wei {
+    pub const ZERO: Wei = Wei(U256::zero());
+
+    pub fn as_u256(&self) -> U256 {
+        self.0
+    }
+
+    /// Cost of `gas` at gas price `self`, `None` on overflow.
+    pub fn checked_mul_gas(self, gas: u64) -> Option<Wei> {
+        self.0.checked_mul(U256::from(gas)).map(Wei)
+    }
+
+    pub fn checked_add(self, other: Wei) -> Option<Wei> {
+        self.0.checked_add(other.0).map(Wei)
+    }
+
+    pub fn saturating_sub(self, other: Wei) -> Wei {
+        Wei(self.0.saturating_sub(other.0))
+    }
+
+    /// Amount in gwei, `None` if it is not whole number of gwei or doesn't fit.
+    pub fn to_gwei(self) -> Option<Gwei> {
+        let (gwei, rem) = self.0.div_mod(U256::from(WEI_PER_GWEI));
+        if !rem.is_zero() || gwei.bits() > 64 {
+            return None;
+        }
+        Some(Gwei(gwei.as_u64()))
+    }
+}
+
+impl Gwei {
+    pub fn to_wei(self) -> Wei {
+        Wei(U256::from(self.0) * U256::from(WEI_PER_GWEI))
+    }
+}
+
+impl From<U256> for Wei {
+    fn from(amount: U256) -> Self {
+        Wei(amount)
+    }
+}
+
+impl From<u64> for Wei {
+    fn from(amount: u64) -> Self {
+        Wei(amount.into())
+    }
+}
+
+impl From<u128> for Wei {
+    fn from(amount: u128) -> Self {
+        Wei(amount.into())
+    }
+}
+
+impl From<Gwei> for Wei {
+    fn from(amount: Gwei) -> Self {
+        amount.to_wei()
+    }
+}
+
+impl From<Wei> for U256 {
+    fn from(amount: Wei) -> Self {
+        amount.0
+    }
+}
+
+impl From<Gwei> for U256 {
+    fn from(amount: Gwei) -> Self {
+        amount.to_wei().0
+    }
+}
+
+/// Panics on overflow like [U256] addition.
+impl Add for Wei {
+    type Output = Wei;
+
+    fn add(self, other: Wei) -> Wei {
+        Wei(self.0 + other.0)
+    }
+}
+
+impl fmt::Display for Wei {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} wei", self.0)
+    }
+}
+
+impl fmt::Display for Gwei {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} gwei", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AccountInfo, BlobTx, InMemoryDB, SpecId, TransactTo, TxValidator};
+    use primitive_types::H160;
+
+    #[test]
+    fn test_units() {
+        assert_eq!(Wei::from(Gwei(3)), Wei::from(3_000_000_000u64));
+        assert_eq!(Wei::from(3_000_000_000u64).to_gwei(), Some(Gwei(3)));
+        assert_eq!(Wei::from(3_000_000_001u64).to_gwei(), None);
+        assert_eq!(Wei(U256::MAX).to_gwei(), None);
+        assert_eq!(Gwei(u64::MAX).to_wei().to_gwei(), Some(Gwei(u64::MAX)));
+        assert_eq!(Wei::from(7u64).checked_mul_gas(3), Some(Wei::from(21u64)));
+        assert_eq!(Wei(U256::MAX).checked_mul_gas(2), None);
+        assert_eq!(Gwei(2).to_string(), "2 gwei");
+
+        let blob = BlobTx {
+            count: 1,
+            max_fee_per_blob_gas: 3_000_000_000,
+        };
+        assert_eq!(blob.max_fee_per_blob_gas_wei().to_gwei(), Some(Gwei(3)));
+        let validator = TxValidator::new(SpecId::CANCUN).excess_blob_gas(0);
+        assert_eq!(validator.blob_gasprice_wei(), Wei::from(1u64));
+
+        let caller = H160::from_low_u64_be(0x1000);
+        let mut evm = crate::new();
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(caller, AccountInfo::from_balance(Gwei(1_000_000).into()));
+        evm.database(db);
+        evm.env.cfg.spec_id = SpecId::LONDON;
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(H160::from_low_u64_be(0x2000));
+        evm.env.tx.gas_limit = 21_000;
+        evm.env.set_basefee_wei(Gwei(10));
+        evm.env.set_gas_price_wei(Gwei(30));
+        evm.env.set_gas_priority_fee_wei(Some(Gwei(2)));
+        evm.env.set_tx_value_wei(Wei::from(5u64));
+        assert_eq!(evm.env.effective_gas_price_wei(), Gwei(12).into());
+        assert_eq!(evm.env.tx.gas_price, U256::from(30_000_000_000u64));
+
+        let (result, state) = evm.transact();
+        let paid = result.fees.paid();
+        assert_eq!(Some(paid), Gwei(12).to_wei().checked_mul_gas(21_000));
+        assert_eq!(
+            Wei(state[&caller].info.balance),
+            Gwei(1_000_000)
+                .to_wei()
+                .saturating_sub(paid + evm.env.tx_value_wei())
+        );
+    }
+}
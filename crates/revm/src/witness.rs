@@ -0,0 +1,469 @@
+//! Execution witness for provers that replay transactions.
+//!
+//! [WitnessInspector] records, in execution order, every state read and write that opcodes do,
+//! code that is executed or copied and every keccak computation with its preimage, including the
+//! ones used to derive addresses of created contracts. [WitnessInspector::finish] adds accounts
+//! changed by the transaction so that the witness is complete without the state diff.
+use crate::{
+    alloc::vec::Vec,
+    evm_impl::EVMData,
+    journaled_state::{sorted_accounts, State},
+    opcode, CreateInputs, CreateScheme, Database, Inspector, Interpreter, OpcodeSet, Return,
+};
+use bytes::Bytes;
+use hashbrown::HashSet;
+use primitive_types::{H160, H256, U256};
+use sha3::{Digest, Keccak256};
+
+/// One access of the witness.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "with-serde", serde(tag = "type", rename_all = "camelCase"))]
+pub enum WitnessEntry {
+    /// Code executed by call or create frame or copied by EXTCODECOPY. Code itself is in
+    /// [Witness::codes].
+    #[cfg_attr(feature = "with-serde", serde(rename_all = "camelCase"))]
+    Code { address: H160, code_hash: H256 },
+    /// Account read by BALANCE, SELFBALANCE, EXTCODESIZE, EXTCODEHASH or EXTCODECOPY.
+    #[cfg_attr(feature = "with-serde", serde(rename_all = "camelCase"))]
+    Account {
+        address: H160,
+        balance: U256,
+        nonce: u64,
+        code_hash: H256,
+    },
+    /// Slot read by SLOAD.
+    StorageRead {
+        address: H160,
+        key: U256,
+        value: U256,
+    },
+    /// Slot written by SSTORE, `previous` is value that was overwritten.
+    StorageWrite {
+        address: H160,
+        key: U256,
+        previous: U256,
+        value: U256,
+    },
+    /// Keccak computed by SHA3 or to derive address of created contract.
+    Keccak {
+        #[cfg_attr(feature = "with-serde", serde(with = "crate::models::serde_hex_bytes"))]
+        preimage: Bytes,
+        hash: H256,
+    },
+    /// Account as it is after the transaction, added by [WitnessInspector::finish].
+    #[cfg_attr(feature = "with-serde", serde(rename_all = "camelCase"))]
+    AccountWrite {
+        address: H160,
+        balance: U256,
+        nonce: u64,
+        code_hash: H256,
+        destroyed: bool,
+    },
+}
+
+/// Code referenced by [WitnessEntry::Code].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "with-serde", serde(rename_all = "camelCase"))]
+pub struct WitnessCode {
+    pub code_hash: H256,
+    #[cfg_attr(feature = "with-serde", serde(with = "crate::models::serde_hex_bytes"))]
+    pub code: Bytes,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Witness {
+    /// Accesses in order of execution. Accesses of reverted frames are included.
+    pub entries: Vec<WitnessEntry>,
+    /// Every accessed code once, in order of first access.
+    pub codes: Vec<WitnessCode>,
+}
+
+/// Inspector that records [Witness] of transaction.
+#[derive(Clone, Debug, Default)]
+pub struct WitnessInspector {
+    witness: Witness,
+    code_hashes: HashSet<H256>,
+    /// Access of opcode that is waiting for its result in `step_end`.
+    pending: Option<Pending>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Pending {
+    Sha3 {
+        offset: usize,
+        len: usize,
+    },
+    Sload {
+        address: H160,
+        key: U256,
+    },
+    Sstore {
+        address: H160,
+        key: U256,
+        value: U256,
+        /// None if slot was not loaded before, overwritten value is then the original one.
+        previous: Option<U256>,
+    },
+    Account {
+        address: H160,
+        code: bool,
+    },
+}
+
+impl WitnessInspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Witness recorded so far.
+    pub fn witness(&self) -> &Witness {
+        &self.witness
+    }
+
+    /// Add accounts changed by transaction, sorted by address, and return the witness.
+    pub fn finish(mut self, state: &State) -> Witness {
+        for (address, account) in sorted_accounts(state) {
+            if account.is_touched || account.is_destroyed {
+                self.witness.entries.push(WitnessEntry::AccountWrite {
+                    address: *address,
+                    balance: account.info.balance,
+                    nonce: account.info.nonce,
+                    code_hash: account.info.code_hash,
+                    destroyed: account.is_destroyed,
+                });
+            }
+        }
+        self.witness
+    }
+
+    fn record_code(&mut self, address: H160, code_hash: H256, code: &[u8]) {
+        if self.code_hashes.insert(code_hash) {
+            self.witness.codes.push(WitnessCode {
+                code_hash,
+                code: Bytes::copy_from_slice(code),
+            });
+        }
+        self.witness
+            .entries
+            .push(WitnessEntry::Code { address, code_hash });
+    }
+
+    fn record_keccak(&mut self, preimage: Bytes) -> H256 {
+        let hash = H256::from_slice(Keccak256::digest(&preimage).as_slice());
+        self.witness
+            .entries
+            .push(WitnessEntry::Keccak { preimage, hash });
+        hash
+    }
+}
+
+fn to_h256(value: U256) -> H256 {
+    let mut out = H256::zero();
+    value.to_big_endian(out.as_bytes_mut());
+    out
+}
+
+impl<DB: Database> Inspector<DB> for WitnessInspector {
+    fn initialize_interp(
+        &mut self,
+        interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+        _is_static: bool,
+    ) -> Return {
+        let bytecode = &interp.contract.bytecode;
+        self.record_code(
+            interp.contract.address,
+            bytecode.hash(),
+            bytecode.original_bytecode_slice(),
+        );
+        Return::Continue
+    }
+
+    fn step_opcodes(&self) -> OpcodeSet {
+        OpcodeSet::new(&[
+            opcode::SHA3,
+            opcode::SLOAD,
+            opcode::SSTORE,
+            opcode::BALANCE,
+            opcode::SELFBALANCE,
+            opcode::EXTCODESIZE,
+            opcode::EXTCODEHASH,
+            opcode::EXTCODECOPY,
+        ])
+    }
+
+    fn step(
+        &mut self,
+        interp: &mut Interpreter,
+        data: &mut EVMData<'_, DB>,
+        _is_static: bool,
+    ) -> Return {
+        let stack = interp.stack();
+        let address = interp.contract.address;
+        let opcode = interp.current_opcode();
+        self.pending = match opcode {
+            opcode::SHA3 => match stack.peek_n::<2>() {
+                Ok([_, len]) if len.is_zero() => Some(Pending::Sha3 { offset: 0, len: 0 }),
+                // too large range makes SHA3 fail.
+                Ok([offset, len])
+                    if offset <= U256::from(usize::MAX) && len <= U256::from(usize::MAX) =>
+                {
+                    Some(Pending::Sha3 {
+                        offset: offset.as_usize(),
+                        len: len.as_usize(),
+                    })
+                }
+                _ => None,
+            },
+            opcode::SLOAD => stack
+                .peek(0)
+                .ok()
+                .map(|key| Pending::Sload { address, key }),
+            opcode::SSTORE => stack.peek_n::<2>().ok().map(|[key, value]| {
+                let previous = data
+                    .journaled_state
+                    .state
+                    .get(&address)
+                    .and_then(|account| account.storage.get(&key))
+                    .map(|slot| slot.present_value());
+                Pending::Sstore {
+                    address,
+                    key,
+                    value,
+                    previous,
+                }
+            }),
+            opcode::SELFBALANCE => Some(Pending::Account {
+                address,
+                code: false,
+            }),
+            _ => stack.peek(0).ok().map(|address| Pending::Account {
+                address: to_h256(address).into(),
+                code: opcode == opcode::EXTCODECOPY,
+            }),
+        };
+        Return::Continue
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        data: &mut EVMData<'_, DB>,
+        _is_static: bool,
+        eval: Return,
+    ) -> Return {
+        let pending = self.pending.take();
+        if eval != Return::Continue {
+            return Return::Continue;
+        }
+        let top = interp.stack().peek(0).unwrap_or_default();
+        match pending {
+            Some(Pending::Sha3 { offset, len }) => {
+                // memory is expanded by SHA3 so range is valid.
+                let preimage = if len == 0 {
+                    Bytes::new()
+                } else {
+                    Bytes::copy_from_slice(interp.memory.get_slice(offset, len))
+                };
+                self.record_keccak(preimage);
+            }
+            Some(Pending::Sload { address, key }) => {
+                self.witness.entries.push(WitnessEntry::StorageRead {
+                    address,
+                    key,
+                    value: top,
+                })
+            }
+            Some(Pending::Sstore {
+                address,
+                key,
+                value,
+                previous,
+            }) => {
+                let previous = previous.unwrap_or_else(|| {
+                    data.journaled_state.state[&address].storage[&key].original_value()
+                });
+                self.witness.entries.push(WitnessEntry::StorageWrite {
+                    address,
+                    key,
+                    previous,
+                    value,
+                })
+            }
+            Some(Pending::Account { address, code }) => {
+                // account is loaded by the opcode.
+                let info = data.journaled_state.state[&address].info.clone();
+                self.witness.entries.push(WitnessEntry::Account {
+                    address,
+                    balance: info.balance,
+                    nonce: info.nonce,
+                    code_hash: info.code_hash,
+                });
+                if code {
+                    // EXTCODECOPY of small range reads only copied part of code, whole code
+                    // is loaded for the witness.
+                    let code = match info.code {
+                        Some(code) => code,
+                        None => match data.journaled_state.load_code(address, data.db) {
+                            Ok(load) => load.data.info.code.clone().unwrap_or_default(),
+                            Err(error) => {
+                                data.error = Some(error);
+                                return Return::FatalExternalError;
+                            }
+                        },
+                    };
+                    self.record_code(address, info.code_hash, &code.bytes()[..code.len()]);
+                }
+            }
+            None => (),
+        }
+        Return::Continue
+    }
+
+    fn create_address(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &CreateInputs,
+        _address: H160,
+        nonce: u64,
+    ) {
+        match inputs.scheme {
+            CreateScheme::Create => {
+                let mut stream = rlp::RlpStream::new_list(2);
+                stream.append(&inputs.caller);
+                stream.append(&nonce);
+                self.record_keccak(stream.out().freeze());
+            }
+            CreateScheme::Create2 { salt } => {
+                let code_hash = self.record_keccak(inputs.init_code.clone());
+                let mut preimage = Vec::with_capacity(85);
+                preimage.push(0xff);
+                preimage.extend_from_slice(inputs.caller.as_bytes());
+                preimage.extend_from_slice(to_h256(salt).as_bytes());
+                preimage.extend_from_slice(code_hash.as_bytes());
+                self.record_keccak(preimage.into());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::InMemoryDB, opcode::*, AccountInfo, Bytecode, TransactTo};
+
+    #[test]
+    fn test_witness_inspector() {
+        let contract = H160::from_low_u64_be(0x1000);
+        let caller = H160::from_low_u64_be(0x2000);
+        let code = [
+            &[PUSH1, 5, PUSH1, 1, SSTORE, PUSH1, 1, SLOAD][..],
+            &[PUSH1, 0, MSTORE, PUSH1, 32, PUSH1, 0, SHA3, POP],
+            // create empty contract with CREATE2 and salt 7.
+            &[PUSH1, 7, PUSH1, 0, DUP1, DUP1, CREATE2, POP],
+            &[ADDRESS, BALANCE, POP, STOP],
+        ]
+        .concat();
+        let mut db = InMemoryDB::default();
+        db.insert_account_info(
+            contract,
+            AccountInfo::new(10.into(), 1, Bytecode::new_raw(code.clone().into())),
+        );
+        db.insert_account_storage(contract, 1.into(), 3.into())
+            .unwrap();
+        let mut evm = crate::new();
+        evm.database(db);
+        evm.env.tx.caller = caller;
+        evm.env.tx.transact_to = TransactTo::Call(contract);
+        evm.env.tx.gas_limit = 100_000;
+
+        let mut inspector = WitnessInspector::new();
+        let (result, state) = evm.inspect(&mut inspector);
+        assert_eq!(result.exit_reason, Return::Stop);
+        let witness = inspector.finish(&state);
+
+        let keccak = |preimage: &[u8]| H256::from_slice(Keccak256::digest(preimage).as_slice());
+        let code_hash = keccak(&code);
+        let empty_hash = keccak(&[]);
+        let created = crate::create2_address(contract, empty_hash, 7.into());
+        let mapped = to_h256(5.into()).as_bytes().to_vec();
+        let create2_preimage = [
+            &[0xff][..],
+            contract.as_bytes(),
+            to_h256(7.into()).as_bytes(),
+            empty_hash.as_bytes(),
+        ]
+        .concat();
+        assert_eq!(
+            witness.entries[..8],
+            [
+                WitnessEntry::Code {
+                    address: contract,
+                    code_hash
+                },
+                WitnessEntry::StorageWrite {
+                    address: contract,
+                    key: 1.into(),
+                    previous: 3.into(),
+                    value: 5.into()
+                },
+                WitnessEntry::StorageRead {
+                    address: contract,
+                    key: 1.into(),
+                    value: 5.into()
+                },
+                WitnessEntry::Keccak {
+                    hash: keccak(&mapped),
+                    preimage: mapped.into(),
+                },
+                // hash of init code and address of CREATE2.
+                WitnessEntry::Keccak {
+                    preimage: Bytes::new(),
+                    hash: empty_hash
+                },
+                WitnessEntry::Keccak {
+                    hash: keccak(&create2_preimage),
+                    preimage: create2_preimage.into(),
+                },
+                WitnessEntry::Code {
+                    address: created,
+                    code_hash: empty_hash
+                },
+                WitnessEntry::Account {
+                    address: contract,
+                    balance: 10.into(),
+                    nonce: 2,
+                    code_hash
+                },
+            ]
+        );
+        // changed accounts: coinbase, contract, caller and created contract.
+        let written: Vec<_> = witness.entries[8..]
+            .iter()
+            .map(|entry| match entry {
+                WitnessEntry::AccountWrite { address, nonce, .. } => (*address, *nonce),
+                _ => unreachable!("only account writes are added by finish"),
+            })
+            .collect();
+        assert_eq!(
+            written,
+            vec![(H160::zero(), 0), (contract, 2), (caller, 1), (created, 1)]
+        );
+        assert_eq!(
+            witness.codes,
+            vec![
+                WitnessCode {
+                    code_hash,
+                    code: code.into()
+                },
+                WitnessCode {
+                    code_hash: empty_hash,
+                    code: Bytes::new()
+                }
+            ]
+        );
+    }
+}
@@ -160,8 +160,8 @@ fn run_pair(
     pair_base_cost: u64,
     target_gas: u64,
 ) -> PrecompileResult {
-    let cost = pair_per_point_cost * input.len() as u64 / PAIR_ELEMENT_LEN as u64 + pair_base_cost;
-    let cost = gas_query(cost, target_gas)?;
+    let cost = pair_per_point_cost.saturating_mul(input.len() as u64) / PAIR_ELEMENT_LEN as u64;
+    let cost = gas_query(cost.saturating_add(pair_base_cost), target_gas)?;
 
     use bn::{AffineG1, AffineG2, Fq, Fq2, Group, Gt, G1, G2};
 
@@ -0,0 +1,29 @@
+use core::fmt;
+
+/// Trusted setup of KZG commitments, used by point evaluation precompile of EIP-4844.
+///
+/// Settings are passed with [crate::PrecompileContext] of every call instead of being global,
+/// so that services can load setup lazily and for every chain separately and tests can use tiny
+/// setups. Missing settings are reported by precompile as failure of the call.
+pub trait KzgSettings: Send + Sync + fmt::Debug {
+    /// Checks that polynomial of `commitment` evaluates to `y` at point `z`.
+    fn verify_kzg_proof(
+        &self,
+        commitment: &[u8; 48],
+        z: &[u8; 32],
+        y: &[u8; 32],
+        proof: &[u8; 48],
+    ) -> bool;
+}
+
+/// Settings are equal only if they are the same instance.
+impl PartialEq for dyn KzgSettings {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(
+            self as *const Self as *const u8,
+            other as *const Self as *const u8,
+        )
+    }
+}
+
+impl Eq for dyn KzgSettings {}
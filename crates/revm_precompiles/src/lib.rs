@@ -1,7 +1,7 @@
 #![no_std]
 
 use bytes::Bytes;
-use once_cell::sync::OnceCell;
+use once_cell::race::OnceBox;
 use primitive_types::{H160 as Address, H256, U256};
 
 mod blake2;
@@ -9,21 +9,26 @@ mod bn128;
 mod error;
 mod hash;
 mod identity;
+mod kzg;
 mod modexp;
 mod secp256k1;
 
 pub use error::Return;
+pub use kzg::KzgSettings;
 
 /// libraries for no_std flag
 #[macro_use]
 extern crate alloc;
-use alloc::vec::Vec;
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
 use core::fmt;
 
 use hashbrown::HashMap;
 
+/// Linear cost of input in words. Saturates on overflow so that huge input results in out of gas.
 pub fn calc_linear_cost_u32(len: usize, base: u64, word: u64) -> u64 {
-    (len as u64 + 32 - 1) / 32 * word + base
+    ((len as u64).saturating_add(32 - 1) / 32)
+        .saturating_mul(word)
+        .saturating_add(base)
 }
 
 pub fn gas_query(gas_used: u64, gas_limit: u64) -> Result<u64, Return> {
@@ -40,7 +45,7 @@ pub struct PrecompileOutput {
     pub logs: Vec<Log>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Log {
     pub address: Address,
     pub topics: Vec<H256>,
@@ -61,11 +66,28 @@ impl PrecompileOutput {
 pub type PrecompileResult = Result<PrecompileOutput, Return>;
 
 pub type StandardPrecompileFn = fn(&[u8], u64) -> PrecompileResult;
-pub type CustomPrecompileFn = fn(&[u8], u64) -> PrecompileResult;
+/// Precompile added by user. It gets context of the call and can emit logs in [PrecompileOutput].
+/// Logs are added to the journal of the call so they are reverted together with it.
+pub type CustomPrecompileFn = fn(&[u8], u64, &PrecompileContext) -> PrecompileResult;
+
+/// Context of the call passed to custom precompiles.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PrecompileContext {
+    /// Address of the precompile.
+    pub address: Address,
+    pub caller: Address,
+    /// Apparent value of the call.
+    pub value: U256,
+    /// Call is static so precompile is not allowed to emit logs.
+    pub is_static: bool,
+    /// Trusted setup of KZG commitments configured in the EVM.
+    pub kzg_settings: Option<Arc<dyn KzgSettings>>,
+}
 
 #[derive(Clone, Debug)]
 pub struct Precompiles {
     fun: HashMap<Address, Precompile>,
+    spec: SpecId,
 }
 
 impl Default for Precompiles {
@@ -95,7 +117,8 @@ pub enum SpecId {
     BYZANTIUM = 1,
     ISTANBUL = 2,
     BERLIN = 3,
-    LATEST = 4,
+    CANCUN = 4,
+    LATEST = 5,
 }
 
 impl SpecId {
@@ -106,7 +129,7 @@ impl SpecId {
 
 impl Precompiles {
     pub fn homestead() -> &'static Self {
-        static INSTANCE: OnceCell<Precompiles> = OnceCell::new();
+        static INSTANCE: OnceBox<Precompiles> = OnceBox::new();
         INSTANCE.get_or_init(|| {
             let fun = vec![
                 secp256k1::ECRECOVER,
@@ -116,14 +139,18 @@ impl Precompiles {
             ]
             .into_iter()
             .collect();
-            Self { fun }
+            Box::new(Self {
+                fun,
+                spec: SpecId::HOMESTEAD,
+            })
         })
     }
 
     pub fn byzantium() -> &'static Self {
-        static INSTANCE: OnceCell<Precompiles> = OnceCell::new();
+        static INSTANCE: OnceBox<Precompiles> = OnceBox::new();
         INSTANCE.get_or_init(|| {
             let mut precompiles = Self::homestead().clone();
+            precompiles.spec = SpecId::BYZANTIUM;
             precompiles.fun.extend(
                 vec![
                     // EIP-196: Precompiled contracts for addition and scalar multiplication on the elliptic curve alt_bn128.
@@ -136,14 +163,15 @@ impl Precompiles {
                 ]
                 .into_iter(),
             );
-            precompiles
+            Box::new(precompiles)
         })
     }
 
     pub fn istanbul() -> &'static Self {
-        static INSTANCE: OnceCell<Precompiles> = OnceCell::new();
+        static INSTANCE: OnceBox<Precompiles> = OnceBox::new();
         INSTANCE.get_or_init(|| {
             let mut precompiles = Self::byzantium().clone();
+            precompiles.spec = SpecId::ISTANBUL;
             precompiles.fun.extend(
                 vec![
                     // EIP-152: Add BLAKE2 compression function `F` precompile.
@@ -155,14 +183,15 @@ impl Precompiles {
                 ]
                 .into_iter(),
             );
-            precompiles
+            Box::new(precompiles)
         })
     }
 
     pub fn berlin() -> &'static Self {
-        static INSTANCE: OnceCell<Precompiles> = OnceCell::new();
+        static INSTANCE: OnceBox<Precompiles> = OnceBox::new();
         INSTANCE.get_or_init(|| {
             let mut precompiles = Self::istanbul().clone();
+            precompiles.spec = SpecId::BERLIN;
             precompiles.fun.extend(
                 vec![
                     // EIP-2565: ModExp Gas Cost.
@@ -170,12 +199,21 @@ impl Precompiles {
                 ]
                 .into_iter(),
             );
-            precompiles
+            Box::new(precompiles)
+        })
+    }
+
+    pub fn cancun() -> &'static Self {
+        static INSTANCE: OnceBox<Precompiles> = OnceBox::new();
+        INSTANCE.get_or_init(|| {
+            let mut precompiles = Self::berlin().clone();
+            precompiles.spec = SpecId::CANCUN;
+            Box::new(precompiles)
         })
     }
 
     pub fn latest() -> &'static Self {
-        Self::berlin()
+        Self::cancun()
     }
 
     pub fn new(spec: SpecId) -> &'static Self {
@@ -184,10 +222,16 @@ impl Precompiles {
             SpecId::BYZANTIUM => Self::byzantium(),
             SpecId::ISTANBUL => Self::istanbul(),
             SpecId::BERLIN => Self::berlin(),
+            SpecId::CANCUN => Self::cancun(),
             SpecId::LATEST => Self::latest(),
         }
     }
 
+    /// Spec that precompiles are defined for.
+    pub fn spec(&self) -> SpecId {
+        self.spec
+    }
+
     pub fn addresses(&self) -> impl IntoIterator<Item = &Address> {
         self.fun.keys()
     }
@@ -196,6 +240,11 @@ impl Precompiles {
         self.fun.contains_key(address)
     }
 
+    /// Add precompile or replace existing one at `address`.
+    pub fn insert(&mut self, address: Address, precompile: Precompile) {
+        self.fun.insert(address, precompile);
+    }
+
     pub fn get(&self, address: &Address) -> Option<Precompile> {
         //return None;
         self.fun.get(address).cloned()
@@ -246,3 +295,80 @@ pub fn u256_to_arr(value: &U256) -> [u8; 32] {
     value.to_big_endian(&mut result);
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo random bytes.
+    fn random_bytes(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn run_all(input: &[u8], gas_limit: u64) {
+        for address in Precompiles::latest().addresses() {
+            let precompile = Precompiles::latest().get(address).unwrap();
+            let run = match precompile {
+                Precompile::Standard(run) => run,
+                Precompile::Custom(_) => unreachable!("only standard precompiles are defined"),
+            };
+            if let Ok(output) = run(input, gas_limit) {
+                // u64::MAX is returned as cost for inputs that can't be paid.
+                assert!(output.cost <= gas_limit || output.cost == u64::MAX);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fuzz_input_sizes() {
+        const MB: usize = 1024 * 1024;
+        for (seed, len) in [0, 1, 31, 32, 33, 64, 96, 128, 192, 213, 384, MB, 4 * MB + 7]
+            .into_iter()
+            .enumerate()
+        {
+            let input = random_bytes(len, seed as u64);
+            for gas_limit in [0, 3_000, 30_000_000, u64::MAX] {
+                run_all(&input, gas_limit);
+            }
+            // all zero input is valid for most precompiles.
+            run_all(&vec![0; len], 30_000_000);
+        }
+    }
+
+    #[test]
+    fn test_modexp_huge_lengths() {
+        let max_len = U256::from(u32::MAX - 1);
+        for lengths in [
+            [max_len, U256::one(), U256::one()],
+            [U256::one(), max_len, U256::one()],
+            [U256::one(), U256::one(), max_len],
+            [U256::zero(), max_len, U256::zero()],
+            [max_len, max_len, max_len],
+            [U256::MAX, U256::MAX, U256::MAX],
+        ] {
+            let mut input = Vec::new();
+            for len in lengths {
+                input.extend_from_slice(&u256_to_arr(&len));
+            }
+            input.extend_from_slice(&random_bytes(1024 * 1024, 1));
+            for (_, precompile) in [modexp::BYZANTIUM, modexp::BERLIN] {
+                let run = match precompile {
+                    Precompile::Standard(run) => run,
+                    Precompile::Custom(_) => unreachable!("modexp is standard precompile"),
+                };
+                match run(&input, 30_000_000) {
+                    Ok(output) => assert!(output.cost <= 30_000_000 || output.cost == u64::MAX),
+                    Err(err) => assert_eq!(err, Return::OutOfGas),
+                }
+            }
+        }
+    }
+}
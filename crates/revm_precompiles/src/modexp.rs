@@ -57,9 +57,10 @@ macro_rules! read_u64_with_overflow {
 
         let mut len_bytes = [0u8; size_of::<u64>()];
         len_bytes[..to - from].copy_from_slice(&$input[from..to]);
-        let out = u64::from_be_bytes(len_bytes) as usize;
-        let overflow = !(out < $overflow_limit && overflow_bytes.iter().all(|&x| x == 0));
-        (out, overflow)
+        let out = u64::from_be_bytes(len_bytes);
+        // compare as u64 so that value is not truncated on 32-bit targets.
+        let overflow = !(out < $overflow_limit as u64 && overflow_bytes.iter().all(|&x| x == 0));
+        (out as usize, overflow)
     }};
 }
 
@@ -77,17 +78,25 @@ where
     }
 
     let (r, gas_cost) = if base_len == 0 && mod_len == 0 {
-        (BigUint::zero(), min_gas)
+        (BigUint::zero(), gas_query(min_gas, gas_limit)?)
     } else {
         // set limit for exp overflow
         if exp_overflow {
             return Ok(PrecompileOutput::without_logs(u64::MAX, Vec::new()));
         }
-        let base_start = 96;
-        let base_end = base_start + base_len;
-        let exp_end = base_end + exp_len;
+        let base_start: usize = 96;
+        // lengths are bounded by u32::MAX but their sum can still overflow on 32-bit targets.
+        let ends = base_start
+            .checked_add(base_len)
+            .and_then(|base_end| Some((base_end, base_end.checked_add(exp_len)?)))
+            .and_then(|(base_end, exp_end)| {
+                Some((base_end, exp_end, exp_end.checked_add(mod_len)?))
+            });
+        let (base_end, exp_end, mod_end) = match ends {
+            Some(ends) => ends,
+            None => return Ok(PrecompileOutput::without_logs(u64::MAX, Vec::new())),
+        };
         let exp_highp_end = base_end + min(32, exp_len);
-        let mod_end = exp_end + mod_len;
 
         let exp_highp = {
             let mut out = vec![0; 32];
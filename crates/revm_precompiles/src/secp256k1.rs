@@ -59,6 +59,9 @@ mod secp256k1 {
     }
 }
 
+#[cfg(not(any(feature = "k256_ecrecover", feature = "secp256k1")))]
+compile_error!("ECRECOVER needs `secp256k1` or `k256_ecrecover` feature to be enabled");
+
 fn ec_recover_run(i: &[u8], target_gas: u64) -> PrecompileResult {
     let cost = gas_query(ECRECOVER_BASE, target_gas)?;
     let mut input = [0u8; 128];
@@ -84,3 +87,34 @@ fn ec_recover_run(i: &[u8], target_gas: u64) -> PrecompileResult {
 
     Ok(PrecompileOutput::without_logs(cost, out))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Return;
+
+    /// Runs with backend selected by features, `cargo test --no-default-features --features
+    /// k256_ecrecover` checks pure Rust backend.
+    #[test]
+    fn test_ecrecover() {
+        let input = hex::decode(
+            "18c547e4f7b0f325ad1e56f57e26c745b09a3e503d86e00e5255ff7f715d3d1c\
+             000000000000000000000000000000000000000000000000000000000000001c\
+             73b1693892219d736caba55bdb67216e485557ea6b6af75f37096c9aa6a5a75f\
+             eeb940b1d03b21e36b0e47e79769f095fe2ab855bd91e3a38756b7d75a9c4549",
+        )
+        .unwrap();
+        let output = ec_recover_run(&input, 3_000).unwrap();
+        assert_eq!(output.cost, 3_000);
+        assert_eq!(
+            hex::encode(output.output),
+            "000000000000000000000000a94f5374fce5edbc8e2a8697c15331677e6ebf0b"
+        );
+
+        // invalid recovery id returns empty output.
+        let mut invalid = input.clone();
+        invalid[63] = 29;
+        assert!(ec_recover_run(&invalid, 3_000).unwrap().output.is_empty());
+        assert_eq!(ec_recover_run(&input, 2_999).unwrap_err(), Return::OutOfGas);
+    }
+}